@@ -0,0 +1,217 @@
+//! Diff results delivered in batches of rows while the diff runs.
+//!
+//! Rows go to the caller as soon as a batch fills, so the UI can render or
+//! persist them before the diff finishes. Modes the chunked differ supports
+//! are classified chunk by chunk; the others run in one pass and are then
+//! split into batches the same way.
+
+use serde::{Deserialize, Serialize};
+use crate::core::CsvDifferInternal;
+use crate::options::DiffOptions;
+use crate::summary::classify_change;
+use crate::types::{AddedRow, DiffResult, DiffSummary, ModifiedRow, RemovedRow, UnchangedRow};
+
+/// Rows per batch unless told otherwise.
+pub const DEFAULT_BATCH_SIZE: usize = 5000;
+
+/// Rows classified since the previous batch, in result order within each kind.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RowBatch {
+    /// Position of the batch in the run, from 0
+    pub index: usize,
+    pub added: Vec<AddedRow>,
+    pub removed: Vec<RemovedRow>,
+    pub modified: Vec<ModifiedRow>,
+    pub unchanged: Vec<UnchangedRow>,
+}
+
+impl RowBatch {
+    fn len(&self) -> usize {
+        self.added.len() + self.removed.len() + self.modified.len() + self.unchanged.len()
+    }
+}
+
+/// Run a diff like `diff_with_options`, handing its rows to `on_batch` in
+/// batches of at most `batch_size` rows. Returns the result without rows:
+/// metadata, warnings and the summary of every batch. An error from
+/// `on_batch` stops the run.
+pub fn diff_in_batches<F, B>(
+    source_csv: &str,
+    target_csv: &str,
+    options: &DiffOptions,
+    batch_size: usize,
+    on_batch: B,
+    mut on_progress: F,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+    B: FnMut(RowBatch) -> Result<(), Box<dyn std::error::Error>>,
+{
+    let batch_size = batch_size.max(1);
+    let mut batcher = Batcher::new(batch_size, on_batch);
+    // Key fallback re-runs the whole diff, so it needs the one-pass engines
+    let chunked = CsvDifferInternal::supports(options) && !(options.is_primary_key() && options.content_match_fallback);
+
+    let mut result = if chunked {
+        let mut differ = CsvDifferInternal::with_options(source_csv, target_csv, options)?;
+        let rows = differ.row_count();
+        let mut first = None;
+        let mut chunk_start = 0;
+        loop {
+            let chunk_rows = batch_size.min(rows - chunk_start);
+            let mut chunk = differ.diff_chunk(chunk_start, batch_size, |progress, message| {
+                on_progress((chunk_start as f64 + progress / 100.0 * chunk_rows as f64) / rows.max(1) as f64 * 100.0, message)
+            })?;
+            batcher.push(&mut chunk)?;
+            // The first chunk carries the run's warnings and schema details
+            first.get_or_insert(chunk);
+            chunk_start += batch_size;
+            if chunk_start >= rows {
+                break;
+            }
+        }
+        first.unwrap_or_default()
+    } else {
+        let mut result = crate::core::diff_with_options(source_csv, target_csv, options, on_progress)?;
+        batcher.push(&mut result)?;
+        result
+    };
+
+    batcher.flush()?;
+    result.summary = batcher.summary;
+    result.omitted_unchanged = batcher.omitted_unchanged;
+    Ok(result)
+}
+
+/// Collects rows into batches and counts what it passes on.
+struct Batcher<B> {
+    size: usize,
+    batch: RowBatch,
+    summary: DiffSummary,
+    omitted_unchanged: usize,
+    on_batch: B,
+}
+
+impl<B> Batcher<B>
+where
+    B: FnMut(RowBatch) -> Result<(), Box<dyn std::error::Error>>,
+{
+    fn new(size: usize, on_batch: B) -> Self {
+        Batcher { size, batch: RowBatch::default(), summary: DiffSummary::default(), omitted_unchanged: 0, on_batch }
+    }
+
+    /// Move the rows of `result` into batches, sending each one that fills.
+    fn push(&mut self, result: &mut DiffResult) -> Result<(), Box<dyn std::error::Error>> {
+        self.omitted_unchanged += result.omitted_unchanged;
+        self.summary.unchanged += result.omitted_unchanged;
+        for row in result.added.drain(..) {
+            self.summary.added += 1;
+            self.batch.added.push(row);
+            self.send_full()?;
+        }
+        for row in result.removed.drain(..) {
+            self.summary.removed += 1;
+            self.batch.removed.push(row);
+            self.send_full()?;
+        }
+        for row in result.modified.drain(..) {
+            self.summary.modified += 1;
+            for difference in &row.differences {
+                self.summary.change_taxonomy.count(classify_change(&difference.old_value, &difference.new_value));
+            }
+            self.batch.modified.push(row);
+            self.send_full()?;
+        }
+        for row in result.unchanged.drain(..) {
+            self.summary.unchanged += 1;
+            self.batch.unchanged.push(row);
+            self.send_full()?;
+        }
+        Ok(())
+    }
+
+    fn send_full(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.batch.len() >= self.size { self.flush() } else { Ok(()) }
+    }
+
+    /// Send the rows collected so far, if any.
+    fn flush(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.batch.len() == 0 {
+            return Ok(());
+        }
+        let index = self.batch.index;
+        let batch = std::mem::replace(&mut self.batch, RowBatch { index: index + 1, ..Default::default() });
+        (self.on_batch)(batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batches_cover_full_diff() {
+        let source = "id,name\n1,a\n2,b\n3,c\n4,d\n5,e\n";
+        let target = "id,name\n1,a\n2,B\n4,d\n5,E\n6,f\n7,g\n";
+        for mode in ["primary-key", "content-match", "positional"] {
+            let options = DiffOptions {
+                mode: mode.to_string(),
+                key_columns: vec!["id".to_string()],
+                include_unchanged: true,
+                ..Default::default()
+            };
+            let full = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+
+            let mut batches = Vec::new();
+            let result = diff_in_batches(source, target, &options, 2, |batch| { batches.push(batch); Ok(()) }, |_, _| {}).unwrap();
+            assert!(batches.iter().enumerate().all(|(i, batch)| batch.index == i && (1..=2).contains(&batch.len())), "{}", mode);
+            assert_eq!(result.summary, full.summary, "{}", mode);
+            assert!(result.added.is_empty() && result.modified.is_empty());
+
+            let mut keys: Vec<String> = batches.iter()
+                .flat_map(|b| b.added.iter().map(|r| &r.key).chain(b.removed.iter().map(|r| &r.key)).chain(b.modified.iter().map(|r| &r.key)).chain(b.unchanged.iter().map(|r| &r.key)))
+                .cloned()
+                .collect();
+            let mut expected: Vec<String> = full.added.iter().map(|r| &r.key)
+                .chain(full.removed.iter().map(|r| &r.key))
+                .chain(full.modified.iter().map(|r| &r.key))
+                .chain(full.unchanged.iter().map(|r| &r.key))
+                .cloned()
+                .collect();
+            keys.sort();
+            expected.sort();
+            assert_eq!(keys, expected, "{}", mode);
+        }
+    }
+
+    #[test]
+    fn test_batches_pair_unmatched_keys() {
+        let source = "id,name,city\n1,Alice,Oslo\n2,Bob,Rome\nA-1001,Carol,Lima\n";
+        let target = "id,name,city\n1,Alice,Oslo\n92,Bob,Rome\nA-1O01,Carol,Lima\n";
+        for options in [
+            DiffOptions { key_columns: vec!["id".to_string()], fuzzy_key_threshold: Some(0.8), ..Default::default() },
+            DiffOptions { key_columns: vec!["id".to_string()], key_change_threshold: Some(0.9), ..Default::default() },
+        ] {
+            assert!(!CsvDifferInternal::supports(&options));
+            assert!(CsvDifferInternal::with_options(source, target, &options).is_err());
+
+            let full = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            let mut modified = Vec::new();
+            let result = diff_in_batches(source, target, &options, 1, |mut batch| { modified.append(&mut batch.modified); Ok(()) }, |_, _| {}).unwrap();
+            assert_eq!(result.summary, full.summary);
+            let pairs = |rows: &[ModifiedRow]| rows.iter().map(|r| (r.key.clone(), r.target_row["id"].clone())).collect::<Vec<_>>();
+            assert_eq!(pairs(&modified), pairs(&full.modified));
+            assert!(!full.modified.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_batch_error_stops_run() {
+        let options = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let mut calls = 0;
+        let result = diff_in_batches("id\n1\n2\n", "id\n3\n4\n", &options, 1, |_| { calls += 1; Err("full".into()) }, |_, _| {});
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+}
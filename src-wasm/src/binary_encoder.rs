@@ -8,36 +8,98 @@
 /// 
 /// ## Important Limitation
 /// 
-/// Character-level diffs are NOT included in binary encoding for performance
-/// and size optimization. This means the `diff` field in `Difference` structs
-/// will be empty when using binary encoding.
+/// Character-level diffs are NOT included in binary encoding by default for
+/// performance and size optimization. This means the `diff` field in
+/// `Difference` structs will be empty when using binary encoding.
 /// 
 /// To get character-level diffs:
+/// - Enable them in the encoding (`BinaryEncoder::with_char_diffs`, or
+///   `binaryCharDiffs` in the diff options)
 /// - Use JSON encoding mode (set `USE_BINARY_ENCODING = false` in worker)
+/// - Fetch them per cell from a session with `get_cell_diff`
 /// - Recompute diffs on the JavaScript side using a diff library
 /// 
 /// This trade-off provides 2x faster serialization at the cost of losing
 ///
 
 use crate::types::*;
+use ahash::AHashMap;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+/// Tags of char diff fragments, after `DiffChange::added`/`removed`.
+const FRAGMENT_UNCHANGED: u8 = 0;
+const FRAGMENT_ADDED: u8 = 1;
+const FRAGMENT_REMOVED: u8 = 2;
+
+/// First bytes of a v2 buffer. A v1 buffer starts with its row count, which
+/// never gets this large.
+pub const V2_MAGIC: [u8; 4] = *b"CDV2";
+/// First bytes of a columnar buffer.
+pub const COLUMNAR_MAGIC: [u8; 4] = *b"CDCO";
+
+/// Missing string or unknown row index in a columnar buffer.
+const COLUMNAR_NONE: u32 = u32::MAX;
+
+/// Layout of binary results.
+///
+/// v1 writes fixed u32 lengths and every string in full. v2 starts with
+/// `V2_MAGIC`, writes lengths and counts as LEB128 varints and stores each
+/// distinct string once in a table that rows refer to by position, so
+/// headers and repeated values cost a byte or two per cell. Columnar writes
+/// the columns of each kind of row once and then only the values, in
+/// column order, as u32 arrays JS can view as `Uint32Array`s. v1 stays the
+/// default so existing decoders keep working; v2 and columnar are opt-in.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BinaryFormat {
+    #[default]
+    V1,
+    V2,
+    Columnar,
+}
+
 pub struct BinaryEncoder {
     buffer: Vec<u8>,
+    format: BinaryFormat,
+    /// Write `Difference::diff` after everything else
+    char_diffs: bool,
 }
 
 impl BinaryEncoder {
+    /// Encoder writing the v1 layout, which every decoder reads.
     pub fn new() -> Self {
+        Self::with_format(BinaryFormat::V1)
+    }
+
+    pub fn with_format(format: BinaryFormat) -> Self {
         Self {
             buffer: Vec::with_capacity(1024 * 1024), // Start with 1MB
+            format,
+            char_diffs: false,
         }
     }
 
+    /// Also encode the char diff fragments of every difference, as a last
+    /// section decoders that predate it never read.
+    pub fn with_char_diffs(mut self, char_diffs: bool) -> Self {
+        self.char_diffs = char_diffs;
+        self
+    }
+
     pub fn into_vec(self) -> Vec<u8> {
         self.buffer
     }
 
     pub fn encode_diff_result(&mut self, result: &DiffResult) {
+        match self.format {
+            BinaryFormat::V1 => self.encode_v1(result),
+            BinaryFormat::V2 => self.encode_v2(result),
+            BinaryFormat::Columnar => self.encode_columnar(result),
+        }
+    }
+
+    fn encode_v1(&mut self, result: &DiffResult) {
         let total_rows = (result.added.len() + result.removed.len() + result.modified.len() + result.unchanged.len()) as u32;
         
         // Header
@@ -82,6 +144,207 @@ impl BinaryEncoder {
             self.write_string(&row.key);
             self.write_row_data(&row.row);
         }
+
+        // Trailer: unchanged rows left out (`DiffOptions::include_unchanged`).
+        // Decoders predating it stop after the rows and never read it
+        self.write_u32(result.omitted_unchanged as u32);
+
+        // Row indices, in row order: target index of added rows, source
+        // index of removed rows, both for modified and unchanged rows
+        for row in &result.added {
+            self.write_index(row.target_index);
+        }
+        for row in &result.removed {
+            self.write_index(row.source_index);
+        }
+        for row in &result.modified {
+            self.write_index(row.source_index);
+            self.write_index(row.target_index);
+        }
+        for row in &result.unchanged {
+            self.write_index(row.source_index);
+            self.write_index(row.target_index);
+        }
+
+        // Char diffs, if enabled: per difference of each modified row, the
+        // fragment count, then the tag and value of each fragment
+        if self.char_diffs {
+            for diff in result.modified.iter().flat_map(|row| &row.differences) {
+                self.write_u32(diff.diff.len() as u32);
+                for change in &diff.diff {
+                    self.write_u8(fragment_tag(change));
+                    self.write_string(&change.value);
+                }
+            }
+        }
+    }
+
+    /// v2 layout, all integers varints:
+    ///
+    /// - `V2_MAGIC`
+    /// - added, removed, modified, unchanged and omitted unchanged counts
+    /// - string count, then each string's byte length and UTF-8 bytes
+    /// - added rows: key, target index, target row
+    /// - removed rows: key, source index, source row
+    /// - modified rows: key, source index, target index, source row, target
+    ///   row, difference count, then column, old and new value of each
+    /// - unchanged rows: key, source index, target index, row
+    /// - with char diffs only: per difference of each modified row, the
+    ///   fragment count, then the tag and value of each fragment
+    ///
+    /// Strings are table positions; a row is its cell count followed by
+    /// column and value of each cell; an index is 0 when unknown, otherwise
+    /// the row index plus one.
+    fn encode_v2(&mut self, result: &DiffResult) {
+        let mut table = StringTable::default();
+        let mut body = Vec::with_capacity(self.buffer.capacity());
+
+        for row in &result.added {
+            table.write(&mut body, &row.key);
+            write_varint(&mut body, v2_index(row.target_index));
+            table.write_row(&mut body, &row.target_row);
+        }
+        for row in &result.removed {
+            table.write(&mut body, &row.key);
+            write_varint(&mut body, v2_index(row.source_index));
+            table.write_row(&mut body, &row.source_row);
+        }
+        for row in &result.modified {
+            table.write(&mut body, &row.key);
+            write_varint(&mut body, v2_index(row.source_index));
+            write_varint(&mut body, v2_index(row.target_index));
+            table.write_row(&mut body, &row.source_row);
+            table.write_row(&mut body, &row.target_row);
+            write_varint(&mut body, row.differences.len() as u64);
+            for diff in &row.differences {
+                table.write(&mut body, &diff.column);
+                table.write(&mut body, &diff.old_value);
+                table.write(&mut body, &diff.new_value);
+            }
+        }
+        for row in &result.unchanged {
+            table.write(&mut body, &row.key);
+            write_varint(&mut body, v2_index(row.source_index));
+            write_varint(&mut body, v2_index(row.target_index));
+            table.write_row(&mut body, &row.row);
+        }
+        if self.char_diffs {
+            for diff in result.modified.iter().flat_map(|row| &row.differences) {
+                write_varint(&mut body, diff.diff.len() as u64);
+                for change in &diff.diff {
+                    body.push(fragment_tag(change));
+                    table.write(&mut body, &change.value);
+                }
+            }
+        }
+
+        self.buffer.extend_from_slice(&V2_MAGIC);
+        for count in [result.added.len(), result.removed.len(), result.modified.len(), result.unchanged.len(), result.omitted_unchanged] {
+            write_varint(&mut self.buffer, count as u64);
+        }
+        write_varint(&mut self.buffer, table.strings.len() as u64);
+        for string in &table.strings {
+            write_varint(&mut self.buffer, string.len() as u64);
+            self.buffer.extend_from_slice(string.as_bytes());
+        }
+        self.buffer.extend_from_slice(&body);
+    }
+
+    /// Columnar layout, all integers u32 and 4-byte aligned:
+    ///
+    /// - `COLUMNAR_MAGIC`
+    /// - added, removed, modified, unchanged and omitted unchanged counts,
+    ///   string count and string byte length
+    /// - string start offsets plus the end offset, then the UTF-8 bytes of
+    ///   all strings, padded to a multiple of 4
+    /// - added rows: column count and columns, keys, target indices, values
+    /// - removed rows: column count and columns, keys, source indices, values
+    /// - modified rows: source and target column counts and columns, keys,
+    ///   source indices, target indices, source values, target values,
+    ///   difference counts, then columns, old and new values of all
+    ///   differences
+    /// - unchanged rows: column count and columns, keys, source indices,
+    ///   target indices, values
+    /// - with char diffs only: fragment count of every difference, then
+    ///   tags and values of all fragments
+    ///
+    /// Strings are table positions. Values run row by row, one per column;
+    /// `COLUMNAR_NONE` marks a cell a row lacks and an unknown index.
+    fn encode_columnar(&mut self, result: &DiffResult) {
+        let mut table = StringTable::default();
+        let mut body: Vec<u32> = Vec::new();
+        let indices = |body: &mut Vec<u32>, indices: &mut dyn Iterator<Item = Option<usize>>| {
+            body.extend(indices.map(|i| i.map_or(COLUMNAR_NONE, |i| i as u32)));
+        };
+
+        let columns = table.columns(&mut body, &result.target.headers, result.added.iter().map(|r| &r.target_row));
+        body.extend(result.added.iter().map(|r| table.id(&r.key)));
+        indices(&mut body, &mut result.added.iter().map(|r| r.target_index));
+        for row in &result.added {
+            table.values(&mut body, &columns, &row.target_row);
+        }
+
+        let columns = table.columns(&mut body, &result.source.headers, result.removed.iter().map(|r| &r.source_row));
+        body.extend(result.removed.iter().map(|r| table.id(&r.key)));
+        indices(&mut body, &mut result.removed.iter().map(|r| r.source_index));
+        for row in &result.removed {
+            table.values(&mut body, &columns, &row.source_row);
+        }
+
+        let source_columns = table.columns(&mut body, &result.source.headers, result.modified.iter().map(|r| &r.source_row));
+        let target_columns = table.columns(&mut body, &result.target.headers, result.modified.iter().map(|r| &r.target_row));
+        body.extend(result.modified.iter().map(|r| table.id(&r.key)));
+        indices(&mut body, &mut result.modified.iter().map(|r| r.source_index));
+        indices(&mut body, &mut result.modified.iter().map(|r| r.target_index));
+        for row in &result.modified {
+            table.values(&mut body, &source_columns, &row.source_row);
+        }
+        for row in &result.modified {
+            table.values(&mut body, &target_columns, &row.target_row);
+        }
+        let differences = || result.modified.iter().flat_map(|r| &r.differences);
+        body.extend(result.modified.iter().map(|r| r.differences.len() as u32));
+        body.extend(differences().map(|d| table.id(&d.column)));
+        body.extend(differences().map(|d| table.id(&d.old_value)));
+        body.extend(differences().map(|d| table.id(&d.new_value)));
+
+        let columns = table.columns(&mut body, &result.source.headers, result.unchanged.iter().map(|r| &r.row));
+        body.extend(result.unchanged.iter().map(|r| table.id(&r.key)));
+        indices(&mut body, &mut result.unchanged.iter().map(|r| r.source_index));
+        indices(&mut body, &mut result.unchanged.iter().map(|r| r.target_index));
+        for row in &result.unchanged {
+            table.values(&mut body, &columns, &row.row);
+        }
+
+        if self.char_diffs {
+            body.extend(differences().map(|d| d.diff.len() as u32));
+            body.extend(differences().flat_map(|d| &d.diff).map(|c| fragment_tag(c) as u32));
+            body.extend(differences().flat_map(|d| &d.diff).map(|c| table.id(&c.value)));
+        }
+
+        self.buffer.extend_from_slice(&COLUMNAR_MAGIC);
+        let string_bytes: usize = table.strings.iter().map(|s| s.len()).sum();
+        for count in [result.added.len(), result.removed.len(), result.modified.len(), result.unchanged.len(), result.omitted_unchanged, table.strings.len(), string_bytes] {
+            self.write_u32(count as u32);
+        }
+        let mut offset = 0;
+        for string in &table.strings {
+            self.write_u32(offset);
+            offset += string.len() as u32;
+        }
+        self.write_u32(offset);
+        for string in &table.strings {
+            self.buffer.extend_from_slice(string.as_bytes());
+        }
+        self.buffer.resize(self.buffer.len().next_multiple_of(4), 0);
+        for word in body {
+            self.write_u32(word);
+        }
+    }
+
+    /// A row index, `u32::MAX` when unknown.
+    fn write_index(&mut self, index: Option<usize>) {
+        self.write_u32(index.map_or(u32::MAX, |i| i as u32));
     }
 
     fn write_u8(&mut self, value: u8) {
@@ -106,3 +369,168 @@ impl BinaryEncoder {
         }
     }
 }
+
+/// Distinct strings of a v2 or columnar buffer, in order of first use.
+#[derive(Default)]
+struct StringTable<'a> {
+    ids: AHashMap<&'a str, u32>,
+    strings: Vec<&'a str>,
+}
+
+impl<'a> StringTable<'a> {
+    fn id(&mut self, value: &'a str) -> u32 {
+        let next = self.strings.len() as u32;
+        *self.ids.entry(value).or_insert_with(|| {
+            self.strings.push(value);
+            next
+        })
+    }
+
+    fn write(&mut self, out: &mut Vec<u8>, value: &'a str) {
+        let id = self.id(value);
+        write_varint(out, id as u64);
+    }
+
+    /// Columns of a columnar section: `headers`, then any other column a
+    /// row has. Writes their count and ids to `out`.
+    fn columns(
+        &mut self,
+        out: &mut Vec<u32>,
+        headers: &'a [String],
+        rows: impl Iterator<Item = &'a HashMap<String, String>>,
+    ) -> Vec<&'a str> {
+        let mut columns: Vec<&'a str> = headers.iter().map(String::as_str).collect();
+        let mut seen: ahash::AHashSet<&str> = columns.iter().copied().collect();
+        for row in rows {
+            for column in row.keys() {
+                if seen.insert(column) {
+                    columns.push(column);
+                }
+            }
+        }
+        out.push(columns.len() as u32);
+        out.extend(columns.iter().map(|column| self.id(column)));
+        columns
+    }
+
+    /// Values of `row` in `columns` order, for a columnar section.
+    fn values(&mut self, out: &mut Vec<u32>, columns: &[&str], row: &'a HashMap<String, String>) {
+        out.extend(columns.iter().map(|column| row.get(*column).map_or(COLUMNAR_NONE, |value| self.id(value))));
+    }
+
+    fn write_row(&mut self, out: &mut Vec<u8>, row: &'a HashMap<String, String>) {
+        write_varint(out, row.len() as u64);
+        for (key, value) in row {
+            self.write(out, key);
+            self.write(out, value);
+        }
+    }
+}
+
+/// `value` as an unsigned LEB128 varint: seven bits per byte, low bits
+/// first, high bit set on every byte but the last.
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn fragment_tag(change: &DiffChange) -> u8 {
+    match (change.added, change.removed) {
+        (true, _) => FRAGMENT_ADDED,
+        (_, true) => FRAGMENT_REMOVED,
+        _ => FRAGMENT_UNCHANGED,
+    }
+}
+
+fn v2_index(index: Option<usize>) -> u64 {
+    index.map_or(0, |i| i as u64 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varint() {
+        let encode = |value| {
+            let mut out = Vec::new();
+            write_varint(&mut out, value);
+            out
+        };
+        assert_eq!(encode(0), [0]);
+        assert_eq!(encode(127), [127]);
+        assert_eq!(encode(128), [0x80, 1]);
+        assert_eq!(encode(300), [0xac, 0x02]);
+    }
+
+    #[test]
+    fn test_v2_shares_repeated_strings() {
+        let source: String = std::iter::once("id,status,region\n".to_string())
+            .chain((0..200).map(|i| format!("{},open,north\n", i)))
+            .collect();
+        let target = source.replace(",open,", ",closed,");
+        let options = crate::options::DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let result = crate::core::diff_with_options(&source, &target, &options, |_, _| {}).unwrap();
+
+        let encode = |format| {
+            let mut encoder = BinaryEncoder::with_format(format);
+            encoder.encode_diff_result(&result);
+            encoder.into_vec()
+        };
+        let (v1, v2) = (encode(BinaryFormat::V1), encode(BinaryFormat::V2));
+        assert_eq!(v2[..4], V2_MAGIC);
+        assert_ne!(v1[..4], V2_MAGIC);
+        // v2 is opt-in: options that don't name a format keep the v1 layout
+        assert_eq!(options.binary_format, BinaryFormat::V1);
+        assert!(v2.len() * 3 < v1.len(), "v1 {} bytes, v2 {} bytes", v1.len(), v2.len());
+    }
+
+    #[test]
+    fn test_columnar_layout() {
+        let options = crate::options::DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let result = crate::core::diff_with_options("id,a,b\n1,x,y\n2,x,z\n", "id,a,b\n1,x,w\n3,x,y\n", &options, |_, _| {}).unwrap();
+        let mut encoder = BinaryEncoder::with_format(BinaryFormat::Columnar);
+        encoder.encode_diff_result(&result);
+        let bytes = encoder.into_vec();
+        assert_eq!(bytes[..4], COLUMNAR_MAGIC);
+        assert_eq!(bytes.len() % 4, 0);
+
+        let words: Vec<u32> = bytes[4..].chunks(4).map(|w| u32::from_le_bytes(w.try_into().unwrap())).collect();
+        assert_eq!(words[..5], [1, 1, 1, 0, 0]);
+        let (string_count, string_bytes) = (words[5] as usize, words[6] as usize);
+        let strings = &bytes[4 + 4 * (8 + string_count)..][..string_bytes];
+        let string = |id: u32| -> &str {
+            let (start, end) = (words[7 + id as usize] as usize, words[8 + id as usize] as usize);
+            std::str::from_utf8(&strings[start..end]).unwrap()
+        };
+
+        // Added rows: the three headers, then key "3", index 1, values
+        let body = &words[8 + string_count + string_bytes.div_ceil(4)..];
+        assert_eq!(body[0], 3);
+        let added: Vec<&str> = body[1..4].iter().chain(&body[4..5]).chain(&body[6..9]).map(|&id| string(id)).collect();
+        assert_eq!(added, ["id", "a", "b", "3", "3", "x", "y"]);
+        assert_eq!(body[5], 1);
+    }
+
+    #[test]
+    fn test_char_diffs_opt_in() {
+        let options = crate::options::DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let result = crate::core::diff_with_options("id,v\n1,abc\n", "id,v\n1,abd\n", &options, |_, _| {}).unwrap();
+        assert!(!result.modified[0].differences[0].diff.is_empty());
+
+        for format in [BinaryFormat::V1, BinaryFormat::V2] {
+            let encode = |char_diffs| {
+                let mut encoder = BinaryEncoder::with_format(format).with_char_diffs(char_diffs);
+                encoder.encode_diff_result(&result);
+                encoder.into_vec()
+            };
+            let (plain, with_diffs) = (encode(false), encode(true));
+            // The section is appended, so the rest of the buffer is unchanged
+            assert_eq!(with_diffs[..plain.len()], plain[..]);
+            assert!(with_diffs.len() > plain.len());
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! MinHash/LSH blocking for content match.
+//!
+//! Fuzzy matching only scores an unmatched source row against the target
+//! rows likely to resemble it. `LshIndex` finds those: a row is the set of
+//! its normalized cell values and their character trigrams (per column),
+//! summarized by a MinHash signature whose bands go into hash buckets. Rows
+//! sharing a bucket probably share many trigrams, so they become candidates
+//! even when no cell value is equal, and a bucket holds only rows that look
+//! alike, which keeps candidate sets small on large files.
+
+use ahash::{AHashMap, RandomState};
+use crate::columnar::Fields;
+use crate::normalize::Normalizer;
+
+/// Bands of a signature; rows sharing any band are candidates.
+const BANDS: usize = 16;
+
+/// MinHash values per band. With 16 bands of 1, rows whose token sets
+/// overlap by 20% (Jaccard) share a band 97 times in 100.
+const ROWS_PER_BAND: usize = 1;
+
+/// Most candidates one bucket yields, so a row has at most 512. Values
+/// common to much of a file (a status column, say) fill huge buckets, but
+/// a similar row shares most bands, including ones of rarer values.
+const MAX_BUCKET_CANDIDATES: usize = 32;
+
+/// MinHash signature of a row.
+pub type Signature = [u64; BANDS * ROWS_PER_BAND];
+
+/// Band buckets of the target rows.
+pub struct LshIndex {
+    state: RandomState,
+    buckets: Vec<AHashMap<u64, Vec<usize>>>,
+}
+
+impl LshIndex {
+    pub fn new(state: RandomState) -> Self {
+        Self { state, buckets: (0..BANDS).map(|_| AHashMap::new()).collect() }
+    }
+
+    /// Signature of a row's normalized `cells` (header name and field
+    /// index). Empty and null values are left out; a row without other
+    /// values has none.
+    pub fn signature<'a, R: Fields + ?Sized>(
+        &self,
+        row: &R,
+        cells: impl IntoIterator<Item = (&'a String, usize)>,
+        normalizer: &Normalizer,
+    ) -> Option<Signature> {
+        let mut signature = [u64::MAX; BANDS * ROWS_PER_BAND];
+        let mut add = |token: u64| {
+            for (i, min) in signature.iter_mut().enumerate() {
+                *min = (*min).min(permute(token, i as u64));
+            }
+        };
+        let mut empty = true;
+        for (column, index) in cells {
+            let raw = row.field(index).unwrap_or("");
+            if raw.trim().is_empty() || normalizer.null_values.is_null(column, raw) {
+                continue;
+            }
+            empty = false;
+            let value = normalizer.normalize(column, raw);
+            add(self.state.hash_one((column, value.as_ref())));
+            let chars: Vec<char> = value.chars().collect();
+            for gram in chars.windows(3) {
+                add(self.state.hash_one((column, gram)));
+            }
+        }
+        (!empty).then_some(signature)
+    }
+
+    pub fn insert(&mut self, row_idx: usize, signature: &Signature) {
+        for (band, bucket) in self.buckets.iter_mut().zip(signature.chunks(ROWS_PER_BAND)) {
+            band.entry(self.state.hash_one(bucket)).or_default().push(row_idx);
+        }
+    }
+
+    /// Rows for which `is_open` holds that share a band with `signature`
+    /// (up to `MAX_BUCKET_CANDIDATES` per band), with the number of bands
+    /// they share.
+    pub fn candidates(&self, signature: &Signature, is_open: impl Fn(usize) -> bool) -> AHashMap<usize, usize> {
+        let mut shared: AHashMap<usize, usize> = AHashMap::new();
+        for (band, bucket) in self.buckets.iter().zip(signature.chunks(ROWS_PER_BAND)) {
+            let Some(rows) = band.get(&self.state.hash_one(bucket)) else { continue };
+            for &row_idx in rows.iter().filter(|&&row_idx| is_open(row_idx)).take(MAX_BUCKET_CANDIDATES) {
+                *shared.entry(row_idx).or_default() += 1;
+            }
+        }
+        shared
+    }
+}
+
+/// `i`-th hash function of MinHash, from one token hash (SplitMix64 mixing
+/// of the token offset by `i`).
+#[inline]
+fn permute(token: u64, i: u64) -> u64 {
+    let mut z = token.wrapping_add(i.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::DiffOptions;
+    use csv::StringRecord;
+
+    #[test]
+    fn test_similar_rows_share_buckets() {
+        let normalizer = Normalizer::new(&DiffOptions::default()).unwrap();
+        let headers = vec!["name".to_string(), "address".to_string()];
+        let cells = || headers.iter().zip(0..);
+        let mut index = LshIndex::new(crate::utils::seeded_state(0));
+        let targets = [
+            StringRecord::from(vec!["Jonathan Smithers", "42 Harbour Street, Bristol"]),
+            StringRecord::from(vec!["Maria Gonzales", "7 Mill Lane, Leeds"]),
+        ];
+        for (i, row) in targets.iter().enumerate() {
+            index.insert(i, &index.signature(row, cells(), &normalizer).unwrap());
+        }
+
+        // No cell value equal to any target's
+        let source = StringRecord::from(vec!["Jonathon Smithers", "42 Harbor Street, Bristol"]);
+        let signature = index.signature(&source, cells(), &normalizer).unwrap();
+        let candidates = index.candidates(&signature, |_| true);
+        assert!(candidates.contains_key(&0));
+        assert!(!candidates.contains_key(&1));
+        assert!(index.candidates(&signature, |row| row != 0).is_empty());
+
+        assert!(index.signature(&StringRecord::from(vec!["", " "]), cells(), &normalizer).is_none());
+    }
+}
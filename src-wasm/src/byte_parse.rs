@@ -0,0 +1,449 @@
+//! Byte-slice parse path for very large inputs.
+//!
+//! Rows are kept as `csv::ByteRecord`s, i.e. one buffer per record instead of
+//! a `String` per field, and input is never validated as UTF-8. Fields are
+//! decoded (lossily) only when a comparison needs normalization or when the
+//! row ends up in the diff result; paths that need the whole input as text
+//! decode it lossily too, so invalid bytes never turn into an error.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use ahash::AHashMap;
+use csv::{ByteRecord, StringRecord};
+use crate::messages::{codes, emit, progress_sink, render, Message};
+use crate::options::{DiffOptions, DuplicateKeys};
+use crate::primary_key::index_keys;
+use crate::parse::{pretokenize, settle_position, ParseOptions, TrimMode, HEADER_SAMPLE_ROWS};
+use crate::types::*;
+use crate::summary::change_kind;
+use crate::normalize::{KeyNormalization, Normalizer};
+
+/// Headers, rows and header map of a byte-slice parse.
+pub type ParsedBytes = (Vec<String>, Vec<ByteRecord>, AHashMap<String, usize>);
+
+/// Parse `input` into byte records using the given tokenization options.
+///
+/// Multi-character delimiters need the pre-tokenization pass, which works on
+/// text, so only those inputs are decoded up front.
+pub fn parse_csv_bytes(input: &[u8], options: &ParseOptions) -> Result<ParsedBytes, Box<dyn std::error::Error>> {
+    let text;
+    let prepared: Cow<[u8]> = if options.is_multi_byte_delimiter() {
+        text = decode(input);
+        match pretokenize(&text, options)? {
+            Cow::Borrowed(text) => Cow::Borrowed(text.as_bytes()),
+            Cow::Owned(text) => Cow::Owned(text.into_bytes()),
+        }
+    } else {
+        Cow::Borrowed(input)
+    };
+
+    // The header row is split off by hand, as in the chunk parser, so it
+    // goes through the same reader settings as the data rows
+    let mut rdr = options.reader_builder(false).from_reader(prepared.as_ref());
+    let mut records = Vec::new();
+    for record in rdr.byte_records() {
+        let mut record = record?;
+        if let Some(mut position) = record.position().cloned() {
+            settle_position(&prepared, &mut position);
+            record.set_position(Some(position));
+        }
+        records.push(record);
+    }
+
+    let mut records = records.into_iter();
+    let (headers, rows): (Vec<String>, Vec<ByteRecord>) = match records.next() {
+        None => (vec![], vec![]),
+        Some(first) => {
+            let header_names: Vec<String> = first.iter().map(|h| decode(h).trim().to_string()).collect();
+            let mut rows: Vec<ByteRecord> = records.collect();
+            let samples: Vec<StringRecord> = rows.iter()
+                .take(HEADER_SAMPLE_ROWS)
+                .map(|r| StringRecord::from_byte_record_lossy(r.clone()))
+                .collect();
+            if options.uses_header_row(&header_names, &samples) {
+                (header_names, rows)
+            } else {
+                rows.insert(0, first);
+                let generated = (0..rows[0].len()).map(|i| format!("Column{}", i + 1)).collect();
+                (generated, rows)
+            }
+        }
+    };
+    let header_map = headers.iter().enumerate().map(|(i, h)| (h.clone(), i)).collect();
+
+    Ok(apply_column_options((headers, rows, header_map), options))
+}
+
+/// `parse::apply_column_options` for byte records.
+fn apply_column_options((mut headers, mut rows, mut header_map): ParsedBytes, options: &ParseOptions) -> ParsedBytes {
+    if !options.trims_uniformly() {
+        let modes: Vec<TrimMode> = headers
+            .iter()
+            .map(|h| options.column_trim.get(h).copied().unwrap_or(options.trim))
+            .collect();
+        rows = rows
+            .iter()
+            .map(|r| rebuild(r, r.iter().enumerate().map(|(i, v)| modes.get(i).copied().unwrap_or(options.trim).apply_bytes(v))))
+            .collect();
+    }
+
+    if options.trim_trailing_empty_columns {
+        let is_empty_column = |i: usize| {
+            let header = headers[i].trim();
+            (header.is_empty() || header == format!("Column{}", i + 1))
+                && rows.iter().all(|r| r.get(i).is_none_or(|v| v.is_empty()))
+        };
+        let mut keep = headers.len();
+        while keep > 0 && is_empty_column(keep - 1) {
+            keep -= 1;
+        }
+        if keep < headers.len() {
+            headers.truncate(keep);
+            header_map.retain(|_, i| *i < keep);
+            rows = rows.iter().map(|r| rebuild(r, r.iter().take(keep))).collect();
+        }
+    }
+
+    (headers, rows, header_map)
+}
+
+fn rebuild<'a>(original: &ByteRecord, fields: impl IntoIterator<Item = &'a [u8]>) -> ByteRecord {
+    let mut record: ByteRecord = fields.into_iter().collect();
+    record.set_position(original.position().cloned());
+    record
+}
+
+/// Lossy UTF-8 view of a field; borrows unless the bytes are invalid.
+pub fn decode(field: &[u8]) -> Cow<'_, str> {
+    String::from_utf8_lossy(field)
+}
+
+pub fn byte_record_to_hashmap(row: &ByteRecord, headers: &[String]) -> HashMap<String, String> {
+    headers.iter().enumerate()
+        .map(|(i, h)| (h.clone(), decode(row.get(i).unwrap_or_default()).into_owned()))
+        .collect()
+}
+
+/// `utils::dataset_rows` for byte records.
+fn dataset_rows(rows: &[ByteRecord], headers: &[String], include: bool) -> Vec<HashMap<String, String>> {
+    if !include {
+        return Vec::new();
+    }
+    rows.iter().map(|row| byte_record_to_hashmap(row, headers)).collect()
+}
+
+fn byte_record_line(row: &ByteRecord) -> Option<u64> {
+    row.position().map(|p| p.line())
+}
+
+fn get_byte_row_key(
+    row: &ByteRecord,
+    header_map: &AHashMap<String, usize>,
+    key_columns: &[String],
+    normalization: &KeyNormalization,
+) -> Vec<u8> {
+    let mut key = Vec::new();
+    for (i, column) in key_columns.iter().enumerate() {
+        if i > 0 {
+            key.push(b'|');
+        }
+        if let Some(value) = header_map.get(column).and_then(|&idx| row.get(idx)) {
+            // Raw bytes unless the key needs cleaning up as text
+            let value = if *normalization == (KeyNormalization { hash: normalization.hash, ..Default::default() }) {
+                Cow::Borrowed(value)
+            } else {
+                Cow::Owned(normalization.apply(column, &decode(value)).into_owned().into_bytes())
+            };
+            // Escaped like `get_row_key` so composite keys cannot collide
+            for &byte in value.iter() {
+                if key_columns.len() > 1 && (byte == b'\\' || byte == b'|') {
+                    key.push(b'\\');
+                }
+                key.push(byte);
+            }
+        }
+    }
+    if normalization.hash { crate::utils::surrogate_key(&key).into_bytes() } else { key }
+}
+
+/// Primary-key diff over byte records. Matches `diff_primary_key_with_options`
+/// row for row, but only decodes rows that end up in the result (and, with
+/// `include_dataset_rows`, the `source`/`target` metadata rows).
+///
+/// Content-match mode has no byte path yet; it decodes the inputs lossily
+/// and runs the regular engine.
+pub fn diff_bytes_with_options<F>(
+    source: &[u8],
+    target: &[u8],
+    options: &DiffOptions,
+    on_progress: F,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    if !options.is_primary_key() {
+        return crate::core::diff_with_options(&decode(source), &decode(target), options, on_progress);
+    }
+    diff_bytes_primary_key(source, &options.parse, target, &options.parse, options, on_progress)
+}
+
+/// Byte primary-key diff with separate tokenization options per file;
+/// `options.parse` is only used for the message format. As in
+/// `core::diff_with_options`, a missing or repeated key falls back to
+/// content match if `options.content_match_fallback` allows it (and both
+/// files share a dialect), and the result is reversed if `options.reverse`
+/// is set.
+pub(crate) fn diff_bytes_primary_key<F>(
+    source: &[u8],
+    source_parse: &ParseOptions,
+    target: &[u8],
+    target_parse: &ParseOptions,
+    options: &DiffOptions,
+    mut on_progress: F,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let result = match diff_bytes_keyed(source, source_parse, target, target_parse, options, &mut on_progress) {
+        Err(error) if options.content_match_fallback => {
+            let Some(reason) = crate::core::fallback_reason(error.as_ref()) else {
+                return Err(error);
+            };
+            // Content match reads both files with one dialect
+            if source_parse.delimiter != target_parse.delimiter || source_parse.has_headers != target_parse.has_headers {
+                return Err(error);
+            }
+            let content = DiffOptions { mode: "content-match".to_string(), parse: source_parse.clone(), ..options.clone() };
+            let mut result = crate::core::diff_with_options(&decode(source), &decode(target), &content, on_progress)?;
+            result.fallback = Some(render(&reason.encode(), options.parse.message_format).into_owned());
+            // Already reversed by `diff_with_options`
+            return Ok(result);
+        }
+        result => result?,
+    };
+    Ok(if options.reverse { result.reversed().ordered(options.row_order) } else { result })
+}
+
+fn diff_bytes_keyed<F>(
+    source: &[u8],
+    source_parse: &ParseOptions,
+    target: &[u8],
+    target_parse: &ParseOptions,
+    options: &DiffOptions,
+    on_progress: F,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let mut on_progress = progress_sink(options.parse.message_format, on_progress);
+    let options = &*options.resolve_keys();
+    let key_columns = &options.key_columns;
+
+    emit(&mut on_progress, 0.0, Message::new(codes::PARSE_SOURCE));
+    let (source_headers, source_rows, source_header_map) = parse_csv_bytes(source, source_parse)?;
+
+    emit(&mut on_progress, 10.0, Message::new(codes::PARSE_TARGET));
+    let (mut target_headers, target_rows, mut target_header_map) = parse_csv_bytes(target, target_parse)?;
+    let renamed_columns = crate::mapping::apply_header_policy(&source_headers, &mut target_headers, &mut target_header_map, options)?;
+    let schema_changes = crate::mapping::schema_changes(&source_headers, &target_headers, options, &renamed_columns);
+    let (excluded_columns, mut warnings) = crate::mapping::resolve_excluded_columns(&source_headers, &target_headers, options)?;
+    if options.duplicate_keys == DuplicateKeys::Group {
+        return Err("Grouping duplicate keys is not supported by the byte engine.".into());
+    }
+
+    for key in key_columns {
+        if !source_header_map.contains_key(key) {
+            return Err(Message::new(codes::KEY_COLUMN_MISSING).with("column", key).with("side", "source").into());
+        }
+        if !target_header_map.contains_key(key) {
+            return Err(Message::new(codes::KEY_COLUMN_MISSING).with("column", key).with("side", "target").into());
+        }
+    }
+
+    emit(&mut on_progress, 20.0, Message::new(codes::BUILD_SOURCE_MAP));
+    let source_keys = source_rows.iter().map(|row| get_byte_row_key(row, &source_header_map, key_columns, &options.key_normalization));
+    let (source_map, _) = index_keys(source_keys, "source", options, &mut warnings)?;
+
+    emit(&mut on_progress, 40.0, Message::new(codes::BUILD_TARGET_MAP));
+    let target_keys = target_rows.iter().map(|row| get_byte_row_key(row, &target_header_map, key_columns, &options.key_normalization));
+    let (target_map, _) = index_keys(target_keys, "target", options, &mut warnings)?;
+    let moved_rows = if options.report_order_changes { crate::primary_key::moved_rows(&source_map, &target_map) } else { Vec::new() };
+
+    // Raw byte equality is exact whenever no normalization is requested
+    let normalizer = Normalizer::new(options)?;
+    let raw_compare = normalizer.is_identity();
+    let normalize = |column: &str, value: &[u8]| -> String {
+        normalizer.normalize(column, &decode(value)).into_owned()
+    };
+    let compared_columns: Vec<(&String, usize, usize)> = source_headers
+        .iter()
+        .filter(|h| !excluded_columns.contains(h))
+        .filter_map(|h| Some((h, source_header_map[h], *target_header_map.get(h)?)))
+        .collect();
+
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let mut modified = Vec::new();
+    let mut unchanged = UnchangedRows::new(options.include_unchanged);
+
+    emit(&mut on_progress, 60.0, Message::new(codes::COMPARE_ROWS));
+
+    for (key, &row_idx) in &source_map {
+        if !target_map.contains_key(key) {
+            let source_row = &source_rows[row_idx];
+            removed.push(RemovedRow {
+                key: decode(key).into_owned(),
+                source_row: byte_record_to_hashmap(source_row, &source_headers),
+                source_line: byte_record_line(source_row),
+                source_index: Some(row_idx),
+            });
+        }
+    }
+
+    let total_target = target_map.len();
+    for (i, (key, &target_row_idx)) in target_map.iter().enumerate() {
+        if i % 1000 == 0 && options.streaming.reports_after(i / 1000) {
+            let p = 60.0 + (i as f64 / total_target as f64) * 30.0;
+            emit(&mut on_progress, p, Message::new(codes::COMPARE_ROWS));
+        }
+
+        let target_row = &target_rows[target_row_idx];
+        let Some(&source_row_idx) = source_map.get(key) else {
+            added.push(AddedRow {
+                key: decode(key).into_owned(),
+                target_row: byte_record_to_hashmap(target_row, &target_headers),
+                target_line: byte_record_line(target_row),
+                target_index: Some(target_row_idx),
+            });
+            continue;
+        };
+
+        let source_row = &source_rows[source_row_idx];
+        let mut differences = Vec::new();
+        for &(header, source_idx, target_idx) in &compared_columns {
+            let source_val = source_row.get(source_idx).unwrap_or_default();
+            let target_val = target_row.get(target_idx).unwrap_or_default();
+            let equal = if raw_compare {
+                source_val == target_val
+            } else {
+                normalize(header, source_val) == normalize(header, target_val)
+            };
+
+            if !equal {
+                let (old_value, new_value) = (decode(source_val), decode(target_val));
+                differences.push(Difference {
+                    column: header.clone(),
+                    change_kind: change_kind(&old_value, &new_value),
+                    diff: normalizer.diff_values(header, &old_value, &new_value),
+                    old_value: old_value.into_owned(),
+                    new_value: new_value.into_owned(),
+                });
+            }
+        }
+
+        if !differences.is_empty() {
+            modified.push(ModifiedRow {
+                key: decode(key).into_owned(),
+                source_row: byte_record_to_hashmap(source_row, &source_headers),
+                target_row: byte_record_to_hashmap(target_row, &target_headers),
+                differences,
+                source_line: byte_record_line(source_row),
+                target_line: byte_record_line(target_row),
+                source_index: Some(source_row_idx),
+                target_index: Some(target_row_idx),
+                match_score: None,
+                target_key: None,
+            });
+        } else {
+            unchanged.push_with(|| UnchangedRow {
+                key: decode(key).into_owned(),
+                row: byte_record_to_hashmap(source_row, &source_headers),
+                source_index: Some(source_row_idx),
+                target_index: Some(target_row_idx),
+            });
+        }
+    }
+
+    modified.extend(crate::primary_key::pair_leftover_rows(&mut removed, &mut added, &compared_columns, options, &normalizer, true));
+
+    emit(&mut on_progress, 100.0, Message::new(codes::COMPLETE));
+
+    Ok(DiffResult {
+        added,
+        removed,
+        modified,
+        unchanged: unchanged.rows,
+        source: DatasetMetadata {
+            rows: dataset_rows(&source_rows, &source_headers, options.include_dataset_rows),
+            headers: source_headers,
+        },
+        target: DatasetMetadata {
+            rows: dataset_rows(&target_rows, &target_headers, options.include_dataset_rows),
+            headers: target_headers,
+        },
+        key_columns: options.key_columns.clone(),
+        excluded_columns,
+        mode: "primary-key".to_string(),
+        summary: DiffSummary::default(),
+        warnings,
+        seed: options.seed,
+        fallback: None,
+        renamed_columns,
+        schema_changes,
+        reversed: false,
+        header_policy: options.header_policy,
+        moved_rows,
+        duplicate_counts: Vec::new(),
+        omitted_unchanged: unchanged.omitted,
+    }.with_summary().ordered(options.row_order))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_parse_matches_string_parse() {
+        let csv = "id,name\n1, Alice \n\n2,\"Bob\nJr\"\n";
+        let (headers, rows, _) = parse_csv_bytes(csv.as_bytes(), &ParseOptions::default()).unwrap();
+        let expected = crate::parse::parse_csv_internal(csv, true).unwrap();
+
+        assert_eq!(headers, expected.0);
+        let decoded: Vec<StringRecord> = rows.iter().map(|r| StringRecord::from_byte_record_lossy(r.clone())).collect();
+        assert_eq!(decoded, expected.1);
+        assert_eq!(rows.iter().map(byte_record_line).collect::<Vec<_>>(), vec![Some(2), Some(4)]);
+    }
+
+    #[test]
+    fn test_byte_diff_matches_primary_key_engine() {
+        let source = "id,name,city\n1,Alice,Paris\n2,Bob,Rome\n3,Carol,Oslo\n";
+        let target = "id,name,city\n1,alice,Paris\n2,Bob,Milan\n4,Dave,Bern\n";
+
+        for (case_sensitive, include_dataset_rows) in [(true, true), (false, true), (true, false)] {
+            let options = DiffOptions {
+                key_columns: vec!["id".to_string()],
+                case_sensitive,
+                include_dataset_rows,
+                ..Default::default()
+            };
+            let bytes = diff_bytes_with_options(source.as_bytes(), target.as_bytes(), &options, |_, _| {}).unwrap();
+            let strings = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+
+            let case = format!("case sensitive: {}, dataset rows: {}", case_sensitive, include_dataset_rows);
+            assert_eq!(serde_json::to_value(&bytes).unwrap(), serde_json::to_value(&strings).unwrap(), "{}", case);
+            assert_eq!(bytes.source.rows.len(), if include_dataset_rows { 3 } else { 0 }, "{}", case);
+        }
+    }
+
+    #[test]
+    fn test_invalid_utf8_is_decoded_lossily() {
+        let options = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let result = diff_bytes_with_options(b"id,v\n1,a\xff\n", b"id,v\n1,b\n", &options, |_, _| {}).unwrap();
+        assert_eq!(result.modified[0].differences[0].old_value, "a\u{FFFD}");
+
+        // Content match decodes the whole input the same way
+        let content = DiffOptions { mode: "content-match".to_string(), ..Default::default() };
+        let result = diff_bytes_with_options(b"v,w,x\na\xff,1,x\n", b"v,w,x\na\xff,1,y\n", &content, |_, _| {}).unwrap();
+        assert_eq!(result.modified[0].source_row["v"], "a\u{FFFD}");
+    }
+}
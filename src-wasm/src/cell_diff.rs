@@ -0,0 +1,65 @@
+//! Character-level diffs of single cells, computed on demand.
+//!
+//! With `DiffOptions::char_diffs` off the engines leave `Difference::diff`
+//! empty; the UI then asks for the diff of a cell when the user expands its
+//! row, and this recomputes it from the session's options.
+
+use crate::normalize::Normalizer;
+use crate::options::{DiffOptions, TextGranularity};
+use crate::session::DiffSession;
+use crate::types::DiffChange;
+
+/// Changes in `column` of the modified row `key` of a session's result, in
+/// `granularity` units (the session's `text_granularity` if unset).
+pub fn cell_diff(
+    session: &DiffSession,
+    key: &str,
+    column: &str,
+    granularity: Option<TextGranularity>,
+) -> Result<Vec<DiffChange>, Box<dyn std::error::Error>> {
+    let row = session.result.modified.iter()
+        .find(|row| row.key == key)
+        .ok_or_else(|| format!("No modified row with key \"{}\"", key))?;
+    let difference = row.differences.iter()
+        .find(|d| d.column == column)
+        .ok_or_else(|| format!("Column \"{}\" did not change in row \"{}\"", column, key))?;
+
+    let options = DiffOptions {
+        char_diffs: true,
+        text_granularity: granularity.unwrap_or(session.options.text_granularity),
+        ..session.options.clone()
+    };
+    let normalizer = Normalizer::new(&options)?;
+    Ok(normalizer.diff_values(column, &difference.old_value, &difference.new_value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cell_diff_on_demand() {
+        let options = DiffOptions { key_columns: vec!["id".to_string()], char_diffs: false, ..Default::default() };
+        let session = DiffSession::run("id,note\n1,the color\n", "id,note\n1,the colour\n", options, |_, _| {}).unwrap();
+        assert!(session.result.modified[0].differences[0].diff.is_empty());
+
+        let changes = |granularity| -> Vec<(bool, bool, String)> {
+            cell_diff(&session, "1", "note", granularity).unwrap().into_iter()
+                .map(|c| (c.added, c.removed, c.value))
+                .collect()
+        };
+        assert_eq!(changes(None), [
+            (false, false, "the ".to_string()),
+            (false, true, "color".to_string()),
+            (true, false, "colour".to_string()),
+        ]);
+        assert_eq!(changes(Some(TextGranularity::Character)), [
+            (false, false, "the colo".to_string()),
+            (true, false, "u".to_string()),
+            (false, false, "r".to_string()),
+        ]);
+
+        assert!(cell_diff(&session, "2", "note", None).is_err());
+        assert!(cell_diff(&session, "1", "id", None).is_err());
+    }
+}
@@ -0,0 +1,345 @@
+//! Push-based CSV parser for incremental input.
+//!
+//! Bytes are fed in arbitrary chunks (e.g. from a `ReadableStream`); records
+//! and quoted fields may span chunk boundaries. Only completed records are
+//! kept, so the full input never has to exist as a single string.
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use js_sys::{Function, Promise, Uint8Array};
+use serde::Serialize;
+use csv::StringRecord;
+use csv_core::{ReadRecordResult, Reader, ReaderBuilder};
+use ahash::AHashMap;
+use crate::messages::{codes, emit, error_text, progress_sink, Message};
+use crate::options::DiffOptions;
+use crate::parse::{apply_column_options, rebuild_record, ParseOptions, ParsedCsv};
+use crate::types::ParseResult;
+use crate::utils::record_to_hashmap;
+
+/// Incremental tokenizer state shared by the wasm wrapper and native callers.
+pub struct ChunkParser {
+    options: ParseOptions,
+    reader: Reader,
+    /// Field bytes of the record currently being read
+    field_buf: Vec<u8>,
+    /// Field end offsets into `field_buf`
+    ends: Vec<usize>,
+    field_len: usize,
+    ends_len: usize,
+    records: Vec<StringRecord>,
+    finished: bool,
+}
+
+impl ChunkParser {
+    pub fn new(options: ParseOptions) -> Result<Self, Box<dyn std::error::Error>> {
+        if options.is_multi_byte_delimiter() {
+            return Err("Chunked parsing supports single-byte delimiters only.".into());
+        }
+        let delimiter = options.delimiter.as_bytes().first().copied().unwrap_or(b',');
+
+        Ok(Self {
+            options,
+            reader: ReaderBuilder::new().delimiter(delimiter).build(),
+            field_buf: vec![0; 1024],
+            ends: vec![0; 64],
+            field_len: 0,
+            ends_len: 0,
+            records: Vec::new(),
+            finished: false,
+        })
+    }
+
+    /// Feed the next chunk of input. Returns the number of complete records so far.
+    pub fn push(&mut self, input: &[u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        if self.finished {
+            return Err("Chunk parser already finished.".into());
+        }
+        self.feed(input)?;
+        Ok(self.records.len())
+    }
+
+    /// Signal end of input and return the parsed headers and rows.
+    pub fn finish(&mut self) -> Result<ParsedCsv, Box<dyn std::error::Error>> {
+        if self.finished {
+            return Err("Chunk parser already finished.".into());
+        }
+        // Empty input tells csv-core the stream ended, flushing a last
+        // record that has no trailing newline
+        self.feed(&[])?;
+        self.finished = true;
+
+        let mut records = std::mem::take(&mut self.records).into_iter();
+        let first = records.next();
+        let (headers, rows): (Vec<String>, Vec<StringRecord>) = match first {
+            None => (vec![], vec![]),
+            Some(first) => {
+                let header_names: Vec<String> = first.iter().map(|h| h.trim().to_string()).collect();
+                let mut rows: Vec<StringRecord> = records.collect();
+                if self.options.uses_header_row(&header_names, &rows) {
+                    (header_names, rows)
+                } else {
+                    rows.insert(0, first);
+                    let generated = (0..rows[0].len()).map(|i| format!("Column{}", i + 1)).collect();
+                    (generated, rows)
+                }
+            }
+        };
+
+        let rows = if self.options.trims_uniformly() {
+            rows.iter().map(|r| rebuild_record(r, r.iter().map(str::trim))).collect()
+        } else {
+            rows
+        };
+        let header_map: AHashMap<String, usize> = headers.iter().enumerate().map(|(i, h)| (h.clone(), i)).collect();
+
+        Ok(apply_column_options((headers, rows, header_map), &self.options))
+    }
+
+    fn feed(&mut self, mut input: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        loop {
+            let (result, nin, nout, nend) = self.reader.read_record(
+                input,
+                &mut self.field_buf[self.field_len..],
+                &mut self.ends[self.ends_len..],
+            );
+            // Field ends are reported relative to the start of the record,
+            // so they index `field_buf` directly
+            let ended_line = input[..nin].last() == Some(&b'\n');
+            input = &input[nin..];
+            self.field_len += nout;
+            self.ends_len += nend;
+
+            match result {
+                ReadRecordResult::InputEmpty | ReadRecordResult::End => return Ok(()),
+                ReadRecordResult::OutputFull => {
+                    let len = self.field_buf.len();
+                    self.field_buf.resize(len * 2, 0);
+                }
+                ReadRecordResult::OutputEndsFull => {
+                    let len = self.ends.len();
+                    self.ends.resize(len * 2, 0);
+                }
+                ReadRecordResult::Record => self.take_record(ended_line)?,
+            }
+        }
+    }
+
+    pub fn record_count(&self) -> usize {
+        self.records.len()
+    }
+
+    fn take_record(&mut self, ended_line: bool) -> Result<(), Box<dyn std::error::Error>> {
+        let bytes = &self.field_buf[..self.field_len];
+        let mut record = StringRecord::with_capacity(self.field_len, self.ends_len);
+        let mut start = 0;
+        for &end in &self.ends[..self.ends_len] {
+            record.push_field(std::str::from_utf8(&bytes[start..end])?);
+            start = end;
+        }
+        // The reader's line count now includes the record's own newlines
+        // (quoted ones and, unless input ended, its terminator)
+        let newlines = bytes.iter().filter(|&&b| b == b'\n').count() as u64 + ended_line as u64;
+        let mut position = csv::Position::new();
+        position.set_line(self.reader.line() - newlines);
+        record.set_position(Some(position));
+
+        if let Some(previous) = self.records.last() {
+            if previous.len() != record.len() {
+                return Err(format!(
+                    "CSV error: record {} has {} fields, but the previous record has {} fields",
+                    self.records.len() + 1,
+                    record.len(),
+                    previous.len()
+                ).into());
+            }
+        }
+        self.records.push(record);
+        self.field_len = 0;
+        self.ends_len = 0;
+        Ok(())
+    }
+}
+
+/// Stateful CSV parser fed with successive `Uint8Array` chunks from JS.
+#[wasm_bindgen]
+pub struct CsvChunkParser {
+    inner: ChunkParser,
+}
+
+#[wasm_bindgen]
+impl CsvChunkParser {
+    /// Create a parser. `options_val` is a camelCase `ParseOptions` object.
+    #[wasm_bindgen(constructor)]
+    pub fn new(options_val: JsValue) -> Result<CsvChunkParser, JsValue> {
+        let options: ParseOptions = if options_val.is_undefined() || options_val.is_null() {
+            ParseOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options_val).map_err(|e| JsValue::from_str(&e.to_string()))?
+        };
+        let inner = ChunkParser::new(options).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(CsvChunkParser { inner })
+    }
+
+    /// Feed the next chunk. Returns the number of complete records parsed so far.
+    pub fn push_chunk(&mut self, chunk: &[u8]) -> Result<usize, JsValue> {
+        self.inner.push(chunk).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Number of complete records parsed so far (including the header row).
+    pub fn record_count(&self) -> usize {
+        self.inner.record_count()
+    }
+
+    /// Finish parsing and return `{ headers, rows }` like `parse_csv`.
+    pub fn finish(&mut self) -> Result<JsValue, JsValue> {
+        let (headers, rows, _) = self.inner.finish().map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let rows_hashmap: Vec<_> = rows.iter()
+            .map(|r| record_to_hashmap(r, &headers))
+            .collect();
+
+        let result = ParseResult { headers, rows: rows_hashmap };
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        result.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+}
+
+/// Parse a CSV by pulling bytes from a JS async reader instead of a string.
+///
+/// `read_chunk` is called until the input ends and must return (a promise of)
+/// either a `{ done, value }` result, as `ReadableStreamDefaultReader.read()`
+/// does, or a `Uint8Array` with `null`/`undefined` marking the end:
+///
+/// ```js
+/// const reader = file.stream().getReader();
+/// const parsed = await parse_csv_from_reader(() => reader.read(), { hasHeaders: true });
+/// ```
+#[wasm_bindgen]
+pub async fn parse_csv_from_reader(read_chunk: Function, options_val: JsValue) -> Result<JsValue, JsValue> {
+    let mut parser = CsvChunkParser::new(options_val)?;
+    pull_chunks(&read_chunk, |chunk| parser.push_chunk(chunk).map(drop)).await?;
+    parser.finish()
+}
+
+/// Diff two CSVs pulled from JS async readers, like `diff_csv_with_options`
+/// but without either input ever existing as a string. Both readers follow
+/// the `parse_csv_from_reader` contract and are parsed with the diff's parse
+/// options (single-byte delimiters only).
+///
+/// ```js
+/// const source = sourceFile.stream().getReader();
+/// const target = targetFile.stream().getReader();
+/// const result = await diff_csv_from_readers(() => source.read(), () => target.read(), options, onProgress);
+/// ```
+#[wasm_bindgen]
+pub async fn diff_csv_from_readers(
+    read_source: Function,
+    read_target: Function,
+    options_val: JsValue,
+    on_progress: Function,
+) -> Result<JsValue, JsValue> {
+    let options: DiffOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let mut on_progress = progress_sink(options.parse.message_format, |progress: f64, message: &str| {
+        let _ = on_progress.call2(&JsValue::NULL, &JsValue::from_f64(progress), &JsValue::from_str(message));
+    });
+
+    emit(&mut on_progress, 0.0, Message::new(codes::PARSE_SOURCE));
+    let source = read_csv(&read_source, &options.parse).await?;
+    emit(&mut on_progress, 10.0, Message::new(codes::PARSE_TARGET));
+    let target = read_csv(&read_target, &options.parse).await?;
+
+    let result = crate::core::diff_parsed(&source, &target, &options, &mut on_progress)
+        .map_err(|e| JsValue::from_str(&error_text(e.as_ref(), options.parse.message_format)))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    result.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Parse everything `read_chunk` yields.
+async fn read_csv(read_chunk: &Function, options: &ParseOptions) -> Result<ParsedCsv, JsValue> {
+    let mut parser = ChunkParser::new(options.clone()).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    pull_chunks(read_chunk, |chunk| parser.push(chunk).map(drop).map_err(|e| JsValue::from_str(&e.to_string()))).await?;
+    parser.finish().map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Call `read_chunk` until the input ends, handing each chunk to `push`.
+async fn pull_chunks<P>(read_chunk: &Function, mut push: P) -> Result<(), JsValue>
+where
+    P: FnMut(&[u8]) -> Result<(), JsValue>,
+{
+    loop {
+        let pending = read_chunk.call0(&JsValue::NULL)?;
+        let result = JsFuture::from(Promise::resolve(&pending)).await?;
+
+        let chunk = if result.is_null() || result.is_undefined() {
+            return Ok(());
+        } else if result.is_instance_of::<Uint8Array>() {
+            result
+        } else {
+            let done = js_sys::Reflect::get(&result, &JsValue::from_str("done"))?;
+            if done.is_truthy() {
+                return Ok(());
+            }
+            js_sys::Reflect::get(&result, &JsValue::from_str("value"))?
+        };
+
+        push(&chunk.unchecked_into::<Uint8Array>().to_vec())?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_boundaries_match_whole_parse() {
+        let csv = "id,name,note\n1,Alice,\"line one\nline two\"\n\n2, Bob ,\"has, comma\"\r\n3,Carol,plain";
+        let expected = crate::parse::parse_csv_internal(csv, true).unwrap();
+        let lines = |rows: &[StringRecord]| rows.iter().map(crate::utils::record_line).collect::<Vec<_>>();
+        assert_eq!(lines(&expected.1), vec![Some(2), Some(5), Some(6)]);
+
+        for chunk_size in [1, 2, 5, 7, 64] {
+            let mut parser = ChunkParser::new(ParseOptions::default()).unwrap();
+            for chunk in csv.as_bytes().chunks(chunk_size) {
+                parser.push(chunk).unwrap();
+            }
+            let (headers, rows, _) = parser.finish().unwrap();
+
+            assert_eq!(headers, expected.0, "chunk size {}", chunk_size);
+            assert_eq!(rows, expected.1, "chunk size {}", chunk_size);
+            assert_eq!(lines(&rows), lines(&expected.1), "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn test_diff_chunk_parsed_tables() {
+        let source = "id,name\n1,Alice\n2,Bob\n";
+        let target = "id,name\n1,Alicia\n3,Carol\n";
+        let options = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let parse = |csv: &str| {
+            let mut parser = ChunkParser::new(options.parse.clone()).unwrap();
+            for chunk in csv.as_bytes().chunks(3) {
+                parser.push(chunk).unwrap();
+            }
+            parser.finish().unwrap()
+        };
+
+        let result = crate::core::diff_parsed(&parse(source), &parse(target), &options, &mut |_, _| {}).unwrap();
+        let expected = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+        assert_eq!(result.summary, expected.summary);
+        assert_eq!(result.modified[0].differences[0].new_value, "Alicia");
+    }
+
+    #[test]
+    fn test_multibyte_utf8_split_across_chunks() {
+        let csv = "name\nJosé\nZoë\n";
+        let mut parser = ChunkParser::new(ParseOptions::default()).unwrap();
+        for chunk in csv.as_bytes().chunks(1) {
+            parser.push(chunk).unwrap();
+        }
+        let (_, rows, _) = parser.finish().unwrap();
+        assert_eq!(&rows[0][0], "José");
+        assert_eq!(&rows[1][0], "Zoë");
+    }
+}
@@ -0,0 +1,177 @@
+//! Column-oriented storage for parsed rows.
+//!
+//! A `StringRecord` is a separate heap allocation per row carrying its own
+//! field buffer, 8-byte field bounds and position. Data kept around after
+//! parsing (differ state, sessions) is instead stored per column: one string
+//! arena plus 4-byte end offsets, and a line number per row. This roughly
+//! halves retained memory for typical files, and scans over one column
+//! (key building, value indexes) touch contiguous memory.
+
+use csv::StringRecord;
+
+/// Read access to the fields of a row, whether stored as a `StringRecord`
+/// or as a view into a `ColumnStore`.
+pub trait Fields {
+    fn field(&self, index: usize) -> Option<&str>;
+
+    /// 1-based line where the row starts in its original file, if known.
+    fn line(&self) -> Option<u64>;
+}
+
+impl Fields for StringRecord {
+    #[inline]
+    fn field(&self, index: usize) -> Option<&str> {
+        self.get(index)
+    }
+
+    fn line(&self) -> Option<u64> {
+        self.position().map(|p| p.line())
+    }
+}
+
+impl<F: Fields + ?Sized> Fields for &F {
+    #[inline]
+    fn field(&self, index: usize) -> Option<&str> {
+        (**self).field(index)
+    }
+
+    fn line(&self) -> Option<u64> {
+        (**self).line()
+    }
+}
+
+/// Values of one column, concatenated.
+#[derive(Default)]
+struct Column {
+    data: String,
+    /// End offset of each row's value in `data`
+    ends: Vec<u32>,
+}
+
+impl Column {
+    #[inline]
+    fn get(&self, row: usize) -> &str {
+        let start = if row == 0 { 0 } else { self.ends[row - 1] as usize };
+        &self.data[start..self.ends[row] as usize]
+    }
+}
+
+/// Parsed rows stored column by column.
+#[derive(Default)]
+pub struct ColumnStore {
+    columns: Vec<Column>,
+    lines: Vec<Option<u64>>,
+}
+
+impl ColumnStore {
+    /// Store `records` with `width` columns. Missing trailing fields are
+    /// stored as empty values and extra fields are dropped.
+    pub fn from_records(records: &[StringRecord], width: usize) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut columns: Vec<Column> = (0..width).map(|_| Column::default()).collect();
+        for (index, column) in columns.iter_mut().enumerate() {
+            let bytes: usize = records.iter().map(|r| r.get(index).map_or(0, str::len)).sum();
+            if u32::try_from(bytes).is_err() {
+                return Err(format!("Column {} exceeds 4 GiB", index + 1).into());
+            }
+            column.data.reserve_exact(bytes);
+            column.ends.reserve_exact(records.len());
+            for record in records {
+                column.data.push_str(record.get(index).unwrap_or(""));
+                column.ends.push(column.data.len() as u32);
+            }
+        }
+
+        Ok(Self {
+            columns,
+            lines: records.iter().map(Fields::line).collect(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Value at `row`, `column`. Panics if `row` is out of range.
+    #[inline]
+    pub fn get(&self, row: usize, column: usize) -> Option<&str> {
+        self.columns.get(column).map(|c| c.get(row))
+    }
+
+    /// View of the row at `index`. Panics if it is out of range.
+    pub fn row(&self, index: usize) -> Row<'_> {
+        assert!(index < self.len(), "row {} out of range for {} rows", index, self.len());
+        Row { store: self, index }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Row<'_>> {
+        (0..self.len()).map(move |index| Row { store: self, index })
+    }
+
+    /// All values of one column in row order.
+    pub fn column(&self, column: usize) -> impl Iterator<Item = &str> {
+        let column = &self.columns[column];
+        (0..column.ends.len()).map(move |row| column.get(row))
+    }
+}
+
+/// A row of a `ColumnStore`.
+#[derive(Clone, Copy)]
+pub struct Row<'a> {
+    store: &'a ColumnStore,
+    index: usize,
+}
+
+impl<'a> Row<'a> {
+    pub fn get(&self, column: usize) -> Option<&'a str> {
+        self.store.get(self.index, column)
+    }
+}
+
+impl Fields for Row<'_> {
+    #[inline]
+    fn field(&self, index: usize) -> Option<&str> {
+        self.get(index)
+    }
+
+    fn line(&self) -> Option<u64> {
+        self.store.lines[self.index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_matches_records() {
+        let csv = "id,name,note\n1,Alice,\"two\nlines\"\n\n2,,x\n3,Carol,\n";
+        let (headers, rows, _) = crate::parse::parse_csv_internal(csv, true).unwrap();
+        let store = ColumnStore::from_records(&rows, headers.len()).unwrap();
+
+        assert_eq!(store.len(), rows.len());
+        for (record, row) in rows.iter().zip(store.iter()) {
+            let fields: Vec<_> = (0..headers.len()).filter_map(|i| row.field(i)).collect();
+            assert_eq!(fields, record.iter().collect::<Vec<_>>());
+            assert_eq!(row.line(), record.line());
+        }
+        assert_eq!(store.column(1).collect::<Vec<_>>(), vec!["Alice", "", "Carol"]);
+        assert_eq!(store.get(0, 3), None);
+    }
+
+    #[test]
+    fn test_store_is_smaller_than_records() {
+        let csv: String = (0..1000).map(|i| format!("{},name{},{}\n", i, i, i % 7)).collect();
+        let (headers, rows, _) = crate::parse::parse_csv_internal(&csv, false).unwrap();
+        let store = ColumnStore::from_records(&rows, headers.len()).unwrap();
+
+        let columns: usize = store.columns.iter().map(|c| c.data.capacity() + c.ends.capacity() * 4).sum();
+        let store_bytes = columns + store.lines.capacity() * std::mem::size_of::<Option<u64>>();
+
+        // Per record: 88 bytes of boxed inner state (position, buffer and
+        // bounds headers), the field buffer and 8-byte bounds
+        let record_bytes: usize = rows.iter()
+            .map(|r| 88 + r.as_slice().len() + r.len() * std::mem::size_of::<usize>())
+            .sum::<usize>() + rows.capacity() * std::mem::size_of::<StringRecord>();
+        assert!(store_bytes * 2 <= record_bytes, "{} vs {}", store_bytes, record_bytes);
+    }
+}
@@ -1,7 +1,16 @@
 use crate::types::*;
+use crate::summary::change_kind;
 use crate::utils::*;
-use super::parse::parse_csv_streaming;
+use crate::options::DiffOptions;
+use crate::normalize::Normalizer;
+use crate::similarity::{CellScorer, WeightedScore};
+use crate::blocking::LshIndex;
+use crate::columnar::Fields;
+use crate::messages::{codes, emit, progress_sink, Message};
+use super::parse::{parse_pair_streaming, ParsedCsv};
+use crate::mapping::{map_target_columns, resolve_excluded_columns, schema_changes};
 use ahash::{AHashMap, AHashSet};
+use csv::StringRecord;
 
 pub fn diff_csv_internal<F>(
     source_csv: &str,
@@ -11,272 +20,577 @@ pub fn diff_csv_internal<F>(
     ignore_empty_vs_null: bool,
     excluded_columns: Vec<String>,
     has_headers: bool,
-    mut on_progress: F,
+    on_progress: F,
 ) -> Result<DiffResult, Box<dyn std::error::Error>>
 where
     F: FnMut(f64, &str),
 {
-    // Use streaming parser for better memory efficiency and progress reporting
-    let (source_headers, source_rows, source_header_map) = parse_csv_streaming(
-        source_csv, 
-        has_headers, 
-        5000,
-        |percent, message| {
-            on_progress(percent * 0.1, &format!("Source: {}", message)); // Scale to 0-10%
-        }
-    )?;
+    let options = DiffOptions::from_flags(
+        "content-match",
+        vec![],
+        case_sensitive,
+        ignore_whitespace,
+        ignore_empty_vs_null,
+        excluded_columns,
+        has_headers,
+    );
+    diff_content_match_with_options(source_csv, target_csv, &options, on_progress)
+}
 
-    let (target_headers_orig, target_rows_orig, target_header_map_orig) = parse_csv_streaming(
-        target_csv, 
-        has_headers, 
-        5000,
-        |percent, message| {
-            on_progress(10.0 + percent * 0.1, &format!("Target: {}", message)); // Scale to 10-20%
-        }
-    )?;
+/// Content-match diff driven by a full `DiffOptions` set
+pub fn diff_content_match_with_options<F>(
+    source_csv: &str,
+    target_csv: &str,
+    options: &DiffOptions,
+    on_progress: F,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let mut on_progress = progress_sink(options.parse.message_format, on_progress);
+    let (source, target) = parse_pair_streaming(source_csv, target_csv, &options.parse, &options.streaming, &mut on_progress)?;
+    compare_content_match(&source, &target, options, &mut on_progress)
+}
 
-    let (target_headers, target_rows, target_header_map) = if source_headers != target_headers_orig && source_headers.len() == target_headers_orig.len() {
-        (source_headers.clone(), target_rows_orig, source_header_map.clone())
-    } else {
-        (target_headers_orig, target_rows_orig, target_header_map_orig)
-    };
+/// Content-match comparison of already parsed tables; progress picks up at
+/// 20%, where parsing ends. `on_progress` must already be a progress sink.
+pub(crate) fn compare_content_match<F>(
+    (source_headers, source_rows, source_header_map): &ParsedCsv,
+    (target_headers, target_rows, target_header_map): &ParsedCsv,
+    options: &DiffOptions,
+    on_progress: &mut F,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let normalizer = Normalizer::new(options)?;
+    let scorer = CellScorer::new(&options.similarity, &normalizer);
+    let (mut target_headers, mut target_header_map) = (target_headers.clone(), target_header_map.clone());
+
+    let renamed_columns = map_target_columns(
+        (source_headers, source_rows),
+        (&mut target_headers, &mut target_header_map, target_rows),
+        options,
+    )?;
+    let schema_changes = schema_changes(source_headers, &target_headers, options, &renamed_columns);
+    let (excluded_columns, warnings) = resolve_excluded_columns(source_headers, &target_headers, options)?;
 
     let mut added = Vec::new();
     let mut removed = Vec::new();
     let mut modified = Vec::new();
-    let mut unchanged = Vec::new();
-
-    on_progress(20.0, "Building fingerprint index for exact matches...");
-
-    // Use HashSet for excluded columns for O(1) lookup
-    let excluded_set: AHashSet<String> = excluded_columns.iter().cloned().collect();
-
-    // Track unmatched target rows
-    let mut unmatched_target_indices: AHashSet<usize> = (0..target_rows.len()).collect();
-
-    // Build fingerprint lookup for exact matches only (optimized)
-    let mut target_fingerprint_lookup: AHashMap<String, Vec<usize>> = AHashMap::new();
-    for (idx, row) in target_rows.iter().enumerate() {
-        let fp = crate::utils::get_row_fingerprint_fast(
-            row,
-            &source_headers,
-            &target_header_map,
-            case_sensitive,
-            ignore_whitespace,
-            ignore_empty_vs_null,
-            &excluded_set
-        );
-        target_fingerprint_lookup.entry(fp).or_default().push(idx);
-    }
+    let mut unchanged = UnchangedRows::new(options.include_unchanged);
+
+    emit(on_progress, 20.0, Message::new(codes::BUILD_FINGERPRINT_INDEX));
+
+    // Columns rows pair on: all compared ones, or the identity columns
+    let identity = PairingColumns::new(options, source_headers, source_header_map, &target_header_map, &excluded_columns)?;
+    let fingerprints = |rows: &[StringRecord], header_map| -> Vec<u64> {
+        rows.iter().map(|row| identity.fingerprint(row, header_map, &normalizer)).collect()
+    };
 
-    // Build value lookup for fuzzy matching optimization
-    let mut target_value_lookup: AHashMap<(usize, String), Vec<usize>> = AHashMap::new();
+    // Blocking index of the target rows for fuzzy matching
+    let mut target_index = LshIndex::new(options.hash_state());
     for (row_idx, row) in target_rows.iter().enumerate() {
-        for (col_idx, cell) in row.iter().enumerate() {
-            let header = &target_headers[col_idx];
-            if excluded_set.contains(header) {
-                continue;
-            }
-            let trimmed = cell.trim();
-            if trimmed.is_empty() {
-                continue;
-            }
-            let key = if case_sensitive {
-                (col_idx, if ignore_whitespace { trimmed.to_string() } else { cell.to_string() })
-            } else {
-                (col_idx, if ignore_whitespace { trimmed.to_lowercase() } else { cell.to_lowercase() })
-            };
-            target_value_lookup.entry(key).or_default().push(row_idx);
+        if let Some(signature) = target_index.signature(row, identity.columns.iter().map(|&(h, _, t)| (h, t)), &normalizer) {
+            target_index.insert(row_idx, &signature);
         }
     }
-    
-    let mut row_counter = 1;
+
+    let mut pairing = RowPairing::new(
+        &fingerprints(source_rows, source_header_map),
+        &fingerprints(target_rows, &target_header_map),
+        target_index,
+        &identity,
+        options,
+    );
+    let target_row = |idx: usize| &target_rows[idx];
     let total_rows = source_rows.len();
 
-    on_progress(30.0, "Matching rows using strsim algorithms...");
+    emit(on_progress, 30.0, Message::new(codes::MATCH_FUZZY));
 
     for (i, source_row) in source_rows.iter().enumerate() {
-        if i % 100 == 0 {
+        // Fuzzy matching is slow per row, so it reports in 100-row steps
+        if i % 100 == 0 && options.streaming.reports_after(i / 100) {
             let progress = 30.0 + (i as f64 / total_rows as f64) * 60.0;
-            on_progress(progress, "Comparing rows with fuzzy matching...");
+            emit(on_progress, progress, Message::new(codes::MATCH_FUZZY));
         }
 
-        // First try exact match via fingerprint
-        let source_fingerprint = crate::utils::get_row_fingerprint_fast(
-            source_row,
-            &source_headers,
-            &source_header_map,
-            case_sensitive,
-            ignore_whitespace,
-            ignore_empty_vs_null,
-            &excluded_set
-        );
-
-        let mut matched_exact = false;
-        if let Some(indices) = target_fingerprint_lookup.get_mut(&source_fingerprint) {
-            while let Some(target_idx) = indices.pop() {
-                if unmatched_target_indices.contains(&target_idx) {
-                    unchanged.push(UnchangedRow {
-                        key: format!("Row {}", row_counter),
-                        row: record_to_hashmap(source_row, &source_headers),
+        let (idx, score, differences) = match pairing.exact(i) {
+            Some(idx) => {
+                let differences = pairing.exact_differences(source_row, &target_rows[idx], &normalizer, true);
+                if differences.is_empty() {
+                    unchanged.push_with(|| UnchangedRow {
+                        key: format!("Row {}", i + 1),
+                        row: record_to_hashmap(source_row, source_headers),
+                        source_index: Some(i),
+                        target_index: Some(idx),
                     });
-                    unmatched_target_indices.remove(&target_idx);
-                    matched_exact = true;
-                    break;
-                }
-            }
-        }
-
-        // If no exact match, use strsim-based fuzzy matching
-        if !matched_exact {
-            let mut best_match_idx: Option<usize> = None;
-            let mut best_similarity_score = 0.0;
-
-            // Optimization: Find candidates that share at least one value
-            let mut candidates: AHashSet<usize> = AHashSet::new();
-            
-            for (col_idx, cell) in source_row.iter().enumerate() {
-                let header = &source_headers[col_idx];
-                if excluded_columns.contains(header) {
-                    continue;
-                }
-                if cell.trim().is_empty() {
                     continue;
                 }
-                
-                if let Some(&target_col_idx) = target_header_map.get(header) {
-                     let key = (target_col_idx, cell.to_string());
-                     if let Some(indices) = target_value_lookup.get(&key) {
-                         for &idx in indices {
-                             if unmatched_target_indices.contains(&idx) {
-                                 candidates.insert(idx);
-                             }
-                         }
-                     }
-                }
-            }
-
-            // Calculate similarity only with candidates
-            for &target_idx in candidates.iter() {
-                let target_row = &target_rows[target_idx];
-                
-                let similarity = calculate_row_similarity(
-                    source_row,
-                    target_row,
-                    &source_headers,
-                    &source_header_map,
-                    &target_header_map,
-                    &excluded_columns,
-                );
-
-                if similarity > best_similarity_score {
-                    best_similarity_score = similarity;
-                    best_match_idx = Some(target_idx);
-                }
+                (idx, 1.0, differences)
             }
-
-            // Threshold for considering a match (50% similarity)
-            if let Some(idx) = best_match_idx {
-                if best_similarity_score > 0.5 {
-                    let target_row = &target_rows[idx];
-                    let mut differences = Vec::new();
-
-                    for header in &source_headers {
-                        if excluded_columns.contains(header) {
-                            continue;
-                        }
-
-                        let source_idx = source_header_map.get(header).unwrap();
-                        let target_idx = match target_header_map.get(header) {
-                            Some(idx) => idx,
-                            None => continue,
-                        };
-
-                        let source_val_raw = source_row.get(*source_idx).unwrap_or("");
-                        let target_val_raw = target_row.get(*target_idx).unwrap_or("");
-
-                        let source_val = normalize_value_with_empty_vs_null(
-                            source_val_raw,
-                            case_sensitive,
-                            ignore_whitespace,
-                            ignore_empty_vs_null
-                        );
-                        let target_val = normalize_value_with_empty_vs_null(
-                            target_val_raw,
-                            case_sensitive,
-                            ignore_whitespace,
-                            ignore_empty_vs_null
-                        );
-
-                        if source_val != target_val {
-                            let diffs = crate::core::diff_text_internal(source_val_raw, target_val_raw, case_sensitive);
-
-                            differences.push(Difference {
-                                column: header.clone(),
-                                old_value: source_val_raw.to_string(),
-                                new_value: target_val_raw.to_string(),
-                                diff: diffs,
-                            });
-                        }
-                    }
-
-                    modified.push(ModifiedRow {
-                        key: format!("Row {}", row_counter),
-                        source_row: record_to_hashmap(source_row, &source_headers),
-                        target_row: record_to_hashmap(target_row, &target_headers),
-                        differences,
-                    });
-                    unmatched_target_indices.remove(&idx);
+            None => {
+                let paired = if pairing.surplus(i) {
+                    None
                 } else {
-                    // Similarity too low, consider as removed
-                    removed.push(RemovedRow {
-                        key: format!("Removed {}", removed.len() + 1),
-                        source_row: record_to_hashmap(source_row, &source_headers),
-                    });
+                    let best = pairing.score(source_row, target_row, &normalizer, &scorer);
+                    pairing.pair(source_row, best, target_row, &normalizer, &scorer)
+                };
+                match paired {
+                    Some((idx, score)) => (idx, score, pairing.differences(source_row, &target_rows[idx], &normalizer, true)),
+                    None => {
+                        // Surplus copy or no candidate similar enough, consider as removed
+                        removed.push(RemovedRow {
+                            key: format!("Removed {}", removed.len() + 1),
+                            source_row: record_to_hashmap(source_row, source_headers),
+                            source_line: record_line(source_row),
+                            source_index: Some(i),
+                        });
+                        continue;
+                    }
                 }
-            } else {
-                // No candidates at all
-                removed.push(RemovedRow {
-                    key: format!("Removed {}", removed.len() + 1),
-                    source_row: record_to_hashmap(source_row, &source_headers),
-                });
             }
-        }
-        row_counter += 1;
+        };
+
+        let target_row = &target_rows[idx];
+        modified.push(ModifiedRow {
+            key: format!("Row {}", i + 1),
+            source_row: record_to_hashmap(source_row, source_headers),
+            source_line: record_line(source_row),
+            target_row: record_to_hashmap(target_row, &target_headers),
+            target_line: record_line(target_row),
+            source_index: Some(i),
+            target_index: Some(idx),
+            match_score: Some(score),
+            target_key: None,
+            differences,
+        });
     }
 
     // All remaining unmatched target rows are added
-    on_progress(90.0, "Processing remaining rows...");
-    let mut added_index = 1;
-    let mut remaining_indices: Vec<_> = unmatched_target_indices.into_iter().collect();
-    remaining_indices.sort();
-
-    for idx in remaining_indices {
+    emit(on_progress, 90.0, Message::new(codes::REMAINING_ROWS));
+    for (n, idx) in pairing.unpaired_targets().into_iter().enumerate() {
         let row = &target_rows[idx];
         added.push(AddedRow {
-            key: format!("Added {}", added_index),
+            key: format!("Added {}", n + 1),
             target_row: record_to_hashmap(row, &target_headers),
+            target_line: record_line(row),
+            target_index: Some(idx),
         });
-        added_index += 1;
     }
 
-    on_progress(100.0, "Comparison complete");
+    let duplicate_counts = pairing.duplicate_counts(|idx| &source_rows[idx], source_headers);
+
+    emit(on_progress, 100.0, Message::new(codes::COMPLETE));
 
     Ok(DiffResult {
         added,
         removed,
         modified,
-        unchanged,
+        unchanged: unchanged.rows,
         source: DatasetMetadata {
             headers: source_headers.clone(),
-            rows: source_rows.iter().map(|r| record_to_hashmap(r, &source_headers)).collect(),
+            rows: dataset_rows(source_rows, source_headers, options.include_dataset_rows),
         },
         target: DatasetMetadata {
             headers: target_headers.clone(),
-            rows: target_rows.iter().map(|r| record_to_hashmap(r, &target_headers)).collect(),
+            rows: dataset_rows(target_rows, &target_headers, options.include_dataset_rows),
         },
         key_columns: vec![],
         excluded_columns: excluded_columns,
         mode: "content-match".to_string(),
-    })
+        summary: DiffSummary::default(),
+        warnings,
+        seed: options.seed,
+        fallback: None,
+        renamed_columns,
+        schema_changes,
+        reversed: false,
+        header_policy: options.header_policy,
+        moved_rows: Vec::new(),
+        duplicate_counts,
+        omitted_unchanged: unchanged.omitted,
+    }.with_summary().ordered(options.row_order))
+}
+
+/// Columns content match pairs rows on: every compared column or, with
+/// `identity_columns`, only those, excluded or not.
+pub struct PairingColumns<'a> {
+    /// Headers hashed into row fingerprints
+    headers: &'a [String],
+    excluded: AHashSet<String>,
+    /// Columns rows pair on
+    pub columns: Vec<ColumnPair<'a>>,
+    /// Columns diffed between paired rows
+    pub compared: Vec<ColumnPair<'a>>,
+    /// Whether `columns` are identity columns rather than all compared ones
+    pub by_identity: bool,
+}
+
+impl<'a> PairingColumns<'a> {
+    /// Pairing and compared columns of a run; identity columns must exist
+    /// in both files.
+    pub fn new(
+        options: &'a DiffOptions,
+        source_headers: &'a [String],
+        source_header_map: &AHashMap<String, usize>,
+        target_header_map: &AHashMap<String, usize>,
+        excluded_columns: &[String],
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let compared = compared_columns(source_headers, source_header_map, target_header_map, excluded_columns);
+        if options.identity_columns.is_empty() {
+            return Ok(Self {
+                headers: source_headers,
+                excluded: excluded_columns.iter().cloned().collect(),
+                columns: compared.clone(),
+                compared,
+                by_identity: false,
+            });
+        }
+
+        for column in &options.identity_columns {
+            if !source_header_map.contains_key(column) {
+                return Err(Message::new(codes::IDENTITY_COLUMN_MISSING).with("column", column).with("side", "source").into());
+            }
+            if !target_header_map.contains_key(column) {
+                return Err(Message::new(codes::IDENTITY_COLUMN_MISSING).with("column", column).with("side", "target").into());
+            }
+        }
+        let columns = source_headers.iter()
+            .filter(|h| options.identity_columns.contains(h))
+            .map(|h| (h, source_header_map[h], target_header_map[h]))
+            .collect();
+        Ok(Self { headers: &options.identity_columns, excluded: AHashSet::new(), columns, compared, by_identity: true })
+    }
+
+    /// Fingerprint of a row's pairing columns.
+    pub fn fingerprint<R: Fields + ?Sized>(&self, row: &R, header_map: &AHashMap<String, usize>, normalizer: &Normalizer) -> u64 {
+        get_row_fingerprint_hash(row, self.headers, header_map, normalizer, &self.excluded)
+    }
+}
+
+/// Row pairing shared by the content-match engines (sequential, parallel
+/// and chunked), so the same input pairs the same rows whichever API ran it.
+///
+/// Rows with equal fingerprints pair first, over all source rows in order.
+/// The rest then pair in source order with their most similar target row
+/// still open, if it scores above `MATCH_SIMILARITY_THRESHOLD`. Candidates
+/// come from the blocking index of the target rows left after exact
+/// matching, so a row's best candidate can be scored ahead of the pairing
+/// (in parallel) and is only rescored when an earlier row took it.
+///
+/// Fingerprints count as multisets: rows whose fingerprint occurs more
+/// often in one file than in the other pair as far as the other file has
+/// copies, and the surplus copies are removed or added rather than paired
+/// by similarity with some other row.
+///
+/// Fingerprints, the index and similarity cover the pairing columns only;
+/// differences cover all compared columns.
+pub struct RowPairing {
+    /// Pairing columns
+    identity: Vec<(String, usize, usize)>,
+    by_identity: bool,
+    /// Compared columns
+    columns: Vec<(String, usize, usize)>,
+    index: LshIndex,
+    max_candidates: Option<usize>,
+    /// `DiffOptions::modified_threshold`
+    modified_threshold: f64,
+    /// Target rows paired below `modified_threshold`, reported as added
+    split: AHashSet<usize>,
+    /// Target row of each source row with an equal fingerprint
+    exact: Vec<Option<usize>>,
+    /// Source rows without exact match whose fingerprint is in the target
+    surplus: AHashSet<usize>,
+    /// First source row, source count and target count of each fingerprint
+    /// occurring in both files a different number of times
+    count_deltas: Vec<(usize, usize, usize)>,
+    /// Target rows left after exact matching, but for surplus copies
+    remaining: AHashSet<usize>,
+    /// Target rows not paired yet
+    open: AHashSet<usize>,
+}
+
+impl RowPairing {
+    /// Pair rows with equal fingerprints; `index` holds the target rows'
+    /// signatures over the pairing columns of `identity`.
+    pub fn new(
+        source_fingerprints: &[u64],
+        target_fingerprints: &[u64],
+        index: LshIndex,
+        identity: &PairingColumns,
+        options: &DiffOptions,
+    ) -> Self {
+        let mut target_fingerprint_lookup: AHashMap<u64, Vec<usize>> = AHashMap::new();
+        for (idx, &fp) in target_fingerprints.iter().enumerate() {
+            target_fingerprint_lookup.entry(fp).or_default().push(idx);
+        }
+        let target_counts: AHashMap<u64, usize> = target_fingerprint_lookup.iter().map(|(&fp, indices)| (fp, indices.len())).collect();
+        let mut open: AHashSet<usize> = (0..target_fingerprints.len()).collect();
+        let exact: Vec<Option<usize>> = source_fingerprints.iter()
+            .map(|fp| {
+                let idx = target_fingerprint_lookup.get_mut(fp)?.pop()?;
+                open.remove(&idx);
+                Some(idx)
+            })
+            .collect();
+
+        let mut source_counts: AHashMap<u64, (usize, usize)> = AHashMap::new();
+        for (idx, &fp) in source_fingerprints.iter().enumerate() {
+            source_counts.entry(fp).or_insert((idx, 0)).1 += 1;
+        }
+        let surplus = (0..source_fingerprints.len())
+            .filter(|&idx| exact[idx].is_none() && target_counts.contains_key(&source_fingerprints[idx]))
+            .collect();
+        let remaining = open.iter()
+            .copied()
+            .filter(|&idx| !source_counts.contains_key(&target_fingerprints[idx]))
+            .collect();
+        let mut count_deltas: Vec<(usize, usize, usize)> = source_counts.iter()
+            .filter_map(|(fp, &(first, source_count))| {
+                let target_count = *target_counts.get(fp)?;
+                (source_count != target_count).then_some((first, source_count, target_count))
+            })
+            .collect();
+        count_deltas.sort_unstable();
+
+        let owned = |columns: &[ColumnPair]| columns.iter().map(|&(h, s, t)| (h.clone(), s, t)).collect();
+        Self {
+            identity: owned(&identity.columns),
+            by_identity: identity.by_identity,
+            columns: owned(&identity.compared),
+            index,
+            max_candidates: options.max_candidates_per_row,
+            modified_threshold: options.modified_threshold.unwrap_or(0.0),
+            split: AHashSet::new(),
+            exact,
+            surplus,
+            count_deltas,
+            remaining,
+            open,
+        }
+    }
+
+    /// Target row the source row at `source_idx` matched exactly.
+    pub fn exact(&self, source_idx: usize) -> Option<usize> {
+        self.exact[source_idx]
+    }
+
+    /// Whether the source row at `source_idx` is a surplus copy of rows
+    /// the target has fewer of; it pairs with nothing.
+    pub fn surplus(&self, source_idx: usize) -> bool {
+        self.surplus.contains(&source_idx)
+    }
+
+    /// Rows occurring in both files a different number of times, with both
+    /// counts, in source order.
+    pub fn duplicate_counts<S: Fields>(&self, source_row: impl Fn(usize) -> S, source_headers: &[String]) -> Vec<DuplicateCount> {
+        self.count_deltas.iter()
+            .map(|&(idx, source_count, target_count)| DuplicateCount {
+                row: record_to_hashmap(&source_row(idx), source_headers),
+                source_count,
+                target_count,
+            })
+            .collect()
+    }
+
+    /// Most similar candidate of a source row without exact match, with
+    /// its similarity, before any fuzzy pairing. Reads only `self`, so
+    /// rows can be scored in parallel.
+    pub fn score<S: Fields, T: Fields>(
+        &self,
+        source_row: &S,
+        target_row: impl Fn(usize) -> T,
+        normalizer: &Normalizer,
+        scorer: &CellScorer,
+    ) -> Option<(usize, f64)> {
+        self.best(source_row, target_row, normalizer, scorer, |_| true)
+    }
+
+    /// Pair the next source row without exact match, given its `score`;
+    /// rows must come in source order. Returns the paired target row and
+    /// similarity, or `None` when nothing open scores above the threshold
+    /// or the pair scores below `modified_threshold`; such a target row is
+    /// taken all the same and left unpaired.
+    pub fn pair<S: Fields, T: Fields>(
+        &mut self,
+        source_row: &S,
+        best: Option<(usize, f64)>,
+        target_row: impl Fn(usize) -> T,
+        normalizer: &Normalizer,
+        scorer: &CellScorer,
+    ) -> Option<(usize, f64)> {
+        // The best candidate is also the best open one unless it was taken
+        let best = match best {
+            Some((idx, _)) if !self.open.contains(&idx) => {
+                self.best(source_row, target_row, normalizer, scorer, |idx| self.open.contains(&idx))
+            }
+            best => best,
+        };
+        let (idx, similarity) = best.filter(|&(_, similarity)| similarity > MATCH_SIMILARITY_THRESHOLD)?;
+        self.open.remove(&idx);
+        if similarity < self.modified_threshold {
+            self.split.insert(idx);
+            return None;
+        }
+        Some((idx, similarity))
+    }
+
+    /// Target rows left unpaired, in index order.
+    pub fn unpaired_targets(&self) -> Vec<usize> {
+        let mut indices: Vec<usize> = self.open.union(&self.split).copied().collect();
+        indices.sort_unstable();
+        indices
+    }
+
+    /// Cell differences of an exact pair: none unless rows pair on identity
+    /// columns, whose equal fingerprints leave the other columns open.
+    pub fn exact_differences<S: Fields, T: Fields>(
+        &self,
+        source_row: &S,
+        target_row: &T,
+        normalizer: &Normalizer,
+        char_diffs: bool,
+    ) -> Vec<Difference> {
+        if !self.by_identity {
+            return Vec::new();
+        }
+        self.differences(source_row, target_row, normalizer, char_diffs)
+    }
+
+    /// Cell differences of a fuzzy pair, with character diffs if `char_diffs`.
+    pub fn differences<S: Fields, T: Fields>(
+        &self,
+        source_row: &S,
+        target_row: &T,
+        normalizer: &Normalizer,
+        char_diffs: bool,
+    ) -> Vec<Difference> {
+        self.columns.iter().filter_map(|(header, source_idx, target_idx)| {
+            let source_val = source_row.field(*source_idx).unwrap_or("");
+            let target_val = target_row.field(*target_idx).unwrap_or("");
+            if normalizer.normalize(header, source_val) == normalizer.normalize(header, target_val) {
+                return None;
+            }
+            Some(Difference {
+                column: header.clone(),
+                old_value: source_val.to_string(),
+                new_value: target_val.to_string(),
+                change_kind: change_kind(source_val, target_val),
+                diff: if char_diffs { normalizer.diff_values(header, source_val, target_val) } else { Vec::new() },
+            })
+        }).collect()
+    }
+
+    /// Highest-scoring candidate for which `is_open` holds; ties go to the
+    /// higher-ranked candidate, and a near-perfect score ends the search.
+    fn best<S: Fields, T: Fields>(
+        &self,
+        source_row: &S,
+        target_row: impl Fn(usize) -> T,
+        normalizer: &Normalizer,
+        scorer: &CellScorer,
+        is_open: impl Fn(usize) -> bool,
+    ) -> Option<(usize, f64)> {
+        let signature = self.index.signature(source_row, self.identity.iter().map(|(h, s, _)| (h, *s)), normalizer)?;
+        let shared = self.index.candidates(&signature, |idx| self.remaining.contains(&idx));
+
+        let mut best = None;
+        let mut best_similarity = 0.0;
+        for idx in ranked_candidates(shared, self.max_candidates).into_iter().filter(|&idx| is_open(idx)) {
+            let target_row = target_row(idx);
+            let mut score = WeightedScore::default();
+            for (header, source_idx, target_idx) in &self.identity {
+                let source_val = source_row.field(*source_idx).unwrap_or("");
+                let target_val = target_row.field(*target_idx).unwrap_or("");
+                score.add(scorer.score(header, source_val, target_val).0, scorer.weight(header));
+            }
+
+            let similarity = score.average();
+            if similarity > best_similarity {
+                best_similarity = similarity;
+                best = Some(idx);
+                if similarity >= EARLY_EXIT_SIMILARITY {
+                    break;
+                }
+            }
+        }
+        best.map(|idx| (idx, best_similarity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_columns_drive_pairing() {
+        // By whole rows, each source row resembles the other id's target row
+        let source = "id,name,city\n1,Alice,Paris\n2,Bob,Rome\n3,Carol,Oslo\n";
+        let target = "id,name,city\n2,Alice,Paris\n1,Bob,Rome\n3,Carol,Oslo\n";
+        let mut options = DiffOptions { mode: "content-match".to_string(), ..Default::default() };
+        let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+        assert_eq!(result.modified[0].differences.len(), 1);
+        assert_eq!(result.modified[0].differences[0].column, "id");
+
+        options.identity_columns = vec!["id".to_string()];
+        for use_parallel in [false, true] {
+            options.use_parallel = use_parallel;
+            let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            assert_eq!((result.unchanged.len(), result.modified.len(), result.removed.len(), result.added.len()), (1, 2, 0, 0));
+            let columns: Vec<&str> = result.modified[0].differences.iter().map(|d| d.column.as_str()).collect();
+            assert_eq!(columns, ["name", "city"]);
+            assert_eq!(result.modified[0].target_row["id"], "1");
+            assert_eq!(result.modified[0].match_score, Some(1.0));
+        }
+
+        options.identity_columns = vec!["sku".to_string()];
+        let error = crate::core::diff_with_options(source, target, &options, |_, _| {}).err().unwrap();
+        assert_eq!(error.to_string(), "Identity column \"sku\" not found in source dataset.");
+    }
+
+    #[test]
+    fn test_surplus_duplicates_stay_unpaired() {
+        // The third copy would otherwise take the near match of row 4
+        let source = "id,name\n1,Alice Smith\n1,Alice Smith\n1,Alice Smith\n1,Alice Smyth\n";
+        let target = "id,name\n1,Alice Smith\n1,Alice Smythe\n1,Alice Smith\n";
+        let mut options = DiffOptions { mode: "content-match".to_string(), ..Default::default() };
+        for use_parallel in [false, true] {
+            options.use_parallel = use_parallel;
+            let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            assert_eq!((result.unchanged.len(), result.modified.len(), result.removed.len(), result.added.len()), (2, 1, 1, 0));
+            assert_eq!(result.modified[0].key, "Row 4");
+            let score = result.modified[0].match_score.unwrap();
+            assert!(score > MATCH_SIMILARITY_THRESHOLD && score < 1.0, "{}", score);
+            assert_eq!(result.removed[0].source_line, Some(4));
+            assert_eq!(result.duplicate_counts.len(), 1);
+            assert_eq!(result.duplicate_counts[0].row["name"], "Alice Smith");
+            assert_eq!((result.duplicate_counts[0].source_count, result.duplicate_counts[0].target_count), (3, 2));
+
+            let reversed = result.reversed();
+            assert_eq!((reversed.duplicate_counts[0].source_count, reversed.duplicate_counts[0].target_count), (2, 3));
+        }
+    }
+
+    #[test]
+    fn test_modified_threshold_splits_weak_pairs() {
+        let source = "id,name,city\n1,Alice Smith,Paris\n2,Bob Jones,Rome\n";
+        let target = "id,name,city\n1,Alice Smith,Lyon\n7,Bob Jonas,Rome\n";
+        let mut options = DiffOptions { mode: "content-match".to_string(), ..Default::default() };
+        let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+        assert_eq!(result.modified.len(), 2);
+        let scores: Vec<f64> = result.modified.iter().map(|row| row.match_score.unwrap()).collect();
+        assert!(scores[1] < scores[0], "{:?}", scores);
+
+        // Only the weaker pair splits; its target row is not paired elsewhere
+        options.modified_threshold = Some((scores[0] + scores[1]) / 2.0);
+        for use_parallel in [false, true] {
+            options.use_parallel = use_parallel;
+            let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            assert_eq!((result.modified.len(), result.removed.len(), result.added.len()), (1, 1, 1));
+            assert_eq!(result.modified[0].key, "Row 1");
+            assert_eq!(result.removed[0].source_row["id"], "2");
+            assert_eq!(result.added[0].target_row["id"], "7");
+        }
+    }
 }
@@ -1,11 +1,22 @@
 pub use crate::parse::parse_csv_internal;
 pub use crate::primary_key::diff_csv_primary_key_internal;
 pub use crate::content_match::diff_csv_internal;
-
-use csv::StringRecord;
-use ahash::{AHashMap, AHashSet};
-use similar::{ChangeTag, TextDiff};
+pub use crate::options::DiffOptions;
+use crate::options::{RowOrder, TextGranularity};
+
+use crate::columnar::ColumnStore;
+use crate::messages::{codes, render, Message, MessageError, MessageFormat};
+use crate::parse::ParsedCsv;
+use crate::profiling::{memory_size_bytes, PhaseTimer};
+use ahash::AHashMap;
+use similar::{capture_diff_slices, Algorithm, ChangeTag};
+use unicode_segmentation::UnicodeSegmentation;
 use crate::types::*;
+use crate::summary::change_kind;
+use crate::normalize::{KeyNormalization, Normalizer};
+use crate::similarity::CellScorer;
+use crate::blocking::LshIndex;
+use crate::content_match::{PairingColumns, RowPairing};
 use crate::utils::*;
 
 // Parsing and diff functions moved to dedicated modules: parse.rs, primary_key.rs, content_match.rs
@@ -14,21 +25,182 @@ use crate::utils::*;
 
 // Content-match diff function moved to `content_match.rs` and re-exported above
 
+/// Run a diff using the mode and engine selected in `options`, falling back
+/// from primary key to content match if `options.content_match_fallback`
+/// allows it, and reversed if `options.reverse` is set.
+pub fn diff_with_options<F>(
+    source_csv: &str,
+    target_csv: &str,
+    options: &DiffOptions,
+    mut on_progress: F,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let options = &*options.resolve_keys();
+    let result = diff_with_fallback(options, |options| run_engine(source_csv, target_csv, options, &mut on_progress))?;
+    // Reversal swaps the positions rows are ordered by
+    Ok(if options.reverse { result.reversed().ordered(options.row_order) } else { result })
+}
+
+/// `diff_with_options` over tables the caller already parsed, so it can
+/// keep them. `on_progress` must already be a progress sink; progress picks
+/// up at 20%, where parsing ends.
+pub(crate) fn diff_parsed<F>(
+    source: &ParsedCsv,
+    target: &ParsedCsv,
+    options: &DiffOptions,
+    on_progress: &mut F,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let options = &*options.resolve_keys();
+    let result = diff_with_fallback(options, |options| compare_engine(source, target, options, on_progress))?;
+    Ok(if options.reverse { result.reversed().ordered(options.row_order) } else { result })
+}
+
+/// Run `engine` with `options` and, when a primary-key run fails in a way
+/// content matching avoids, again in content-match mode.
+fn diff_with_fallback<E>(options: &DiffOptions, mut engine: E) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    E: FnMut(&DiffOptions) -> Result<DiffResult, Box<dyn std::error::Error>>,
+{
+    if !(options.is_primary_key() && options.content_match_fallback) {
+        return engine(options);
+    }
+    let keyed = engine(options);
+    let Some(reason) = keyed.as_ref().err().and_then(|error| fallback_reason(error.as_ref())) else {
+        return keyed;
+    };
+    let content = DiffOptions { mode: "content-match".to_string(), ..options.clone() };
+    let mut result = engine(&content)?;
+    result.fallback = Some(render(&reason.encode(), options.parse.message_format).into_owned());
+    Ok(result)
+}
+
+/// Why a failed primary-key run can be redone by content: a key column is
+/// missing or a key repeats.
+pub(crate) fn fallback_reason(error: &(dyn std::error::Error + 'static)) -> Option<Message> {
+    let MessageError(message) = error.downcast_ref::<MessageError>()?;
+    let code = match message.code.as_str() {
+        codes::KEY_COLUMN_MISSING => codes::FALLBACK_KEY_COLUMN_MISSING,
+        codes::DUPLICATE_KEY => codes::FALLBACK_DUPLICATE_KEY,
+        _ => return None,
+    };
+    Some(Message { code: code.to_string(), ..message.clone() })
+}
+
+fn run_engine<F>(
+    source_csv: &str,
+    target_csv: &str,
+    options: &DiffOptions,
+    on_progress: F,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    if options.is_positional() {
+        return crate::positional::diff_positional_with_options(source_csv, target_csv, options, on_progress);
+    }
+    if options.is_sequence() {
+        return crate::positional::diff_sequence_with_options(source_csv, target_csv, options, on_progress);
+    }
+    match (options.is_primary_key(), options.use_parallel) {
+        (true, true) => crate::parallel::diff_primary_key_parallel_with_options(source_csv, target_csv, options, on_progress),
+        (true, false) => crate::primary_key::diff_primary_key_with_options(source_csv, target_csv, options, on_progress),
+        (false, true) => crate::parallel::diff_content_match_parallel_with_options(source_csv, target_csv, options, on_progress),
+        (false, false) => crate::content_match::diff_content_match_with_options(source_csv, target_csv, options, on_progress),
+    }
+}
+
+/// `run_engine` over already parsed tables.
+fn compare_engine<F>(
+    source: &ParsedCsv,
+    target: &ParsedCsv,
+    options: &DiffOptions,
+    on_progress: &mut F,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    if options.is_positional() {
+        return crate::positional::compare_positional(source, target, options, on_progress);
+    }
+    if options.is_sequence() {
+        return crate::positional::compare_sequence(source, target, options, on_progress);
+    }
+    match (options.is_primary_key(), options.use_parallel) {
+        (true, true) => crate::parallel::compare_primary_key_parallel(source, target, options, on_progress),
+        (true, false) => crate::primary_key::compare_parsed(source, target, options, on_progress),
+        (false, true) => crate::parallel::compare_content_match_parallel(source, target, options, on_progress),
+        (false, false) => crate::content_match::compare_content_match(source, target, options, on_progress),
+    }
+}
+
+/// `diff_with_options`, then report anonymous performance metrics of the
+/// run to `on_metrics`. Phases are timed from the engine's progress messages.
+pub fn diff_with_metrics<F, M>(
+    source_csv: &str,
+    target_csv: &str,
+    options: &DiffOptions,
+    mut on_progress: F,
+    on_metrics: M,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+    M: FnOnce(&DiffMetrics),
+{
+    // Run with message codes so phases can be recognized whatever the
+    // caller's format, and render for the caller here instead
+    let mut coded = options.clone();
+    coded.parse.message_format = MessageFormat::Code;
+    let mut timer = PhaseTimer::new();
+    let result = diff_with_options(source_csv, target_csv, &coded, |percent, raw| {
+        timer.observe(raw);
+        on_progress(percent, &render(raw, options.parse.message_format));
+    })?;
+
+    let (total_ms, phases) = timer.finish();
+    let counts = crate::summary::summarize(&result);
+    let paired = counts.modified + counts.unchanged;
+    let strategy = if options.use_parallel { format!("{}-parallel", result.mode) } else { result.mode.clone() };
+    on_metrics(&DiffMetrics {
+        engine_version: env!("CARGO_PKG_VERSION").to_string(),
+        mode: result.mode.clone(),
+        strategy,
+        source_rows: counts.removed + paired,
+        target_rows: counts.added + paired,
+        columns: result.source.headers.len(),
+        total_ms,
+        phases,
+        memory_bytes: memory_size_bytes(),
+    });
+    Ok(result)
+}
+
+/// Word-level changes from `old` to `new`; see `diff_text_with_granularity`.
 pub fn diff_text_internal(old: &str, new: &str, case_sensitive: bool) -> Vec<DiffChange> {
-    let old_lower;
-    let new_lower;
-    
-    let diff = if case_sensitive {
-        TextDiff::from_words(old, new)
+    diff_text_with_granularity(old, new, case_sensitive, TextGranularity::default())
+}
+
+/// Changes from `old` to `new` in `granularity` units, semantically
+/// cleaned up (see `cleanup_semantic`). Without `case_sensitive`, units
+/// compare lowercased but the changes keep the original text; unchanged
+/// units read as in `old`.
+pub fn diff_text_with_granularity(old: &str, new: &str, case_sensitive: bool, granularity: TextGranularity) -> Vec<DiffChange> {
+    let old_units = text_units(old, granularity);
+    let new_units = text_units(new, granularity);
+    let ops = if case_sensitive {
+        capture_diff_slices(Algorithm::Myers, &old_units, &new_units)
     } else {
-        old_lower = old.to_lowercase();
-        new_lower = new.to_lowercase();
-        TextDiff::from_words(&old_lower, &new_lower)
+        let lowercase = |units: &[&str]| -> Vec<String> { units.iter().map(|unit| unit.to_lowercase()).collect() };
+        capture_diff_slices(Algorithm::Myers, &lowercase(&old_units), &lowercase(&new_units))
     };
 
     let mut changes = Vec::new();
 
-    for change in diff.iter_all_changes() {
+    for change in ops.iter().flat_map(|op| op.iter_changes(&old_units, &new_units)) {
         let (added, removed) = match change.tag() {
             ChangeTag::Delete => (false, true),
             ChangeTag::Insert => (true, false),
@@ -39,36 +211,170 @@ pub fn diff_text_internal(old: &str, new: &str, case_sensitive: bool) -> Vec<Dif
             added,
             removed,
             value: change.value().to_string(),
+            path: None,
+            intraline: Vec::new(),
         });
     }
+    let mut changes = cleanup_semantic(changes);
+    if granularity == TextGranularity::Word {
+        refine_intraline(&mut changes);
+    }
     changes
 }
 
+/// `text` split into `granularity` units: grapheme clusters, Unicode word
+/// boundaries (each run of spaces or punctuation is a unit too), or lines
+/// with their line breaks.
+fn text_units(text: &str, granularity: TextGranularity) -> Vec<&str> {
+    match granularity {
+        TextGranularity::Character => text.graphemes(true).collect(),
+        TextGranularity::Word => text.split_word_bounds().collect(),
+        TextGranularity::Line => text.split_inclusive('\n').collect(),
+    }
+}
+
+/// Give each removed word followed by its replacement the characters the
+/// two share and those that differ, so "-ORDER_ID_2023 +ORDER_ID_2024"
+/// highlights just "3" and "4". Pairs with nothing in common stay as they
+/// are.
+fn refine_intraline(changes: &mut [DiffChange]) {
+    for pair in changes.chunk_by_mut(|a, b| a.removed && b.added) {
+        let [old, new] = pair else { continue };
+        let characters = diff_text_with_granularity(&old.value, &new.value, true, TextGranularity::Character);
+        if characters.iter().all(|change| change.added || change.removed) {
+            continue;
+        }
+        old.intraline = characters.iter().filter(|change| !change.added).cloned().collect();
+        new.intraline = characters.into_iter().filter(|change| !change.removed).collect();
+    }
+}
+
+/// Stretch of a text diff: unchanged text, or text replaced by other text.
+enum Fragment {
+    Equal(String),
+    Edit { removed: String, added: String },
+}
+
+impl Fragment {
+    /// Characters of the longer side of an edit.
+    fn edit_len(&self) -> usize {
+        match self {
+            Fragment::Equal(_) => 0,
+            Fragment::Edit { removed, added } => removed.chars().count().max(added.chars().count()),
+        }
+    }
+}
+
+/// Readable form of a text diff, after diff-match-patch's semantic
+/// cleanup: adjacent removals and additions merge into one removal followed
+/// by one addition, and an unchanged island no longer than the edits on
+/// both sides of it (a lone space or letter between two changed words)
+/// joins them, so "-a +x = -b +y" reads as "-a b +x y".
+fn cleanup_semantic(changes: Vec<DiffChange>) -> Vec<DiffChange> {
+    let mut fragments: Vec<Fragment> = Vec::new();
+    for change in changes {
+        match (fragments.last_mut(), change.added || change.removed) {
+            (Some(Fragment::Edit { removed, added }), true) => {
+                if change.removed { removed.push_str(&change.value) } else { added.push_str(&change.value) }
+            }
+            (Some(Fragment::Equal(text)), false) => text.push_str(&change.value),
+            (_, true) if change.removed => fragments.push(Fragment::Edit { removed: change.value, added: String::new() }),
+            (_, true) => fragments.push(Fragment::Edit { removed: String::new(), added: change.value }),
+            (_, false) => fragments.push(Fragment::Equal(change.value)),
+        }
+        // Absorb an unchanged island between the last two edits
+        while let [.., before @ Fragment::Edit { .. }, Fragment::Equal(island), after @ Fragment::Edit { .. }] = fragments.as_slice() {
+            let island_len = island.chars().count();
+            if island_len > before.edit_len() || island_len > after.edit_len() {
+                break;
+            }
+            let (Some(Fragment::Edit { removed: after_removed, added: after_added }), Some(Fragment::Equal(island))) =
+                (fragments.pop(), fragments.pop()) else { unreachable!() };
+            if let Some(Fragment::Edit { removed, added }) = fragments.last_mut() {
+                removed.push_str(&island);
+                removed.push_str(&after_removed);
+                added.push_str(&island);
+                added.push_str(&after_added);
+            }
+        }
+    }
+
+    let mut cleaned = Vec::with_capacity(fragments.len() * 2);
+    for fragment in fragments {
+        let parts = match fragment {
+            Fragment::Equal(value) => vec![(false, false, value)],
+            Fragment::Edit { removed, added } => vec![(false, true, removed), (true, false, added)],
+        };
+        for (added, removed, value) in parts {
+            if !value.is_empty() {
+                cleaned.push(DiffChange { added, removed, value, path: None, intraline: Vec::new() });
+            }
+        }
+    }
+    cleaned
+}
+
+/// Primary key of every row. A single key column is read as one contiguous
+/// column scan instead of row by row.
+fn row_keys(
+    rows: &ColumnStore,
+    header_map: &AHashMap<String, usize>,
+    key_columns: &[String],
+    normalization: &KeyNormalization,
+) -> Vec<String> {
+    match key_columns {
+        [key] => match header_map.get(key) {
+            Some(&index) => rows.column(index).map(|value| normalization.apply(key, value).into_owned()).collect(),
+            None => vec![String::new(); rows.len()],
+        },
+        _ => rows.iter().map(|row| get_row_key(&row, header_map, key_columns, normalization)).collect(),
+    }
+}
+
 pub struct CsvDifferInternal {
     source_headers: Vec<String>,
-    source_rows: Vec<StringRecord>,
+    source_rows: ColumnStore,
     source_header_map: AHashMap<String, usize>,
     target_headers: Vec<String>,
-    target_rows: Vec<StringRecord>,
+    target_rows: ColumnStore,
     target_header_map: AHashMap<String, usize>,
     
     key_columns: Vec<String>,
+    key_normalization: KeyNormalization,
     excluded_columns: Vec<String>,
-    case_sensitive: bool,
-    ignore_whitespace: bool,
-    ignore_empty_vs_null: bool,
+    warnings: Vec<String>,
+    renamed_columns: Vec<crate::mapping::MappingProposal>,
+    schema_changes: crate::types::SchemaChanges,
+    normalizer: Normalizer,
     mode: String,
+    streaming: crate::streaming::StreamingConfig,
+    chunk_size: usize,
+    /// `DiffOptions::seed`, for the order-sensitive maps and sets below
+    seed: u64,
+    similarity: crate::similarity::SimilarityOptions,
+    /// `DiffOptions::reverse`, applied to every chunk's result
+    reverse: bool,
+    header_policy: crate::mapping::HeaderPolicy,
+    report_order_changes: bool,
+    /// `DiffOptions::include_unchanged`
+    include_unchanged: bool,
+    /// `DiffOptions::row_order`, applied within each chunk
+    row_order: RowOrder,
 
     // PK Mode State
     source_map: Option<AHashMap<String, usize>>,
     target_map: Option<AHashMap<String, usize>>,
+    /// Columns that differ on at least one keyed pair, with their indices
+    changed_columns: Option<Vec<(String, usize, usize)>>,
 
     // Content Match Mode State
-    unmatched_target_indices: Option<AHashSet<usize>>,
-    target_fingerprint_lookup: Option<AHashMap<String, Vec<usize>>>,
+    pairing: Option<RowPairing>,
+    /// Removed rows reported by earlier chunks, for their keys
+    removed_count: usize,
 }
 
 impl CsvDifferInternal {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         source_csv: &str,
         target_csv: &str,
@@ -80,16 +386,63 @@ impl CsvDifferInternal {
         has_headers: bool,
         mode: String,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        // Parse CSVs
-        let (source_headers, source_rows, source_header_map) = parse_csv_internal(source_csv, has_headers)?;
-        let (target_headers_orig, target_rows_orig, target_header_map_orig) = parse_csv_internal(target_csv, has_headers)?;
+        let options = DiffOptions::from_flags(
+            &mode,
+            key_columns,
+            case_sensitive,
+            ignore_whitespace,
+            ignore_empty_vs_null,
+            excluded_columns,
+            has_headers,
+        );
+        Self::with_options(source_csv, target_csv, &options)
+    }
 
-        // Align headers for Content Match if needed
-        let (target_headers, target_rows, target_header_map) = if mode == "content-match" && source_headers != target_headers_orig && source_headers.len() == target_headers_orig.len() {
-            (source_headers.clone(), target_rows_orig, source_header_map.clone())
-        } else {
-            (target_headers_orig, target_rows_orig, target_header_map_orig)
-        };
+    /// Whether `with_options` accepts `options`: modes, duplicate-key
+    /// policies and pairing of unmatched keyed rows need every row at once
+    /// and have no chunked differ.
+    pub fn supports(options: &DiffOptions) -> bool {
+        !options.is_positional()
+            && !options.is_sequence()
+            && !(options.is_primary_key() && options.duplicate_keys == crate::options::DuplicateKeys::Group)
+            && !(options.is_primary_key() && options.pairs_leftover_rows())
+    }
+
+    /// Differ driven by a full `DiffOptions` set, including its `StreamingConfig`.
+    pub fn with_options(
+        source_csv: &str,
+        target_csv: &str,
+        options: &DiffOptions,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let options = &*options.resolve_keys();
+        if options.is_positional() {
+            return Err("Positional mode has no chunked differ; run it in one pass.".into());
+        }
+        if options.is_sequence() {
+            return Err("Sequence mode has no chunked differ; run it in one pass.".into());
+        }
+        if options.is_primary_key() && options.duplicate_keys == crate::options::DuplicateKeys::Group {
+            return Err("Grouped duplicate keys have no chunked differ; run the diff in one pass.".into());
+        }
+        if options.is_primary_key() && options.pairs_leftover_rows() {
+            return Err("Pairing unmatched keys has no chunked differ; run the diff in one pass.".into());
+        }
+        let mode = if options.is_primary_key() { "primary-key" } else { "content-match" }.to_string();
+
+        // Parse CSVs
+        let (source_headers, source_rows, source_header_map) = crate::parse::parse_csv_with_options(source_csv, &options.parse)?;
+        let (mut target_headers, target_rows, mut target_header_map) = crate::parse::parse_csv_with_options(target_csv, &options.parse)?;
+        let renamed_columns = crate::mapping::map_target_columns(
+            (&source_headers, &source_rows),
+            (&mut target_headers, &mut target_header_map, &target_rows),
+            options,
+        )?;
+        let schema_changes = crate::mapping::schema_changes(&source_headers, &target_headers, options, &renamed_columns);
+        let (excluded_columns, warnings) = crate::mapping::resolve_excluded_columns(&source_headers, &target_headers, options)?;
+
+        // Rows stay alive for every chunk, so keep them column-oriented
+        let source_rows = ColumnStore::from_records(&source_rows, source_headers.len())?;
+        let target_rows = ColumnStore::from_records(&target_rows, target_headers.len())?;
 
         let mut differ = CsvDifferInternal {
             source_headers,
@@ -98,28 +451,40 @@ impl CsvDifferInternal {
             target_headers,
             target_rows,
             target_header_map,
-            key_columns,
+            key_columns: options.key_columns.clone(),
+            key_normalization: options.key_normalization.clone(),
             excluded_columns,
-            case_sensitive,
-            ignore_whitespace,
-            ignore_empty_vs_null,
+            warnings,
+            renamed_columns,
+            schema_changes,
+            normalizer: Normalizer::new(options)?,
             mode: mode.clone(),
+            streaming: options.streaming.clone(),
+            chunk_size: options.streaming.chunk_size_for(source_csv),
+            seed: options.seed,
+            similarity: options.similarity.clone(),
+            reverse: options.reverse,
+            include_unchanged: options.include_unchanged,
+            row_order: options.row_order,
+            header_policy: options.header_policy,
+            report_order_changes: options.report_order_changes,
             source_map: None,
             target_map: None,
-            unmatched_target_indices: None,
-            target_fingerprint_lookup: None,
+            changed_columns: None,
+            pairing: None,
+            removed_count: 0,
         };
 
         if mode == "primary-key" {
-            differ.init_primary_key()?;
+            differ.init_primary_key(options)?;
         } else {
-            differ.init_content_match()?;
+            differ.init_content_match(options)?;
         }
 
         Ok(differ)
     }
 
-    fn init_primary_key(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    fn init_primary_key(&mut self, options: &DiffOptions) -> Result<(), Box<dyn std::error::Error>> {
         // Validation
         for key in &self.key_columns {
             if !self.source_header_map.contains_key(key) {
@@ -131,125 +496,122 @@ impl CsvDifferInternal {
         }
 
         // Build maps
-        let mut source_map = AHashMap::new();
-        for (i, row) in self.source_rows.iter().enumerate() {
-            let key = get_row_key(row, &self.source_header_map, &self.key_columns);
-            if source_map.contains_key(&key) {
-                 return Err(format!("Duplicate Primary Key found in source: \"{}\". Primary Keys must be unique.", key).into());
-            }
-            source_map.insert(key, i);
-        }
-
-        let mut target_map = AHashMap::new();
-        for (i, row) in self.target_rows.iter().enumerate() {
-            let key = get_row_key(row, &self.target_header_map, &self.key_columns);
-            if target_map.contains_key(&key) {
-                 return Err(format!("Duplicate Primary Key found in target: \"{}\". Primary Keys must be unique.", key).into());
-            }
-            target_map.insert(key, i);
-        }
-
+        let source_keys = row_keys(&self.source_rows, &self.source_header_map, &self.key_columns, &self.key_normalization);
+        let (source_map, _) = crate::primary_key::index_keys(source_keys, "source", options, &mut self.warnings)?;
+        let target_keys = row_keys(&self.target_rows, &self.target_header_map, &self.key_columns, &self.key_normalization);
+        let (target_map, _) = crate::primary_key::index_keys(target_keys, "target", options, &mut self.warnings)?;
+
+        let columns = changed_columns(
+            compared_columns(&self.source_headers, &self.source_header_map, &self.target_header_map, &self.excluded_columns),
+            target_map.iter().filter_map(|(key, &t)| Some((self.source_rows.row(*source_map.get(key)?), self.target_rows.row(t)))),
+            &self.normalizer,
+        );
+        self.changed_columns = Some(columns.into_iter().map(|(h, s, t)| (h.clone(), s, t)).collect());
         self.source_map = Some(source_map);
         self.target_map = Some(target_map);
         Ok(())
     }
 
-    fn init_content_match(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let unmatched_target_indices: AHashSet<usize> = (0..self.target_rows.len()).collect();
-        let mut target_fingerprint_lookup: AHashMap<String, Vec<usize>> = AHashMap::new();
-
-        // Build fingerprint lookup for exact matches only
-        for (idx, row) in self.target_rows.iter().enumerate() {
-            let fp = get_row_fingerprint(
-                row, 
-                &self.source_headers, 
-                &self.target_header_map,
-                self.case_sensitive, 
-                self.ignore_whitespace,
-                self.ignore_empty_vs_null,
-                &self.excluded_columns
-            );
-            target_fingerprint_lookup.entry(fp).or_default().push(idx);
+    fn init_content_match(&mut self, options: &DiffOptions) -> Result<(), Box<dyn std::error::Error>> {
+        // Columns rows pair on: all compared ones, or the identity columns
+        let identity = PairingColumns::new(options, &self.source_headers, &self.source_header_map, &self.target_header_map, &self.excluded_columns)?;
+        let fingerprints = |rows: &ColumnStore, header_map| -> Vec<u64> {
+            rows.iter().map(|row| identity.fingerprint(&row, header_map, &self.normalizer)).collect()
+        };
+
+        // Blocking index of the target rows for fuzzy matching
+        let mut target_index = LshIndex::new(options.hash_state());
+        for (row_idx, row) in self.target_rows.iter().enumerate() {
+            if let Some(signature) = target_index.signature(&row, identity.columns.iter().map(|&(h, _, t)| (h, t)), &self.normalizer) {
+                target_index.insert(row_idx, &signature);
+            }
         }
 
-        self.unmatched_target_indices = Some(unmatched_target_indices);
-        self.target_fingerprint_lookup = Some(target_fingerprint_lookup);
+        // Exact matches are paired here, for all chunks
+        let pairing = RowPairing::new(
+            &fingerprints(&self.source_rows, &self.source_header_map),
+            &fingerprints(&self.target_rows, &self.target_header_map),
+            target_index,
+            &identity,
+            options,
+        );
+        self.pairing = Some(pairing);
         Ok(())
     }
 
+    /// Chunk size to pass to `diff_chunk`: the configured one, or auto-tuned
+    /// from the source input.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Rows `diff_chunk` walks: target rows in primary-key mode, source
+    /// rows otherwise.
+    pub fn row_count(&self) -> usize {
+        if self.mode == "primary-key" { self.target_rows.len() } else { self.source_rows.len() }
+    }
+
     pub fn diff_chunk<F>(&mut self, chunk_start: usize, chunk_size: usize, on_progress: F) -> Result<DiffResult, Box<dyn std::error::Error>>
     where F: FnMut(f64, &str) {
-        if self.mode == "primary-key" {
-            self.diff_primary_key_chunk(chunk_start, chunk_size, on_progress)
+        let result = if self.mode == "primary-key" {
+            self.diff_primary_key_chunk(chunk_start, chunk_size, on_progress)?
         } else {
-            self.diff_content_match_chunk(chunk_start, chunk_size, on_progress)
-        }
+            self.diff_content_match_chunk(chunk_start, chunk_size, on_progress)?
+        };
+        let result = if self.reverse { result.reversed() } else { result };
+        Ok(result.ordered(self.row_order))
     }
 
     fn diff_primary_key_chunk<F>(&self, chunk_start: usize, chunk_size: usize, mut on_progress: F) -> Result<DiffResult, Box<dyn std::error::Error>>
     where F: FnMut(f64, &str) {
         let source_map = self.source_map.as_ref().unwrap();
         let target_map = self.target_map.as_ref().unwrap();
+        let changed_columns = self.changed_columns.as_ref().unwrap();
 
         let mut added = Vec::new();
         let mut removed = Vec::new();
         let mut modified = Vec::new();
-        let mut unchanged = Vec::new();
+        let mut unchanged = UnchangedRows::new(self.include_unchanged);
 
         // Iterate target rows by index to ensure stability
         let chunk_end = (chunk_start + chunk_size).min(self.target_rows.len());
         
         for i in chunk_start..chunk_end {
-            if (i - chunk_start) % 100 == 0 {
+            if (i - chunk_start) % 100 == 0 && self.streaming.reports_after((i - chunk_start) / 100) {
                 let chunk_progress = (i - chunk_start) as f64 / (chunk_end - chunk_start) as f64;
                 on_progress(chunk_progress * 100.0, &format!("Processing row {} of chunk...", i - chunk_start));
             }
 
-            let target_row = &self.target_rows[i];
-            let key = get_row_key(target_row, &self.target_header_map, &self.key_columns);
+            let target_row = &self.target_rows.row(i);
+            let key = get_row_key(target_row, &self.target_header_map, &self.key_columns, &self.key_normalization);
 
             match source_map.get(&key) {
                 None => {
                     added.push(AddedRow {
                         key: key.clone(),
                         target_row: record_to_hashmap(target_row, &self.target_headers),
+                        target_line: record_line(target_row),
+                        target_index: Some(i),
                     });
                 }
                 Some(&source_row_idx) => {
-                    let source_row = &self.source_rows[source_row_idx];
+                    let source_row = &self.source_rows.row(source_row_idx);
                     let mut differences = Vec::new();
                     
-                    for header in &self.source_headers {
-                        if self.excluded_columns.contains(header) { continue; }
-                        
-                        let source_idx = self.source_header_map.get(header).unwrap();
-                        let target_idx = match self.target_header_map.get(header) {
-                            Some(idx) => idx,
-                            None => continue,
-                        };
-
+                    for (header, source_idx, target_idx) in changed_columns {
                         let source_val_raw = source_row.get(*source_idx).unwrap_or("");
                         let target_val_raw = target_row.get(*target_idx).unwrap_or("");
 
-                        let source_val = normalize_value_with_empty_vs_null(
-                            source_val_raw,
-                            self.case_sensitive,
-                            self.ignore_whitespace,
-                            self.ignore_empty_vs_null
-                        );
-                        let target_val = normalize_value_with_empty_vs_null(
-                            target_val_raw,
-                            self.case_sensitive,
-                            self.ignore_whitespace,
-                            self.ignore_empty_vs_null
-                        );
+                        let source_val = self.normalizer.normalize(header, source_val_raw);
+                        let target_val = self.normalizer.normalize(header, target_val_raw);
 
                         if source_val != target_val {
-                            let diffs = diff_text_internal(source_val_raw, target_val_raw, self.case_sensitive);
+                            let diffs = self.normalizer.diff_values(header, source_val_raw, target_val_raw);
                             differences.push(Difference {
                                 column: header.clone(),
                                 old_value: source_val_raw.to_string(),
                                 new_value: target_val_raw.to_string(),
+                                change_kind: change_kind(source_val_raw, target_val_raw),
                                 diff: diffs,
                             });
                         }
@@ -259,13 +621,21 @@ impl CsvDifferInternal {
                         modified.push(ModifiedRow {
                             key: key.clone(),
                             source_row: record_to_hashmap(source_row, &self.source_headers),
+                            source_line: record_line(source_row),
                             target_row: record_to_hashmap(target_row, &self.target_headers),
+                            target_line: record_line(target_row),
+                            source_index: Some(source_row_idx),
+                            target_index: Some(i),
+                            match_score: None,
+                            target_key: None,
                             differences,
                         });
                     } else {
-                        unchanged.push(UnchangedRow {
+                        unchanged.push_with(|| UnchangedRow {
                             key: key.clone(),
                             row: record_to_hashmap(source_row, &self.source_headers),
+                            source_index: Some(source_row_idx),
+                            target_index: Some(i),
                         });
                     }
                 }
@@ -278,7 +648,9 @@ impl CsvDifferInternal {
                 if !target_map.contains_key(key) {
                     removed.push(RemovedRow {
                         key: key.clone(),
-                        source_row: record_to_hashmap(&self.source_rows[row_idx], &self.source_headers),
+                        source_row: record_to_hashmap(&self.source_rows.row(row_idx), &self.source_headers),
+                        source_line: record_line(&self.source_rows.row(row_idx)),
+                        source_index: Some(row_idx),
                     });
                 }
             }
@@ -288,156 +660,110 @@ impl CsvDifferInternal {
             added,
             removed,
             modified,
-            unchanged,
+            unchanged: unchanged.rows,
             source: DatasetMetadata { headers: self.source_headers.clone(), rows: vec![] },
             target: DatasetMetadata { headers: self.target_headers.clone(), rows: vec![] },
             key_columns: self.key_columns.clone(),
             excluded_columns: self.excluded_columns.clone(),
             mode: "primary-key".to_string(),
-        })
+            summary: DiffSummary::default(),
+            // Reported once, with the first chunk
+            warnings: if chunk_start == 0 { self.warnings.clone() } else { Vec::new() },
+            seed: self.seed,
+            fallback: None,
+            renamed_columns: if chunk_start == 0 { self.renamed_columns.clone() } else { Vec::new() },
+            schema_changes: if chunk_start == 0 { self.schema_changes.clone() } else { Default::default() },
+            reversed: false,
+            header_policy: self.header_policy,
+            moved_rows: if chunk_start == 0 && self.report_order_changes { crate::primary_key::moved_rows(source_map, target_map) } else { Vec::new() },
+            duplicate_counts: Vec::new(),
+            omitted_unchanged: unchanged.omitted,
+        }.with_summary())
     }
 
     fn diff_content_match_chunk<F>(&mut self, chunk_start: usize, chunk_size: usize, mut on_progress: F) -> Result<DiffResult, Box<dyn std::error::Error>>
     where F: FnMut(f64, &str) {
-        let unmatched_target_indices = self.unmatched_target_indices.as_mut().unwrap();
-        let target_fingerprint_lookup = self.target_fingerprint_lookup.as_mut().unwrap();
+        let pairing = self.pairing.as_mut().unwrap();
+        let scorer = CellScorer::new(&self.similarity, &self.normalizer);
+        let target_row = |idx: usize| self.target_rows.row(idx);
 
         let mut added = Vec::new();
         let mut removed = Vec::new();
         let mut modified = Vec::new();
-        let mut unchanged = Vec::new();
+        let mut unchanged = UnchangedRows::new(self.include_unchanged);
 
         let chunk_end = (chunk_start + chunk_size).min(self.source_rows.len());
-        let mut row_counter = chunk_start + 1;
 
-        for (i, source_row) in self.source_rows.iter().enumerate().skip(chunk_start).take(chunk_end - chunk_start) {
-             if (i - chunk_start) % 50 == 0 {
+        for i in chunk_start..chunk_end {
+            let source_row = &self.source_rows.row(i);
+             if (i - chunk_start) % 50 == 0 && self.streaming.reports_after((i - chunk_start) / 50) {
                 let chunk_progress = (i - chunk_start) as f64 / (chunk_end - chunk_start) as f64;
                 on_progress(chunk_progress * 100.0, &format!("Fuzzy matching row {} of chunk...", i - chunk_start));
             }
 
-            // Try exact match via fingerprint first
-            let source_fingerprint = get_row_fingerprint(
-                source_row, 
-                &self.source_headers, 
-                &self.source_header_map,
-                self.case_sensitive, 
-                self.ignore_whitespace,
-                self.ignore_empty_vs_null,
-                &self.excluded_columns
-            );
-
-            let mut matched_exact = false;
-            if let Some(indices) = target_fingerprint_lookup.get_mut(&source_fingerprint) {
-                while let Some(target_idx) = indices.pop() {
-                    if unmatched_target_indices.contains(&target_idx) {
-                        unchanged.push(UnchangedRow {
-                            key: format!("Row {}", row_counter),
+            let (idx, score, differences) = match pairing.exact(i) {
+                Some(idx) => {
+                    let differences = pairing.exact_differences(source_row, &self.target_rows.row(idx), &self.normalizer, true);
+                    if differences.is_empty() {
+                        unchanged.push_with(|| UnchangedRow {
+                            key: format!("Row {}", i + 1),
                             row: record_to_hashmap(source_row, &self.source_headers),
+                            source_index: Some(i),
+                            target_index: Some(idx),
                         });
-                        unmatched_target_indices.remove(&target_idx);
-                        matched_exact = true;
-                        break;
+                        continue;
                     }
+                    (idx, 1.0, differences)
                 }
-            }
-
-            // If no exact match, use strsim-based fuzzy matching
-            if !matched_exact {
-                let mut best_match_idx: Option<usize> = None;
-                let mut best_similarity_score = 0.0;
-
-                // Calculate similarity with all unmatched target rows
-                for &target_idx in unmatched_target_indices.iter() {
-                    let target_row = &self.target_rows[target_idx];
-                    
-                    let similarity = calculate_row_similarity(
-                        source_row,
-                        target_row,
-                        &self.source_headers,
-                        &self.source_header_map,
-                        &self.target_header_map,
-                        &self.excluded_columns,
-                    );
-
-                    if similarity > best_similarity_score {
-                        best_similarity_score = similarity;
-                        best_match_idx = Some(target_idx);
-                    }
-                }
-
-                // Threshold for considering a match (50% similarity)
-                if let Some(idx) = best_match_idx {
-                    if best_similarity_score > 0.5 {
-                        let target_row = &self.target_rows[idx];
-                        let mut differences = Vec::new();
-                        for header in &self.source_headers {
-                            if self.excluded_columns.contains(header) { continue; }
-                            let source_idx = self.source_header_map.get(header).unwrap();
-                            let target_idx = match self.target_header_map.get(header) {
-                                Some(idx) => idx,
-                                None => continue,
-                            };
-                            let source_val_raw = source_row.get(*source_idx).unwrap_or("");
-                            let target_val_raw = target_row.get(*target_idx).unwrap_or("");
-                            let source_val = normalize_value_with_empty_vs_null(
-                                source_val_raw,
-                                self.case_sensitive,
-                                self.ignore_whitespace,
-                                self.ignore_empty_vs_null
-                            );
-                            let target_val = normalize_value_with_empty_vs_null(
-                                target_val_raw,
-                                self.case_sensitive,
-                                self.ignore_whitespace,
-                                self.ignore_empty_vs_null
-                            );
-                            if source_val != target_val {
-                                let diffs = diff_text_internal(source_val_raw, target_val_raw, self.case_sensitive);
-                                differences.push(Difference {
-                                    column: header.clone(),
-                                    old_value: source_val_raw.to_string(),
-                                    new_value: target_val_raw.to_string(),
-                                    diff: diffs,
-                                });
-                            }
-                        }
-                        modified.push(ModifiedRow {
-                            key: format!("Row {}", row_counter),
-                            source_row: record_to_hashmap(source_row, &self.source_headers),
-                            target_row: record_to_hashmap(target_row, &self.target_headers),
-                            differences,
-                        });
-                        unmatched_target_indices.remove(&idx);
+                None => {
+                    // Chunks come in source order, so rows pair as in one pass
+                    let paired = if pairing.surplus(i) {
+                        None
                     } else {
-                        removed.push(RemovedRow {
-                            key: format!("Removed {}", removed.len() + 1),
-                            source_row: record_to_hashmap(source_row, &self.source_headers),
-                        });
+                        let best = pairing.score(source_row, target_row, &self.normalizer, &scorer);
+                        pairing.pair(source_row, best, target_row, &self.normalizer, &scorer)
+                    };
+                    match paired {
+                        Some((idx, score)) => (idx, score, pairing.differences(source_row, &self.target_rows.row(idx), &self.normalizer, true)),
+                        None => {
+                            self.removed_count += 1;
+                            removed.push(RemovedRow {
+                                key: format!("Removed {}", self.removed_count),
+                                source_row: record_to_hashmap(source_row, &self.source_headers),
+                                source_line: record_line(source_row),
+                                source_index: Some(i),
+                            });
+                            continue;
+                        }
                     }
-                } else {
-                    removed.push(RemovedRow {
-                        key: format!("Removed {}", removed.len() + 1),
-                        source_row: record_to_hashmap(source_row, &self.source_headers),
-                    });
                 }
-            }
-            row_counter += 1;
+            };
+
+            let target_row = &self.target_rows.row(idx);
+            modified.push(ModifiedRow {
+                key: format!("Row {}", i + 1),
+                source_row: record_to_hashmap(source_row, &self.source_headers),
+                source_line: record_line(source_row),
+                target_row: record_to_hashmap(target_row, &self.target_headers),
+                target_line: record_line(target_row),
+                source_index: Some(i),
+                target_index: Some(idx),
+                match_score: Some(score),
+                target_key: None,
+                differences,
+            });
         }
 
         // On the last chunk (of source rows), find added rows
         if chunk_end >= self.source_rows.len() {
-            let mut added_index = 1;
-            let mut remaining_indices: Vec<_> = unmatched_target_indices.iter().cloned().collect();
-            remaining_indices.sort();
-
-            for idx in remaining_indices {
-                let row = &self.target_rows[idx];
+            for (n, idx) in pairing.unpaired_targets().into_iter().enumerate() {
+                let row = &self.target_rows.row(idx);
                 added.push(AddedRow {
-                    key: format!("Added {}", added_index),
+                    key: format!("Added {}", n + 1),
                     target_row: record_to_hashmap(row, &self.target_headers),
+                    target_line: record_line(row),
+                    target_index: Some(idx),
                 });
-                added_index += 1;
             }
         }
 
@@ -445,12 +771,24 @@ impl CsvDifferInternal {
             added,
             removed,
             modified,
-            unchanged,
+            unchanged: unchanged.rows,
             source: DatasetMetadata { headers: self.source_headers.clone(), rows: vec![] },
             target: DatasetMetadata { headers: self.target_headers.clone(), rows: vec![] },
             key_columns: vec![],
             excluded_columns: self.excluded_columns.clone(),
             mode: "content-match".to_string(),
-        })
+            summary: DiffSummary::default(),
+            // Reported once, with the first chunk
+            warnings: if chunk_start == 0 { self.warnings.clone() } else { Vec::new() },
+            seed: self.seed,
+            fallback: None,
+            renamed_columns: if chunk_start == 0 { self.renamed_columns.clone() } else { Vec::new() },
+            schema_changes: if chunk_start == 0 { self.schema_changes.clone() } else { Default::default() },
+            reversed: false,
+            header_policy: self.header_policy,
+            moved_rows: Vec::new(),
+            duplicate_counts: if chunk_start == 0 { pairing.duplicate_counts(|idx| self.source_rows.row(idx), &self.source_headers) } else { Vec::new() },
+            omitted_unchanged: unchanged.omitted,
+        }.with_summary())
     }
 }
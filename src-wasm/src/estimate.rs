@@ -0,0 +1,178 @@
+//! Change-rate estimation.
+//!
+//! `estimate_change_rate` compares a sample of keyed rows instead of every
+//! row, so users can see roughly how far two files diverge before paying
+//! for a full diff. Keys are looked up exhaustively; only the cell-by-cell
+//! comparison is sampled.
+
+use ahash::{AHashMap, AHashSet};
+use crate::mapping::{map_target_columns, resolve_excluded_columns};
+use crate::messages::{codes, Message};
+use crate::normalize::Normalizer;
+use crate::options::DiffOptions;
+use crate::parse::parse_csv_with_options;
+use crate::types::{ChangeRateEstimate, RateEstimate};
+use crate::utils::{compared_columns, get_row_key};
+
+/// z-score of the two-sided 95% confidence interval.
+const Z_95: f64 = 1.96;
+
+/// Estimate the share of added, removed and modified rows from up to
+/// `sample_keys` rows spread over each file. Needs key columns.
+pub fn estimate_change_rate(
+    source_csv: &str,
+    target_csv: &str,
+    options: &DiffOptions,
+    sample_keys: usize,
+) -> Result<ChangeRateEstimate, Box<dyn std::error::Error>> {
+    let options = &*options.resolve_keys();
+    if options.key_columns.is_empty() {
+        return Err("Change-rate estimation needs key columns.".into());
+    }
+    let normalizer = Normalizer::new(options)?;
+
+    let (source_headers, source_rows, source_header_map) = parse_csv_with_options(source_csv, &options.parse)?;
+    let (mut target_headers, target_rows, mut target_header_map) = parse_csv_with_options(target_csv, &options.parse)?;
+    map_target_columns(
+        (&source_headers, &source_rows),
+        (&mut target_headers, &mut target_header_map, &target_rows),
+        options,
+    )?;
+    let (excluded_columns, _) = resolve_excluded_columns(&source_headers, &target_headers, options)?;
+
+    for key in &options.key_columns {
+        if !source_header_map.contains_key(key) {
+            return Err(Message::new(codes::KEY_COLUMN_MISSING).with("column", key).with("side", "source").into());
+        }
+        if !target_header_map.contains_key(key) {
+            return Err(Message::new(codes::KEY_COLUMN_MISSING).with("column", key).with("side", "target").into());
+        }
+    }
+
+    let source_keys: AHashSet<String> = source_rows.iter()
+        .map(|row| get_row_key(row, &source_header_map, &options.key_columns, &options.key_normalization))
+        .collect();
+    let target_index: AHashMap<String, usize> = target_rows.iter()
+        .enumerate()
+        .map(|(i, row)| (get_row_key(row, &target_header_map, &options.key_columns, &options.key_normalization), i))
+        .collect();
+    let columns = compared_columns(&source_headers, &source_header_map, &target_header_map, &excluded_columns);
+
+    let source_sample = sample_indices(source_rows.len(), sample_keys, options.seed);
+    let (mut removed, mut modified) = (0, 0);
+    for &i in &source_sample {
+        let source_row = &source_rows[i];
+        let key = get_row_key(source_row, &source_header_map, &options.key_columns, &options.key_normalization);
+        match target_index.get(&key) {
+            None => removed += 1,
+            Some(&t) => {
+                let target_row = &target_rows[t];
+                let differs = columns.iter().any(|&(column, s, t)| {
+                    normalizer.normalize(column, source_row.get(s).unwrap_or("")) != normalizer.normalize(column, target_row.get(t).unwrap_or(""))
+                });
+                if differs {
+                    modified += 1;
+                }
+            }
+        }
+    }
+
+    let target_sample = sample_indices(target_rows.len(), sample_keys, options.seed);
+    let added = target_sample.iter()
+        .filter(|&&i| !source_keys.contains(&get_row_key(&target_rows[i], &target_header_map, &options.key_columns, &options.key_normalization)))
+        .count();
+
+    Ok(ChangeRateEstimate {
+        source_rows: source_rows.len(),
+        target_rows: target_rows.len(),
+        sampled_source: source_sample.len(),
+        sampled_target: target_sample.len(),
+        added: rate_estimate(added, target_sample.len(), target_rows.len()),
+        removed: rate_estimate(removed, source_sample.len(), source_rows.len()),
+        modified: rate_estimate(modified, source_sample.len(), source_rows.len()),
+        seed: options.seed,
+    })
+}
+
+/// Up to `count` indices, one from each of `count` equal strata of
+/// `0..len`: changes clustered at either end of a file are still sampled,
+/// and the pseudo-random pick within each stratum avoids aliasing with
+/// periodic changes. Deterministic for a given `seed`, so repeated
+/// estimates agree.
+fn sample_indices(len: usize, count: usize, seed: u64) -> Vec<usize> {
+    let count = count.min(len);
+    (0..count)
+        .map(|i| {
+            let (start, end) = (i * len / count, (i + 1) * len / count);
+            start + (splitmix64(seed ^ i as u64) % (end - start) as u64) as usize
+        })
+        .collect()
+}
+
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Extrapolate `hits` out of `sample` rows to a file of `population` rows,
+/// with a 95% Wilson score interval. A full sample is exact.
+fn rate_estimate(hits: usize, sample: usize, population: usize) -> RateEstimate {
+    if sample == 0 {
+        return RateEstimate { percent: 0.0, lower_percent: 0.0, upper_percent: 0.0, estimated_rows: 0 };
+    }
+    let n = sample as f64;
+    let p = hits as f64 / n;
+    let (lower, upper) = if sample >= population {
+        (p, p)
+    } else {
+        let z2 = Z_95 * Z_95;
+        let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+        let margin = Z_95 * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt() / (1.0 + z2 / n);
+        ((center - margin).max(0.0), (center + margin).min(1.0))
+    };
+    RateEstimate {
+        percent: p * 100.0,
+        lower_percent: lower * 100.0,
+        upper_percent: upper * 100.0,
+        estimated_rows: (p * population as f64).round() as usize,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_change_rate() {
+        let mut source = String::from("id,value\n");
+        let mut target = String::from("id,value\n");
+        for i in 0..1000 {
+            source.push_str(&format!("{},v{}\n", i, i));
+            // Every 10th row modified, every 20th removed, 40 rows added
+            if i % 20 == 5 {
+                continue;
+            }
+            let value = if i % 10 == 0 { format!("w{}", i) } else { format!("v{}", i) };
+            target.push_str(&format!("{},{}\n", i, value));
+        }
+        for i in 1000..1040 {
+            target.push_str(&format!("{},v{}\n", i, i));
+        }
+        let options = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+
+        let full = estimate_change_rate(&source, &target, &options, 5000).unwrap();
+        assert_eq!((full.removed.estimated_rows, full.modified.estimated_rows, full.added.estimated_rows), (50, 100, 40));
+        assert_eq!(full.modified.lower_percent, full.modified.upper_percent);
+
+        let sampled = estimate_change_rate(&source, &target, &options, 200).unwrap();
+        assert_eq!((sampled.sampled_source, sampled.sampled_target), (200, 200));
+        for (estimate, actual) in [(&sampled.removed, 5.0), (&sampled.modified, 10.0), (&sampled.added, 40.0 / 9.9)] {
+            assert!(estimate.lower_percent <= actual && actual <= estimate.upper_percent, "{:?} vs {}", estimate, actual);
+        }
+
+        let no_keys = DiffOptions::default();
+        assert!(estimate_change_rate(&source, &target, &no_keys, 10).is_err());
+    }
+}
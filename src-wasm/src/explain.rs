@@ -0,0 +1,283 @@
+//! Match explanations and normalization previews.
+//!
+//! Recomputes, for one source/target row pair, the fingerprint comparison and
+//! per-column similarity scores the content-match engine uses to pair rows,
+//! and shows the normalized form the comparator uses for individual values.
+
+use ahash::{AHashMap, AHashSet};
+use serde::{Deserialize, Serialize};
+use crate::options::DiffOptions;
+use crate::normalize::Normalizer;
+use crate::session::DiffSession;
+use crate::columnar::ColumnStore;
+use crate::parse::parse_csv_with_options;
+use crate::types::{ColumnExplanation, MatchExplanation, NormalizationPreview, NormalizationSample};
+use crate::utils::*;
+use crate::similarity::CellScorer;
+
+/// Reference to a row either by 0-based index or by result key.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum RowRef {
+    Index(usize),
+    Key(String),
+}
+
+/// Explain how a session's content-match engine scores a row pair.
+pub fn explain_match(
+    session: &DiffSession,
+    source_ref: &RowRef,
+    target_ref: &RowRef,
+) -> Result<MatchExplanation, Box<dyn std::error::Error>> {
+    let options = &session.options;
+
+    let target_header_map = &session.target_header_map;
+
+    let source_index = resolve_row(&session.source_rows, &session.source_header_map, options, source_ref, "source")?;
+    let target_index = resolve_row(&session.target_rows, target_header_map, options, target_ref, "target")?;
+    let source_row = &session.source_rows.row(source_index);
+    let target_row = &session.target_rows.row(target_index);
+
+    let excluded_set: AHashSet<String> = options.excluded_columns.iter().cloned().collect();
+    let normalizer = Normalizer::new(options)?;
+    let fingerprint = |row, header_map| get_row_fingerprint_hash(
+        row,
+        &session.source_headers,
+        header_map,
+        &normalizer,
+        &excluded_set,
+    );
+    let exact_match = fingerprint(source_row, &session.source_header_map) == fingerprint(target_row, target_header_map);
+
+    let scorer = CellScorer::new(&options.similarity, &normalizer);
+    let similarity = calculate_row_similarity(
+        source_row,
+        target_row,
+        &session.source_headers,
+        &session.source_header_map,
+        target_header_map,
+        &options.excluded_columns,
+        &scorer,
+    );
+
+    let columns = session.source_headers.iter().map(|header| {
+        let source_value = session.source_header_map.get(header)
+            .and_then(|&i| source_row.get(i))
+            .unwrap_or("");
+        let target_value = target_header_map.get(header).and_then(|&i| target_row.get(i));
+        let excluded = excluded_set.contains(header);
+        let scored = target_value.filter(|_| !excluded).map(|t| scorer.score(header, source_value, t));
+        let target_value = target_value.unwrap_or("");
+
+        let mut steps = normalization_steps(&normalizer, header, source_value);
+        for step in normalization_steps(&normalizer, header, target_value) {
+            if !steps.contains(&step) {
+                steps.push(step);
+            }
+        }
+
+        ColumnExplanation {
+            column: header.clone(),
+            source_value: source_value.to_string(),
+            target_value: target_value.to_string(),
+            normalized_source: normalizer.normalize(header, source_value).into_owned(),
+            normalized_target: normalizer.normalize(header, target_value).into_owned(),
+            normalization_steps: steps,
+            similarity: scored.map(|(score, _)| score),
+            algorithm: scored.map(|(_, name)| name.to_string()),
+            excluded,
+        }
+    }).collect();
+
+    Ok(MatchExplanation {
+        source_index,
+        target_index,
+        exact_match,
+        similarity,
+        threshold: MATCH_SIMILARITY_THRESHOLD,
+        above_threshold: exact_match || similarity > MATCH_SIMILARITY_THRESHOLD,
+        columns,
+    })
+}
+
+fn resolve_row(
+    rows: &ColumnStore,
+    header_map: &AHashMap<String, usize>,
+    options: &DiffOptions,
+    row_ref: &RowRef,
+    side: &str,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let index = match row_ref {
+        RowRef::Index(i) => Some(*i),
+        RowRef::Key(key) if options.is_primary_key() => rows
+            .iter()
+            .position(|r| get_row_key(&r, header_map, &options.key_columns, &options.key_normalization) == *key),
+        // Content-match keys are "Row N" (1-based source position)
+        RowRef::Key(key) => key
+            .strip_prefix("Row ")
+            .and_then(|n| n.parse::<usize>().ok())
+            .and_then(|n| n.checked_sub(1)),
+    };
+
+    match index {
+        Some(i) if i < rows.len() => Ok(i),
+        _ => Err(format!("No {} row matches {:?}", side, row_ref).into()),
+    }
+}
+
+/// Normalized form of a single `column` value under `options`, as the
+/// comparator computes it. Without a column only the column-independent
+/// rules apply.
+pub fn preview_normalization(
+    value: &str,
+    column: Option<&str>,
+    options: &DiffOptions,
+) -> Result<NormalizationPreview, Box<dyn std::error::Error>> {
+    Ok(preview_value(&Normalizer::new(options)?, column.unwrap_or(""), value))
+}
+
+/// Parse up to `max_rows` rows of `csv_content` with the diff's parse options
+/// and preview the normalization of every cell.
+pub fn preview_normalization_sample(
+    csv_content: &str,
+    options: &DiffOptions,
+    max_rows: usize,
+) -> Result<NormalizationSample, Box<dyn std::error::Error>> {
+    let (headers, rows, _) = parse_csv_with_options(csv_content, &options.parse)?;
+    let normalizer = Normalizer::new(options)?;
+    let rows = rows
+        .iter()
+        .take(max_rows)
+        .map(|r| r.iter().zip(&headers).map(|(v, header)| preview_value(&normalizer, header, v)).collect())
+        .collect();
+
+    Ok(NormalizationSample { headers, rows })
+}
+
+fn preview_value(normalizer: &Normalizer, column: &str, value: &str) -> NormalizationPreview {
+    NormalizationPreview {
+        original: value.to_string(),
+        normalized: normalizer.normalize(column, value).into_owned(),
+        steps: normalization_steps(normalizer, column, value),
+    }
+}
+
+/// Names of the normalization steps that change a `column` value, in
+/// application order.
+fn normalization_steps(normalizer: &Normalizer, column: &str, value: &str) -> Vec<String> {
+    let mut steps = Vec::new();
+    if normalizer.ignore_whitespace && value.trim().len() != value.len() {
+        steps.push("trim".to_string());
+    }
+    if normalizer.null_values.is_null(column, value) {
+        steps.push("empty-or-null".to_string());
+        return steps;
+    }
+    let canonical = normalizer.canonicalize(column, value);
+    if canonical != value {
+        steps.push("canonicalize".to_string());
+    }
+    let canonical = if normalizer.ignore_whitespace { canonical.trim() } else { &canonical };
+    if !normalizer.case_sensitive && canonical.to_lowercase() != canonical {
+        steps.push("lowercase".to_string());
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explain_content_match_pair() {
+        let options = DiffOptions {
+            mode: "content-match".to_string(),
+            case_sensitive: false,
+            ignore_whitespace: true,
+            ..Default::default()
+        };
+        let session = DiffSession::run(
+            "name,city\nAlice,Paris\n",
+            "name,city\n ALICE ,Lyon\n",
+            options,
+            |_, _| {},
+        ).unwrap();
+
+        let explanation = explain_match(&session, &RowRef::Key("Row 1".to_string()), &RowRef::Index(0)).unwrap();
+        assert!(!explanation.exact_match);
+        assert_eq!(explanation.columns[0].normalized_source, explanation.columns[0].normalized_target);
+        assert_eq!(explanation.columns[0].normalization_steps, vec!["lowercase"]);
+        assert!(explanation.columns[1].similarity.unwrap() < 1.0);
+
+        assert!(explain_match(&session, &RowRef::Index(5), &RowRef::Index(0)).is_err());
+    }
+
+    #[test]
+    fn test_explain_normalizes_like_fingerprint() {
+        let options = DiffOptions {
+            mode: "content-match".to_string(),
+            value_transforms: vec![crate::normalize::ValueTransform {
+                column: "phone".to_string(),
+                find: "-".to_string(),
+                replace: String::new(),
+            }],
+            ..Default::default()
+        };
+        let session = DiffSession::run("name,phone\nA,555-1234\n", "name,phone\nA,5551234\n", options, |_, _| {}).unwrap();
+
+        let explanation = explain_match(&session, &RowRef::Index(0), &RowRef::Index(0)).unwrap();
+        assert!(explanation.exact_match);
+        assert_eq!(explanation.columns[1].normalized_source, "5551234");
+        assert_eq!(explanation.columns[1].normalized_source, explanation.columns[1].normalized_target);
+    }
+
+    #[test]
+    fn test_preview_normalization() {
+        let options = DiffOptions {
+            case_sensitive: false,
+            ignore_whitespace: true,
+            ignore_empty_vs_null: true,
+            ..Default::default()
+        };
+
+        let preview = preview_normalization("  Hello ", None, &options).unwrap();
+        assert_eq!(preview.normalized, "hello");
+        assert_eq!(preview.steps, vec!["trim", "lowercase"]);
+        assert_eq!(preview_normalization("NULL", None, &options).unwrap().steps, vec!["empty-or-null"]);
+
+        let sample = preview_normalization_sample("a,b\nX,null\nY,z\nZ,w\n", &options, 2).unwrap();
+        assert_eq!(sample.headers, vec!["a", "b"]);
+        assert_eq!(sample.rows.len(), 2);
+        assert_eq!(sample.rows[0][0].normalized, "x");
+    }
+
+    #[test]
+    fn test_preview_normalization_by_column() {
+        let options = DiffOptions {
+            value_transforms: vec![crate::normalize::ValueTransform {
+                column: "phone".to_string(),
+                find: "-".to_string(),
+                replace: String::new(),
+            }],
+            ..Default::default()
+        };
+
+        let preview = preview_normalization("555-1234", Some("phone"), &options).unwrap();
+        assert_eq!(preview.normalized, "5551234");
+        assert_eq!(preview.steps, vec!["canonicalize"]);
+        assert_eq!(preview_normalization("555-1234", None, &options).unwrap().normalized, "555-1234");
+
+        let sample = preview_normalization_sample("name,phone\n555-1,555-1\n", &options, 1).unwrap();
+        assert_eq!(sample.rows[0][0].normalized, "555-1");
+        assert_eq!(sample.rows[0][1].normalized, "5551");
+
+        assert!(preview_normalization("x", None, &DiffOptions {
+            value_transforms: vec![crate::normalize::ValueTransform {
+                column: "phone".to_string(),
+                find: "(".to_string(),
+                replace: String::new(),
+            }],
+            ..Default::default()
+        }).is_err());
+    }
+}
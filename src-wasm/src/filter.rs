@@ -0,0 +1,121 @@
+//! Row filters over a finished diff result.
+//!
+//! Filtering runs over the result a session retains, so the UI only
+//! receives the rows it shows. Every criterion that is set must hold;
+//! criteria about changed cells only modified rows can meet.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use crate::types::{DiffResult, FilteredRows};
+
+/// Kind of a result row.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RowStatus {
+    Added,
+    Removed,
+    Modified,
+    Unchanged,
+}
+
+/// Criteria for `filter_result`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FilterCriteria {
+    /// Row kinds to keep; every kind when empty
+    pub statuses: Vec<RowStatus>,
+    /// Keep modified rows changing any of these columns
+    pub changed_columns: Vec<String>,
+    /// Keep rows whose key starts with this
+    pub key_prefix: Option<String>,
+    /// Regular expression some value of the row must match; either side
+    /// counts for modified rows
+    pub value_pattern: Option<String>,
+    /// Keep modified rows changing at least this many cells
+    pub min_differences: usize,
+}
+
+/// The rows of `result` meeting `criteria`, in result order.
+pub fn filter_result(result: &DiffResult, criteria: &FilterCriteria) -> Result<FilteredRows, Box<dyn std::error::Error>> {
+    let pattern = criteria.value_pattern.as_deref()
+        .map(Regex::new)
+        .transpose()
+        .map_err(|e| format!("Invalid filter pattern: {}", e))?;
+    let changes_only = !criteria.changed_columns.is_empty() || criteria.min_differences > 0;
+    let keeps = |status: RowStatus| {
+        (criteria.statuses.is_empty() || criteria.statuses.contains(&status))
+            && (!changes_only || status == RowStatus::Modified)
+    };
+    let key_matches = |key: &str| criteria.key_prefix.as_deref().is_none_or(|prefix| key.starts_with(prefix));
+
+    let mut rows = FilteredRows::default();
+    if keeps(RowStatus::Added) {
+        rows.added = result.added.iter()
+            .filter(|row| key_matches(&row.key) && values_match(pattern.as_ref(), row.target_row.values()))
+            .cloned()
+            .collect();
+    }
+    if keeps(RowStatus::Removed) {
+        rows.removed = result.removed.iter()
+            .filter(|row| key_matches(&row.key) && values_match(pattern.as_ref(), row.source_row.values()))
+            .cloned()
+            .collect();
+    }
+    if keeps(RowStatus::Modified) {
+        rows.modified = result.modified.iter()
+            .filter(|row| {
+                key_matches(&row.key)
+                    && row.differences.len() >= criteria.min_differences
+                    && (criteria.changed_columns.is_empty()
+                        || row.differences.iter().any(|d| criteria.changed_columns.contains(&d.column)))
+                    && values_match(pattern.as_ref(), row.source_row.values().chain(row.target_row.values()))
+            })
+            .cloned()
+            .collect();
+    }
+    if keeps(RowStatus::Unchanged) {
+        rows.unchanged = result.unchanged.iter()
+            .filter(|row| key_matches(&row.key) && values_match(pattern.as_ref(), row.row.values()))
+            .cloned()
+            .collect();
+    }
+    Ok(rows)
+}
+
+fn values_match<'a>(pattern: Option<&Regex>, mut values: impl Iterator<Item = &'a String>) -> bool {
+    pattern.is_none_or(|pattern| values.any(|value| pattern.is_match(value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::DiffOptions;
+
+    #[test]
+    fn test_filter_result() {
+        let source = "id,name,price\nA1,Apple,1\nA2,Apricot,2\nB1,Banana,3\nB2,Blueberry,4\n";
+        let target = "id,name,price\nA1,Apple,1.5\nA2,Apricots,2.5\nB1,Banana,3\nC1,Cherry,5\n";
+        let options = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+        let keys = |criteria: FilterCriteria| {
+            let rows = filter_result(&result, &criteria).unwrap();
+            let mut keys: Vec<String> = rows.added.iter().map(|r| r.key.clone())
+                .chain(rows.removed.iter().map(|r| r.key.clone()))
+                .chain(rows.modified.iter().map(|r| r.key.clone()))
+                .chain(rows.unchanged.iter().map(|r| r.key.clone()))
+                .collect();
+            keys.sort();
+            keys
+        };
+
+        assert_eq!(keys(FilterCriteria::default()).len(), 5);
+        assert_eq!(keys(FilterCriteria { changed_columns: vec!["price".to_string()], ..Default::default() }), ["A1", "A2"]);
+        assert_eq!(keys(FilterCriteria { min_differences: 2, ..Default::default() }), ["A2"]);
+        assert_eq!(keys(FilterCriteria { key_prefix: Some("B".to_string()), ..Default::default() }), ["B1", "B2"]);
+        assert_eq!(
+            keys(FilterCriteria { value_pattern: Some("^(Cherry|Blue)".to_string()), statuses: vec![RowStatus::Added], ..Default::default() }),
+            ["C1"]
+        );
+        assert!(filter_result(&result, &FilterCriteria { value_pattern: Some("(".to_string()), ..Default::default() }).is_err());
+    }
+}
@@ -0,0 +1,203 @@
+//! Framed paired input.
+//!
+//! Both files travel in one buffer behind a small header, so JS can stream
+//! them into a single `alloc`ated region and diff with one call. Layout (all
+//! integers little-endian):
+//!
+//! ```text
+//! "CSVF"  version:u8
+//! source: length:u32  has_headers:u8  delimiter_len:u8  delimiter bytes
+//! target: length:u32  has_headers:u8  delimiter_len:u8  delimiter bytes
+//! source bytes  target bytes
+//! ```
+//!
+//! `has_headers` is 0 (absent), 1 (present), 2 (auto) or 255 (use the diff
+//! options); a zero `delimiter_len` also defers to the diff options.
+
+use serde::{Deserialize, Serialize};
+use crate::options::DiffOptions;
+use crate::parse::{HeaderMode, ParseOptions};
+use crate::types::DiffResult;
+
+const MAGIC: &[u8; 4] = b"CSVF";
+const VERSION: u8 = 1;
+const INHERIT: u8 = 255;
+
+/// Per-file tokenization overrides carried in the frame header. Unset
+/// fields fall back to the diff options.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Dialect {
+    pub delimiter: Option<String>,
+    pub has_headers: Option<HeaderMode>,
+}
+
+impl Dialect {
+    fn apply(&self, parse: &ParseOptions) -> ParseOptions {
+        let mut parse = parse.clone();
+        if let Some(delimiter) = &self.delimiter {
+            parse.delimiter = delimiter.clone();
+        }
+        if let Some(has_headers) = self.has_headers {
+            parse.has_headers = has_headers;
+        }
+        parse
+    }
+}
+
+/// The two files of a framed buffer, borrowed from it.
+#[derive(Debug)]
+pub struct FramedInput<'a> {
+    pub source: &'a [u8],
+    pub source_dialect: Dialect,
+    pub target: &'a [u8],
+    pub target_dialect: Dialect,
+}
+
+/// Header to put in front of a source and a target of the given lengths.
+pub fn encode_frame_header(
+    source_len: usize,
+    source_dialect: &Dialect,
+    target_len: usize,
+    target_dialect: &Dialect,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut header = MAGIC.to_vec();
+    header.push(VERSION);
+    for (len, dialect) in [(source_len, source_dialect), (target_len, target_dialect)] {
+        header.extend_from_slice(&u32::try_from(len).map_err(|_| "Framed inputs are limited to 4 GiB per file.")?.to_le_bytes());
+        header.push(match dialect.has_headers {
+            Some(HeaderMode::Absent) => 0,
+            Some(HeaderMode::Present) => 1,
+            Some(HeaderMode::Auto) => 2,
+            None => INHERIT,
+        });
+        let delimiter = dialect.delimiter.as_deref().unwrap_or("").as_bytes();
+        header.push(u8::try_from(delimiter.len()).map_err(|_| "Frame delimiters are limited to 255 bytes.")?);
+        header.extend_from_slice(delimiter);
+    }
+    Ok(header)
+}
+
+/// Split a framed buffer into its two files. The payload must be exactly
+/// the declared lengths.
+pub fn decode_frame(buffer: &[u8]) -> Result<FramedInput<'_>, Box<dyn std::error::Error>> {
+    let mut reader = FrameReader { buffer, offset: 0 };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err("Not a framed diff input (missing \"CSVF\" header).".into());
+    }
+    let version = reader.byte()?;
+    if version != VERSION {
+        return Err(format!("Unsupported framed input version {}.", version).into());
+    }
+    let (source_len, source_dialect) = reader.file_header()?;
+    let (target_len, target_dialect) = reader.file_header()?;
+
+    let payload = &buffer[reader.offset..];
+    if payload.len() as u64 != source_len as u64 + target_len as u64 {
+        return Err(format!(
+            "Framed input declares {} bytes of data but carries {}.",
+            source_len as u64 + target_len as u64,
+            payload.len()
+        ).into());
+    }
+    let (source, target) = payload.split_at(source_len);
+    Ok(FramedInput { source, source_dialect, target, target_dialect })
+}
+
+/// Diff the two files of a framed buffer. Primary-key mode parses each file
+/// with its own dialect; content-match mode needs both dialects to agree.
+pub fn diff_framed<F>(
+    buffer: &[u8],
+    options: &DiffOptions,
+    on_progress: F,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let input = decode_frame(buffer)?;
+    let source_parse = input.source_dialect.apply(&options.parse);
+    let target_parse = input.target_dialect.apply(&options.parse);
+
+    if options.is_primary_key() {
+        return crate::byte_parse::diff_bytes_primary_key(input.source, &source_parse, input.target, &target_parse, options, on_progress);
+    }
+    if source_parse.delimiter != target_parse.delimiter || source_parse.has_headers != target_parse.has_headers {
+        return Err("Content-match mode needs both framed files in the same dialect.".into());
+    }
+    let options = DiffOptions { parse: source_parse, ..options.clone() };
+    crate::byte_parse::diff_bytes_with_options(input.source, input.target, &options, on_progress)
+}
+
+struct FrameReader<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> FrameReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Box<dyn std::error::Error>> {
+        let bytes = self.buffer
+            .get(self.offset..self.offset + len)
+            .ok_or("Framed input header is truncated.")?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, Box<dyn std::error::Error>> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn file_header(&mut self) -> Result<(usize, Dialect), Box<dyn std::error::Error>> {
+        let len = u32::from_le_bytes(self.take(4)?.try_into()?) as usize;
+        let has_headers = match self.byte()? {
+            0 => Some(HeaderMode::Absent),
+            1 => Some(HeaderMode::Present),
+            2 => Some(HeaderMode::Auto),
+            INHERIT => None,
+            other => return Err(format!("Invalid framed has_headers value {}.", other).into()),
+        };
+        let delimiter_len = self.byte()? as usize;
+        let delimiter = match self.take(delimiter_len)? {
+            [] => None,
+            bytes => Some(std::str::from_utf8(bytes)?.to_string()),
+        };
+        Ok((len, Dialect { delimiter, has_headers }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(source: &str, source_dialect: &Dialect, target: &str, target_dialect: &Dialect) -> Vec<u8> {
+        let mut buffer = encode_frame_header(source.len(), source_dialect, target.len(), target_dialect).unwrap();
+        buffer.extend_from_slice(source.as_bytes());
+        buffer.extend_from_slice(target.as_bytes());
+        buffer
+    }
+
+    #[test]
+    fn test_framed_diff_with_per_file_dialects() {
+        let source = "id,name\n1,Alice\n2,Bob\n";
+        let target = "1;Alice\n2;Robert\n";
+        let target_dialect = Dialect { delimiter: Some(";".to_string()), has_headers: Some(HeaderMode::Absent) };
+        let buffer = frame(source, &Dialect::default(), target, &target_dialect);
+
+        let input = decode_frame(&buffer).unwrap();
+        assert_eq!((input.source, input.target_dialect.clone()), (source.as_bytes(), target_dialect.clone()));
+
+        let options = DiffOptions {
+            key_columns: vec!["id".to_string()],
+            column_mapping: [("id", "Column1"), ("name", "Column2")].iter()
+                .map(|(s, t)| (s.to_string(), t.to_string()))
+                .collect(),
+            ..Default::default()
+        };
+        let result = diff_framed(&buffer, &options, |_, _| {}).unwrap();
+        assert_eq!((result.unchanged.len(), result.modified.len()), (1, 1));
+
+        let content_match = DiffOptions { mode: "content-match".to_string(), ..Default::default() };
+        assert!(diff_framed(&buffer, &content_match, |_, _| {}).is_err());
+        assert!(decode_frame(&buffer[..buffer.len() - 1]).is_err());
+        assert!(decode_frame(b"CSV").is_err());
+    }
+}
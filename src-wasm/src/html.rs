@@ -0,0 +1,202 @@
+//! Standalone HTML reports of a diff result.
+//!
+//! The report is one self-contained page with inline styles: the summary
+//! counts, then a table each for added, removed and modified rows (and,
+//! optionally, unchanged rows). Changed cells of modified rows show the
+//! word-level diff with removed text struck through and added text
+//! highlighted, so a saved or emailed report reads like the app's view.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use serde::{Deserialize, Serialize};
+use crate::types::{DiffChange, DiffResult, Difference};
+
+const STYLE: &str = "\
+body{font-family:system-ui,sans-serif;margin:2rem;color:#1f2328}\
+h1{font-size:1.5rem}h2{font-size:1.2rem;margin-top:2rem}\
+.summary span{display:inline-block;margin-right:1rem;padding:.25rem .6rem;border-radius:4px}\
+table{border-collapse:collapse;font-size:.85rem}\
+th,td{border:1px solid #d0d7de;padding:.25rem .5rem;text-align:left;vertical-align:top;white-space:pre-wrap}\
+th{background:#f6f8fa}\
+.added{background:#dafbe1}.removed{background:#ffebe9}.modified{background:#fff8c5}\
+td.changed{background:#fff8c5}\
+del{background:#ffcecb;text-decoration:line-through}ins{background:#abf2bc;text-decoration:none}\
+.note{color:#656d76;font-size:.85rem}";
+
+/// Options for `write_result_html`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HtmlOptions {
+    /// Page title and heading
+    pub title: String,
+    /// Add a table of unchanged rows
+    pub include_unchanged: bool,
+    /// Most rows per table; the rest are counted in a note
+    pub max_rows: Option<usize>,
+}
+
+impl Default for HtmlOptions {
+    fn default() -> Self {
+        Self {
+            title: "CSV diff report".to_string(),
+            include_unchanged: false,
+            max_rows: None,
+        }
+    }
+}
+
+/// Render `result` as a standalone HTML page.
+pub fn write_result_html(result: &DiffResult, options: &HtmlOptions) -> String {
+    let mut html = String::new();
+    let title = escape(&options.title);
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{title}</title><style>{STYLE}</style></head><body>\n<h1>{title}</h1>\n"
+    );
+
+    let summary = &result.summary;
+    let _ = writeln!(
+        html,
+        "<p class=\"summary\"><span class=\"added\">{} added</span><span class=\"removed\">{} removed</span>\
+         <span class=\"modified\">{} modified</span><span>{} unchanged</span></p>",
+        summary.added, summary.removed, summary.modified, summary.unchanged
+    );
+    if !result.key_columns.is_empty() {
+        let _ = writeln!(html, "<p class=\"note\">Mode {}, key {}</p>", escape(&result.mode), escape(&result.key_columns.join(", ")));
+    } else {
+        let _ = writeln!(html, "<p class=\"note\">Mode {}</p>", escape(&result.mode));
+    }
+
+    let headers = &result.target.headers;
+    section(&mut html, "Added rows", "added", headers, options, result.added.iter().map(|row| (&row.key, &row.target_row)));
+    section(&mut html, "Removed rows", "removed", &result.source.headers, options, result.removed.iter().map(|row| (&row.key, &row.source_row)));
+    modified_section(&mut html, result, options);
+    if options.include_unchanged {
+        section(&mut html, "Unchanged rows", "", headers, options, result.unchanged.iter().map(|row| (&row.key, &row.row)));
+    }
+
+    html.push_str("</body></html>\n");
+    html
+}
+
+/// A table of whole rows, one cell per header.
+fn section<'a>(
+    html: &mut String,
+    heading: &str,
+    class: &str,
+    headers: &[String],
+    options: &HtmlOptions,
+    rows: impl ExactSizeIterator<Item = (&'a String, &'a HashMap<String, String>)>,
+) {
+    if rows.len() == 0 {
+        return;
+    }
+    let total = rows.len();
+    table_start(html, heading, headers);
+    for (key, row) in rows.take(options.max_rows.unwrap_or(usize::MAX)) {
+        let _ = write!(html, "<tr class=\"{class}\"><td>{}</td>", escape(key));
+        for header in headers {
+            let _ = write!(html, "<td>{}</td>", escape(row.get(header).map_or("", String::as_str)));
+        }
+        html.push_str("</tr>\n");
+    }
+    table_end(html, total, options);
+}
+
+/// Modified rows with their changed cells highlighted.
+fn modified_section(html: &mut String, result: &DiffResult, options: &HtmlOptions) {
+    if result.modified.is_empty() {
+        return;
+    }
+    let headers = &result.target.headers;
+    table_start(html, "Modified rows", headers);
+    for row in result.modified.iter().take(options.max_rows.unwrap_or(usize::MAX)) {
+        let _ = write!(html, "<tr><td>{}</td>", escape(&row.key));
+        for header in headers {
+            match row.differences.iter().find(|d| &d.column == header) {
+                Some(difference) => {
+                    let _ = write!(html, "<td class=\"changed\">{}</td>", changed_cell(difference));
+                }
+                None => {
+                    let _ = write!(html, "<td>{}</td>", escape(row.target_row.get(header).map_or("", String::as_str)));
+                }
+            }
+        }
+        html.push_str("</tr>\n");
+    }
+    table_end(html, result.modified.len(), options);
+}
+
+fn table_start(html: &mut String, heading: &str, headers: &[String]) {
+    let _ = write!(html, "<h2>{heading}</h2>\n<table><thead><tr><th>Key</th>");
+    for header in headers {
+        let _ = write!(html, "<th>{}</th>", escape(header));
+    }
+    html.push_str("</tr></thead><tbody>\n");
+}
+
+fn table_end(html: &mut String, total: usize, options: &HtmlOptions) {
+    html.push_str("</tbody></table>\n");
+    if let Some(max_rows) = options.max_rows.filter(|&max| total > max) {
+        let _ = writeln!(html, "<p class=\"note\">{} more rows not shown</p>", total - max_rows);
+    }
+}
+
+/// The word-level diff of a cell, or old and new value when the diff holds
+/// JSON paths rather than text.
+fn changed_cell(difference: &Difference) -> String {
+    let is_text = difference.diff.iter().all(|change| change.path.is_none());
+    if !is_text || difference.diff.is_empty() {
+        return format!("<del>{}</del> <ins>{}</ins>", escape(&difference.old_value), escape(&difference.new_value));
+    }
+    difference.diff.iter().map(change_html).collect()
+}
+
+fn change_html(change: &DiffChange) -> String {
+    let text = escape(&change.value);
+    if change.removed {
+        format!("<del>{text}</del>")
+    } else if change.added {
+        format!("<ins>{text}</ins>")
+    } else {
+        text
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::DiffOptions;
+
+    #[test]
+    fn test_html_report() {
+        let source = "id,name\n1,Alice Smith\n2,<b>Bob</b>\n";
+        let target = "id,name\n1,Alice Jones\n3,Carol\n";
+        let options = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+
+        let html = write_result_html(&result, &HtmlOptions::default());
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("1 added") && html.contains("1 removed") && html.contains("1 modified"));
+        assert!(html.contains("&lt;b&gt;Bob&lt;/b&gt;"));
+        assert!(html.contains("<del>Smith</del>") && html.contains("<ins>Jones</ins>"), "{}", html);
+
+        let limited = write_result_html(&result, &HtmlOptions { max_rows: Some(0), ..Default::default() });
+        assert!(limited.contains("1 more rows not shown"));
+    }
+}
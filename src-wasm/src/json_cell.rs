@@ -0,0 +1,127 @@
+//! JSON documents stored in cells.
+//!
+//! Columns listed in `DiffOptions::json_columns` compare their object and
+//! array values structurally: both sides are parsed and re-serialized with
+//! sorted keys and no whitespace. Their differences list the changed paths
+//! (as JSON Pointers, "" for the whole document) instead of a word diff of
+//! the serialized text.
+
+use serde_json::Value;
+use crate::types::DiffChange;
+
+/// The parsed document in `value`, if it is a JSON object or array.
+pub fn parse_document(value: &str) -> Option<Value> {
+    let trimmed = value.trim();
+    if !(trimmed.starts_with('{') || trimmed.starts_with('[')) {
+        return None;
+    }
+    serde_json::from_str(trimmed).ok()
+}
+
+/// Compact text of `value` with object keys sorted, so documents differing
+/// only in key order or whitespace give equal text.
+pub fn canonical_json(value: &str) -> Option<String> {
+    // serde_json maps are ordered by key, so serializing sorts them
+    parse_document(value).map(|document| document.to_string())
+}
+
+/// One removed and/or added entry per path whose value differs. Arrays
+/// compare by index.
+pub fn diff_documents(old: &Value, new: &Value) -> Vec<DiffChange> {
+    let mut changes = Vec::new();
+    diff_at(&mut String::new(), old, new, &mut changes);
+    changes
+}
+
+fn diff_at(path: &mut String, old: &Value, new: &Value, changes: &mut Vec<DiffChange>) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            for (key, old_value) in old_map {
+                with_segment(path, key, |path| match new_map.get(key) {
+                    Some(new_value) => diff_at(path, old_value, new_value, changes),
+                    None => changes.push(change(path, old_value, false)),
+                });
+            }
+            for (key, new_value) in new_map {
+                if !old_map.contains_key(key) {
+                    with_segment(path, key, |path| changes.push(change(path, new_value, true)));
+                }
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items)) => {
+            for i in 0..old_items.len().max(new_items.len()) {
+                with_segment(path, &i.to_string(), |path| match (old_items.get(i), new_items.get(i)) {
+                    (Some(old_item), Some(new_item)) => diff_at(path, old_item, new_item, changes),
+                    (Some(old_item), None) => changes.push(change(path, old_item, false)),
+                    (None, Some(new_item)) => changes.push(change(path, new_item, true)),
+                    (None, None) => unreachable!(),
+                });
+            }
+        }
+        _ if old != new => {
+            changes.push(change(path, old, false));
+            changes.push(change(path, new, true));
+        }
+        _ => {}
+    }
+}
+
+/// Run `f` with `segment` appended to the JSON Pointer `path`.
+fn with_segment(path: &mut String, segment: &str, f: impl FnOnce(&mut String)) {
+    let len = path.len();
+    path.push('/');
+    path.push_str(&segment.replace('~', "~0").replace('/', "~1"));
+    f(path);
+    path.truncate(len);
+}
+
+fn change(path: &str, value: &Value, added: bool) -> DiffChange {
+    DiffChange {
+        added,
+        removed: !added,
+        value: value.to_string(),
+        path: Some(path.to_string()),
+        intraline: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::DiffOptions;
+
+    #[test]
+    fn test_json_columns() {
+        assert_eq!(canonical_json(" { \"b\": [1, 2], \"a\": {\"y\": null, \"x\": true} }").as_deref(), Some(r#"{"a":{"x":true,"y":null},"b":[1,2]}"#));
+        assert_eq!(canonical_json("42"), None);
+        assert_eq!(canonical_json("{not json"), None);
+
+        let source = "id,meta\n1,\"{\"\"a\"\": 1, \"\"b\"\": 2}\"\n2,\"{\"\"tags\"\": [\"\"x\"\", \"\"y\"\"], \"\"n\"\": {\"\"k/v\"\": 1}}\"\n";
+        let target = "id,meta\n1,\"{\"\"b\"\":2,\"\"a\"\":1}\"\n2,\"{\"\"n\"\": {\"\"k/v\"\": 2}, \"\"tags\"\": [\"\"x\"\"], \"\"new\"\": 0}\"\n";
+        let options = DiffOptions {
+            key_columns: vec!["id".to_string()],
+            json_columns: vec!["meta".to_string()],
+            ..Default::default()
+        };
+        for use_parallel in [false, true] {
+            let parallel_phases = crate::options::ParallelPhases::all();
+            let options = DiffOptions { use_parallel, parallel_phases, ..options.clone() };
+            let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            assert_eq!((result.unchanged.len(), result.modified.len()), (1, 1));
+            let changes: Vec<(Option<&str>, bool, &str)> = result.modified[0].differences[0].diff.iter()
+                .map(|change| (change.path.as_deref(), change.added, change.value.as_str()))
+                .collect();
+            assert_eq!(changes, vec![
+                (Some("/n/k~1v"), false, "1"),
+                (Some("/n/k~1v"), true, "2"),
+                (Some("/tags/1"), false, "\"y\""),
+                (Some("/new"), true, "0"),
+            ]);
+        }
+
+        // Without the option the reordered document is a change
+        let plain = DiffOptions { json_columns: Vec::new(), ..options };
+        let result = crate::core::diff_with_options(source, target, &plain, |_, _| {}).unwrap();
+        assert_eq!(result.modified.len(), 2);
+    }
+}
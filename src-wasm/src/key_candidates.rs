@@ -0,0 +1,103 @@
+//! Primary-key suggestions.
+//!
+//! `analyze_key_candidates` measures how unique and how complete each
+//! column of a file is, and each pair of the most promising columns, so the
+//! key picker can offer the combinations that identify rows best. Values
+//! are cleaned up by `DiffOptions::key_normalization`, as the differ would.
+
+use ahash::AHashSet;
+use crate::normalize::NullValues;
+use crate::options::DiffOptions;
+use crate::parse::parse_csv_with_options;
+use crate::types::{KeyAnalysis, KeyCandidate};
+use crate::utils::{get_row_key, is_empty_or_null};
+
+/// Columns, best single-column score first, whose pairs are also measured.
+const PAIR_CANDIDATE_COLUMNS: usize = 12;
+
+/// Suggestions returned at most.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Measure every column of `csv_content` as a primary key, and pairs of the
+/// best `PAIR_CANDIDATE_COLUMNS` columns where neither column alone is a
+/// perfect key. Suggestions rank by score, then fewer columns, then header
+/// order; pairs are only suggested when they beat both of their columns.
+pub fn analyze_key_candidates(
+    csv_content: &str,
+    options: &DiffOptions,
+) -> Result<KeyAnalysis, Box<dyn std::error::Error>> {
+    let (headers, rows, header_map) = parse_csv_with_options(csv_content, &options.parse)?;
+    let null_values = NullValues::resolve(options);
+    let measure = |columns: Vec<String>| {
+        let indices: Vec<usize> = columns.iter().map(|c| header_map[c]).collect();
+        let keys: AHashSet<String> = rows.iter()
+            .map(|row| get_row_key(row, &header_map, &columns, &options.key_normalization))
+            .collect();
+        let nulls = rows.iter()
+            .filter(|row| indices.iter().zip(&columns).any(|(&i, column)| {
+                let value = row.get(i).unwrap_or("");
+                if null_values.is_empty() { is_empty_or_null(value) } else { null_values.is_null(column, value) }
+            }))
+            .count();
+        let (uniqueness, null_rate) = if rows.is_empty() {
+            (0.0, 0.0)
+        } else {
+            (keys.len() as f64 / rows.len() as f64, nulls as f64 / rows.len() as f64)
+        };
+        KeyCandidate { columns, uniqueness, null_rate, score: uniqueness * (1.0 - null_rate) }
+    };
+
+    let columns: Vec<KeyCandidate> = headers.iter().map(|h| measure(vec![h.clone()])).collect();
+
+    let mut by_score: Vec<usize> = (0..columns.len()).filter(|&i| columns[i].score < 1.0).collect();
+    by_score.sort_by(|&a, &b| columns[b].score.total_cmp(&columns[a].score).then(a.cmp(&b)));
+    by_score.truncate(PAIR_CANDIDATE_COLUMNS);
+    by_score.sort_unstable();
+
+    let mut suggestions: Vec<(usize, KeyCandidate)> = columns.iter().cloned().enumerate().collect();
+    for (n, &a) in by_score.iter().enumerate() {
+        for &b in &by_score[n + 1..] {
+            let pair = measure(vec![headers[a].clone(), headers[b].clone()]);
+            if pair.score > columns[a].score.max(columns[b].score) {
+                suggestions.push((a, pair));
+            }
+        }
+    }
+    suggestions.retain(|(_, candidate)| candidate.score > 0.0);
+    suggestions.sort_by(|(a, x), (b, y)| {
+        y.score.total_cmp(&x.score)
+            .then(x.columns.len().cmp(&y.columns.len()))
+            .then(a.cmp(b))
+    });
+    suggestions.truncate(MAX_SUGGESTIONS);
+
+    Ok(KeyAnalysis {
+        rows: rows.len(),
+        columns,
+        suggestions: suggestions.into_iter().map(|(_, candidate)| candidate).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_key_candidates() {
+        let csv = "id,first,last,city\n1,Ann,Lee,Oslo\n2,Ann,Kim,Oslo\n3,Bob,Lee,\n,Bob,Kim,Rome\n";
+        let analysis = analyze_key_candidates(csv, &DiffOptions::default()).unwrap();
+        assert_eq!(analysis.rows, 4);
+
+        let id = &analysis.columns[0];
+        assert_eq!((id.uniqueness, id.null_rate), (1.0, 0.25));
+        let city = &analysis.columns[3];
+        assert_eq!((city.uniqueness, city.null_rate), (0.75, 0.25));
+
+        let ranked: Vec<Vec<&str>> = analysis.suggestions.iter()
+            .map(|s| s.columns.iter().map(String::as_str).collect())
+            .collect();
+        assert_eq!(ranked[0], vec!["first", "last"]);
+        assert_eq!(analysis.suggestions[0].score, 1.0);
+        assert_eq!(ranked[1], vec!["id"]);
+    }
+}
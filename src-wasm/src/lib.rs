@@ -1,8 +1,22 @@
 mod types;
 mod utils;
+mod normalize;
+mod json_cell;
+mod multi_value;
+mod similarity;
+mod blocking;
+mod messages;
 mod parse;
+mod columnar;
+mod mapping;
+mod paste;
+mod chunk_parser;
+mod byte_parse;
+mod framing;
+pub mod options;
 mod primary_key;
 mod content_match;
+mod positional;
 pub mod core;
 mod binary;
 mod binary_encoder;
@@ -10,11 +24,36 @@ mod profiling;
 pub mod parallel;
 mod streaming;
 mod memory;
+mod summary;
+mod snapshot;
+mod report;
+mod session;
+mod explain;
+mod estimate;
+mod three_way;
+mod n_way;
+mod key_candidates;
+mod writer;
+mod patch;
+mod merge;
+mod html;
+mod sql;
+mod filter;
+mod sort;
+mod search;
+mod cell_diff;
+mod quick_summary;
+mod batches;
+#[cfg(feature = "xlsx")]
+mod xlsx;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 mod wasm_api;
 mod wasm_tests;
 
 pub use wasm_api::*;
 pub use memory::*;
+pub use chunk_parser::{diff_csv_from_readers, parse_csv_from_reader, CsvChunkParser};
 
 #[cfg(test)]
 mod test_data;
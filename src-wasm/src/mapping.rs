@@ -0,0 +1,740 @@
+//! Source-to-target column mapping.
+//!
+//! When the two files name or order their columns differently, a
+//! `ColumnMapping` (source header → target header) says which target column
+//! each source column is compared with. Mapped target headers are renamed to
+//! their source names right after parsing, so the engines keep matching
+//! columns by name. `propose_column_mapping` suggests a mapping by
+//! fuzzy-matching header names. Excluded columns are resolved against the
+//! mapped headers by `resolve_excluded_columns`. `schema_changes` lists
+//! added, removed, renamed and reordered columns for diff results, and
+//! `diff_headers` does the same from two files' header rows alone. With
+//! `DiffOptions::detect_renames`, `map_target_columns` also maps columns
+//! whose names and values suggest a rename.
+
+use std::collections::BTreeMap;
+use ahash::{AHashMap, AHashSet};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use strsim::jaro_winkler;
+use crate::columnar::Fields;
+use crate::messages::{codes, render, Message};
+use crate::options::DiffOptions;
+use crate::parse::parse_headers;
+use crate::types::{HeaderDiff, SchemaChanges};
+
+/// Source header → target header. Unmapped columns are matched by name.
+pub type ColumnMapping = BTreeMap<String, String>;
+
+/// Minimum similarity of normalized header names for a fuzzy proposal.
+const HEADER_SIMILARITY_THRESHOLD: f64 = 0.85;
+
+/// Rows per file whose values are compared when detecting renames.
+const RENAME_SAMPLE_ROWS: usize = 1000;
+
+/// Minimum average of header similarity and value overlap for a detected
+/// rename.
+const RENAME_SCORE_THRESHOLD: f64 = 0.7;
+
+/// Rename the mapped target headers to their source names, in both the
+/// header list and the header map.
+pub fn apply_column_mapping(
+    headers: &mut [String],
+    header_map: &mut AHashMap<String, usize>,
+    mapping: &ColumnMapping,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if mapping.is_empty() {
+        return Ok(());
+    }
+
+    let mut renamed: Vec<Option<&String>> = vec![None; headers.len()];
+    for (source, target) in mapping {
+        let &index = header_map
+            .get(target)
+            .ok_or_else(|| format!("Mapped column \"{}\" not found in target dataset.", target))?;
+        if renamed[index].replace(source).is_some() {
+            return Err(format!("Target column \"{}\" is mapped more than once.", target).into());
+        }
+    }
+
+    // Renames apply simultaneously, so swapping two names is allowed
+    let new_headers: Vec<String> = headers.iter().zip(&renamed)
+        .map(|(header, source)| source.map_or_else(|| header.clone(), |s| s.clone()))
+        .collect();
+    let mut new_map = AHashMap::with_capacity(new_headers.len());
+    for (index, header) in new_headers.iter().enumerate() {
+        if new_map.insert(header.clone(), index).is_some() {
+            return Err(format!("Column mapping produces duplicate target column \"{}\".", header).into());
+        }
+    }
+
+    headers.clone_from_slice(&new_headers);
+    *header_map = new_map;
+    Ok(())
+}
+
+/// How to treat excluded columns that match no header in either file.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Strictness {
+    Ignore,
+    /// Report them in `DiffResult::warnings`
+    #[default]
+    Warn,
+    /// Fail the diff
+    Error,
+}
+
+/// Resolve `options.excluded_columns` and `options.included_columns` against
+/// the parsed (and mapped) headers. An entry is a header name, a wildcard
+/// pattern (`*` and `?`, as in `*_timestamp`) or a regex between slashes
+/// (`/^audit_/`). Without case sensitivity an entry matches headers ignoring
+/// case. With included columns, every header they don't match is excluded
+/// too, as is every unmapped header under `HeaderPolicy::ByMapping`. Returns the header names to exclude and the warnings for entries
+/// matching no header; unknown plain excluded names are kept as given.
+pub fn resolve_excluded_columns(
+    source_headers: &[String],
+    target_headers: &[String],
+    options: &DiffOptions,
+) -> Result<(Vec<String>, Vec<String>), Box<dyn std::error::Error>> {
+    let headers = || source_headers.iter().chain(target_headers);
+    let mut excluded: Vec<String> = Vec::new();
+    let mut unknown: Vec<(&str, &String)> = Vec::new();
+    let mut exclude = |name: &String| {
+        if !excluded.contains(name) {
+            excluded.push(name.clone());
+        }
+    };
+    for entry in &options.excluded_columns {
+        let (matched, is_pattern) = matching_headers(entry, headers(), options.case_sensitive)?;
+        if matched.is_empty() {
+            unknown.push((codes::UNKNOWN_EXCLUDED_COLUMN, entry));
+            if !is_pattern {
+                exclude(entry);
+            }
+        }
+        matched.into_iter().for_each(&mut exclude);
+    }
+
+    if !options.included_columns.is_empty() {
+        let mut included: Vec<&String> = Vec::new();
+        for entry in &options.included_columns {
+            let (matched, _) = matching_headers(entry, headers(), options.case_sensitive)?;
+            if matched.is_empty() {
+                unknown.push((codes::UNKNOWN_INCLUDED_COLUMN, entry));
+            }
+            included.extend(matched);
+        }
+        headers().filter(|header| !included.contains(header)).for_each(&mut exclude);
+    }
+    if options.header_policy == HeaderPolicy::ByMapping {
+        headers().filter(|header| !options.column_mapping.contains_key(*header)).for_each(&mut exclude);
+    }
+
+    let warnings = match (options.unknown_excluded_columns, unknown.first()) {
+        (Strictness::Error, Some(&(code, column))) => {
+            return Err(Message::new(code).with("column", column).into());
+        }
+        (Strictness::Warn, _) => unknown.iter()
+            .map(|&(code, column)| {
+                let code = if code == codes::UNKNOWN_INCLUDED_COLUMN { codes::WARN_UNKNOWN_INCLUDED_COLUMN } else { codes::WARN_UNKNOWN_EXCLUDED_COLUMN };
+                let warning = Message::new(code).with("column", column);
+                render(&warning.encode(), options.parse.message_format).into_owned()
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    Ok((excluded, warnings))
+}
+
+/// How source and target columns are paired up.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum HeaderPolicy {
+    /// Columns with the same name (after `column_mapping`) are compared
+    #[default]
+    ByName,
+    /// Column N is compared with column N whatever the names (and
+    /// `column_mapping`); the files must have as many columns
+    Positional,
+    /// Only the columns in `column_mapping` are compared
+    ByMapping,
+    /// Fail unless both files have the same headers in the same order
+    /// (after `column_mapping`)
+    Error,
+}
+
+/// Apply `options.column_mapping` and `options.header_policy` to the target
+/// headers. Returns the positional renames.
+pub fn apply_header_policy(
+    source_headers: &[String],
+    target_headers: &mut [String],
+    target_header_map: &mut AHashMap<String, usize>,
+    options: &DiffOptions,
+) -> Result<Vec<MappingProposal>, Box<dyn std::error::Error>> {
+    match options.header_policy {
+        HeaderPolicy::Positional => {
+            if source_headers.len() != target_headers.len() {
+                return Err(Message::new(codes::HEADER_COUNT_MISMATCH)
+                    .with("source", source_headers.len())
+                    .with("target", target_headers.len())
+                    .into());
+            }
+            let renames: Vec<MappingProposal> = source_headers.iter().zip(target_headers.iter())
+                .filter(|(source, target)| source != target)
+                .map(|(source, target)| proposal(source, target, 1.0, "position"))
+                .collect();
+            let mapping: ColumnMapping = renames.iter().map(|r| (r.source.clone(), r.target.clone())).collect();
+            apply_column_mapping(target_headers, target_header_map, &mapping)?;
+            Ok(renames)
+        }
+        HeaderPolicy::ByMapping if options.column_mapping.is_empty() => {
+            Err("The by-mapping header policy needs a column mapping.".into())
+        }
+        HeaderPolicy::ByName | HeaderPolicy::ByMapping => {
+            apply_column_mapping(target_headers, target_header_map, &options.column_mapping)?;
+            Ok(Vec::new())
+        }
+        HeaderPolicy::Error => {
+            apply_column_mapping(target_headers, target_header_map, &options.column_mapping)?;
+            let mismatch = (0..source_headers.len().max(target_headers.len()))
+                .find(|&i| source_headers.get(i) != target_headers.get(i));
+            match mismatch {
+                Some(i) => Err(Message::new(codes::HEADER_MISMATCH)
+                    .with("position", i + 1)
+                    .with("source", source_headers.get(i).map_or("", String::as_str))
+                    .with("target", target_headers.get(i).map_or("", String::as_str))
+                    .into()),
+                None => Ok(Vec::new()),
+            }
+        }
+    }
+}
+
+/// Apply `options.column_mapping` and `options.header_policy` to the target
+/// and, with `options.detect_renames`, map the remaining target columns
+/// that look like renamed source columns as well. Returns the positional
+/// and detected renames.
+pub fn map_target_columns<S: Fields, T: Fields>(
+    (source_headers, source_rows): (&[String], &[S]),
+    (target_headers, target_header_map, target_rows): (&mut [String], &mut AHashMap<String, usize>, &[T]),
+    options: &DiffOptions,
+) -> Result<Vec<MappingProposal>, Box<dyn std::error::Error>> {
+    let positional = apply_header_policy(source_headers, target_headers, target_header_map, options)?;
+    if !options.detect_renames || options.header_policy != HeaderPolicy::ByName {
+        return Ok(positional);
+    }
+    let renames = detect_renames((source_headers, source_rows), (target_headers, target_rows));
+    let mapping: ColumnMapping = renames.iter().map(|r| (r.source.clone(), r.target.clone())).collect();
+    apply_column_mapping(target_headers, target_header_map, &mapping)?;
+    Ok(renames)
+}
+
+/// Pair source and target columns missing from the other file when the
+/// average of their header similarity and the overlap of their distinct
+/// values (over the first `RENAME_SAMPLE_ROWS` rows) reaches
+/// `RENAME_SCORE_THRESHOLD`, best pairs first. Values carry half the score,
+/// so `cust_id` → `customer_id` is found with unchanged values while
+/// similarly named columns with unrelated values are not.
+fn detect_renames<S: Fields, T: Fields>(
+    (source_headers, source_rows): (&[String], &[S]),
+    (target_headers, target_rows): (&[String], &[T]),
+) -> Vec<MappingProposal> {
+    let unmatched_source: Vec<usize> = (0..source_headers.len())
+        .filter(|&s| !target_headers.contains(&source_headers[s]))
+        .collect();
+    let unmatched_target: Vec<usize> = (0..target_headers.len())
+        .filter(|&t| !source_headers.contains(&target_headers[t]))
+        .collect();
+    let target_values: Vec<AHashSet<&str>> = unmatched_target.iter().map(|&t| sampled_values(target_rows, t)).collect();
+
+    let mut candidates: Vec<(f64, usize, usize)> = Vec::new();
+    for &s in &unmatched_source {
+        let values = sampled_values(source_rows, s);
+        let name = normalize_header(&source_headers[s]);
+        for (&t, other) in unmatched_target.iter().zip(&target_values) {
+            let union = values.union(other).count();
+            let overlap = if union == 0 { 0.0 } else { values.intersection(other).count() as f64 / union as f64 };
+            let score = (jaro_winkler(&name, &normalize_header(&target_headers[t])) + overlap) / 2.0;
+            if score >= RENAME_SCORE_THRESHOLD {
+                candidates.push((score, s, t));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0).then((a.1, a.2).cmp(&(b.1, b.2))));
+
+    let (mut mapped_source, mut mapped_target) = (vec![false; source_headers.len()], vec![false; target_headers.len()]);
+    let mut renames = Vec::new();
+    for (score, s, t) in candidates {
+        if mapped_source[s] || mapped_target[t] {
+            continue;
+        }
+        mapped_source[s] = true;
+        mapped_target[t] = true;
+        renames.push(proposal(&source_headers[s], &target_headers[t], score, "detected"));
+    }
+    renames
+}
+
+/// Distinct values of column `index` in the first `RENAME_SAMPLE_ROWS` rows.
+fn sampled_values<R: Fields>(rows: &[R], index: usize) -> AHashSet<&str> {
+    rows.iter().take(RENAME_SAMPLE_ROWS).map(|row| row.field(index).unwrap_or("")).collect()
+}
+
+/// Headers matched by an excluded or included column entry, and whether the
+/// entry is a pattern rather than a plain name.
+fn matching_headers<'h>(
+    entry: &str,
+    headers: impl Iterator<Item = &'h String>,
+    case_sensitive: bool,
+) -> Result<(Vec<&'h String>, bool), Box<dyn std::error::Error>> {
+    let pattern = column_pattern(entry, case_sensitive)?;
+    let matched = headers
+        .filter(|header| match &pattern {
+            Some(regex) => regex.is_match(header),
+            None => *header == entry || (!case_sensitive && header.to_lowercase() == entry.to_lowercase()),
+        })
+        .collect();
+    Ok((matched, pattern.is_some()))
+}
+
+/// The regex a column entry stands for, or `None` for a plain header name.
+fn column_pattern(entry: &str, case_sensitive: bool) -> Result<Option<Regex>, Box<dyn std::error::Error>> {
+    let pattern = if let Some(regex) = entry.strip_prefix('/').and_then(|e| e.strip_suffix('/')).filter(|r| !r.is_empty()) {
+        regex.to_string()
+    } else if entry.contains(['*', '?']) {
+        let glob: String = entry.chars()
+            .map(|c| match c {
+                '*' => ".*".to_string(),
+                '?' => ".".to_string(),
+                c => regex::escape(c.encode_utf8(&mut [0; 4])),
+            })
+            .collect();
+        format!("^{}$", glob)
+    } else {
+        return Ok(None);
+    };
+    let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(!case_sensitive)
+        .build()
+        .map_err(|e| format!("Invalid excluded column pattern \"{}\": {}", entry, e))?;
+    Ok(Some(regex))
+}
+
+/// A suggested source → target column pairing.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MappingProposal {
+    pub source: String,
+    pub target: String,
+    /// Similarity of the normalized header names (1.0 for exact matches)
+    pub score: f64,
+    /// "normalized", "fuzzy" or "position"; "mapping" and "detected" for
+    /// explicit and detected renames
+    pub reason: String,
+}
+
+/// Propose target columns for the source columns whose names do not appear
+/// verbatim in the target. Pairs are chosen greedily by header similarity
+/// (case, spacing and punctuation ignored); when both files have the same
+/// number of columns, leftovers at the same position are paired last.
+pub fn propose_column_mapping(source_headers: &[String], target_headers: &[String]) -> Vec<MappingProposal> {
+    let unmatched_source: Vec<usize> = (0..source_headers.len())
+        .filter(|&i| !target_headers.contains(&source_headers[i]))
+        .collect();
+    let mut free_target: Vec<bool> = target_headers.iter().map(|t| !source_headers.contains(t)).collect();
+
+    let mut candidates: Vec<(f64, usize, usize)> = Vec::new();
+    for &s in &unmatched_source {
+        let source = normalize_header(&source_headers[s]);
+        for (t, target) in target_headers.iter().enumerate().filter(|&(t, _)| free_target[t]) {
+            let score = jaro_winkler(&source, &normalize_header(target));
+            if score >= HEADER_SIMILARITY_THRESHOLD {
+                candidates.push((score, s, t));
+            }
+        }
+    }
+    // Best score first; ties keep header order
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0).then((a.1, a.2).cmp(&(b.1, b.2))));
+
+    let mut proposals: Vec<(usize, MappingProposal)> = Vec::new();
+    let mut mapped_source = vec![false; source_headers.len()];
+    for (score, s, t) in candidates {
+        if mapped_source[s] || !free_target[t] {
+            continue;
+        }
+        mapped_source[s] = true;
+        free_target[t] = false;
+        let reason = if score >= 1.0 { "normalized" } else { "fuzzy" };
+        proposals.push((s, proposal(&source_headers[s], &target_headers[t], score, reason)));
+    }
+
+    if source_headers.len() == target_headers.len() {
+        for &s in &unmatched_source {
+            if !mapped_source[s] && free_target[s] {
+                free_target[s] = false;
+                let score = jaro_winkler(&normalize_header(&source_headers[s]), &normalize_header(&target_headers[s]));
+                proposals.push((s, proposal(&source_headers[s], &target_headers[s], score, "position")));
+            }
+        }
+    }
+
+    proposals.sort_by_key(|(s, _)| *s);
+    proposals.into_iter().map(|(_, p)| p).collect()
+}
+
+/// Compare the columns of two files without parsing their rows. Explicitly
+/// mapped and proposed renames count as shared columns; `reordered` lists
+/// the fewest shared columns whose moves explain the target order.
+pub fn diff_headers(
+    source_csv: &str,
+    target_csv: &str,
+    options: &DiffOptions,
+) -> Result<HeaderDiff, Box<dyn std::error::Error>> {
+    let source_headers = parse_headers(source_csv, &options.parse)?;
+    let target_headers = parse_headers(target_csv, &options.parse)?;
+
+    let mut mapped = target_headers.clone();
+    let mut mapped_map: AHashMap<String, usize> = mapped.iter().enumerate().map(|(i, h)| (h.clone(), i)).collect();
+    apply_column_mapping(&mut mapped, &mut mapped_map, &options.column_mapping)?;
+    let proposals = propose_column_mapping(&source_headers, &mapped);
+    let proposed: ColumnMapping = proposals.iter().map(|p| (p.source.clone(), p.target.clone())).collect();
+    apply_column_mapping(&mut mapped, &mut mapped_map, &proposed)?;
+
+    let changes = schema_changes(&source_headers, &mapped, options, &proposals);
+    Ok(HeaderDiff {
+        identical: source_headers == target_headers,
+        source_headers,
+        target_headers,
+        added: changes.added,
+        removed: changes.removed,
+        reordered: changes.reordered,
+        renamed: changes.renamed,
+    })
+}
+
+/// Column changes between files with `source_headers` and, once
+/// `options.column_mapping` and the `detected` renames are applied,
+/// `mapped_target_headers`. Renamed columns count as shared; `reordered`
+/// lists the fewest shared columns whose moves explain the target order.
+pub fn schema_changes(
+    source_headers: &[String],
+    mapped_target_headers: &[String],
+    options: &DiffOptions,
+    detected: &[MappingProposal],
+) -> SchemaChanges {
+    let mut renamed: Vec<MappingProposal> = source_headers.iter()
+        .filter_map(|s| options.column_mapping.get(s).map(|t| proposal(s, t, 1.0, "mapping")))
+        .collect();
+    renamed.extend_from_slice(detected);
+
+    let removed = source_headers.iter()
+        .filter(|s| !mapped_target_headers.contains(s))
+        .cloned()
+        .collect();
+    let added = mapped_target_headers.iter()
+        .filter(|t| !source_headers.contains(t))
+        .cloned()
+        .collect();
+
+    let target_order: Vec<&str> = mapped_target_headers.iter()
+        .filter(|t| source_headers.contains(t))
+        .map(String::as_str)
+        .collect();
+    let source_order: Vec<&str> = source_headers.iter()
+        .map(String::as_str)
+        .filter(|s| target_order.contains(s))
+        .collect();
+    let in_place = longest_common_subsequence(&source_order, &target_order);
+    let reordered = source_order.iter()
+        .filter(|s| !in_place.contains(s))
+        .map(|s| s.to_string())
+        .collect();
+
+    SchemaChanges { added, removed, renamed, reordered }
+}
+
+/// Elements of one longest common subsequence of `a` and `b`.
+fn longest_common_subsequence<'a>(a: &[&'a str], b: &[&str]) -> Vec<&'a str> {
+    // lengths[i][j]: LCS length of a[i..] and b[j..]
+    let mut lengths = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut common = Vec::with_capacity(lengths[0][0]);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            common.push(a[i]);
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    common
+}
+
+fn proposal(source: &str, target: &str, score: f64, reason: &str) -> MappingProposal {
+    MappingProposal {
+        source: source.to_string(),
+        target: target.to_string(),
+        score,
+        reason: reason.to_string(),
+    }
+}
+
+/// Lowercase alphanumerics only, so "Customer ID" matches "customer_id".
+fn normalize_header(header: &str) -> String {
+    header.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::DiffOptions;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_propose_column_mapping() {
+        let source = strings(&["id", "Customer ID", "E-mail", "city", "notes"]);
+        let target = strings(&["id", "customer_id", "email_address", "town", "comment"]);
+        let proposals = propose_column_mapping(&source, &target);
+        let pairs: Vec<(&str, &str, &str)> = proposals.iter()
+            .map(|p| (p.source.as_str(), p.target.as_str(), p.reason.as_str()))
+            .collect();
+        assert_eq!(pairs, vec![
+            ("Customer ID", "customer_id", "normalized"),
+            ("E-mail", "email_address", "fuzzy"),
+            ("city", "town", "position"),
+            ("notes", "comment", "position"),
+        ]);
+    }
+
+    #[test]
+    fn test_mapping_is_honored_by_both_modes() {
+        let source = "id,name,city\n1,Alice,Oslo\n2,Bob,Rome\n";
+        let target = "town,full_name,id\nOslo,Alice,1\nParis,Bob,2\n";
+        let mapping: ColumnMapping = [("name", "full_name"), ("city", "town")].iter()
+            .map(|(s, t)| (s.to_string(), t.to_string()))
+            .collect();
+
+        for (mode, key_columns) in [("primary-key", vec!["id".to_string()]), ("content-match", vec![])] {
+            for use_parallel in [false, true] {
+                let options = DiffOptions {
+                    mode: mode.to_string(),
+                    key_columns: key_columns.clone(),
+                    column_mapping: mapping.clone(),
+                    use_parallel,
+                    ..Default::default()
+                };
+                let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+                assert_eq!((result.unchanged.len(), result.modified.len()), (1, 1), "{} parallel={}", mode, use_parallel);
+                let difference = &result.modified[0].differences[0];
+                assert_eq!((difference.column.as_str(), difference.new_value.as_str()), ("city", "Paris"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_detect_renames() {
+        let source = "id,cust_id,name\n1,C-10,Ann\n2,C-11,Bob\n3,C-12,Cy\n4,C-13,Di\n";
+        let target = "id,customer_id,name\n1,C-10,Ann\n2,C-11,Bob\n3,C-12,Cy\n4,C-99,Di\n";
+        for (mode, use_parallel) in [("primary-key", false), ("primary-key", true), ("content-match", false), ("positional", false)] {
+            let mut options = DiffOptions {
+                mode: mode.to_string(),
+                key_columns: strings(&["id"]),
+                use_parallel,
+                ..Default::default()
+            };
+            let plain = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            assert!(plain.renamed_columns.is_empty());
+
+            options.detect_renames = true;
+            let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            let renames: Vec<(&str, &str, &str)> = result.renamed_columns.iter()
+                .map(|r| (r.source.as_str(), r.target.as_str(), r.reason.as_str()))
+                .collect();
+            assert_eq!(renames, vec![("cust_id", "customer_id", "detected")], "{}", mode);
+            assert_eq!((result.summary.unchanged, result.summary.modified), (3, 1), "{}", mode);
+            let difference = &result.modified[0].differences[0];
+            assert_eq!((difference.column.as_str(), difference.new_value.as_str()), ("cust_id", "C-99"));
+        }
+
+        // Scores and ratings share neither name nor values
+        let rows = |values: &[&str]| values.iter().map(|v| csv::StringRecord::from(vec![*v])).collect::<Vec<_>>();
+        let renames = detect_renames((&strings(&["score"]), &rows(&["5", "7"])), (&strings(&["rating"]), &rows(&["high", "low"])));
+        assert!(renames.is_empty());
+    }
+
+    #[test]
+    fn test_schema_changes() {
+        let source = "id,name,email,city,fax\n1,Ann,a@x,Oslo,0\n2,Bob,b@x,Rome,0\n";
+        let target = "id,full_name,city,email,zip\n1,Ann,Oslo,a@x,N1\n2,Bob,Rome,b@x,R2\n";
+        for mode in ["primary-key", "content-match"] {
+            let options = DiffOptions {
+                mode: mode.to_string(),
+                key_columns: strings(&["id"]),
+                column_mapping: [("name".to_string(), "full_name".to_string())].into_iter().collect(),
+                excluded_columns: strings(&["fax", "zip"]),
+                ..Default::default()
+            };
+            let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            let changes = &result.schema_changes;
+            assert_eq!(changes.added, strings(&["zip"]), "{}", mode);
+            assert_eq!(changes.removed, strings(&["fax"]), "{}", mode);
+            assert_eq!(changes.reordered, strings(&["email"]), "{}", mode);
+            let renamed: Vec<(&str, &str, &str)> = changes.renamed.iter()
+                .map(|r| (r.source.as_str(), r.target.as_str(), r.reason.as_str()))
+                .collect();
+            assert_eq!(renamed, vec![("name", "full_name", "mapping")], "{}", mode);
+        }
+    }
+
+    #[test]
+    fn test_header_policies() {
+        let source = "id,name,city\n1,Ann,Oslo\n2,Bob,Rome\n";
+        let target = "id,city,name\n1,Ann,Oslo\n2,Bob,Milan\n";
+        let run = |header_policy: HeaderPolicy, column_mapping: &[(&str, &str)]| {
+            let options = DiffOptions {
+                mode: "content-match".to_string(),
+                header_policy,
+                column_mapping: column_mapping.iter().map(|&(s, t)| (s.to_string(), t.to_string())).collect(),
+                ..Default::default()
+            };
+            crate::core::diff_with_options(source, target, &options, |_, _| {})
+        };
+
+        // By name, the swapped columns compare Ann with Oslo
+        let by_name = run(HeaderPolicy::ByName, &[]).unwrap();
+        assert_eq!((by_name.header_policy, by_name.summary.unchanged), (HeaderPolicy::ByName, 0));
+
+        let positional = run(HeaderPolicy::Positional, &[]).unwrap();
+        assert_eq!((positional.summary.unchanged, positional.summary.modified), (1, 1));
+        let renames: Vec<(&str, &str, &str)> = positional.renamed_columns.iter()
+            .map(|r| (r.source.as_str(), r.target.as_str(), r.reason.as_str()))
+            .collect();
+        assert_eq!(renames, vec![("name", "city", "position"), ("city", "name", "position")]);
+        assert_eq!(positional.header_policy, HeaderPolicy::Positional);
+
+        let by_mapping = run(HeaderPolicy::ByMapping, &[("id", "id")]).unwrap();
+        assert_eq!(by_mapping.excluded_columns, strings(&["name", "city"]));
+        assert_eq!(by_mapping.summary.unchanged, 2);
+        assert!(run(HeaderPolicy::ByMapping, &[]).is_err());
+
+        let error = run(HeaderPolicy::Error, &[]).err().unwrap().to_string();
+        assert_eq!(error, "Headers differ at column 2: \"name\" in source, \"city\" in target.");
+        assert!(run(HeaderPolicy::Error, &[("name", "city"), ("city", "name")]).is_ok());
+    }
+
+    #[test]
+    fn test_resolve_excluded_columns() {
+        let source = strings(&["id", "Email", "note"]);
+        let target = strings(&["id", "email", "notes"]);
+        let options = DiffOptions {
+            excluded_columns: strings(&["EMAIL", "nots", "note"]),
+            case_sensitive: false,
+            ..Default::default()
+        };
+        let (excluded, warnings) = resolve_excluded_columns(&source, &target, &options).unwrap();
+        assert_eq!(excluded, strings(&["Email", "email", "nots", "note"]));
+        assert_eq!(warnings, vec!["Excluded column \"nots\" not found in either dataset."]);
+
+        let strict = DiffOptions { unknown_excluded_columns: Strictness::Error, ..options.clone() };
+        assert!(resolve_excluded_columns(&source, &target, &strict).is_err());
+        let sensitive = DiffOptions { case_sensitive: true, unknown_excluded_columns: Strictness::Ignore, ..options };
+        let (excluded, warnings) = resolve_excluded_columns(&source, &target, &sensitive).unwrap();
+        assert_eq!((excluded.len(), warnings.len()), (3, 0));
+
+        let options = DiffOptions { excluded_columns: strings(&["nme"]), key_columns: strings(&["id"]), ..Default::default() };
+        let result = crate::core::diff_with_options("id,name\n1,a\n", "id,name\n1,b\n", &options, |_, _| {}).unwrap();
+        assert_eq!((result.modified.len(), result.warnings.len()), (1, 1));
+    }
+
+    #[test]
+    fn test_excluded_column_patterns() {
+        let source = strings(&["id", "created_timestamp", "Updated_Timestamp", "audit_user", "name"]);
+        let target = strings(&["id", "created_timestamp", "audit_host", "name"]);
+        let options = DiffOptions {
+            excluded_columns: strings(&["*_timestamp", "/^audit_/", "n?me", "*_unused"]),
+            case_sensitive: false,
+            ..Default::default()
+        };
+        let (excluded, warnings) = resolve_excluded_columns(&source, &target, &options).unwrap();
+        assert_eq!(excluded, strings(&["created_timestamp", "Updated_Timestamp", "audit_user", "audit_host", "name"]));
+        assert_eq!(warnings, vec!["Excluded column \"*_unused\" not found in either dataset."]);
+
+        let sensitive = DiffOptions { case_sensitive: true, ..options };
+        let (excluded, _) = resolve_excluded_columns(&source, &target, &sensitive).unwrap();
+        assert!(!excluded.contains(&"Updated_Timestamp".to_string()));
+
+        let invalid = DiffOptions { excluded_columns: strings(&["/[/"]), ..Default::default() };
+        assert!(resolve_excluded_columns(&source, &target, &invalid).is_err());
+    }
+
+    #[test]
+    fn test_included_columns() {
+        let source = "id,name,city,score\n1,Alice,Oslo,10\n2,Bob,Rome,20\n";
+        let target = "id,name,city,score\n1,Alice,Paris,10\n2,Bob,Rome,25\n";
+        let options = DiffOptions {
+            key_columns: strings(&["id"]),
+            included_columns: strings(&["id", "s*", "zip"]),
+            ..Default::default()
+        };
+        let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+        assert_eq!((result.unchanged.len(), result.modified.len()), (1, 1));
+        assert_eq!(result.modified[0].differences[0].column, "score");
+        assert_eq!(result.excluded_columns, strings(&["name", "city"]));
+        assert_eq!(result.warnings, vec!["Included column \"zip\" not found in either dataset."]);
+    }
+
+    #[test]
+    fn test_invalid_mapping_is_rejected() {
+        let mut headers = strings(&["a", "b"]);
+        let mut header_map: AHashMap<String, usize> = [("a".to_string(), 0), ("b".to_string(), 1)].into_iter().collect();
+        let missing: ColumnMapping = [("x".to_string(), "c".to_string())].into_iter().collect();
+        assert!(apply_column_mapping(&mut headers, &mut header_map, &missing).is_err());
+        let duplicate: ColumnMapping = [("b".to_string(), "a".to_string())].into_iter().collect();
+        assert!(apply_column_mapping(&mut headers, &mut header_map, &duplicate).is_err());
+
+        let swap: ColumnMapping = [("a".to_string(), "b".to_string()), ("b".to_string(), "a".to_string())].into_iter().collect();
+        apply_column_mapping(&mut headers, &mut header_map, &swap).unwrap();
+        assert_eq!(headers, strings(&["b", "a"]));
+        assert_eq!(header_map["a"], 1);
+    }
+
+    #[test]
+    fn test_diff_headers() {
+        let source = "id,name,email,city,phone\n1,Alice,a@x,Oslo,1\n";
+        let target = "id,Full Name,city,e-mail,zip,country\n1,Alice,Oslo,a@x,0150,NO\n";
+        let options = DiffOptions {
+            column_mapping: [("name".to_string(), "Full Name".to_string())].into_iter().collect(),
+            ..Default::default()
+        };
+        let diff = diff_headers(source, target, &options).unwrap();
+        assert_eq!(diff.added, strings(&["zip", "country"]));
+        assert_eq!(diff.removed, strings(&["phone"]));
+        assert_eq!(diff.reordered, strings(&["email"]));
+        let renamed: Vec<(&str, &str, &str)> = diff.renamed.iter()
+            .map(|p| (p.source.as_str(), p.target.as_str(), p.reason.as_str()))
+            .collect();
+        assert_eq!(renamed, vec![("name", "Full Name", "mapping"), ("email", "e-mail", "normalized")]);
+        assert!(!diff.identical);
+
+        // Numeric first rows are data, as in a full parse
+        let diff = diff_headers("1,2\n3,4\n", "a,b\n3,4\n", &DiffOptions::default()).unwrap();
+        assert_eq!(diff.source_headers, strings(&["Column1", "Column2"]));
+    }
+}
@@ -1,25 +1,126 @@
 use wasm_bindgen::prelude::*;
 use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use serde::Serialize;
 use std::thread_local;
 
 /// WASM memory allocation helpers and last binary result metadata
 
-/// Storage for last binary result length.
-/// # Safety
-/// These statics are mutated from other modules; ensure proper synchronization if used by multiple threads.
-static mut LAST_BINARY_RESULT_LENGTH: usize = 0;
-static mut LAST_BINARY_RESULT_CAPACITY: usize = 0;
+/// Length and capacity of the buffer returned by the last pointer-returning
+/// `*_binary` call. Diffs that may overlap (e.g. one started from another's
+/// progress callback) should use the `BinaryResult` handles instead, since a
+/// later call overwrites these.
+static LAST_BINARY_RESULT_LENGTH: AtomicUsize = AtomicUsize::new(0);
+static LAST_BINARY_RESULT_CAPACITY: AtomicUsize = AtomicUsize::new(0);
 
 /// Get the last binary result length
 #[wasm_bindgen]
 pub fn get_last_binary_result_length() -> usize {
-    unsafe { LAST_BINARY_RESULT_LENGTH }
+    LAST_BINARY_RESULT_LENGTH.load(Ordering::Relaxed)
 }
 
 /// Get the last binary result capacity
 #[wasm_bindgen]
 pub fn get_last_binary_result_capacity() -> usize {
-    unsafe { LAST_BINARY_RESULT_CAPACITY }
+    LAST_BINARY_RESULT_CAPACITY.load(Ordering::Relaxed)
+}
+
+/// Buffers handed to JS by pointer (`alloc`, pointer-returning `*_binary`
+/// calls), by address, with their capacity. Lets `dealloc` ignore pointers
+/// that were already reclaimed and `free_all_buffers` reclaim the rest.
+static LEAKED_BUFFERS: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+/// Data of live `BinaryResult` handles.
+static BINARY_RESULTS: Mutex<BTreeMap<u32, Vec<u8>>> = Mutex::new(BTreeMap::new());
+static NEXT_BINARY_RESULT: AtomicU32 = AtomicU32::new(1);
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Binary-encoded result owned by its handle, so concurrent diffs never share
+/// result metadata. Read `len` bytes at `ptr` from wasm memory (or copy them
+/// with `to_bytes`), then call `free()`. After `free_all` the handle reads as
+/// empty.
+#[wasm_bindgen]
+pub struct BinaryResult {
+    id: u32,
+}
+
+impl BinaryResult {
+    pub(crate) fn new(data: Vec<u8>) -> Self {
+        let id = NEXT_BINARY_RESULT.fetch_add(1, Ordering::Relaxed);
+        lock(&BINARY_RESULTS).insert(id, data);
+        Self { id }
+    }
+
+    fn with_data<T>(&self, f: impl FnOnce(&[u8]) -> T) -> T {
+        f(lock(&BINARY_RESULTS).get(&self.id).map_or(&[], Vec::as_slice))
+    }
+}
+
+impl Drop for BinaryResult {
+    fn drop(&mut self) {
+        lock(&BINARY_RESULTS).remove(&self.id);
+    }
+}
+
+#[wasm_bindgen]
+impl BinaryResult {
+    #[wasm_bindgen(getter)]
+    pub fn ptr(&self) -> *const u8 {
+        self.with_data(|data| if data.is_empty() { std::ptr::null() } else { data.as_ptr() })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> usize {
+        self.with_data(<[u8]>::len)
+    }
+
+    #[wasm_bindgen(getter, js_name = isEmpty)]
+    pub fn is_empty(&self) -> bool {
+        self.with_data(<[u8]>::is_empty)
+    }
+
+    /// Copy of the encoded bytes as a `Uint8Array`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.with_data(<[u8]>::to_vec)
+    }
+}
+
+/// Number of items each kind of retained state held before `free_all`.
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FreedCounts {
+    pub sessions: usize,
+    pub binary_results: usize,
+    pub buffers: usize,
+}
+
+/// Drop every `BinaryResult` payload and every buffer still handed out by
+/// pointer. Any view JS still holds on them becomes invalid.
+pub(crate) fn free_all_buffers() -> FreedCounts {
+    let binary_results = std::mem::take(&mut *lock(&BINARY_RESULTS)).len();
+    let buffers = std::mem::take(&mut *lock(&LEAKED_BUFFERS));
+    for (&ptr, &capacity) in &buffers {
+        unsafe {
+            let _ = Vec::from_raw_parts(ptr as *mut u8, 0, capacity);
+        }
+    }
+    LAST_BINARY_RESULT_LENGTH.store(0, Ordering::Relaxed);
+    LAST_BINARY_RESULT_CAPACITY.store(0, Ordering::Relaxed);
+
+    FreedCounts { sessions: 0, binary_results, buffers: buffers.len() }
+}
+
+/// Remember a buffer handed to JS by pointer. Zero-capacity buffers own no
+/// memory (and share a dangling address), so they are not tracked.
+fn track_buffer(ptr: *mut u8, capacity: usize) {
+    if capacity > 0 {
+        lock(&LEAKED_BUFFERS).insert(ptr as usize, capacity);
+    }
 }
 
 // Thread-local dummy variable to ensure TLS initialization symbols
@@ -54,40 +155,60 @@ thread_local! {
 
 #[wasm_bindgen]
 pub fn alloc(size: usize) -> *mut u8 {
-    let mut buf = Vec::with_capacity(size);
+    let mut buf: Vec<u8> = Vec::with_capacity(size);
     let ptr = buf.as_mut_ptr();
+    track_buffer(ptr, buf.capacity());
     std::mem::forget(buf);
     ptr
 }
 
+/// Free a buffer from `alloc` or a pointer-returning `*_binary` call.
+/// Pointers that are unknown or already reclaimed by `free_all` are ignored.
+// Only pointers found in `LEAKED_BUFFERS` are dereferenced
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 #[wasm_bindgen]
 pub fn dealloc(ptr: *mut u8, size: usize) {
     if ptr.is_null() {
         return;
     }
+    let Some(capacity) = lock(&LEAKED_BUFFERS).remove(&(ptr as usize)) else {
+        return;
+    };
+    debug_assert!(size <= capacity);
     unsafe {
-        let _ = Vec::from_raw_parts(ptr, 0, size);
+        let _ = Vec::from_raw_parts(ptr, 0, capacity);
     }
 }
 
-pub(crate) fn set_last_binary_result_length(len: usize) {
-    unsafe {
-        LAST_BINARY_RESULT_LENGTH = len;
+/// Run `f` on the first `len` bytes of a buffer from `alloc`, which JS has
+/// filled in. `None` if `ptr` is not a live `alloc` buffer that large.
+pub(crate) fn with_allocated<R>(ptr: *const u8, len: usize, f: impl FnOnce(&[u8]) -> R) -> Option<R> {
+    let capacity = lock(&LEAKED_BUFFERS).get(&(ptr as usize)).copied()?;
+    if len > capacity {
+        return None;
     }
+    // Tracked buffers are live until `dealloc`/`free_all`, and JS wrote the
+    // first `len` bytes
+    Some(f(unsafe { std::slice::from_raw_parts(ptr, len) }))
 }
 
-pub(crate) fn set_last_binary_result_capacity(cap: usize) {
-    unsafe {
-        LAST_BINARY_RESULT_CAPACITY = cap;
-    }
+/// Hand `data` over to JS as a raw pointer, recording its length and
+/// capacity for `get_last_binary_result_*` and `dealloc`.
+pub(crate) fn leak_binary_result(mut data: Vec<u8>) -> *mut u8 {
+    let ptr = data.as_mut_ptr();
+    LAST_BINARY_RESULT_LENGTH.store(data.len(), Ordering::Relaxed);
+    LAST_BINARY_RESULT_CAPACITY.store(data.capacity(), Ordering::Relaxed);
+    track_buffer(ptr, data.capacity());
+    std::mem::forget(data); // Don't drop, JS will read it
+    ptr
 }
 
 #[wasm_bindgen]
 pub fn get_binary_result_length() -> usize {
-    unsafe { LAST_BINARY_RESULT_LENGTH }
+    get_last_binary_result_length()
 }
 
 #[wasm_bindgen]
 pub fn get_binary_result_capacity() -> usize {
-    unsafe { LAST_BINARY_RESULT_CAPACITY }
+    get_last_binary_result_capacity()
 }
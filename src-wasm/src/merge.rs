@@ -0,0 +1,88 @@
+//! Merged files: the source file with a chosen subset of a diff's changes
+//! accepted.
+//!
+//! Every added, removed or modified row takes either its target side
+//! (the change is accepted) or its source side (the change is rejected).
+//! The merge drops rejected changes from the result and applies the
+//! remaining ones as a patch, so the merged file keeps the order of the
+//! source rows and appends accepted additions.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::parse::ParseOptions;
+use crate::patch::{apply_patch, build_patch};
+use crate::types::{DatasetMetadata, DiffResult};
+
+/// Side of a changed row that goes into the merged file.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeSide {
+    #[default]
+    Target,
+    Source,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MergeOptions {
+    /// Side taken by rows without a decision
+    pub strategy: MergeSide,
+    /// Per-row overrides of `strategy`, by the row's key in the result
+    pub decisions: HashMap<String, MergeSide>,
+}
+
+impl MergeOptions {
+    fn side(&self, key: &str) -> MergeSide {
+        self.decisions.get(key).copied().unwrap_or(self.strategy)
+    }
+}
+
+/// Merge `result` into `source_csv`, parsed with `parse` as in the diff
+/// that produced the result, taking each changed row from the side
+/// `options` picks. Headers follow the target file.
+pub fn merge_csv(
+    source_csv: &str,
+    result: &DiffResult,
+    options: &MergeOptions,
+    parse: &ParseOptions,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let accept = |key: &str| options.side(key) == MergeSide::Target;
+    // Only the changes, keys and target headers feed the patch
+    let accepted = DiffResult {
+        added: result.added.iter().filter(|row| accept(&row.key)).cloned().collect(),
+        removed: result.removed.iter().filter(|row| accept(&row.key)).cloned().collect(),
+        modified: result.modified.iter().filter(|row| accept(&row.key)).cloned().collect(),
+        key_columns: result.key_columns.clone(),
+        target: DatasetMetadata { headers: result.target.headers.clone(), rows: Vec::new() },
+        ..Default::default()
+    };
+    apply_patch(source_csv, &build_patch(&accepted), parse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::DiffOptions;
+
+    #[test]
+    fn test_merge_sides() {
+        let source = "id,name\n1,Alice\n2,Bob\n3,Carol\n";
+        let target = "id,name\n1,Alicia\n3,Carol\n4,Dan\n";
+        let options = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+        let merge = |merge: MergeOptions| merge_csv(source, &result, &merge, &options.parse).unwrap();
+
+        assert_eq!(merge(MergeOptions::default()), "id,name\n1,Alicia\n3,Carol\n4,Dan\n");
+        assert_eq!(merge(MergeOptions { strategy: MergeSide::Source, ..Default::default() }), source);
+
+        // Keep Bob and Alice's old name, accept Dan
+        let decisions = HashMap::from([
+            ("1".to_string(), MergeSide::Source),
+            ("2".to_string(), MergeSide::Source),
+        ]);
+        assert_eq!(
+            merge(MergeOptions { decisions, ..Default::default() }),
+            "id,name\n1,Alice\n2,Bob\n3,Carol\n4,Dan\n"
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//! Message catalog for progress and error strings.
+//!
+//! Every user-facing message has a stable code, named parameters and an
+//! English template. Engines emit messages in an encoded form
+//! (`MSG|{"code":..,"params":{..}}`); the `progress_sink` wrapped around the
+//! caller's callback either renders them to English (the default) or passes
+//! the encoded form through so JS can localize. Codes are part of the public
+//! contract and must not change once released.
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+/// Prefix of encoded messages, following the `THREAD_PROGRESS_JSON|` convention.
+pub const MESSAGE_PREFIX: &str = "MSG|";
+
+pub mod codes {
+    pub const PARSE_INIT: &str = "parse.init";
+    pub const PARSE_READING: &str = "parse.reading";
+    pub const PARSE_ROWS: &str = "parse.rows";
+    pub const PARSE_COMPLETE: &str = "parse.complete";
+    pub const PARSE_HEADERLESS: &str = "parse.headerless";
+    pub const PARSE_HEADERLESS_COMPLETE: &str = "parse.headerlessComplete";
+    pub const PARSE_SOURCE: &str = "parse.source";
+    pub const PARSE_TARGET: &str = "parse.target";
+    pub const PARSE_SQLITE_TABLE: &str = "parse.sqliteTable";
+    pub const BUILD_SOURCE_MAP: &str = "diff.buildSourceMap";
+    pub const BUILD_TARGET_MAP: &str = "diff.buildTargetMap";
+    pub const BUILD_FINGERPRINT_INDEX: &str = "diff.buildFingerprintIndex";
+    pub const MATCH_EXACT: &str = "diff.matchExact";
+    pub const MATCH_FUZZY: &str = "diff.matchFuzzy";
+    pub const MATCH_FUZZY_PROGRESS: &str = "diff.matchFuzzyProgress";
+    pub const COMPARE_ROWS: &str = "diff.compareRows";
+    pub const COMPARE_ROWS_PROGRESS: &str = "diff.compareRowsProgress";
+    pub const REMAINING_ROWS: &str = "diff.remainingRows";
+    pub const COMPLETE: &str = "diff.complete";
+    pub const KEY_COLUMN_MISSING: &str = "error.keyColumnMissing";
+    pub const DUPLICATE_KEY: &str = "error.duplicateKey";
+    pub const IDENTITY_COLUMN_MISSING: &str = "error.identityColumnMissing";
+    pub const WARN_DUPLICATE_KEYS: &str = "warn.duplicateKeys";
+    pub const FALLBACK_KEY_COLUMN_MISSING: &str = "fallback.keyColumnMissing";
+    pub const FALLBACK_DUPLICATE_KEY: &str = "fallback.duplicateKey";
+    pub const UNKNOWN_EXCLUDED_COLUMN: &str = "error.unknownExcludedColumn";
+    pub const WARN_UNKNOWN_EXCLUDED_COLUMN: &str = "warn.unknownExcludedColumn";
+    pub const UNKNOWN_INCLUDED_COLUMN: &str = "error.unknownIncludedColumn";
+    pub const WARN_UNKNOWN_INCLUDED_COLUMN: &str = "warn.unknownIncludedColumn";
+    pub const HEADER_MISMATCH: &str = "error.headerMismatch";
+    pub const HEADER_COUNT_MISMATCH: &str = "error.headerCountMismatch";
+    pub const SUMMARY_IDENTICAL: &str = "summary.identical";
+    pub const SUMMARY_ROW_COUNTS: &str = "summary.rowCounts";
+    pub const SUMMARY_HOT_COLUMN: &str = "summary.hotColumn";
+    pub const SUMMARY_HOT_COLUMNS: &str = "summary.hotColumns";
+}
+
+/// English templates. `{name}` placeholders are filled from message params.
+pub const CATALOG: &[(&str, &str)] = &[
+    (codes::PARSE_INIT, "Initializing CSV reader..."),
+    (codes::PARSE_READING, "Reading CSV data in chunks..."),
+    (codes::PARSE_ROWS, "Processed {count} rows"),
+    (codes::PARSE_COMPLETE, "CSV parsing complete"),
+    (codes::PARSE_HEADERLESS, "Parsing as headerless CSV..."),
+    (codes::PARSE_HEADERLESS_COMPLETE, "Headerless CSV parsing complete"),
+    (codes::PARSE_SOURCE, "Parsing source CSV..."),
+    (codes::PARSE_TARGET, "Parsing target CSV..."),
+    (codes::PARSE_SQLITE_TABLE, "Reading SQLite table \"{table}\"..."),
+    (codes::BUILD_SOURCE_MAP, "Building source map..."),
+    (codes::BUILD_TARGET_MAP, "Building target map..."),
+    (codes::BUILD_FINGERPRINT_INDEX, "Building fingerprint index..."),
+    (codes::MATCH_EXACT, "Matching exact rows..."),
+    (codes::MATCH_FUZZY, "Comparing rows with fuzzy matching..."),
+    (codes::MATCH_FUZZY_PROGRESS, "Fuzzy matching in parallel... ({processed}/{total})"),
+    (codes::COMPARE_ROWS, "Comparing rows..."),
+    (codes::COMPARE_ROWS_PROGRESS, "Comparing rows... ({processed}/{total})"),
+    (codes::REMAINING_ROWS, "Processing remaining rows..."),
+    (codes::COMPLETE, "Comparison complete"),
+    (codes::KEY_COLUMN_MISSING, "Primary key column \"{column}\" not found in {side} dataset."),
+    (codes::DUPLICATE_KEY, "Duplicate Primary Key found in {side}: \"{key}\". Primary Keys must be unique."),
+    (codes::IDENTITY_COLUMN_MISSING, "Identity column \"{column}\" not found in {side} dataset."),
+    (codes::FALLBACK_KEY_COLUMN_MISSING, "Primary key column \"{column}\" not found in {side} dataset; rows were matched by content instead."),
+    (codes::FALLBACK_DUPLICATE_KEY, "Duplicate Primary Key found in {side}: \"{key}\"; rows were matched by content instead."),
+    (codes::WARN_DUPLICATE_KEYS, "{count} rows in {side} repeat an earlier primary key (first: \"{key}\"); only the first row per key was compared."),
+    (codes::UNKNOWN_EXCLUDED_COLUMN, "Excluded column \"{column}\" not found in either dataset."),
+    (codes::WARN_UNKNOWN_EXCLUDED_COLUMN, "Excluded column \"{column}\" not found in either dataset."),
+    (codes::UNKNOWN_INCLUDED_COLUMN, "Included column \"{column}\" not found in either dataset."),
+    (codes::WARN_UNKNOWN_INCLUDED_COLUMN, "Included column \"{column}\" not found in either dataset."),
+    (codes::HEADER_MISMATCH, "Headers differ at column {position}: \"{source}\" in source, \"{target}\" in target."),
+    (codes::HEADER_COUNT_MISMATCH, "Positional header matching needs as many columns in both files ({source} in source, {target} in target)."),
+    (codes::SUMMARY_IDENTICAL, "No differences; {unchanged} rows unchanged"),
+    (codes::SUMMARY_ROW_COUNTS, "{added} rows added, {removed} removed, {modified} modified"),
+    (codes::SUMMARY_HOT_COLUMN, "{percent}% of modifications are in column {column}"),
+    (codes::SUMMARY_HOT_COLUMNS, "{percent}% of modifications are in columns {columns}"),
+];
+
+/// How messages reach the caller's progress callback and error values.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageFormat {
+    /// Rendered English text
+    #[default]
+    Text,
+    /// `MSG|{"code":..,"params":{..},"text":..}` for localization in JS
+    Code,
+}
+
+/// A catalog message with its parameters.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Message {
+    pub code: String,
+    #[serde(default)]
+    pub params: std::collections::BTreeMap<String, String>,
+    /// Scope of a nested message, e.g. "source" for parse progress of the source file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+impl Message {
+    pub fn new(code: &str) -> Self {
+        Self { code: code.to_string(), params: Default::default(), scope: None }
+    }
+
+    pub fn with(mut self, name: &str, value: impl ToString) -> Self {
+        self.params.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Encoded form passed through progress callbacks.
+    pub fn encode(&self) -> String {
+        format!("{}{}", MESSAGE_PREFIX, serde_json::to_string(self).unwrap_or_default())
+    }
+
+    pub fn decode(raw: &str) -> Option<Self> {
+        serde_json::from_str(raw.strip_prefix(MESSAGE_PREFIX)?).ok()
+    }
+
+    /// Encoded form including the rendered English text, for JS consumers.
+    fn encode_with_text(&self) -> String {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        value["text"] = serde_json::Value::String(self.to_string());
+        format!("{}{}", MESSAGE_PREFIX, value)
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let template = CATALOG
+            .iter()
+            .find(|(code, _)| *code == self.code)
+            .map(|(_, template)| *template)
+            .unwrap_or(self.code.as_str());
+
+        let mut text = template.to_string();
+        for (name, value) in &self.params {
+            text = text.replace(&format!("{{{}}}", name), value);
+        }
+        match self.scope.as_deref() {
+            Some("source") => write!(f, "Source: {}", text),
+            Some("target") => write!(f, "Target: {}", text),
+            Some(scope) => write!(f, "{}: {}", scope, text),
+            None => f.write_str(&text),
+        }
+    }
+}
+
+/// Error carrying a catalog message so the wasm layer can report its code.
+#[derive(Debug)]
+pub struct MessageError(pub Message);
+
+impl fmt::Display for MessageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for MessageError {}
+
+impl From<Message> for Box<dyn std::error::Error> {
+    fn from(message: Message) -> Self {
+        Box::new(MessageError(message))
+    }
+}
+
+/// Emit `message` through a progress callback in encoded form.
+pub fn emit<F: FnMut(f64, &str)>(on_progress: &mut F, percent: f64, message: Message) {
+    on_progress(percent, &message.encode());
+}
+
+/// Re-emit a nested progress message under `scope` ("source"/"target").
+pub fn scoped(scope: &str, raw: &str) -> String {
+    match Message::decode(raw) {
+        Some(mut message) => {
+            message.scope = Some(scope.to_string());
+            message.encode()
+        }
+        None if scope == "source" => format!("Source: {}", raw),
+        None if scope == "target" => format!("Target: {}", raw),
+        None => format!("{}: {}", scope, raw),
+    }
+}
+
+/// Convert an encoded message to what the caller asked for. Strings that are
+/// not catalog messages (e.g. `THREAD_PROGRESS|...`) pass through unchanged.
+pub fn render(raw: &str, format: MessageFormat) -> std::borrow::Cow<'_, str> {
+    match Message::decode(raw) {
+        Some(message) => match format {
+            MessageFormat::Text => message.to_string().into(),
+            MessageFormat::Code => message.encode_with_text().into(),
+        },
+        None => raw.into(),
+    }
+}
+
+/// Wrap a caller's progress callback so it receives messages in `format`.
+pub fn progress_sink<F: FnMut(f64, &str)>(format: MessageFormat, mut on_progress: F) -> impl FnMut(f64, &str) {
+    move |percent, raw| on_progress(percent, &render(raw, format))
+}
+
+/// Render an error for the caller: catalog errors become encoded messages in
+/// `Code` format, everything else is its display text.
+pub fn error_text(error: &(dyn std::error::Error + 'static), format: MessageFormat) -> String {
+    match (error.downcast_ref::<MessageError>(), format) {
+        (Some(MessageError(message)), MessageFormat::Code) => message.encode_with_text(),
+        _ => error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_rendering() {
+        let message = Message::new(codes::KEY_COLUMN_MISSING).with("column", "id").with("side", "source");
+        assert_eq!(message.to_string(), "Primary key column \"id\" not found in source dataset.");
+
+        let encoded = scoped("target", &Message::new(codes::PARSE_ROWS).with("count", 5).encode());
+        assert_eq!(render(&encoded, MessageFormat::Text), "Target: Processed 5 rows");
+
+        let coded = render(&encoded, MessageFormat::Code);
+        let decoded = Message::decode(&coded).unwrap();
+        assert_eq!(decoded.code, codes::PARSE_ROWS);
+        assert_eq!(decoded.params["count"], "5");
+
+        assert_eq!(render("THREAD_PROGRESS|0|1|2", MessageFormat::Code), "THREAD_PROGRESS|0|1|2");
+    }
+
+    #[test]
+    fn test_engine_progress_formats() {
+        let mut options = crate::options::DiffOptions {
+            key_columns: vec!["id".to_string()],
+            ..Default::default()
+        };
+        let mut text = Vec::new();
+        crate::core::diff_with_options("id\n1\n", "id\n1\n", &options, |_, m| text.push(m.to_string())).unwrap();
+        assert!(text.contains(&"Source: Initializing CSV reader...".to_string()));
+        assert!(text.iter().all(|m| !m.starts_with(MESSAGE_PREFIX)));
+
+        options.parse.message_format = MessageFormat::Code;
+        let mut coded = Vec::new();
+        crate::core::diff_with_options("id\n1\n", "id\n1\n", &options, |_, m| coded.push(m.to_string())).unwrap();
+        let first = Message::decode(&coded[0]).unwrap();
+        assert_eq!((first.code.as_str(), first.scope.as_deref()), (codes::PARSE_INIT, Some("source")));
+
+        let Err(error) = crate::core::diff_with_options("id\n1\n1\n", "id\n1\n", &options, |_, _| {}) else {
+            panic!("duplicate keys should fail");
+        };
+        let error = Message::decode(&error_text(error.as_ref(), MessageFormat::Code)).unwrap();
+        assert_eq!(error.code, codes::DUPLICATE_KEY);
+    }
+
+    #[test]
+    fn test_catalog_codes_are_unique() {
+        let mut seen = std::collections::HashSet::new();
+        for (code, _) in CATALOG {
+            assert!(seen.insert(code), "duplicate message code {}", code);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! Cells holding several delimited values.
+//!
+//! A `MultiValueColumn` rule makes "a;b;c" and "c;a;b" compare equal: the
+//! sub-values are compared as a multiset (or a set, ignoring repeats), and
+//! the diff of a changed cell lists the sub-values added and removed.
+
+use std::borrow::Cow;
+use ahash::{AHashMap, AHashSet};
+use serde::{Deserialize, Serialize};
+use crate::types::DiffChange;
+use crate::utils::normalize_value_cow;
+
+/// A column whose cells are `delimiter`-separated values in no particular
+/// order, e.g. `{ column: "tags", delimiter: ";" }`. With
+/// `ignoreDuplicates` repeated sub-values count once.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MultiValueColumn {
+    pub column: String,
+    pub delimiter: String,
+    #[serde(default)]
+    pub ignore_duplicates: bool,
+}
+
+impl MultiValueColumn {
+    /// Sub-values of `value`, each paired with its comparison form.
+    fn split<'a>(&self, value: &'a str, case_sensitive: bool, ignore_whitespace: bool) -> Vec<(&'a str, Cow<'a, str>)> {
+        if value.is_empty() {
+            return Vec::new();
+        }
+        let mut parts: Vec<(&str, Cow<str>)> = value.split(self.delimiter.as_str())
+            .map(|part| (part, normalize_value_cow(part, case_sensitive, ignore_whitespace, false)))
+            .collect();
+        if self.ignore_duplicates {
+            let mut seen = AHashSet::new();
+            parts.retain(|(_, normalized)| seen.insert(normalized.clone()));
+        }
+        parts
+    }
+
+    /// `value` with its sub-values in comparison form, sorted.
+    pub fn canonicalize(&self, value: &str, case_sensitive: bool, ignore_whitespace: bool) -> String {
+        let mut parts: Vec<Cow<str>> = self.split(value, case_sensitive, ignore_whitespace)
+            .into_iter()
+            .map(|(_, normalized)| normalized)
+            .collect();
+        parts.sort_unstable();
+        parts.join(&self.delimiter)
+    }
+
+    /// Sub-values of `old` missing from `new` as removed changes, then those
+    /// of `new` missing from `old` as added ones, in their original order.
+    pub fn diff(&self, old: &str, new: &str, case_sensitive: bool, ignore_whitespace: bool) -> Vec<DiffChange> {
+        let old_parts = self.split(old, case_sensitive, ignore_whitespace);
+        let new_parts = self.split(new, case_sensitive, ignore_whitespace);
+        let mut changes = unmatched(&old_parts, &new_parts, false);
+        changes.extend(unmatched(&new_parts, &old_parts, true));
+        changes
+    }
+}
+
+/// Sub-values of `parts` left over once each one in `others` has cancelled
+/// out one equal sub-value.
+fn unmatched(parts: &[(&str, Cow<str>)], others: &[(&str, Cow<str>)], added: bool) -> Vec<DiffChange> {
+    let mut available: AHashMap<&str, usize> = AHashMap::new();
+    for (_, normalized) in others {
+        *available.entry(normalized.as_ref()).or_default() += 1;
+    }
+    parts.iter()
+        .filter(|(_, normalized)| match available.get_mut(normalized.as_ref()) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        })
+        .map(|(part, _)| DiffChange { added, removed: !added, value: part.to_string(), path: None, intraline: Vec::new() })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::DiffOptions;
+
+    #[test]
+    fn test_multi_value_columns() {
+        let tags = MultiValueColumn { column: "tags".to_string(), delimiter: ";".to_string(), ignore_duplicates: false };
+        assert_eq!(tags.canonicalize("c;a;b", true, false), "a;b;c");
+        assert_ne!(tags.canonicalize("a;a;b", true, false), tags.canonicalize("a;b", true, false));
+        let set = MultiValueColumn { ignore_duplicates: true, ..tags.clone() };
+        assert_eq!(set.canonicalize("a;a;b", true, false), set.canonicalize("b;a", true, false));
+
+        let changes: Vec<(bool, String)> = tags.diff("a;b;b;c", "c;b;d", true, false).into_iter()
+            .map(|change| (change.added, change.value))
+            .collect();
+        assert_eq!(changes, vec![(false, "a".to_string()), (false, "b".to_string()), (true, "d".to_string())]);
+
+        let source = "id,tags\n1,\"a;b;c\"\n2,\"x; y\"\n";
+        let target = "id,tags\n1,\"c;a;b\"\n2,\"y;z\"\n";
+        let options = DiffOptions {
+            key_columns: vec!["id".to_string()],
+            multi_value_columns: vec![tags],
+            ignore_whitespace: true,
+            ..Default::default()
+        };
+        let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+        assert_eq!((result.unchanged.len(), result.modified.len()), (1, 1));
+        let diff = &result.modified[0].differences[0].diff;
+        assert_eq!((diff.len(), diff[0].value.as_str(), diff[1].value.as_str()), (2, "x", "z"));
+
+        let bad = DiffOptions {
+            multi_value_columns: vec![MultiValueColumn { column: "tags".to_string(), delimiter: String::new(), ignore_duplicates: false }],
+            ..Default::default()
+        };
+        assert!(crate::core::diff_with_options(source, target, &bad, |_, _| {}).is_err());
+    }
+}
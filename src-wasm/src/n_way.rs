@@ -0,0 +1,142 @@
+//! N-way diff: one source against several targets.
+//!
+//! Comparing daily exports against a baseline diffs the same source many
+//! times. `diff_csv_n_way` parses the source once and returns a result per
+//! target plus totals across them.
+
+use std::collections::BTreeSet;
+use ahash::{AHashMap, AHashSet};
+use crate::messages::{codes, emit, progress_sink, scoped, Message};
+use crate::options::DiffOptions;
+use crate::parse::parse_csv_streaming_with_options;
+use crate::types::*;
+
+/// Diff `source_csv` against each of `target_csvs`. The source is parsed
+/// once and each target diffed against it as `diff_with_options` would,
+/// with the mode, engine, fallback and reversal `options` select.
+/// Progress messages are scoped "target N".
+pub fn diff_csv_n_way<F>(
+    source_csv: &str,
+    target_csvs: &[&str],
+    options: &DiffOptions,
+    on_progress: F,
+) -> Result<NWayResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    if target_csvs.is_empty() {
+        return Err("N-way diff needs at least one target.".into());
+    }
+    let mut on_progress = progress_sink(options.parse.message_format, on_progress);
+    let options = &*options.resolve_keys();
+
+    let source = parse_csv_streaming_with_options(source_csv, &options.parse, &options.streaming, |percent, message| {
+        on_progress(percent * 0.1, &scoped("source", message)); // Scale to 0-10%
+    })?;
+
+    let share = 90.0 / target_csvs.len() as f64;
+    let mut results = Vec::with_capacity(target_csvs.len());
+    for (i, target_csv) in target_csvs.iter().enumerate() {
+        let scope = format!("target {}", i + 1);
+        let start = 10.0 + i as f64 * share;
+        let mut scaled = |percent: f64, message: &str| on_progress(start + percent * share / 100.0, &scoped(&scope, message));
+        let target = parse_csv_streaming_with_options(target_csv, &options.parse, &options.streaming, |percent, message| {
+            scaled(10.0 + percent * 0.1, message);
+        })?;
+        results.push(crate::core::diff_parsed(&source, &target, options, &mut scaled)?);
+    }
+    emit(&mut on_progress, 100.0, Message::new(codes::COMPLETE));
+
+    Ok(NWayResult { summary: summarize(&results), results })
+}
+
+/// Totals and per-key agreement across the targets' results.
+fn summarize(results: &[DiffResult]) -> NWaySummary {
+    let mut changed: AHashMap<&str, usize> = AHashMap::new();
+    // Positions of the source rows some target removes or modifies, counted
+    // from the rows themselves since unchanged ones may be left out
+    let mut changed_rows: AHashSet<usize> = AHashSet::new();
+    let mut source_rows = 0;
+    let mut summary = NWaySummary { targets: results.len(), ..Default::default() };
+    for result in results {
+        summary.added += result.summary.added;
+        summary.removed += result.summary.removed;
+        summary.modified += result.summary.modified;
+        // A key may be both added and removed (duplicate groups); count it once per target
+        let keys: BTreeSet<&str> = result.added.iter().map(|r| r.key.as_str())
+            .chain(result.removed.iter().map(|r| r.key.as_str()))
+            .chain(result.modified.iter().map(|r| r.key.as_str()))
+            .collect();
+        for key in keys {
+            *changed.entry(key).or_default() += 1;
+        }
+        // Reversed results have the n-way source on their target side
+        if result.reversed {
+            source_rows = result.summary.added + result.summary.modified + result.summary.unchanged;
+            changed_rows.extend(result.added.iter().filter_map(|r| r.target_index));
+            changed_rows.extend(result.modified.iter().filter_map(|r| r.target_index));
+        } else {
+            source_rows = result.summary.removed + result.summary.modified + result.summary.unchanged;
+            changed_rows.extend(result.removed.iter().filter_map(|r| r.source_index));
+            changed_rows.extend(result.modified.iter().filter_map(|r| r.source_index));
+        }
+    }
+
+    summary.changed_in_any = changed.len();
+    let mut changed_in_all: Vec<String> = changed.into_iter()
+        .filter(|&(_, count)| count == results.len())
+        .map(|(key, _)| key.to_string())
+        .collect();
+    changed_in_all.sort();
+    summary.changed_in_all = changed_in_all;
+    summary.unchanged_in_all = source_rows.saturating_sub(changed_rows.len());
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_n_way_diff() {
+        let source = "id,v\n1,a\n2,b\n3,c\n";
+        let monday = "id,v\n1,a\n2,B\n3,c\n4,d\n";
+        let tuesday = "id,v\n1,a\n2,X\n";
+        let keyed = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        for options in [
+            keyed.clone(),
+            DiffOptions { mode: "content-match".to_string(), ..keyed.clone() },
+            DiffOptions { reverse: true, ..keyed.clone() },
+            DiffOptions { use_parallel: true, ..keyed.clone() },
+            DiffOptions { key_columns: vec!["missing".to_string()], content_match_fallback: true, ..keyed.clone() },
+        ] {
+            let mode = format!("{} reverse={} parallel={} fallback={}", options.mode, options.reverse, options.use_parallel, options.content_match_fallback);
+            let separate: Vec<DiffSummary> = [monday, tuesday].iter()
+                .map(|target| crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap().summary)
+                .collect();
+
+            let mut last = 0.0;
+            let result = diff_csv_n_way(source, &[monday, tuesday], &options, |percent, _| {
+                assert!(percent >= last, "{}", mode);
+                last = percent;
+            }).unwrap();
+            let summaries: Vec<DiffSummary> = result.results.iter().map(|r| r.summary.clone()).collect();
+            assert_eq!(summaries, separate, "{}", mode);
+            assert!(result.results.iter().all(|r| r.reversed == options.reverse && r.fallback.is_some() == options.content_match_fallback), "{}", mode);
+        }
+
+        let options = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let result = diff_csv_n_way(source, &[monday, tuesday], &options, |_, _| {}).unwrap();
+        assert_eq!((result.summary.targets, result.summary.added, result.summary.removed, result.summary.modified), (2, 1, 1, 2));
+        assert_eq!(result.summary.unchanged_in_all, 1);
+        assert_eq!(result.summary.changed_in_all, vec!["2"]);
+        assert_eq!(result.summary.changed_in_any, 3);
+
+        // Unchanged rows left out of the results still count
+        let lean = DiffOptions { include_unchanged: false, ..options.clone() };
+        let result = diff_csv_n_way(source, &[monday, tuesday], &lean, |_, _| {}).unwrap();
+        assert!(result.results.iter().all(|r| r.unchanged.is_empty()));
+        assert_eq!(result.summary.unchanged_in_all, 1);
+        assert!(diff_csv_n_way(source, &[], &options, |_, _| {}).is_err());
+    }
+}
@@ -0,0 +1,838 @@
+//! Comparison normalization.
+//!
+//! `Normalizer` bundles the options that decide when two cell values count
+//! as equal: per-column regex transforms, numeric and boolean
+//! canonicalization, structural JSON comparison, multi-value cells,
+//! whitespace trimming, null equivalence, case folding and date parsing.
+//! Engines build one per diff and use it for cell comparison, fingerprints
+//! and column hashes, so every path agrees on equality.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use ahash::{AHashMap, AHashSet};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use crate::json_cell::{canonical_json, diff_documents, parse_document};
+use crate::multi_value::MultiValueColumn;
+use crate::options::{DiffOptions, TextGranularity};
+use crate::types::DiffChange;
+use crate::utils::normalize_value_cow;
+
+/// Canonical text of a parsed date/time; equal instants give equal text.
+const CANONICAL_DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+/// Normal form of every null value.
+pub const NULL_VALUE: &str = "EMPTY_OR_NULL";
+
+/// Values longer than this are never tried as dates.
+const MAX_DATE_LEN: usize = 40;
+
+/// Digit grouping and decimal mark of amounts in numeric columns.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NumberLocale {
+    /// "1,234.50"
+    En,
+    /// "1.234,50"
+    De,
+    /// "1 234,50"
+    Fr,
+    /// "1'234.50"
+    Ch,
+}
+
+impl NumberLocale {
+    fn separators(self) -> (&'static [char], char) {
+        match self {
+            Self::En => (&[','], '.'),
+            Self::De => (&['.'], ','),
+            Self::Fr => (&[' ', '\u{a0}', '\u{202f}'], ','),
+            Self::Ch => (&['\'', '\u{2019}'], '.'),
+        }
+    }
+}
+
+/// A regex find/replace applied to one column's values before comparison,
+/// e.g. `{ column: "price", find: "[$,]", replace: "" }`. `replace` may use
+/// `$1`-style group references.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueTransform {
+    pub column: String,
+    pub find: String,
+    #[serde(default)]
+    pub replace: String,
+}
+
+/// Spellings of a missing value that compare equal to each other, e.g.
+/// `{ values: ["", "null", "none", "-"], columns: { "end_date": ["", "9999-12-31"] } }`.
+/// Values match trimmed and ignoring ASCII case.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NullValues {
+    /// Null values of every column not listed in `columns`
+    pub values: Vec<String>,
+    /// Columns with their own null values; an empty list keeps all values
+    /// of the column distinct
+    pub columns: BTreeMap<String, Vec<String>>,
+}
+
+impl NullValues {
+    /// Null values of `options`, where `ignore_empty_vs_null` stands for ""
+    /// and "null" unless `null_values.values` lists others.
+    pub fn resolve(options: &DiffOptions) -> Self {
+        let mut null_values = options.null_values.clone();
+        if options.ignore_empty_vs_null && null_values.values.is_empty() {
+            null_values.values = vec![String::new(), "null".to_string()];
+        }
+        null_values
+    }
+
+    /// Whether no value of any column counts as null.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty() && self.columns.values().all(Vec::is_empty)
+    }
+
+    /// Null values of `column`.
+    pub fn of(&self, column: &str) -> &[String] {
+        self.columns.get(column).unwrap_or(&self.values)
+    }
+
+    /// Whether `value` is one of `null_values`.
+    pub fn matches(null_values: &[String], value: &str) -> bool {
+        let value = value.trim();
+        null_values.iter().any(|null| null.trim().eq_ignore_ascii_case(value))
+    }
+
+    /// Whether `value` is a null value of `column`.
+    #[inline]
+    pub fn is_null(&self, column: &str, value: &str) -> bool {
+        !self.is_empty() && Self::matches(self.of(column), value)
+    }
+}
+
+/// Cleanup of key values before rows are matched by key, so keys like
+/// "A001 " and "a001" find each other. Results report the cleaned keys.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct KeyNormalization {
+    /// Drop leading and trailing whitespace
+    pub trim: bool,
+    /// Match keys case-insensitively
+    pub case_fold: bool,
+    /// Drop leading zeros, so "007" matches "7"
+    pub strip_leading_zeros: bool,
+    /// Replace each key with a hash of it; set by `DiffOptions::resolve_keys`
+    #[serde(skip)]
+    pub hash: bool,
+    /// Null values, which all key as ""; set by `DiffOptions::resolve_keys`
+    #[serde(skip)]
+    pub null_values: NullValues,
+}
+
+impl KeyNormalization {
+    /// The key value a `column` value is matched by.
+    pub fn apply<'a>(&self, column: &str, value: &'a str) -> Cow<'a, str> {
+        if self.null_values.is_null(column, value) {
+            return Cow::Borrowed("");
+        }
+        let mut value = if self.trim { value.trim() } else { value };
+        if self.strip_leading_zeros {
+            let stripped = value.trim_start_matches('0');
+            // Keep one zero before a decimal point or when nothing else is left
+            let keep = usize::from(stripped.len() < value.len() && (stripped.is_empty() || stripped.starts_with('.')));
+            value = &value[value.len() - stripped.len() - keep..];
+        }
+        if self.case_fold { Cow::Owned(value.to_lowercase()) } else { Cow::Borrowed(value) }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Normalizer {
+    pub case_sensitive: bool,
+    pub ignore_whitespace: bool,
+    /// Values that compare equal as missing
+    pub null_values: NullValues,
+    date_formats: Vec<DateFormat>,
+    /// Compiled `ValueTransform`s by column, in the order given
+    transforms: AHashMap<String, Vec<(Regex, String)>>,
+    /// Columns whose decimal values compare by numeric value
+    numeric_columns: AHashSet<String>,
+    /// Read numeric column values as amounts in this locale
+    number_locale: Option<NumberLocale>,
+    /// Whether every column's boolean spellings compare equal
+    all_booleans: bool,
+    /// Columns whose boolean spellings compare equal
+    boolean_columns: AHashSet<String>,
+    /// Columns whose JSON documents compare structurally
+    json_columns: AHashSet<String>,
+    /// Multi-value rules by column
+    multi_values: AHashMap<String, MultiValueColumn>,
+    /// Unit of text cell diffs
+    text_granularity: TextGranularity,
+    /// Whether `diff_values` computes anything (`DiffOptions::char_diffs`)
+    char_diffs: bool,
+}
+
+impl Normalizer {
+    pub fn new(options: &DiffOptions) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut transforms: AHashMap<String, Vec<(Regex, String)>> = AHashMap::new();
+        for transform in &options.value_transforms {
+            let regex = Regex::new(&transform.find)
+                .map_err(|e| format!("Invalid transform pattern for column \"{}\": {}", transform.column, e))?;
+            transforms.entry(transform.column.clone()).or_default().push((regex, transform.replace.clone()));
+        }
+        let mut multi_values = AHashMap::new();
+        for rule in &options.multi_value_columns {
+            if rule.delimiter.is_empty() {
+                return Err(format!("Multi-value column \"{}\" needs a delimiter.", rule.column).into());
+            }
+            multi_values.insert(rule.column.clone(), rule.clone());
+        }
+        Ok(Self {
+            date_formats: options.date_formats.iter().map(|f| DateFormat::parse(f)).collect::<Result<_, _>>()?,
+            transforms,
+            numeric_columns: options.numeric_columns.iter().cloned().collect(),
+            number_locale: options.number_locale,
+            all_booleans: options.normalize_booleans,
+            boolean_columns: options.boolean_columns.iter().cloned().collect(),
+            json_columns: options.json_columns.iter().cloned().collect(),
+            multi_values,
+            text_granularity: options.text_granularity,
+            char_diffs: options.char_diffs,
+            null_values: NullValues::resolve(options),
+            ..Self::from_flags(options.case_sensitive, options.ignore_whitespace, false)
+        })
+    }
+
+    /// Normalizer for the positional-flag entry points (no date parsing).
+    pub fn from_flags(case_sensitive: bool, ignore_whitespace: bool, ignore_empty_vs_null: bool) -> Self {
+        Self {
+            case_sensitive,
+            ignore_whitespace,
+            null_values: NullValues::resolve(&DiffOptions { ignore_empty_vs_null, ..Default::default() }),
+            date_formats: Vec::new(),
+            transforms: AHashMap::new(),
+            numeric_columns: AHashSet::new(),
+            number_locale: None,
+            all_booleans: false,
+            boolean_columns: AHashSet::new(),
+            json_columns: AHashSet::new(),
+            multi_values: AHashMap::new(),
+            text_granularity: TextGranularity::default(),
+            char_diffs: true,
+        }
+    }
+
+    /// Whether every value is its own normal form, so raw equality is exact.
+    pub fn is_identity(&self) -> bool {
+        self.case_sensitive && !self.ignore_whitespace && self.null_values.is_empty()
+            && self.date_formats.is_empty() && self.transforms.is_empty() && self.numeric_columns.is_empty()
+            && !self.all_booleans && self.boolean_columns.is_empty() && self.json_columns.is_empty()
+            && self.multi_values.is_empty()
+    }
+
+    /// Form of a `column` value that compares equal exactly when values are
+    /// equivalent.
+    pub fn normalize<'a>(&self, column: &str, value: &'a str) -> Cow<'a, str> {
+        if self.null_values.is_null(column, value) {
+            return Cow::Borrowed(NULL_VALUE);
+        }
+        match self.canonicalize(column, value) {
+            Cow::Borrowed(value) => normalize_value_cow(value, self.case_sensitive, self.ignore_whitespace, false),
+            Cow::Owned(value) => Cow::Owned(
+                normalize_value_cow(&value, self.case_sensitive, self.ignore_whitespace, false).into_owned(),
+            ),
+        }
+    }
+
+    /// A `column` value with its transforms applied and format-only
+    /// variation removed (numbers in numeric columns without leading zeros,
+    /// plus signs, trailing fraction zeros, exponents or percent signs; JSON
+    /// documents in JSON columns with sorted keys and no whitespace;
+    /// multi-value cells with their sub-values sorted; booleans as "true"/"false";
+    /// dates as canonical timestamps), before trimming, null and case
+    /// handling apply.
+    pub fn canonicalize<'a>(&self, column: &str, value: &'a str) -> Cow<'a, str> {
+        let mut value = Cow::Borrowed(value);
+        for (regex, replacement) in self.transforms.get(column).into_iter().flatten() {
+            if let Cow::Owned(replaced) = regex.replace_all(&value, replacement.as_str()) {
+                value = Cow::Owned(replaced);
+            }
+        }
+        if let Some(rule) = self.multi_values.get(column) {
+            return Cow::Owned(rule.canonicalize(&value, self.case_sensitive, self.ignore_whitespace));
+        }
+        if self.json_columns.contains(column) {
+            if let Some(document) = canonical_json(&value) {
+                return Cow::Owned(document);
+            }
+        }
+        if let Some(number) = self.canonical_number(column, &value) {
+            value = Cow::Owned(number);
+        }
+        if self.all_booleans || self.boolean_columns.contains(column) {
+            let candidate = if self.ignore_whitespace { value.trim() } else { &value };
+            if let Some(boolean) = canonicalize_boolean(candidate) {
+                return Cow::Borrowed(boolean);
+            }
+        }
+        match value {
+            Cow::Borrowed(value) => self.canonicalize_date(value),
+            Cow::Owned(value) => Cow::Owned(self.canonicalize_date(&value).into_owned()),
+        }
+    }
+
+    /// Changes between two `column` values that compare unequal: the
+    /// changed paths of JSON documents in JSON columns, the added and
+    /// removed sub-values of multi-value cells, otherwise a text diff in
+    /// `text_granularity` units. None when char diffs are off.
+    pub fn diff_values(&self, column: &str, old: &str, new: &str) -> Vec<DiffChange> {
+        if !self.char_diffs {
+            return Vec::new();
+        }
+        if let Some(rule) = self.multi_values.get(column) {
+            return rule.diff(old, new, self.case_sensitive, self.ignore_whitespace);
+        }
+        if self.json_columns.contains(column) {
+            if let (Some(old_document), Some(new_document)) = (parse_document(old), parse_document(new)) {
+                return diff_documents(&old_document, &new_document);
+            }
+        }
+        crate::core::diff_text_with_granularity(old, new, self.case_sensitive, self.text_granularity)
+    }
+
+    /// Value of a number in a numeric `column`, for distance-based
+    /// similarity.
+    pub fn numeric_value(&self, column: &str, value: &str) -> Option<f64> {
+        self.canonical_number(column, value)?.parse().ok()
+    }
+
+    /// Instant a date value denotes in the first of `date_formats` that
+    /// parses it.
+    pub fn date_value(&self, value: &str) -> Option<NaiveDateTime> {
+        if self.date_formats.is_empty() {
+            return None;
+        }
+        let candidate = if self.ignore_whitespace { value.trim() } else { value };
+        let looks_like_date = candidate.len() <= MAX_DATE_LEN
+            && candidate.starts_with(|c: char| c.is_ascii_digit() || c == '-');
+        if !looks_like_date {
+            return None;
+        }
+        // First format that parses wins, so list the preferred reading of
+        // ambiguous dates like 01/05/2023 first
+        self.date_formats.iter().find_map(|format| format.parse_value(candidate))
+    }
+
+    fn canonical_number(&self, column: &str, value: &str) -> Option<String> {
+        if !self.numeric_columns.contains(column) {
+            return None;
+        }
+        let candidate = if self.ignore_whitespace { value.trim() } else { value };
+        match self.number_locale {
+            Some(locale) => parse_amount(candidate, locale),
+            None => canonicalize_number(candidate),
+        }
+    }
+
+    fn canonicalize_date<'a>(&self, value: &'a str) -> Cow<'a, str> {
+        self.date_value(value)
+            .map_or(Cow::Borrowed(value), |instant| Cow::Owned(instant.format(CANONICAL_DATE_FORMAT).to_string()))
+    }
+}
+
+/// "true" or "false" for the usual spellings of a boolean, in any case:
+/// true/yes/y/1 and false/no/n/0.
+fn canonicalize_boolean(value: &str) -> Option<&'static str> {
+    const TRUE: [&str; 4] = ["true", "yes", "y", "1"];
+    const FALSE: [&str; 4] = ["false", "no", "n", "0"];
+    if TRUE.iter().any(|t| value.eq_ignore_ascii_case(t)) {
+        Some("true")
+    } else if FALSE.iter().any(|f| value.eq_ignore_ascii_case(f)) {
+        Some("false")
+    } else {
+        None
+    }
+}
+
+/// Canonical text of an amount written in `locale`, with an optional
+/// currency symbol or ISO code on either side ("€1.234,50", "1,234.50 USD"),
+/// or `None` if `value` is not one. Digit groups must be three wide.
+fn parse_amount(value: &str, locale: NumberLocale) -> Option<String> {
+    fn is_currency(c: char) -> bool {
+        matches!(c, '$' | '€' | '£' | '¥' | '₹' | '₽' | '₩' | '₺' | '₪' | '¢' | '₫' | '₴' | '₦' | '₱' | '฿')
+    }
+    fn strip_sign(s: &str) -> (bool, &str) {
+        match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        }
+    }
+    fn is_code(code: Option<&str>) -> bool {
+        code.is_some_and(|code| code.bytes().all(|b| b.is_ascii_uppercase()))
+    }
+
+    let (negative, rest) = strip_sign(value.trim());
+    let rest = rest.trim_start_matches(is_currency);
+    let rest = if is_code(rest.get(..3)) { &rest[3..] } else { rest };
+    let (negative_inner, rest) = strip_sign(rest.trim_start());
+    let body = rest.trim_end_matches(is_currency);
+    let code_start = body.len().checked_sub(3);
+    let body = match code_start {
+        Some(start) if is_code(body.get(start..)) => &body[..start],
+        _ => body,
+    };
+    let body = body.trim_end();
+    let (body, percent) = match body.strip_suffix('%') {
+        Some(body) => (body.trim_end(), "%"),
+        None => (body, ""),
+    };
+
+    let (grouping, decimal_mark) = locale.separators();
+    let (integer, fraction) = body.split_once(decimal_mark).unwrap_or((body, ""));
+    let groups: Vec<&str> = integer.split(|c| grouping.contains(&c)).collect();
+    if groups.len() > 1 && (!(1..=3).contains(&groups[0].len()) || groups[1..].iter().any(|g| g.len() != 3)) {
+        return None;
+    }
+    let sign = if negative != negative_inner { "-" } else { "" };
+    canonicalize_number(&format!("{}{}.{}{}", sign, groups.concat(), fraction, percent))
+}
+
+/// Exponents beyond this compare as text rather than expanding to that many
+/// zeros.
+const MAX_EXPONENT: i32 = 1000;
+
+/// Canonical text of a decimal ("-007.50" → "-7.5", "+0.0" → "0"), in
+/// scientific notation ("1e3" → "1000") or a percentage ("50%" → "0.5"),
+/// or `None` if `value` is not one. Works on the digits, so precision is
+/// never lost to floating point.
+fn canonicalize_number(value: &str) -> Option<String> {
+    let (negative, unsigned) = match value.as_bytes().first()? {
+        b'-' => (true, &value[1..]),
+        b'+' => (false, &value[1..]),
+        _ => (false, value),
+    };
+    let (unsigned, mut exponent) = match unsigned.strip_suffix('%') {
+        Some(rest) => (rest, -2),
+        None => (unsigned, 0),
+    };
+    let mantissa = match unsigned.split_once(['e', 'E']) {
+        Some((mantissa, power)) => {
+            exponent += power.parse::<i32>().ok().filter(|power| power.abs() <= MAX_EXPONENT)?;
+            mantissa
+        }
+        None => unsigned,
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let is_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if integer.is_empty() && fraction.is_empty() || !is_digits(integer) || !is_digits(fraction) {
+        return None;
+    }
+
+    // Move the decimal point by the exponent, padding with zeros
+    let digits = format!("{}{}", integer, fraction);
+    let point = integer.len() as i32 + exponent;
+    let (integer, fraction) = if point <= 0 {
+        (String::new(), "0".repeat(point.unsigned_abs() as usize) + &digits)
+    } else if point as usize >= digits.len() {
+        (digits.clone() + &"0".repeat(point as usize - digits.len()), String::new())
+    } else {
+        let (integer, fraction) = digits.split_at(point as usize);
+        (integer.to_string(), fraction.to_string())
+    };
+
+    let integer = integer.trim_start_matches('0');
+    let fraction = fraction.trim_end_matches('0');
+    let mut number = String::with_capacity(value.len());
+    if negative && !(integer.is_empty() && fraction.is_empty()) {
+        number.push('-');
+    }
+    number.push_str(if integer.is_empty() { "0" } else { integer });
+    if !fraction.is_empty() {
+        number.push('.');
+        number.push_str(fraction);
+    }
+    Some(number)
+}
+
+/// A date format accepted in `DiffOptions::date_formats`: "iso", "us"
+/// (month first), "eu" (day first), "epoch" (Unix seconds or milliseconds)
+/// or a chrono strftime pattern such as "%d %b %Y".
+#[derive(Clone, Debug)]
+enum DateFormat {
+    Iso,
+    Us,
+    Eu,
+    Epoch,
+    Pattern(String),
+}
+
+impl DateFormat {
+    fn parse(name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(match name.to_ascii_lowercase().as_str() {
+            "iso" => Self::Iso,
+            "us" => Self::Us,
+            "eu" => Self::Eu,
+            "epoch" => Self::Epoch,
+            _ if !name.contains('%') || StrftimeItems::new(name).any(|item| matches!(item, Item::Error)) => {
+                return Err(format!("Invalid date format \"{}\".", name).into());
+            }
+            _ => Self::Pattern(name.to_string()),
+        })
+    }
+
+    /// Instant `value` denotes in this format; offsets are converted to UTC
+    /// and values without one are taken as UTC.
+    fn parse_value(&self, value: &str) -> Option<NaiveDateTime> {
+        match self {
+            Self::Iso => DateTime::parse_from_rfc3339(value).ok().map(|dt| dt.naive_utc()).or_else(|| {
+                parse_with(value, &["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"], &["%Y-%m-%d"])
+            }),
+            Self::Us => parse_with(value, &["%m/%d/%Y %H:%M:%S", "%m/%d/%Y %H:%M"], &["%m/%d/%Y"]),
+            Self::Eu => parse_with(
+                value,
+                &["%d/%m/%Y %H:%M:%S", "%d/%m/%Y %H:%M", "%d.%m.%Y %H:%M:%S", "%d.%m.%Y %H:%M"],
+                &["%d/%m/%Y", "%d.%m.%Y"],
+            ),
+            Self::Epoch => {
+                let digits = value.strip_prefix('-').unwrap_or(value);
+                if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+                let number: i64 = value.parse().ok()?;
+                // Up to 11 digits are seconds (until year 5138), 12 to 14 milliseconds
+                let instant = match digits.len() {
+                    0..=11 => DateTime::from_timestamp(number, 0),
+                    12..=14 => DateTime::from_timestamp_millis(number),
+                    _ => None,
+                };
+                instant.map(|dt| dt.naive_utc())
+            }
+            Self::Pattern(pattern) => parse_with(value, &[pattern], &[pattern]),
+        }
+    }
+}
+
+fn parse_with(value: &str, datetime_formats: &[&str], date_formats: &[&str]) -> Option<NaiveDateTime> {
+    datetime_formats.iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            date_formats.iter()
+                .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+                .map(|date| date.and_time(NaiveTime::MIN))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_formats(formats: &[&str]) -> Normalizer {
+        let options = DiffOptions {
+            date_formats: formats.iter().map(|f| f.to_string()).collect(),
+            ..Default::default()
+        };
+        Normalizer::new(&options).unwrap()
+    }
+
+    #[test]
+    fn test_dates_in_different_formats_compare_equal() {
+        let normalizer = with_formats(&["iso", "us", "epoch"]);
+        let iso = normalizer.normalize("shipped", "2023-01-05");
+        assert_eq!(iso, "2023-01-05T00:00:00");
+        assert_eq!(normalizer.normalize("shipped", "01/05/2023"), iso);
+        assert_eq!(normalizer.normalize("shipped", "1672876800"), iso);
+        assert_eq!(normalizer.normalize("shipped", "2023-01-05T01:00:00+01:00"), iso);
+        assert_ne!(normalizer.normalize("shipped", "2023-01-06"), iso);
+        assert_eq!(normalizer.normalize("shipped", "Jan 5"), "Jan 5");
+
+        // Day-first when "eu" is listed before "us"
+        assert_eq!(with_formats(&["eu", "us"]).normalize("shipped", "01/05/2023"), "2023-05-01T00:00:00");
+        assert_eq!(with_formats(&["%d %b %Y"]).normalize("shipped", "05 Jan 2023"), iso);
+        assert!(Normalizer::new(&DiffOptions { date_formats: vec!["ymd".to_string()], ..Default::default() }).is_err());
+    }
+
+    #[test]
+    fn test_key_normalization() {
+        let all = KeyNormalization { trim: true, case_fold: true, strip_leading_zeros: true, ..Default::default() };
+        assert_eq!(all.apply("id", " A001 "), "a001");
+        assert_eq!(all.apply("id", "007"), "7");
+        assert_eq!((all.apply("id", "000"), all.apply("id", "0.5"), all.apply("id", "")), ("0".into(), "0.5".into(), "".into()));
+        assert_eq!(KeyNormalization::default().apply("id", " 007 "), " 007 ");
+
+        let source = "id,name\nA001 ,Alice\n0042,Bob\n";
+        let target = "id,name\na001,Alice\n42,Bob\n";
+        for use_parallel in [false, true] {
+            let mut options = DiffOptions { key_columns: vec!["id".to_string()], use_parallel, ..Default::default() };
+            let exact = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            assert_eq!(exact.summary.unchanged, 0);
+
+            options.key_normalization = all.clone();
+            let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            assert_eq!((result.summary.added, result.summary.removed), (0, 0));
+            // The key columns themselves still differ
+            assert_eq!(result.summary.modified, 2);
+        }
+    }
+
+    #[test]
+    fn test_date_aware_diff() {
+        let options = DiffOptions {
+            key_columns: vec!["id".to_string()],
+            date_formats: vec!["iso".to_string(), "us".to_string()],
+            ..Default::default()
+        };
+        let source = "id,shipped\n1,2023-01-05\n2,2023-02-01\n";
+        let target = "id,shipped\n1,01/05/2023\n2,02/02/2023\n";
+        for mode in ["primary-key", "content-match"] {
+            for use_parallel in [false, true] {
+                let options = DiffOptions { mode: mode.to_string(), use_parallel, ..options.clone() };
+                let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+                assert_eq!((result.unchanged.len(), result.modified.len()), (1, 1), "{} parallel={}", mode, use_parallel);
+                // Results keep the original text
+                assert_eq!(result.modified[0].differences[0].new_value, "02/02/2023");
+            }
+        }
+    }
+
+    #[test]
+    fn test_value_transforms() {
+        let transform = |column: &str, find: &str, replace: &str| ValueTransform {
+            column: column.to_string(),
+            find: find.to_string(),
+            replace: replace.to_string(),
+        };
+        let options = DiffOptions {
+            key_columns: vec!["id".to_string()],
+            value_transforms: vec![
+                transform("price", "[$,]", ""),
+                transform("phone", r"\D", ""),
+                transform("phone", r"^1(\d{10})$", "$1"),
+            ],
+            ..Default::default()
+        };
+        let normalizer = Normalizer::new(&options).unwrap();
+        assert_eq!(normalizer.normalize("price", "$1,200"), "1200");
+        assert_eq!(normalizer.normalize("phone", "+1 (555) 010-2000"), "5550102000");
+        assert_eq!(normalizer.normalize("name", "$1,200"), "$1,200");
+
+        let source = "id,price,phone\n1,\"$1,200\",555-010-2000\n2,$5,555-010-3000\n";
+        let target = "id,price,phone\n1,1200,(555) 010 2000\n2,6,1-555-010-3000\n";
+        for mode in ["primary-key", "content-match"] {
+            for use_parallel in [false, true] {
+                let options = DiffOptions { mode: mode.to_string(), use_parallel, ..options.clone() };
+                let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+                assert_eq!((result.unchanged.len(), result.modified.len()), (1, 1), "{} parallel={}", mode, use_parallel);
+                assert_eq!(result.modified[0].differences.len(), 1);
+                assert_eq!(result.modified[0].differences[0].new_value, "6");
+            }
+        }
+
+        let invalid = DiffOptions { value_transforms: vec![transform("price", "[", "")], ..Default::default() };
+        assert!(Normalizer::new(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_numeric_columns() {
+        for (value, canonical) in [("007", Some("7")), ("+7", Some("7")), ("7.0", Some("7")), ("-0.50", Some("-0.5")),
+                                   ("-0.0", Some("0")), (".5", Some("0.5")), ("7.", Some("7")), ("-", None),
+                                   ("1e3", Some("1000")), ("1.5E+2", Some("150")), ("-25e-3", Some("-0.025")), ("50%", Some("0.5")),
+                                   ("12.5%", Some("0.125")), ("1e-2%", Some("0.0001")), ("1e", None), ("%", None), ("1e99999", None)] {
+            assert_eq!(canonicalize_number(value).as_deref(), canonical, "{}", value);
+        }
+
+        let source = "id,qty,code\n1,007,007\n2,7.0,7\n3,8,8\n4,1e3,1\n5,50%,1\n";
+        let target = "id,qty,code\n1,7,7\n2,+7,7\n3,8.5,8\n4,1000,1\n5,0.5,1\n";
+        let options = DiffOptions {
+            key_columns: vec!["id".to_string()],
+            numeric_columns: vec!["qty".to_string()],
+            ..Default::default()
+        };
+        for use_parallel in [false, true] {
+            let options = DiffOptions { use_parallel, ..options.clone() };
+            let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            let mut changed: Vec<(String, String)> = result.modified.iter()
+                .flat_map(|row| row.differences.iter().map(move |d| (row.key.clone(), d.column.clone())))
+                .collect();
+            changed.sort();
+            assert_eq!(changed, vec![("1".to_string(), "code".to_string()), ("3".to_string(), "qty".to_string())]);
+        }
+    }
+
+    #[test]
+    fn test_amounts_in_locales() {
+        let cases = [
+            (NumberLocale::En, "1,234.50", Some("1234.5")),
+            (NumberLocale::En, "$1,234.50", Some("1234.5")),
+            (NumberLocale::En, "-$1,234", Some("-1234")),
+            (NumberLocale::En, "1234.5 USD", Some("1234.5")),
+            (NumberLocale::En, "1,23.5", None),
+            (NumberLocale::De, "€1.234,50", Some("1234.5")),
+            (NumberLocale::De, "1234,5 €", Some("1234.5")),
+            (NumberLocale::Fr, "1 234,50", Some("1234.5")),
+            (NumberLocale::Ch, "CHF 1'234.50", Some("1234.5")),
+            (NumberLocale::Fr, "12,5 %", Some("0.125")),
+            (NumberLocale::En, "n/a", None),
+            (NumberLocale::En, "", None),
+            (NumberLocale::En, "A", None),
+            (NumberLocale::De, " AB ", None),
+        ];
+        for (locale, value, expected) in cases {
+            assert_eq!(parse_amount(value, locale).as_deref(), expected, "{:?} {}", locale, value);
+        }
+
+        let options = DiffOptions {
+            key_columns: vec!["id".to_string()],
+            numeric_columns: vec!["amount".to_string()],
+            number_locale: Some(NumberLocale::De),
+            ..Default::default()
+        };
+        let result = crate::core::diff_with_options(
+            "id,amount\n1,\"€1.234,50\"\n2,\"10,00\"\n",
+            "id,amount\n1,\"1234,5\"\n2,\"10,01\"\n",
+            &options,
+            |_, _| {},
+        ).unwrap();
+        assert_eq!((result.unchanged.len(), result.modified.len()), (1, 1));
+    }
+
+    #[test]
+    fn test_boolean_columns() {
+        let source = "id,active,code\nA,true,1\nB,no,Y\nC,yes,0\n";
+        let target = "id,active,code\nA,Y,yes\nB,0,y\nC,false,n\n";
+        let changed = |options: &DiffOptions| -> Vec<String> {
+            let result = crate::core::diff_with_options(source, target, options, |_, _| {}).unwrap();
+            let mut columns: Vec<String> = result.modified.iter()
+                .flat_map(|row| row.differences.iter().map(|d| format!("{}.{}", row.key, d.column)))
+                .collect();
+            columns.sort();
+            columns
+        };
+        let per_column = DiffOptions {
+            key_columns: vec!["id".to_string()],
+            boolean_columns: vec!["active".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(changed(&per_column), vec!["A.code", "B.code", "C.active", "C.code"]);
+
+        let global = DiffOptions { normalize_booleans: true, boolean_columns: Vec::new(), ..per_column };
+        assert_eq!(changed(&global), vec!["C.active"]);
+    }
+
+    #[test]
+    fn test_text_granularity() {
+        let changes = |granularity: TextGranularity, old: &str, new: &str| -> Vec<String> {
+            let options = DiffOptions { text_granularity: granularity, ..Default::default() };
+            Normalizer::new(&options).unwrap().diff_values("note", old, new).iter()
+                .map(|c| format!("{}{}", if c.added { "+" } else if c.removed { "-" } else { "=" }, c.value))
+                .collect()
+        };
+        assert_eq!(changes(TextGranularity::Character, "AB-12", "AB-13"), vec!["=AB-1", "-2", "+3"]);
+        assert_eq!(changes(TextGranularity::Word, "AB-12 x", "AB-13 x"), vec!["=AB-", "-12", "+13", "= x"]);
+        assert_eq!(
+            changes(TextGranularity::Line, "one two\nthree\n", "one two\nfour\n"),
+            vec!["=one two\n", "-three\n", "+four\n"],
+        );
+    }
+
+    #[test]
+    fn test_semantic_cleanup() {
+        let normalizer = Normalizer::new(&DiffOptions::default()).unwrap();
+        let changes: Vec<(bool, bool, String)> = normalizer.diff_values("note", "red big box on top", "blue small box on top")
+            .into_iter()
+            .map(|c| (c.added, c.removed, c.value))
+            .collect();
+        assert_eq!(changes, vec![
+            (false, true, "red big".to_string()),
+            (true, false, "blue small".to_string()),
+            (false, false, " box on top".to_string()),
+        ]);
+
+        let characters = DiffOptions { text_granularity: TextGranularity::Character, ..Default::default() };
+        let values: Vec<String> = Normalizer::new(&characters).unwrap().diff_values("code", "kitten", "sitting")
+            .into_iter()
+            .map(|c| c.value)
+            .collect();
+        // "itt" outweighs the edits around it; "n" does not
+        assert_eq!(values, vec!["k", "s", "itt", "en", "ing"]);
+    }
+
+    #[test]
+    fn test_unicode_segmentation() {
+        let changes = |granularity: TextGranularity, old: &str, new: &str| -> Vec<String> {
+            let options = DiffOptions { text_granularity: granularity, ..Default::default() };
+            Normalizer::new(&options).unwrap().diff_values("note", old, new).into_iter()
+                .map(|c| format!("{}{}", if c.added { "+" } else if c.removed { "-" } else { "=" }, c.value))
+                .collect()
+        };
+        assert_eq!(changes(TextGranularity::Word, "東京都に住む", "京都府に住む"), vec!["-東", "=京都", "+府", "=に住む"]);
+        assert_eq!(changes(TextGranularity::Word, "déjà-vu, fine", "déjà-vu, good"), vec!["=déjà-vu, ", "-fine", "+good"]);
+        // "e" + combining acute accent stays one character
+        assert_eq!(changes(TextGranularity::Character, "cafe\u{301}", "cafe"), vec!["=caf", "-e\u{301}", "+e"]);
+    }
+
+    #[test]
+    fn test_intraline_refinement() {
+        let normalizer = Normalizer::new(&DiffOptions::default()).unwrap();
+        let changes = normalizer.diff_values("ref", "see ORDER_ID_2023 now", "see ORDER_ID_2024 now");
+        let marked = |changes: &[DiffChange]| -> Vec<String> {
+            changes.iter()
+                .map(|c| format!("{}{}", if c.added { "+" } else if c.removed { "-" } else { "=" }, c.value))
+                .collect()
+        };
+        assert_eq!(marked(&changes), vec!["=see ", "-ORDER_ID_2023", "+ORDER_ID_2024", "= now"]);
+        assert_eq!(marked(&changes[1].intraline), vec!["=ORDER_ID_202", "-3"]);
+        assert_eq!(marked(&changes[2].intraline), vec!["=ORDER_ID_202", "+4"]);
+        assert!(changes[0].intraline.is_empty());
+
+        let unrelated = normalizer.diff_values("ref", "apple", "kiwi");
+        assert!(unrelated.iter().all(|c| c.intraline.is_empty()));
+    }
+
+    #[test]
+    fn test_case_insensitive_diff_keeps_casing() {
+        let options = DiffOptions { case_sensitive: false, ..Default::default() };
+        let changes: Vec<(bool, bool, String)> = Normalizer::new(&options).unwrap()
+            .diff_values("name", "Hello World Inc", "HELLO world LLC")
+            .into_iter()
+            .map(|c| (c.added, c.removed, c.value))
+            .collect();
+        assert_eq!(changes, vec![
+            (false, false, "Hello World ".to_string()),
+            (false, true, "Inc".to_string()),
+            (true, false, "LLC".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_null_values() {
+        let mut null_values = NullValues { values: vec!["".into(), "null".into(), "none".into(), "-".into()], ..Default::default() };
+        null_values.columns.insert("end".to_string(), vec!["".into(), "9999-12-31".into()]);
+        let options = DiffOptions { null_values, key_columns: vec!["id".to_string()], ..Default::default() };
+        let normalizer = Normalizer::new(&options).unwrap();
+        assert_eq!(normalizer.normalize("name", " None "), normalizer.normalize("name", "-"));
+        assert_ne!(normalizer.normalize("end", "-"), normalizer.normalize("end", ""));
+        assert_eq!(normalizer.normalize("end", "9999-12-31"), NULL_VALUE);
+
+        let source = "id,name,city,country,end\n1,NULL,Oslo,NO,\nnone,Bob,Rome,IT,-\n";
+        let target = "id,name,city,country,end\n1,-,Oslo,NO,9999-12-31\n,Bob,Rome,IT,\n";
+        for mode in ["primary-key", "content-match"] {
+            for use_parallel in [false, true] {
+                let options = DiffOptions { mode: mode.to_string(), use_parallel, ..options.clone() };
+                let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+                let changed: Vec<&str> = result.modified.iter()
+                    .flat_map(|row| row.differences.iter().map(|d| d.column.as_str()))
+                    .collect();
+                assert_eq!((result.added.len(), result.removed.len()), (0, 0), "{mode}");
+                assert_eq!(changed, vec!["end"], "{mode}");
+            }
+        }
+
+        let legacy = Normalizer::from_flags(true, false, true);
+        assert_eq!(legacy.normalize("any", "Null"), legacy.normalize("any", " "));
+        assert_ne!(legacy.normalize("any", "none"), legacy.normalize("any", ""));
+    }
+}
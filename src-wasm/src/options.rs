@@ -0,0 +1,372 @@
+//! Options accepted by the options-based diff entry points.
+//!
+//! The positional-argument functions (`diff_csv_primary_key_internal`,
+//! `diff_csv_internal`, ...) remain for existing callers and build a
+//! `DiffOptions` internally. New settings are only added here.
+
+use std::borrow::Cow;
+use serde::{Deserialize, Serialize};
+use crate::binary_encoder::BinaryFormat;
+use crate::parse::ParseOptions;
+use crate::streaming::StreamingConfig;
+use crate::mapping::{ColumnMapping, HeaderPolicy, Strictness};
+use crate::normalize::{KeyNormalization, NullValues, NumberLocale, ValueTransform};
+use crate::multi_value::MultiValueColumn;
+use crate::similarity::SimilarityOptions;
+
+/// Seed used unless a run sets its own, so repeated runs agree.
+pub const DEFAULT_SEED: u64 = 0;
+
+/// Full set of options for a diff run, deserialized from a camelCase JS object.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DiffOptions {
+    /// "primary-key", "content-match", "positional" (row N against row N) or
+    /// "sequence" (ordered diff of rows, like `diff` on lines)
+    pub mode: String,
+    /// Sequence diff algorithm used in "sequence" mode
+    pub sequence_algorithm: SequenceAlgorithm,
+    pub key_columns: Vec<String>,
+    /// Columns whose combined values identify a row in files without a
+    /// unique key column; primary-key mode then keys rows by a hash of them
+    /// (16 hex digits) instead of by `key_columns`
+    pub surrogate_key_columns: Vec<String>,
+    /// Trimming, case folding and zero stripping of key values before rows
+    /// are matched by key
+    pub key_normalization: KeyNormalization,
+    /// What to do when several rows of a file share a primary key
+    pub duplicate_keys: DuplicateKeys,
+    /// Match rows by content instead of failing when a key column is
+    /// missing or, with `DuplicateKeys::Error`, a key repeats. The result's
+    /// `fallback` says why.
+    pub content_match_fallback: bool,
+    /// In primary-key mode, pair each key left unmatched with the most
+    /// similar unmatched key on the other side scoring at least this (0-1),
+    /// so typos in keys show as modified rows instead of a removal plus an
+    /// addition. Unset matches keys exactly.
+    pub fuzzy_key_threshold: Option<f64>,
+    /// In primary-key mode, also report the matched rows whose order
+    /// changed (`DiffResult::moved_rows`): the fewest rows whose moves
+    /// explain the target order
+    pub report_order_changes: bool,
+    /// In primary-key mode, then pair each row still unmatched with the most
+    /// similar unmatched row on the other side whose non-key columns score
+    /// at least this (0-1), so rows with regenerated keys show as modified
+    /// rows with a `target_key` instead of a removal plus an addition
+    pub key_change_threshold: Option<f64>,
+    pub case_sensitive: bool,
+    pub ignore_whitespace: bool,
+    /// Shorthand for `null_values` of "" and "null" in every column
+    pub ignore_empty_vs_null: bool,
+    /// Values that compare equal as missing, in all columns or per column;
+    /// they also key as "" in primary-key mode
+    pub null_values: NullValues,
+    /// Formats tried, in order, to compare date values as instants: "iso",
+    /// "us", "eu", "epoch" or strftime patterns. Empty compares dates as text.
+    pub date_formats: Vec<String>,
+    /// Regex find/replace transforms applied to a column's values before
+    /// comparison (both files, in order). Results keep the original values.
+    pub value_transforms: Vec<ValueTransform>,
+    /// Columns compared by numeric value, so "007", "+7" and "7.0" are equal,
+    /// as are "1e3" and "1000" or "50%" and "0.5". Values that are not
+    /// numbers compare as text.
+    pub numeric_columns: Vec<String>,
+    /// Read numeric column values as amounts in this locale, ignoring digit
+    /// grouping and currency symbols ("€1.234,50" equals "1234.5" with "de")
+    pub number_locale: Option<NumberLocale>,
+    /// Compare true/yes/y/1 and false/no/n/0 (any case) as equal booleans in
+    /// every column; `boolean_columns` limits this to the listed columns
+    pub normalize_booleans: bool,
+    pub boolean_columns: Vec<String>,
+    /// Columns holding JSON objects or arrays, compared structurally (key
+    /// order and whitespace ignored) and diffed by changed path
+    pub json_columns: Vec<String>,
+    /// Columns holding delimited values in any order ("a;b;c" equals
+    /// "c;a;b"), diffed by the sub-values added and removed
+    pub multi_value_columns: Vec<MultiValueColumn>,
+    /// Unit of the highlighted changes inside modified text cells
+    pub text_granularity: TextGranularity,
+    /// Compute `Difference::diff` for every changed cell. When off it stays
+    /// empty and `cell_diff::cell_diff` computes it for the cells a user
+    /// actually expands, which saves most of the time on large diffs
+    pub char_diffs: bool,
+    /// String similarity metric(s) for fuzzy row matching in content-match
+    /// mode
+    pub similarity: SimilarityOptions,
+    /// In content-match mode, score at most this many candidate rows per
+    /// unmatched row, those sharing the most values first, bounding the
+    /// work on files with many similar rows. Unset scores every candidate.
+    pub max_candidates_per_row: Option<usize>,
+    /// In content-match mode, report rows paired with a similarity below
+    /// this (0-1) as a removal plus an addition instead of a modification.
+    /// Rows still pair as before, so a weak pair never lets its target row
+    /// go to another row; unset reports every pair as modified.
+    pub modified_threshold: Option<f64>,
+    /// In content-match mode, pair rows on these columns only, exactly and
+    /// then by similarity; the other columns are just compared, so a row
+    /// whose identity matches but whose other values changed is modified.
+    /// Empty pairs rows on all compared columns.
+    pub identity_columns: Vec<String>,
+    /// Header names, wildcard patterns (`*_timestamp`) or `/regex/` entries
+    pub excluded_columns: Vec<String>,
+    /// When set, only these columns (names or patterns, as for
+    /// `excluded_columns`) are compared
+    pub included_columns: Vec<String>,
+    /// Whether excluded or included columns missing from both files are
+    /// ignored, reported as warnings or rejected
+    pub unknown_excluded_columns: Strictness,
+    /// Source header → target header for columns named differently
+    pub column_mapping: ColumnMapping,
+    /// Whether columns pair up by name, by position, only through
+    /// `column_mapping`, or must be identical; echoed in the result
+    pub header_policy: HeaderPolicy,
+    /// Also map columns found in only one file whose names and values
+    /// suggest a rename; the result lists them in `renamed_columns`
+    pub detect_renames: bool,
+    /// Report the diff from target to source (see `DiffResult::reversed`)
+    pub reverse: bool,
+    /// List unchanged rows in the result. When off, only their number is
+    /// kept (`DiffResult::omitted_unchanged`), which saves most of the
+    /// memory and transfer time on files that are largely identical
+    pub include_unchanged: bool,
+    /// Copy every parsed row into `source.rows` and `target.rows` of the
+    /// result. When off they stay empty; sessions serve rows on demand
+    /// (`DiffSession::rows`) from the inputs they retain anyway
+    pub include_dataset_rows: bool,
+    /// Order of the rows of each kind in the result
+    pub row_order: RowOrder,
+    /// Layout of the results of options-based `*_binary` calls
+    pub binary_format: BinaryFormat,
+    /// Include `Difference::diff` in binary results. Off by default: the
+    /// fragments often outweigh the rest of a result
+    pub binary_char_diffs: bool,
+    pub use_parallel: bool,
+    /// Seed for hash iteration order (result row order with
+    /// `RowOrder::Unsorted`, fuzzy-match tie-breaks) and sampling; echoed in
+    /// the result for reproduction
+    pub seed: u64,
+    /// Which phases of the parallel engines actually use the thread pool
+    pub parallel_phases: ParallelPhases,
+    /// Chunking of the streaming parse; chunk size is auto-tuned unless set
+    pub streaming: StreamingConfig,
+    #[serde(flatten)]
+    pub parse: ParseOptions,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            mode: "primary-key".to_string(),
+            sequence_algorithm: SequenceAlgorithm::default(),
+            key_columns: Vec::new(),
+            surrogate_key_columns: Vec::new(),
+            key_normalization: KeyNormalization::default(),
+            duplicate_keys: DuplicateKeys::default(),
+            content_match_fallback: false,
+            fuzzy_key_threshold: None,
+            report_order_changes: false,
+            key_change_threshold: None,
+            case_sensitive: true,
+            ignore_whitespace: false,
+            ignore_empty_vs_null: false,
+            null_values: NullValues::default(),
+            date_formats: Vec::new(),
+            value_transforms: Vec::new(),
+            numeric_columns: Vec::new(),
+            number_locale: None,
+            normalize_booleans: false,
+            boolean_columns: Vec::new(),
+            json_columns: Vec::new(),
+            multi_value_columns: Vec::new(),
+            text_granularity: TextGranularity::default(),
+            char_diffs: true,
+            similarity: SimilarityOptions::default(),
+            max_candidates_per_row: None,
+            modified_threshold: None,
+            identity_columns: Vec::new(),
+            excluded_columns: Vec::new(),
+            included_columns: Vec::new(),
+            unknown_excluded_columns: Strictness::default(),
+            column_mapping: ColumnMapping::new(),
+            header_policy: HeaderPolicy::default(),
+            detect_renames: false,
+            reverse: false,
+            include_unchanged: true,
+            include_dataset_rows: true,
+            row_order: RowOrder::default(),
+            binary_format: BinaryFormat::default(),
+            binary_char_diffs: false,
+            use_parallel: false,
+            seed: DEFAULT_SEED,
+            parallel_phases: ParallelPhases::default(),
+            streaming: StreamingConfig::default(),
+            parse: ParseOptions::default(),
+        }
+    }
+}
+
+/// Algorithm aligning the rows of both files in "sequence" mode.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SequenceAlgorithm {
+    /// Shortest edit script
+    #[default]
+    Myers,
+    /// Anchors on rows that occur once in each file, which keeps moved
+    /// blocks of repetitive rows (blank or boilerplate lines) readable
+    Patience,
+}
+
+/// Unit in which the changes between two text cells are reported.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TextGranularity {
+    /// Single characters (grapheme clusters, so accents stay on their
+    /// letter), for short codes and identifiers
+    Character,
+    /// Words and the whitespace and punctuation between them, by Unicode
+    /// word boundaries (each CJK ideograph is a word)
+    #[default]
+    Word,
+    /// Lines, for long multi-line text cells
+    Line,
+}
+
+/// Order of the rows of each kind (added, removed, modified, unchanged) in
+/// a result, applied by every engine before returning it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RowOrder {
+    /// File order: added rows by target position, the others by source
+    /// position (then target position)
+    #[default]
+    Position,
+    /// Row key, with digit runs compared as numbers; equal keys in file order
+    Key,
+    /// As the engine produced them, which skips a sort; keyed modes follow
+    /// hash order, so the order only repeats for equal seeds
+    Unsorted,
+}
+
+/// Handling of rows that repeat a primary key within one file.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicateKeys {
+    /// Fail the diff
+    #[default]
+    Error,
+    /// Compare the first row per key and report the others in
+    /// `DiffResult::warnings`
+    FirstWins,
+    /// Compare all rows sharing a key as a group: identical rows pair up
+    /// first, the rest in file order, and leftovers count as added or
+    /// removed. Only the one-pass string engines support this.
+    Group,
+}
+
+/// Per-phase parallelism policy for `use_parallel` runs.
+///
+/// On low-core devices parallel fuzzy matching pays off while parallel
+/// parsing mostly multiplies peak memory, so each phase is opt-in/out on its
+/// own. Phases that are off run sequentially on the calling thread, except
+/// char diffs, which the parallel engines skip unless enabled.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ParallelPhases {
+    /// Parse source and target concurrently
+    pub parse: bool,
+    /// Key comparison (primary key) / fingerprint matching (content match)
+    pub exact_match: bool,
+    /// Similarity search for rows without an exact match
+    pub fuzzy_match: bool,
+    /// Character-level diffs of modified cells; when off `Difference::diff`
+    /// stays empty
+    pub char_diffs: bool,
+}
+
+impl Default for ParallelPhases {
+    fn default() -> Self {
+        Self {
+            parse: false,
+            exact_match: true,
+            fuzzy_match: true,
+            char_diffs: false,
+        }
+    }
+}
+
+impl ParallelPhases {
+    /// Every phase on the thread pool.
+    pub fn all() -> Self {
+        Self { parse: true, exact_match: true, fuzzy_match: true, char_diffs: true }
+    }
+
+    /// Every phase sequential.
+    pub fn none() -> Self {
+        Self { parse: false, exact_match: false, fuzzy_match: false, char_diffs: false }
+    }
+}
+
+impl DiffOptions {
+    /// Options for the legacy positional-argument entry points.
+    pub fn from_flags(
+        mode: &str,
+        key_columns: Vec<String>,
+        case_sensitive: bool,
+        ignore_whitespace: bool,
+        ignore_empty_vs_null: bool,
+        excluded_columns: Vec<String>,
+        has_headers: bool,
+    ) -> Self {
+        Self {
+            mode: mode.to_string(),
+            key_columns,
+            case_sensitive,
+            ignore_whitespace,
+            ignore_empty_vs_null,
+            excluded_columns,
+            parse: ParseOptions::new(has_headers),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_primary_key(&self) -> bool {
+        self.mode == "primary-key" || self.mode == "primary_key"
+    }
+
+    pub fn is_positional(&self) -> bool {
+        self.mode == "positional"
+    }
+
+    pub fn is_sequence(&self) -> bool {
+        self.mode == "sequence"
+    }
+
+    /// Whether primary-key rows left unmatched are paired afterwards
+    /// (`fuzzy_key_threshold`, `key_change_threshold`).
+    pub fn pairs_leftover_rows(&self) -> bool {
+        self.fuzzy_key_threshold.is_some() || self.key_change_threshold.is_some()
+    }
+
+    /// These options with `surrogate_key_columns`, if any, as the key
+    /// columns of a hashed key, and null values keyed as "". Engines key
+    /// rows by the result.
+    pub fn resolve_keys(&self) -> Cow<'_, DiffOptions> {
+        let null_values = NullValues::resolve(self);
+        if self.surrogate_key_columns.is_empty() && null_values == self.key_normalization.null_values {
+            return Cow::Borrowed(self);
+        }
+        let mut options = self.clone();
+        if !options.surrogate_key_columns.is_empty() {
+            options.key_columns = std::mem::take(&mut options.surrogate_key_columns);
+            options.key_normalization.hash = true;
+        }
+        options.key_normalization.null_values = null_values;
+        Cow::Owned(options)
+    }
+
+    /// Hash state for this run's order-sensitive maps and sets.
+    pub fn hash_state(&self) -> ahash::RandomState {
+        crate::utils::seeded_state(self.seed)
+    }
+}
@@ -1,16 +1,27 @@
 /// Parallel processing module for multi-threaded CSV operations
 /// Currently provides a parallel-like interface that's implemented sequentially for WASM compatibility
 use csv::StringRecord;
-use ahash::{AHashMap, AHashSet};
-use crate::types::{AddedRow, RemovedRow, ModifiedRow, UnchangedRow, Difference, DiffResult};
-use crate::utils::{record_to_hashmap, normalize_value_cow, get_row_key, get_row_fingerprint_fast, normalize_value_with_empty_vs_null};
+use ahash::AHashMap;
+use crate::types::{AddedRow, RemovedRow, ModifiedRow, UnchangedRow, UnchangedRows, Difference, DiffResult, DiffSummary};
+use crate::summary::change_kind;
+use crate::options::{DiffOptions, ParallelPhases};
+use crate::normalize::Normalizer;
+use crate::similarity::CellScorer;
+use crate::blocking::LshIndex;
+use crate::content_match::{PairingColumns, RowPairing};
+use crate::streaming::StreamingConfig;
+use crate::messages::{codes, emit, progress_sink, Message};
+use crate::parse::{parse_csv_with_options, ParsedCsv};
+use crate::primary_key::{diff_key_groups, index_keys};
+use crate::utils::{dataset_rows, record_to_hashmap, record_line, get_row_key};
 use rayon::prelude::*;
-use strsim::jaro_winkler;
 
 /// Initialize the thread pool for parallel processing
 /// /// When using rayon in WASM, this will configure the thread pool size
 /// to match the requested number of threads. This should be called before
 /// any parallel operations to ensure optimal thread distribution.
+/// The pool is global and built once; concurrent diffs share it and keep all
+/// their own state in per-call values, so later calls only log a warning.
 /// /// # Arguments
 /// * `num_threads` - Number of threads to configure in the pool
 pub fn init_thread_pool(num_threads: usize) {
@@ -24,13 +35,72 @@ pub fn init_thread_pool(num_threads: usize) {
         });
 }
 
+/// Rows per parallel work batch unless `StreamingConfig::chunk_size` is set.
+/// Batches bound work and progress granularity rather than memory, so the
+/// memory-based auto-tuning does not apply to them.
+const DEFAULT_WORK_CHUNK: usize = 1000;
+
+fn work_chunk_size(streaming: &StreamingConfig) -> usize {
+    streaming.chunk_size.unwrap_or(DEFAULT_WORK_CHUNK).max(1)
+}
+
+/// Map `items` on the thread pool when `parallel` is set, otherwise on the
+/// calling thread. Output order matches input order either way.
+fn map_phase<T, R, M>(items: &[T], parallel: bool, map: M) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    M: Fn(&T) -> R + Sync + Send,
+{
+    if parallel {
+        items.par_iter().map(map).collect()
+    } else {
+        items.iter().map(map).collect()
+    }
+}
+
+/// Fill in, on the thread pool, the character-level diffs the parallel
+/// comparators skip.
+pub fn fill_char_diffs(modified: &mut [ModifiedRow], normalizer: &Normalizer) {
+    modified.par_iter_mut().for_each(|row| {
+        for difference in &mut row.differences {
+            difference.diff = normalizer.diff_values(&difference.column, &difference.old_value, &difference.new_value);
+        }
+    });
+}
+
+/// Parse source and target, concurrently when `phases.parse` is set.
+fn parse_both<F>(
+    source_csv: &str,
+    target_csv: &str,
+    options: &DiffOptions,
+    on_progress: &mut F,
+) -> Result<(ParsedCsv, ParsedCsv), Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    emit(on_progress, 0.0, Message::new(codes::PARSE_SOURCE));
+    let (source, target) = if options.parallel_phases.parse {
+        emit(on_progress, 0.0, Message::new(codes::PARSE_TARGET));
+        // Box<dyn Error> is not Send, so carry errors across threads as text
+        let parse = |csv| parse_csv_with_options(csv, &options.parse).map_err(|e| e.to_string());
+        let (source, target) = rayon::join(|| parse(source_csv), || parse(target_csv));
+        (source?, target?)
+    } else {
+        let source = parse_csv_with_options(source_csv, &options.parse)?;
+        emit(on_progress, 10.0, Message::new(codes::PARSE_TARGET));
+        (source, parse_csv_with_options(target_csv, &options.parse)?)
+    };
+    Ok((source, target))
+}
+
 /// Parallel comparison of target rows against source map
 /// This is the most compute-intensive part of the diff operation
 ///
 /// # Performance Trade-offs
-/// Character-level diffs are intentionally skipped in parallel mode for performance.
-/// The `diff` field in `Difference` will be empty. If character-level diffs are required,
-/// use the non-parallel comparison functions or post-process the results.
+/// Character-level diffs are skipped while comparing for performance. The `diff`
+/// field in `Difference` stays empty unless `phases.char_diffs` is set, in which
+/// case they are filled in afterwards by `fill_char_diffs`.
 #[allow(clippy::too_many_arguments)]
 pub fn parallel_compare_rows<F>(
     target_map: &AHashMap<String, usize>,
@@ -42,17 +112,25 @@ pub fn parallel_compare_rows<F>(
     source_headers: &[String],
     source_header_map: &AHashMap<String, usize>,
     excluded_columns: &[String],
-    case_sensitive: bool,
-    ignore_whitespace: bool,
-    ignore_empty_vs_null: bool,
+    (source_crcs, target_crcs): (&[u32], &[u32]),
+    normalizer: &Normalizer,
+    phases: &ParallelPhases,
+    streaming: &StreamingConfig,
+    unchanged: &mut UnchangedRows,
     mut on_progress: F,
-) -> (Vec<AddedRow>, Vec<ModifiedRow>, Vec<UnchangedRow>)
+) -> (Vec<AddedRow>, Vec<ModifiedRow>)
 where
     F: FnMut(f64, &str),
 {
+    // Skip columns that agree on every keyed pair before comparing cell by cell
+    let columns = crate::utils::changed_columns(
+        crate::utils::compared_columns(source_headers, source_header_map, target_header_map, excluded_columns),
+        target_map.iter().filter_map(|(key, &t)| Some((&source_rows[*source_map.get(key)?], &target_rows[t]))),
+        normalizer,
+    );
+
     // Convert HashMap to Vec for iteration
     let target_keys: Vec<_> = target_map.iter().collect();
-    const CHUNK_SIZE: usize = 1000;
     let num_threads = rayon::current_num_threads();
     use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Arc;
@@ -64,12 +142,10 @@ where
     
     let mut all_added = Vec::new();
     let mut all_modified = Vec::new();
-    let mut all_unchanged = Vec::new();
+    let keep_unchanged = unchanged.keeps();
     
-    for chunk in target_keys.chunks(CHUNK_SIZE) {
-        let chunk_results: Vec<_> = chunk
-            .par_iter()
-            .map(|(key, &target_row_idx)| {
+    for (chunk_index, chunk) in target_keys.chunks(work_chunk_size(streaming)).enumerate() {
+        let chunk_results: Vec<_> = map_phase(chunk, phases.exact_match, |(key, &target_row_idx)| {
             // Track per-thread processed counts for better progress reporting
             let thread_idx = rayon::current_thread_index().unwrap_or(0);
                 let _processed_for_thread = per_thread_counters[thread_idx].fetch_add(1, Ordering::Relaxed) + 1;
@@ -81,87 +157,82 @@ where
                         (Some(AddedRow {
                             key: (*key).clone(),
                             target_row: record_to_hashmap(target_row, target_headers),
+                            target_line: record_line(target_row),
+                            target_index: Some(target_row_idx),
                         }), None, None)
                     }
                     Some(&source_row_idx) => {
                         let source_row = &source_rows[source_row_idx];
                         let mut differences = Vec::new();
                         
-                        // Compare all columns
-                        for header in source_headers {
-                            if excluded_columns.contains(header) {
-                                continue;
-                            }
-                            
-                            let source_idx = source_header_map.get(header).unwrap();
-                            let target_idx = match target_header_map.get(header) {
-                                Some(idx) => idx,
-                                None => continue,
-                            };
-                            
-                            let source_val_raw = source_row.get(*source_idx).unwrap_or("");
-                            let target_val_raw = target_row.get(*target_idx).unwrap_or("");
+                        // Compare the columns that changed somewhere, unless
+                        // matching checksums already show the row unchanged
+                        let columns = if source_crcs[source_row_idx] == target_crcs[target_row_idx] { &[][..] } else { &columns[..] };
+                        for &(header, source_idx, target_idx) in columns {
+                            let source_val_raw = source_row.get(source_idx).unwrap_or("");
+                            let target_val_raw = target_row.get(target_idx).unwrap_or("");
                             
-                            let source_val = normalize_value_cow(
-                                source_val_raw,
-                                case_sensitive,
-                                ignore_whitespace,
-                                ignore_empty_vs_null
-                            );
-                            let target_val = normalize_value_cow(
-                                target_val_raw,
-                                case_sensitive,
-                                ignore_whitespace,
-                                ignore_empty_vs_null
-                            );
+                            let source_val = normalizer.normalize(header, source_val_raw);
+                            let target_val = normalizer.normalize(header, target_val_raw);
                             
                             if source_val != target_val {
                                 differences.push(Difference {
                                     column: header.clone(),
                                     old_value: source_val_raw.to_string(),
                                     new_value: target_val_raw.to_string(),
+                                    change_kind: change_kind(source_val_raw, target_val_raw),
                                     diff: Vec::new(), // Skip char diffs in parallel mode for performance
                                 });
                             }
                         }
                         
                         if differences.is_empty() {
-                            // Row unchanged
-                            (None, None, Some(UnchangedRow {
+                            // Row unchanged, built only if the result lists it
+                            (None, None, Some(keep_unchanged.then(|| UnchangedRow {
                                 key: (*key).clone(),
                                 row: record_to_hashmap(source_row, source_headers),
-                            }))
+                                source_index: Some(source_row_idx),
+                                target_index: Some(target_row_idx),
+                            })))
                         } else {
                             // Row modified
                             (None, Some(ModifiedRow {
                                 key: (*key).clone(),
                                 source_row: record_to_hashmap(source_row, source_headers),
+                                source_line: record_line(source_row),
                                 target_row: record_to_hashmap(target_row, target_headers),
+                                target_line: record_line(target_row),
+                                source_index: Some(source_row_idx),
+                                target_index: Some(target_row_idx),
+                                match_score: None,
+                                target_key: None,
                                 differences,
                             }), None)
                         }
                     }
                 }
-            })
-            .collect();
+            });
         
         // Collect results from chunk
-        for (added, modified, unchanged) in chunk_results {
+        for (added, modified, unchanged_row) in chunk_results {
             if let Some(a) = added {
                 all_added.push(a);
             }
             if let Some(m) = modified {
                 all_modified.push(m);
             }
-            if let Some(u) = unchanged {
-                all_unchanged.push(u);
+            if let Some(u) = unchanged_row {
+                unchanged.record(u);
             }
         }
         
         processed_keys += chunk.len();
+        if !streaming.reports_after(chunk_index + 1) {
+            continue;
+        }
         let progress = 60.0 + (processed_keys as f64 / total_keys as f64) * 40.0;
         // Emit global progress
-        on_progress(progress, &format!("Comparing rows... ({}/{})", processed_keys, total_keys));
+        emit(&mut on_progress, progress, Message::new(codes::COMPARE_ROWS_PROGRESS).with("processed", processed_keys).with("total", total_keys));
         
         // Calculate estimated per-thread totals based on chunk distribution
         // Rayon uses work-stealing, so this is an approximation but better than showing total for each thread
@@ -190,7 +261,11 @@ where
         }
     }
     
-    (all_added, all_modified, all_unchanged)
+    if phases.char_diffs {
+        fill_char_diffs(&mut all_modified, normalizer);
+    }
+
+    (all_added, all_modified)
 }
 
 /// Parallel extraction of removed rows
@@ -199,22 +274,25 @@ pub fn parallel_find_removed(
     source_rows: &[StringRecord],
     source_headers: &[String],
     target_map: &AHashMap<String, usize>,
+    parallel: bool,
 ) -> Vec<RemovedRow> {
     let source_keys: Vec<_> = source_map.iter().collect();
     
-    source_keys
-        .par_iter()
-        .filter_map(|(key, &row_idx)| {
-            if !target_map.contains_key(*key) {
-                Some(RemovedRow {
-                    key: (*key).clone(),
-                    source_row: record_to_hashmap(&source_rows[row_idx], source_headers),
-                })
-            } else {
-                None
-            }
-        })
-        .collect()
+    map_phase(&source_keys, parallel, |(key, &row_idx)| {
+        if !target_map.contains_key(*key) {
+            Some(RemovedRow {
+                key: (*key).clone(),
+                source_row: record_to_hashmap(&source_rows[row_idx], source_headers),
+                source_line: record_line(&source_rows[row_idx]),
+                source_index: Some(row_idx),
+            })
+        } else {
+            None
+        }
+    })
+    .into_iter()
+    .flatten()
+    .collect()
 }
 
 /// Parallel implementation of CSV diff using primary keys
@@ -229,109 +307,166 @@ pub fn diff_csv_parallel_internal<F>(
     ignore_empty_vs_null: bool,
     excluded_columns: Vec<String>,
     has_headers: bool,
-    mut on_progress: F,
+    on_progress: F,
 ) -> Result<crate::types::DiffResult, Box<dyn std::error::Error>>
 where
     F: FnMut(f64, &str),
 {
-    on_progress(0.0, "Parsing source CSV...");
-    let (source_headers, source_rows, source_header_map) = crate::core::parse_csv_internal(source_csv, has_headers)?;
+    let options = DiffOptions::from_flags(
+        "primary-key",
+        key_columns,
+        case_sensitive,
+        ignore_whitespace,
+        ignore_empty_vs_null,
+        excluded_columns,
+        has_headers,
+    );
+    diff_primary_key_parallel_with_options(source_csv, target_csv, &options, on_progress)
+}
 
-    on_progress(10.0, "Parsing target CSV...");
-    let (target_headers, target_rows, target_header_map) = crate::core::parse_csv_internal(target_csv, has_headers)?;
+/// Parallel primary-key diff driven by a full `DiffOptions` set
+pub fn diff_primary_key_parallel_with_options<F>(
+    source_csv: &str,
+    target_csv: &str,
+    options: &DiffOptions,
+    on_progress: F,
+) -> Result<crate::types::DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let mut on_progress = progress_sink(options.parse.message_format, on_progress);
+    let (source, target) = parse_both(source_csv, target_csv, options, &mut on_progress)?;
+    compare_primary_key_parallel(&source, &target, options, &mut on_progress)
+}
+
+/// Parallel primary-key comparison of already parsed tables; progress picks
+/// up at 20%, where parsing ends. `on_progress` must already be a progress sink.
+pub(crate) fn compare_primary_key_parallel<F>(
+    (source_headers, source_rows, source_header_map): &ParsedCsv,
+    (target_headers, target_rows, target_header_map): &ParsedCsv,
+    options: &DiffOptions,
+    on_progress: &mut F,
+) -> Result<crate::types::DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let key_columns = options.key_columns.clone();
+    let normalizer = Normalizer::new(options)?;
+    let phases = options.parallel_phases;
+
+    let (mut target_headers, mut target_header_map) = (target_headers.clone(), target_header_map.clone());
+    let renamed_columns = crate::mapping::map_target_columns(
+        (source_headers, source_rows),
+        (&mut target_headers, &mut target_header_map, target_rows),
+        options,
+    )?;
+    let schema_changes = crate::mapping::schema_changes(source_headers, &target_headers, options, &renamed_columns);
+    let (excluded_columns, mut warnings) = crate::mapping::resolve_excluded_columns(source_headers, &target_headers, options)?;
 
     // Validation of key columns
     for key in &key_columns {
         if !source_header_map.contains_key(key) {
-             return Err(format!("Primary key column \"{}\" not found in source dataset.", key).into());
+             return Err(Message::new(codes::KEY_COLUMN_MISSING).with("column", key).with("side", "source").into());
         }
         if !target_header_map.contains_key(key) {
-             return Err(format!("Primary key column \"{}\" not found in target dataset.", key).into());
+             return Err(Message::new(codes::KEY_COLUMN_MISSING).with("column", key).with("side", "target").into());
         }
     }
 
-    on_progress(20.0, "Building source map...");
-    let source_map: AHashMap<String, usize> = source_rows
-        .iter()
-        .enumerate()
-        .map(|(i, row)| {
-            let key = get_row_key(row, &source_header_map, &key_columns);
-            (key, i)
-        })
-        .collect();
-
-    // Check for duplicate keys
-    let mut source_keys = AHashSet::new();
-    for key in source_map.keys() {
-        if !source_keys.insert(key) {
-            return Err(format!("Duplicate Primary Key found in source: \"{}\". Primary Keys must be unique.", key).into());
-        }
-    }
-
-    on_progress(40.0, "Building target map...");
-    let target_map: AHashMap<String, usize> = target_rows
-        .iter()
-        .enumerate()
-        .map(|(i, row)| {
-            let key = get_row_key(row, &target_header_map, &key_columns);
-            (key, i)
-        })
-        .collect();
-
-    // Check for duplicate keys
-    let mut target_keys = AHashSet::new();
-    for key in target_map.keys() {
-        if !target_keys.insert(key) {
-            return Err(format!("Duplicate Primary Key found in target: \"{}\". Primary Keys must be unique.", key).into());
-        }
-    }
-
-    on_progress(60.0, "Comparing rows...");
+    emit(on_progress, 20.0, Message::new(codes::BUILD_SOURCE_MAP));
+    let source_keys = source_rows.iter().map(|row| get_row_key(row, source_header_map, &key_columns, &options.key_normalization));
+    let (mut source_map, source_duplicates) = index_keys(source_keys, "source", options, &mut warnings)?;
+
+    emit(on_progress, 40.0, Message::new(codes::BUILD_TARGET_MAP));
+    let target_keys = target_rows.iter().map(|row| get_row_key(row, &target_header_map, &key_columns, &options.key_normalization));
+    let (mut target_map, target_duplicates) = index_keys(target_keys, "target", options, &mut warnings)?;
+
+    // Per-row checksums let the comparison skip unchanged pairs
+    let compared = crate::utils::compared_columns(source_headers, source_header_map, &target_header_map, &excluded_columns);
+    let source_crcs = map_phase(source_rows, phases.exact_match, |row| {
+        crate::utils::row_crc(row, compared.iter().map(|&(h, s, _)| (h, s)), &normalizer)
+    });
+    let target_crcs = map_phase(target_rows, phases.exact_match, |row| {
+        crate::utils::row_crc(row, compared.iter().map(|&(h, _, t)| (h, t)), &normalizer)
+    });
+
+    emit(on_progress, 60.0, Message::new(codes::COMPARE_ROWS));
+
+    // Repeated keys are compared group by group and leave the key maps
+    let (mut group_added, mut group_removed, mut group_modified) = Default::default();
+    let mut unchanged = UnchangedRows::new(options.include_unchanged);
+    diff_key_groups(
+        (&mut source_map, source_duplicates, source_rows, source_headers, &source_crcs),
+        (&mut target_map, target_duplicates, target_rows, &target_headers, &target_crcs),
+        &compared,
+        &normalizer,
+        phases.char_diffs,
+        (&mut group_added, &mut group_removed, &mut group_modified, &mut unchanged),
+    );
+    let moved_rows = if options.report_order_changes { crate::primary_key::moved_rows(&source_map, &target_map) } else { Vec::new() };
 
     // Find removed rows in parallel
-    let removed = parallel_find_removed(
+    let mut removed = parallel_find_removed(
         &source_map,
-        &source_rows,
-        &source_headers,
+        source_rows,
+        source_headers,
         &target_map,
+        phases.exact_match,
     );
 
     // Find added, modified, and unchanged rows in parallel
-    let (added, modified, unchanged) = parallel_compare_rows(
+    let (mut added, mut modified) = parallel_compare_rows(
         &target_map,
-        &target_rows,
+        target_rows,
         &target_headers,
         &target_header_map,
         &source_map,
-        &source_rows,
-        &source_headers,
-        &source_header_map,
+        source_rows,
+        source_headers,
+        source_header_map,
         &excluded_columns,
-        case_sensitive,
-        ignore_whitespace,
-        ignore_empty_vs_null,
+        (&source_crcs, &target_crcs),
+        &normalizer,
+        &phases,
+        &options.streaming,
+        &mut unchanged,
         |p, m| on_progress(p, m),
     );
+    added.append(&mut group_added);
+    removed.append(&mut group_removed);
+    modified.append(&mut group_modified);
+    modified.extend(crate::primary_key::pair_leftover_rows(&mut removed, &mut added, &compared, options, &normalizer, phases.char_diffs));
 
-    on_progress(100.0, "Complete");
+    emit(on_progress, 100.0, Message::new(codes::COMPLETE));
 
     Ok(DiffResult {
         added,
         removed,
         modified,
-        unchanged,
+        unchanged: unchanged.rows,
         source: crate::types::DatasetMetadata {
             headers: source_headers.clone(),
-            rows: source_rows.iter().map(|r| record_to_hashmap(r, &source_headers)).collect(),
+            rows: dataset_rows(source_rows, source_headers, options.include_dataset_rows),
         },
         target: crate::types::DatasetMetadata {
             headers: target_headers.clone(),
-            rows: target_rows.iter().map(|r| record_to_hashmap(r, &target_headers)).collect(),
+            rows: dataset_rows(target_rows, &target_headers, options.include_dataset_rows),
         },
         key_columns,
         excluded_columns,
         mode: "primary_key".to_string(),
-    })
+        summary: DiffSummary::default(),
+        warnings,
+        seed: options.seed,
+        fallback: None,
+        renamed_columns,
+        schema_changes,
+        reversed: false,
+        header_policy: options.header_policy,
+        moved_rows,
+        duplicate_counts: Vec::new(),
+        omitted_unchanged: unchanged.omitted,
+    }.with_summary().ordered(options.row_order))
 }
 
 /// Parallel implementation of CSV diff using content matching (fuzzy matching)
@@ -343,127 +478,105 @@ pub fn diff_csv_content_match_parallel<F>(
     ignore_empty_vs_null: bool,
     excluded_columns: Vec<String>,
     has_headers: bool,
-    mut on_progress: F,
+    on_progress: F,
 ) -> Result<crate::types::DiffResult, Box<dyn std::error::Error>>
 where
     F: FnMut(f64, &str),
 {
-    on_progress(0.0, "Parsing source CSV...");
-    let (source_headers, source_rows, source_header_map) = crate::core::parse_csv_internal(source_csv, has_headers)?;
+    let options = DiffOptions::from_flags(
+        "content-match",
+        vec![],
+        case_sensitive,
+        ignore_whitespace,
+        ignore_empty_vs_null,
+        excluded_columns,
+        has_headers,
+    );
+    diff_content_match_parallel_with_options(source_csv, target_csv, &options, on_progress)
+}
 
-    on_progress(10.0, "Parsing target CSV...");
-    let (target_headers_orig, target_rows_orig, target_header_map_orig) = crate::core::parse_csv_internal(target_csv, has_headers)?;
+/// Parallel content-match diff driven by a full `DiffOptions` set
+pub fn diff_content_match_parallel_with_options<F>(
+    source_csv: &str,
+    target_csv: &str,
+    options: &DiffOptions,
+    on_progress: F,
+) -> Result<crate::types::DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let mut on_progress = progress_sink(options.parse.message_format, on_progress);
+    let (source, target) = parse_both(source_csv, target_csv, options, &mut on_progress)?;
+    compare_content_match_parallel(&source, &target, options, &mut on_progress)
+}
 
-    let (target_headers, target_rows, target_header_map) = if source_headers != target_headers_orig && source_headers.len() == target_headers_orig.len() {
-        (source_headers.clone(), target_rows_orig, source_header_map.clone())
-    } else {
-        (target_headers_orig, target_rows_orig, target_header_map_orig)
-    };
+/// Parallel content-match comparison of already parsed tables; progress
+/// picks up at 20%, where parsing ends. `on_progress` must already be a
+/// progress sink.
+pub(crate) fn compare_content_match_parallel<F>(
+    (source_headers, source_rows, source_header_map): &ParsedCsv,
+    (target_headers, target_rows, target_header_map): &ParsedCsv,
+    options: &DiffOptions,
+    on_progress: &mut F,
+) -> Result<crate::types::DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let normalizer = Normalizer::new(options)?;
+    let phases = options.parallel_phases;
+
+    let (mut target_headers, mut target_header_map) = (target_headers.clone(), target_header_map.clone());
+    let renamed_columns = crate::mapping::map_target_columns(
+        (source_headers, source_rows),
+        (&mut target_headers, &mut target_header_map, target_rows),
+        options,
+    )?;
+    let schema_changes = crate::mapping::schema_changes(source_headers, &target_headers, options, &renamed_columns);
+    let (excluded_columns, warnings) = crate::mapping::resolve_excluded_columns(source_headers, &target_headers, options)?;
 
     let mut added = Vec::new();
     let mut removed = Vec::new();
     let mut modified = Vec::new();
-    let mut unchanged = Vec::new();
-
-    on_progress(20.0, "Building fingerprint index...");
-
-    // Use HashSet for excluded columns for O(1) lookup
-    let excluded_set: AHashSet<String> = excluded_columns.iter().cloned().collect();
-
-    // Track unmatched target rows
-    let mut unmatched_target_indices: AHashSet<usize> = (0..target_rows.len()).collect();
-
-    // Build fingerprint lookup for exact matches (optimized)
-    let mut target_fingerprint_lookup: AHashMap<String, Vec<usize>> = AHashMap::new();
-    for (idx, row) in target_rows.iter().enumerate() {
-        let fp = get_row_fingerprint_fast(
-            row,
-            &source_headers,
-            &target_header_map,
-            case_sensitive,
-            ignore_whitespace,
-            ignore_empty_vs_null,
-            &excluded_set
-        );
-        target_fingerprint_lookup.entry(fp).or_default().push(idx);
-    }
-
-    // Build value lookup for fuzzy matching optimization (avoid unnecessary allocations)
-    let mut target_value_lookup: AHashMap<(usize, String), Vec<usize>> = AHashMap::new();
-    for (row_idx, row) in target_rows.iter().enumerate() {
-        for (col_idx, cell) in row.iter().enumerate() {
-            let header = &target_headers[col_idx];
-            if excluded_set.contains(header) {
-                continue;
-            }
-            let trimmed = cell.trim();
-            if trimmed.is_empty() {
-                continue;
-            }
-            // Only allocate if needed
-            let key = if case_sensitive {
-                (col_idx, if ignore_whitespace { trimmed.to_string() } else { cell.to_string() })
-            } else {
-                (col_idx, if ignore_whitespace { trimmed.to_lowercase() } else { cell.to_lowercase() })
-            };
-            target_value_lookup.entry(key).or_default().push(row_idx);
+    let mut unchanged = UnchangedRows::new(options.include_unchanged);
+
+    emit(on_progress, 20.0, Message::new(codes::BUILD_FINGERPRINT_INDEX));
+
+    // Columns rows pair on: all compared ones, or the identity columns
+    let identity = PairingColumns::new(options, source_headers, source_header_map, &target_header_map, &excluded_columns)?;
+
+    // Fingerprints for exact matches, per the exact-match phase
+    let fingerprints = |rows: &[StringRecord], header_map| map_phase(rows, phases.exact_match, |row| {
+        identity.fingerprint(row, header_map, &normalizer)
+    });
+
+    // Blocking index of the target rows for fuzzy matching; signatures per
+    // the fuzzy-match phase, bucketing is sequential
+    let mut target_index = LshIndex::new(options.hash_state());
+    let target_signatures = map_phase(target_rows, phases.fuzzy_match, |row| {
+        target_index.signature(row, identity.columns.iter().map(|&(h, _, t)| (h, t)), &normalizer)
+    });
+    for (row_idx, signature) in target_signatures.iter().enumerate() {
+        if let Some(signature) = signature {
+            target_index.insert(row_idx, signature);
         }
     }
 
-    on_progress(30.0, "Matching exact rows...");
-
-    let mut unmatched_source_indices = Vec::new();
+    emit(on_progress, 30.0, Message::new(codes::MATCH_EXACT));
 
-    // Exact matching (Sequential)
-    for (i, source_row) in source_rows.iter().enumerate() {
-        let source_fingerprint = get_row_fingerprint_fast(
-            source_row,
-            &source_headers,
-            &source_header_map,
-            case_sensitive,
-            ignore_whitespace,
-            ignore_empty_vs_null,
-            &excluded_set
-        );
-
-        let mut matched_exact = false;
-        if let Some(indices) = target_fingerprint_lookup.get_mut(&source_fingerprint) {
-            while let Some(target_idx) = indices.pop() {
-                if unmatched_target_indices.contains(&target_idx) {
-                    unchanged.push(UnchangedRow {
-                        key: format!("Row {}", i + 1),
-                        row: record_to_hashmap(source_row, &source_headers),
-                    });
-                    unmatched_target_indices.remove(&target_idx);
-                    matched_exact = true;
-                    break;
-                }
-            }
-        }
+    let mut pairing = RowPairing::new(
+        &fingerprints(source_rows, source_header_map),
+        &fingerprints(target_rows, &target_header_map),
+        target_index,
+        &identity,
+        options,
+    );
+    let unmatched_source_indices: Vec<usize> = (0..source_rows.len()).filter(|&i| pairing.exact(i).is_none() && !pairing.surplus(i)).collect();
 
-        if !matched_exact {
-            unmatched_source_indices.push(i);
-        }
-    }
+    emit(on_progress, 50.0, Message::new(codes::MATCH_FUZZY));
 
-    on_progress(50.0, "Fuzzy matching in parallel...");
+    // Fuzzy matching: best candidates are scored in parallel, then rows
+    // pair in source order exactly as the sequential engine pairs them
 
-    // Fuzzy matching (Parallel)
-    // We calculate best matches for all unmatched source rows in parallel
-    // Then we resolve conflicts based on score
-    
-    // Convert unmatched target indices to a Vec for easier access if needed, 
-    // but we use target_value_lookup mostly.
-    // We need a read-only view of unmatched targets for the parallel part.
-    let unmatched_targets_set: AHashSet<usize> = unmatched_target_indices.clone();
-
-    struct MatchCandidate {
-        source_idx: usize,
-        target_idx: usize,
-        score: f64,
-    }
-
-    const CHUNK_SIZE: usize = 1000;
     let total_unmatched = unmatched_source_indices.len();
     let mut processed_unmatched = 0;
     // Per-thread counters for fuzzy matching
@@ -473,112 +586,23 @@ where
     let fuzzy_per_thread_counters: Vec<Arc<AtomicUsize>> = (0..num_threads)
         .map(|_| Arc::new(AtomicUsize::new(0)))
         .collect();
-    let mut all_potential_matches = Vec::new();
+    let mut best_candidates = Vec::with_capacity(total_unmatched);
 
-    for chunk in unmatched_source_indices.chunks(CHUNK_SIZE) {
-        let chunk_matches: Vec<MatchCandidate> = chunk
-            .par_iter()
-            .filter_map(|&source_idx| {
+    let scorer = CellScorer::new(&options.similarity, &normalizer);
+    let target_row = |idx: usize| &target_rows[idx];
+    for (chunk_index, chunk) in unmatched_source_indices.chunks(work_chunk_size(&options.streaming)).enumerate() {
+        best_candidates.extend(map_phase(chunk, phases.fuzzy_match, |&source_idx| {
             let thread_idx = rayon::current_thread_index().unwrap_or(0);
-            let _processed = fuzzy_per_thread_counters[thread_idx].fetch_add(1, Ordering::Relaxed) + 1;
-                let source_row = &source_rows[source_idx];
-                
-                // Find candidates using value lookup
-                let mut candidates = AHashSet::new();
-                for (col_idx, cell) in source_row.iter().enumerate() {
-                    let header = &source_headers[col_idx];
-                    if excluded_columns.contains(header) {
-                        continue;
-                    }
-                    if cell.trim().is_empty() {
-                        continue;
-                    }
-                    
-                    // Map source column to target column
-                    if let Some(target_col_idx) = target_header_map.get(header) {
-                        let key = (*target_col_idx, cell.to_string());
-                        if let Some(indices) = target_value_lookup.get(&key) {
-                            for &idx in indices {
-                                if unmatched_targets_set.contains(&idx) {
-                                    candidates.insert(idx);
-                                }
-                            }
-                        }
-                    }
-                }
-
-                // If no candidates found via lookup, check all unmatched targets (slow path)
-                // To avoid massive performance hit, we might skip this or limit it.
-                // For now, if candidates is empty, we skip fuzzy match for this row (it will be "Removed")
-                // This matches the optimization in content_match.rs
-                
-                if candidates.is_empty() {
-                    return None;
-                }
-
-                let mut best_match_idx = None;
-                let mut best_match_score = 0.0;
-                const SIMILARITY_THRESHOLD: f64 = 0.5;
-
-                for target_idx in candidates {
-                    let target_row = &target_rows[target_idx];
-                    
-                    let mut total_score = 0.0;
-                    let mut comparisons = 0;
-
-                    for (header, source_col_idx) in &source_header_map {
-                        if excluded_columns.contains(header) {
-                            continue;
-                        }
-                        
-                        let target_col_idx = match target_header_map.get(header) {
-                            Some(idx) => idx,
-                            None => continue,
-                        };
-
-                        let s_val = source_row.get(*source_col_idx).unwrap_or("");
-                        let t_val = target_row.get(*target_col_idx).unwrap_or("");
-
-                        let s_norm = normalize_value_with_empty_vs_null(s_val, case_sensitive, ignore_whitespace, ignore_empty_vs_null);
-                        let t_norm = normalize_value_with_empty_vs_null(t_val, case_sensitive, ignore_whitespace, ignore_empty_vs_null);
-
-                        if s_norm == t_norm {
-                            total_score += 1.0;
-                        } else {
-                            total_score += jaro_winkler(&s_norm, &t_norm);
-                        }
-                        comparisons += 1;
-                    }
-
-                    let avg_score = if comparisons > 0 {
-                        total_score / comparisons as f64
-                    } else {
-                        0.0
-                    };
-
-                    if avg_score > SIMILARITY_THRESHOLD && avg_score > best_match_score {
-                        best_match_score = avg_score;
-                        best_match_idx = Some(target_idx);
-                    }
-                }
-
-                if let Some(target_idx) = best_match_idx {
-                    Some(MatchCandidate {
-                        source_idx,
-                        target_idx,
-                        score: best_match_score,
-                    })
-                } else {
-                    None
-                }
-            })
-            .collect();
-        
-        all_potential_matches.extend(chunk_matches);
+            fuzzy_per_thread_counters[thread_idx].fetch_add(1, Ordering::Relaxed);
+            pairing.score(&source_rows[source_idx], target_row, &normalizer, &scorer)
+        }));
         
         processed_unmatched += chunk.len();
+        if !options.streaming.reports_after(chunk_index + 1) {
+            continue;
+        }
         let progress = 50.0 + (processed_unmatched as f64 / total_unmatched as f64) * 50.0;
-        on_progress(progress, &format!("Fuzzy matching in parallel... ({}/{})", processed_unmatched, total_unmatched));
+        emit(on_progress, progress, Message::new(codes::MATCH_FUZZY_PROGRESS).with("processed", processed_unmatched).with("total", total_unmatched));
         // Calculate per-thread total for fuzzy matching
         let per_thread_total = if total_unmatched >= num_threads {
             total_unmatched / num_threads + (total_unmatched % num_threads != 0) as usize
@@ -605,94 +629,81 @@ where
         }
     }
 
-    let potential_matches = all_potential_matches;
-
-    // Resolve conflicts
-    // Sort by score descending
-    let mut sorted_matches = potential_matches;
-    sorted_matches.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
-
-    let mut matched_source_indices = AHashSet::new();
-    // unmatched_target_indices is already tracked, but we need to track what we use in this phase
-    let mut used_target_indices_in_fuzzy = AHashSet::new();
-
-    for m in sorted_matches {
-        if matched_source_indices.contains(&m.source_idx) {
-            continue;
-        }
-        if !unmatched_target_indices.contains(&m.target_idx) || used_target_indices_in_fuzzy.contains(&m.target_idx) {
-            continue;
-        }
-
-        // Accept match
-        matched_source_indices.insert(m.source_idx);
-        used_target_indices_in_fuzzy.insert(m.target_idx);
-        unmatched_target_indices.remove(&m.target_idx);
-
-        let source_row = &source_rows[m.source_idx];
-        let target_row = &target_rows[m.target_idx];
-
-        let mut differences = Vec::new();
-        for header in &source_headers {
-            if excluded_columns.contains(header) {
-                continue;
+    let mut best_candidates = best_candidates.into_iter();
+    for (i, source_row) in source_rows.iter().enumerate() {
+        // Char diffs are filled in below, on the thread pool
+        let (idx, score, differences) = match pairing.exact(i) {
+            Some(idx) => {
+                let differences = pairing.exact_differences(source_row, &target_rows[idx], &normalizer, false);
+                if differences.is_empty() {
+                    unchanged.push_with(|| UnchangedRow {
+                        key: format!("Row {}", i + 1),
+                        row: record_to_hashmap(source_row, source_headers),
+                        source_index: Some(i),
+                        target_index: Some(idx),
+                    });
+                    continue;
+                }
+                (idx, 1.0, differences)
             }
-            
-            let source_idx = source_header_map.get(header).unwrap();
-            let target_idx = match target_header_map.get(header) {
-                Some(idx) => idx,
-                None => continue,
-            };
-            
-            let s_val = source_row.get(*source_idx).unwrap_or("");
-            let t_val = target_row.get(*target_idx).unwrap_or("");
-            
-            let s_norm = normalize_value_with_empty_vs_null(s_val, case_sensitive, ignore_whitespace, ignore_empty_vs_null);
-            let t_norm = normalize_value_with_empty_vs_null(t_val, case_sensitive, ignore_whitespace, ignore_empty_vs_null);
-            
-            if s_norm != t_norm {
-                differences.push(Difference {
-                    column: header.clone(),
-                    old_value: s_val.to_string(),
-                    new_value: t_val.to_string(),
-                    diff: Vec::new(), // Skip char diffs in parallel
-                });
+            None => {
+                let paired = if pairing.surplus(i) {
+                    None
+                } else {
+                    let best = best_candidates.next().flatten();
+                    pairing.pair(source_row, best, target_row, &normalizer, &scorer)
+                };
+                match paired {
+                    Some((idx, score)) => (idx, score, pairing.differences(source_row, &target_rows[idx], &normalizer, false)),
+                    None => {
+                        removed.push(RemovedRow {
+                            key: format!("Removed {}", removed.len() + 1),
+                            source_row: record_to_hashmap(source_row, source_headers),
+                            source_line: record_line(source_row),
+                            source_index: Some(i),
+                        });
+                        continue;
+                    }
+                }
             }
-        }
+        };
 
+        let target_row = &target_rows[idx];
         modified.push(ModifiedRow {
-            key: format!("Row {}", m.source_idx + 1),
-            source_row: record_to_hashmap(source_row, &source_headers),
+            key: format!("Row {}", i + 1),
+            source_row: record_to_hashmap(source_row, source_headers),
+            source_line: record_line(source_row),
             target_row: record_to_hashmap(target_row, &target_headers),
+            target_line: record_line(target_row),
+            source_index: Some(i),
+            target_index: Some(idx),
+            match_score: Some(score),
+            target_key: None,
             differences,
         });
     }
 
-    // Remaining unmatched source rows are Removed
-    for &i in &unmatched_source_indices {
-        if !matched_source_indices.contains(&i) {
-            removed.push(RemovedRow {
-                key: format!("Row {}", i + 1),
-                source_row: record_to_hashmap(&source_rows[i], &source_headers),
-            });
-        }
+    if phases.char_diffs {
+        fill_char_diffs(&mut modified, &normalizer);
     }
 
     // Remaining unmatched target rows are Added
-    for i in unmatched_target_indices {
+    for (n, idx) in pairing.unpaired_targets().into_iter().enumerate() {
         added.push(AddedRow {
-            key: format!("Row {}", i + 1),
-            target_row: record_to_hashmap(&target_rows[i], &target_headers),
+            key: format!("Added {}", n + 1),
+            target_row: record_to_hashmap(&target_rows[idx], &target_headers),
+            target_line: record_line(&target_rows[idx]),
+            target_index: Some(idx),
         });
     }
 
-    on_progress(100.0, "Complete");
+    emit(on_progress, 100.0, Message::new(codes::COMPLETE));
 
     Ok(DiffResult {
         added,
         removed,
         modified,
-        unchanged,
+        unchanged: unchanged.rows,
         // For large datasets, avoid converting all rows to HashMap (huge perf win)
         source: crate::types::DatasetMetadata {
             headers: source_headers.clone(),
@@ -704,8 +715,19 @@ where
         },
         key_columns: vec![],
         excluded_columns,
-        mode: "content_match".to_string(),
-    })
+        mode: "content-match".to_string(),
+        summary: DiffSummary::default(),
+        warnings,
+        seed: options.seed,
+        fallback: None,
+        renamed_columns,
+        schema_changes,
+        reversed: false,
+        header_policy: options.header_policy,
+        moved_rows: Vec::new(),
+        duplicate_counts: pairing.duplicate_counts(|idx| &source_rows[idx], source_headers),
+        omitted_unchanged: unchanged.omitted,
+    }.with_summary().ordered(options.row_order))
 }
 
 #[cfg(test)]
@@ -718,4 +740,80 @@ mod tests {
         init_thread_pool(4);
         init_thread_pool(2); // Should handle re-initialization gracefully
     }
+
+    #[test]
+    fn test_parallel_phases_agree() {
+        let source = "id,name\n1,Alice\n2,Bob\n3,Carol\n";
+        let target = "id,name\n1,Alice\n2,Bobby\n4,Dave\n";
+
+        for mode in ["primary-key", "content-match"] {
+            let mut options = DiffOptions {
+                mode: mode.to_string(),
+                key_columns: vec!["id".to_string()],
+                use_parallel: true,
+                parallel_phases: ParallelPhases::none(),
+                ..Default::default()
+            };
+            let sequential = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            assert!(sequential.modified.iter().all(|m| m.differences.iter().all(|d| d.diff.is_empty())));
+
+            options.parallel_phases = ParallelPhases::all();
+            let parallel = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+
+            assert_eq!(parallel.summary, sequential.summary, "mode {}", mode);
+            assert!(!parallel.modified[0].differences[0].diff.is_empty(), "mode {}", mode);
+        }
+    }
+
+    #[test]
+    fn test_max_candidates_per_row() {
+        // "shares" has more values in common with the source row, "close"
+        // has the more similar row overall
+        let source = "tag,group,code,label,note\nshares,g1,ABC,order confirmed,n1\n";
+        let target = "tag,group,code,label,note\nshares,g1,ABC,zzzz,qqqq\nclose,g1,ABD,order confirmd,n2\n";
+        for use_parallel in [false, true] {
+            let mut options = DiffOptions { mode: "content-match".to_string(), use_parallel, ..Default::default() };
+            let best = |options: &DiffOptions| {
+                let result = crate::core::diff_with_options(source, target, options, |_, _| {}).unwrap();
+                result.modified[0].target_row["tag"].clone()
+            };
+            assert_eq!(best(&options), "close");
+            options.max_candidates_per_row = Some(1);
+            assert_eq!(best(&options), "shares");
+        }
+    }
+
+    #[test]
+    fn test_content_match_engines_agree() {
+        // Rows 2 and 3 both resemble target row 9; row 3 more closely, but
+        // row 2 comes first. Row 4 is duplicated in the source only.
+        let source = "id,name,city\n1,Alice Smith,Paris\n2,Robert Jones,Lyon\n9,Robert Jonas,Londn\n4,Dup,Rome\n4,Dup,Rome\n5,Zed,Oslo\n";
+        let target = "id,name,city\n4,Dup,Rome\n9,Robert Jonas,London\n1,Alice Smith,Paris\n6,Carl Berg,Bergen\n";
+        let rows = |result: &DiffResult| serde_json::json!([result.unchanged, result.modified, result.removed, result.added]);
+
+        let mut options = DiffOptions { mode: "content-match".to_string(), ..Default::default() };
+        let sequential = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+        assert_eq!(sequential.summary.modified, 1);
+        assert_eq!(sequential.modified[0].key, "Row 2");
+
+        options.use_parallel = true;
+        options.parallel_phases = ParallelPhases::all();
+        let parallel = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+        assert_eq!(parallel.mode, sequential.mode);
+        assert_eq!(parallel.summary, sequential.summary);
+        assert_eq!(rows(&parallel), rows(&sequential));
+
+        for chunk_size in [1, 2, 10] {
+            let mut differ = crate::core::CsvDifferInternal::with_options(source, target, &options).unwrap();
+            let mut chunked = differ.diff_chunk(0, chunk_size, |_, _| {}).unwrap();
+            for chunk_start in (chunk_size..6).step_by(chunk_size) {
+                let chunk = differ.diff_chunk(chunk_start, chunk_size, |_, _| {}).unwrap();
+                chunked.unchanged.extend(chunk.unchanged);
+                chunked.modified.extend(chunk.modified);
+                chunked.removed.extend(chunk.removed);
+                chunked.added.extend(chunk.added);
+            }
+            assert_eq!(rows(&chunked), rows(&sequential), "chunk size {}", chunk_size);
+        }
+    }
 }
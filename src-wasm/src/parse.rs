@@ -1,15 +1,354 @@
 use csv::ReaderBuilder;
 use csv::StringRecord;
 use ahash::AHashMap;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use crate::messages::{codes, emit, progress_sink, scoped, Message, MessageFormat};
+use crate::streaming::StreamingConfig;
+use crate::types::{HeaderDetection, HeaderEvidence};
+
+/// Byte substituted for multi-character delimiters before handing input to the csv reader.
+const MULTI_DELIMITER_PLACEHOLDER: char = '\u{1F}';
+
+/// Records after the first one that header detection looks at.
+pub(crate) const HEADER_SAMPLE_ROWS: usize = 20;
+
+/// Headers, data rows and header-name -> column-index map of a parsed CSV.
+pub type ParsedCsv = (Vec<String>, Vec<StringRecord>, AHashMap<String, usize>);
+
+/// Options controlling how raw CSV text is tokenized.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ParseOptions {
+    pub has_headers: HeaderMode,
+    /// Field delimiter. May be longer than one byte (e.g. `||` or `~|~`).
+    pub delimiter: String,
+    /// Drop trailing columns that have no header and no values (Excel padding).
+    pub trim_trailing_empty_columns: bool,
+    /// Whitespace trimming applied to fields of columns not listed in `column_trim`.
+    pub trim: TrimMode,
+    /// Per-column trimming overrides, keyed by header name.
+    pub column_trim: HashMap<String, TrimMode>,
+    /// Whether progress messages and errors are rendered text or message codes.
+    pub message_format: MessageFormat,
+}
+
+/// Whether the first record is a header row. Serialized as `true`, `false`
+/// or `"auto"`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HeaderMode {
+    /// A header row, unless it looks like data (see `header_looks_like_data`)
+    #[default]
+    Present,
+    Absent,
+    /// Decided per file by `detect_header_row`
+    Auto,
+}
+
+impl From<bool> for HeaderMode {
+    fn from(has_headers: bool) -> Self {
+        if has_headers { HeaderMode::Present } else { HeaderMode::Absent }
+    }
+}
+
+impl Serialize for HeaderMode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            HeaderMode::Present => serializer.serialize_bool(true),
+            HeaderMode::Absent => serializer.serialize_bool(false),
+            HeaderMode::Auto => serializer.serialize_str("auto"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for HeaderMode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Flag(bool),
+            Name(String),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Flag(has_headers) => Ok(has_headers.into()),
+            Repr::Name(name) if name == "auto" => Ok(HeaderMode::Auto),
+            Repr::Name(name) => Err(serde::de::Error::custom(format!("invalid hasHeaders \"{}\", expected true, false or \"auto\"", name))),
+        }
+    }
+}
+
+/// How leading/trailing whitespace is stripped from a field.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TrimMode {
+    None,
+    Start,
+    End,
+    #[default]
+    Both,
+}
+
+impl TrimMode {
+    pub fn apply(self, value: &str) -> &str {
+        match self {
+            TrimMode::None => value,
+            TrimMode::Start => value.trim_start(),
+            TrimMode::End => value.trim_end(),
+            TrimMode::Both => value.trim(),
+        }
+    }
+
+    /// Byte-slice variant of `apply`; trims ASCII whitespace only.
+    pub fn apply_bytes(self, value: &[u8]) -> &[u8] {
+        match self {
+            TrimMode::None => value,
+            TrimMode::Start => value.trim_ascii_start(),
+            TrimMode::End => value.trim_ascii_end(),
+            TrimMode::Both => value.trim_ascii(),
+        }
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            has_headers: HeaderMode::Present,
+            delimiter: ",".to_string(),
+            trim_trailing_empty_columns: false,
+            trim: TrimMode::Both,
+            column_trim: HashMap::new(),
+            message_format: MessageFormat::Text,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn new(has_headers: bool) -> Self {
+        Self {
+            has_headers: has_headers.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Builder pattern for configuration
+    pub fn with_delimiter(mut self, delimiter: impl Into<String>) -> Self {
+        self.delimiter = delimiter.into();
+        self
+    }
+
+    pub fn with_trim_trailing_empty_columns(mut self, enabled: bool) -> Self {
+        self.trim_trailing_empty_columns = enabled;
+        self
+    }
+
+    pub fn with_column_trim(mut self, column: impl Into<String>, mode: TrimMode) -> Self {
+        self.column_trim.insert(column.into(), mode);
+        self
+    }
+
+    /// True when every field gets the default `Trim::All` treatment from the reader.
+    pub(crate) fn trims_uniformly(&self) -> bool {
+        self.trim == TrimMode::Both && self.column_trim.values().all(|m| *m == TrimMode::Both)
+    }
+
+    /// Whether the first record, `header_names`, is the header row, given
+    /// the records that follow it (only the first few are inspected).
+    pub(crate) fn uses_header_row(&self, header_names: &[String], rows: &[StringRecord]) -> bool {
+        match self.has_headers {
+            HeaderMode::Present => !rows.first().is_some_and(|r| header_looks_like_data(header_names, r)),
+            HeaderMode::Absent => false,
+            HeaderMode::Auto => detect_header_row(header_names, &rows[..rows.len().min(HEADER_SAMPLE_ROWS)]).has_headers,
+        }
+    }
+
+    pub(crate) fn is_multi_byte_delimiter(&self) -> bool {
+        self.delimiter.len() > 1
+    }
+
+    /// Reader builder configured for (already pre-tokenized) input.
+    pub(crate) fn reader_builder(&self, has_headers: bool) -> ReaderBuilder {
+        let delimiter = match self.delimiter.as_bytes() {
+            [single] => *single,
+            [] => b',',
+            _ => MULTI_DELIMITER_PLACEHOLDER as u8,
+        };
+        let mut builder = ReaderBuilder::new();
+        builder
+            .has_headers(has_headers)
+            .delimiter(delimiter)
+            .trim(if self.trims_uniformly() { csv::Trim::All } else { csv::Trim::Headers });
+        builder
+    }
+}
+
+/// Pre-tokenization pass for multi-character delimiters.
+///
+/// Rewrites every delimiter occurrence outside quoted fields to a single
+/// placeholder byte so the regular csv reader can split records. Single-byte
+/// delimiters are returned borrowed without copying the input.
+pub fn pretokenize<'a>(
+    csv_content: &'a str,
+    options: &ParseOptions,
+) -> Result<Cow<'a, str>, Box<dyn std::error::Error>> {
+    if !options.is_multi_byte_delimiter() {
+        return Ok(Cow::Borrowed(csv_content));
+    }
+    if csv_content.contains(MULTI_DELIMITER_PLACEHOLDER) {
+        return Err("Input contains the unit separator (0x1F) character, which is reserved for multi-character delimiter parsing.".into());
+    }
+
+    let delimiter = options.delimiter.as_str();
+    let mut output = String::with_capacity(csv_content.len());
+    let mut in_quotes = false;
+    let mut rest = csv_content;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        } else if !in_quotes && rest.starts_with(delimiter) {
+            output.push(MULTI_DELIMITER_PLACEHOLDER);
+            rest = &rest[delimiter.len()..];
+            continue;
+        }
+        output.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    Ok(Cow::Owned(output))
+}
+
+/// Header auto-detection heuristic: the "header" row is really data when it
+/// has the same width as the first row and contains a purely numeric cell.
+pub fn header_looks_like_data(headers: &[String], first_row: &StringRecord) -> bool {
+    headers.len() == first_row.len() &&
+        headers.iter().any(|h| {
+            let trimmed = h.trim();
+            // Consider it data if it's purely numeric or looks like an ID.
+            // Blank headers (e.g. Excel padding columns) are not evidence either way.
+            !trimmed.is_empty() && (trimmed.chars().all(|c| c.is_ascii_digit()) ||
+            (trimmed.len() <= 6 && trimmed.chars().all(|c| c.is_ascii_digit())))
+        })
+}
+
+/// Score the evidence that `header_names` is a header row rather than the
+/// first data record, judging by the `rows` that follow it. Files usually
+/// have headers, so without evidence the answer leans towards yes.
+pub fn detect_header_row(header_names: &[String], rows: &[StringRecord]) -> HeaderDetection {
+    let mut evidence = Vec::new();
+    let mut signal = |name: &str, columns: Vec<usize>, weight_per_column: f64, cap: f64| {
+        if !columns.is_empty() {
+            let weight = (weight_per_column * columns.len() as f64).clamp(-cap, cap);
+            evidence.push(HeaderEvidence { signal: name.to_string(), columns, weight });
+        }
+    };
+
+    let column_values = |i: usize| rows.iter().filter_map(move |r| r.get(i)).map(str::trim).filter(|v| !v.is_empty());
+    let names = || header_names.iter().enumerate().map(|(i, h)| (i, h.trim()));
+
+    signal("numericHeader", names().filter(|(_, h)| looks_typed(h)).map(|(i, _)| i).collect(), -3.0, 3.0);
+    // Text above a column of numbers or dates is what headers look like
+    signal("typedColumns", names()
+        .filter(|&(_, h)| !h.is_empty() && !looks_typed(h))
+        .filter(|&(i, _)| column_values(i).next().is_some() && column_values(i).all(looks_typed))
+        .map(|(i, _)| i)
+        .collect(), 1.5, 4.5);
+    signal("repeatedValue", names()
+        .filter(|&(i, h)| !h.is_empty() && column_values(i).any(|v| v == h))
+        .map(|(i, _)| i)
+        .collect(), -1.0, 3.0);
+    signal("duplicateNames", names()
+        .filter(|&(i, h)| !h.is_empty() && names().take(i).any(|(_, other)| other == h))
+        .map(|(i, _)| i)
+        .collect(), -1.5, 1.5);
+    // Blank headers above blank columns are padding, not evidence
+    signal("blankNames", names()
+        .filter(|&(i, h)| h.is_empty() && column_values(i).next().is_some())
+        .map(|(i, _)| i)
+        .collect(), -0.5, 1.5);
+
+    let score = 0.5 + evidence.iter().map(|e| e.weight).sum::<f64>();
+    let header_probability = 1.0 / (1.0 + (-score).exp());
+    HeaderDetection {
+        has_headers: score >= 0.0,
+        confidence: header_probability.max(1.0 - header_probability),
+        evidence,
+    }
+}
+
+/// Numbers, dates and times: digits with separators and signs only.
+fn looks_typed(value: &str) -> bool {
+    value.chars().any(|c| c.is_ascii_digit())
+        && value.chars().all(|c| c.is_ascii_digit() || "+-.,:/ %".contains(c))
+}
 
 pub fn parse_csv_internal(
     csv_content: &str,
     has_headers: bool,
-) -> Result<(Vec<String>, Vec<StringRecord>, AHashMap<String, usize>), Box<dyn std::error::Error>> {
-    let mut rdr = ReaderBuilder::new()
-        .has_headers(has_headers)
-        .trim(csv::Trim::All)
+) -> Result<ParsedCsv, Box<dyn std::error::Error>> {
+    parse_csv_with_options(csv_content, &ParseOptions::new(has_headers))
+}
+
+/// Parse a CSV using the given tokenization options.
+pub fn parse_csv_with_options(
+    csv_content: &str,
+    options: &ParseOptions,
+) -> Result<ParsedCsv, Box<dyn std::error::Error>> {
+    let parsed = read_csv(csv_content, options)?;
+    Ok(apply_column_options(parsed, options))
+}
+
+/// Headers `parse_csv_with_options` would produce, read from the first two
+/// records only. Trailing empty columns are not trimmed since that needs
+/// every row.
+pub fn parse_headers(
+    csv_content: &str,
+    options: &ParseOptions,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let (headers, samples) = leading_records(csv_content, options)?;
+    if options.uses_header_row(&headers, &samples) {
+        Ok(headers)
+    } else {
+        Ok((0..headers.len()).map(|i| format!("Column{}", i + 1)).collect())
+    }
+}
+
+/// Guess whether the first record of `csv_content` is a header row, from it
+/// and the records that follow. `options.has_headers` is ignored.
+pub fn detect_headers(
+    csv_content: &str,
+    options: &ParseOptions,
+) -> Result<HeaderDetection, Box<dyn std::error::Error>> {
+    let (headers, samples) = leading_records(csv_content, options)?;
+    Ok(detect_header_row(&headers, &samples))
+}
+
+/// Trimmed fields of the first record (empty for empty input) and up to
+/// `HEADER_SAMPLE_ROWS` records after it.
+fn leading_records(
+    csv_content: &str,
+    options: &ParseOptions,
+) -> Result<(Vec<String>, Vec<StringRecord>), Box<dyn std::error::Error>> {
+    let prepared = pretokenize(csv_content, options)?;
+    let mut rdr = options.reader_builder(false)
+        .from_reader(prepared.as_bytes());
+    let mut records = rdr.records();
+    let headers = match records.next().transpose()? {
+        Some(first) => first.iter().map(|h| h.trim().to_string()).collect(),
+        None => Vec::new(),
+    };
+    let samples = records.take(HEADER_SAMPLE_ROWS).collect::<Result<Vec<_>, _>>()?;
+    Ok((headers, samples))
+}
+
+fn read_csv(
+    csv_content: &str,
+    options: &ParseOptions,
+) -> Result<ParsedCsv, Box<dyn std::error::Error>> {
+    let has_headers = options.has_headers != HeaderMode::Absent;
+    let prepared = pretokenize(csv_content, options)?;
+    let csv_content: &str = &prepared;
+    let mut rdr = options.reader_builder(has_headers)
         .from_reader(csv_content.as_bytes());
     
     let headers: Vec<String>;
@@ -20,33 +359,23 @@ pub fn parse_csv_internal(
         headers = header_record.iter().map(|s| s.to_string()).collect();
         
         // Collect all rows first
-        let rows: Vec<StringRecord> = rdr.records().collect::<Result<Vec<_>, _>>()?;
+        let rows: Vec<StringRecord> = records(&mut rdr, csv_content.as_bytes()).collect::<Result<Vec<_>, _>>()?;
         
         // Auto-detect if headers are actually data
         if !headers.is_empty() && !rows.is_empty() {
             let first_row = &rows[0];
             
-            // Check if "headers" look like data (numeric values)
-            let header_looks_like_data = headers.len() == first_row.len() && 
-                headers.iter().any(|h| {
-                    let trimmed = h.trim();
-                    // Consider it data if it's purely numeric or looks like an ID
-                    trimmed.chars().all(|c| c.is_ascii_digit()) || 
-                    (trimmed.len() <= 6 && trimmed.chars().all(|c| c.is_ascii_digit()))
-                });
-            
-            if header_looks_like_data {
+            // Check if "headers" look like data
+            if !options.uses_header_row(&headers, &rows) {
                 // Re-parse as CSV without headers
-                let mut rdr_no_headers = ReaderBuilder::new()
-                    .has_headers(false)
-                    .trim(csv::Trim::All)
+                let mut rdr_no_headers = options.reader_builder(false)
                     .from_reader(csv_content.as_bytes());
                 
                 let auto_headers: Vec<String> = (0..first_row.len())
                     .map(|i| format!("Column{}", i + 1))
                     .collect();
                 
-                let auto_rows = rdr_no_headers.records()
+                let auto_rows = records(&mut rdr_no_headers, csv_content.as_bytes())
                     .collect::<Result<Vec<_>, _>>()?;
                 
                 for (i, h) in auto_headers.iter().enumerate() {
@@ -67,7 +396,7 @@ pub fn parse_csv_internal(
         headers = vec![]; // Placeholder
     }
 
-    let rows: Vec<StringRecord> = rdr.records()
+    let rows: Vec<StringRecord> = records(&mut rdr, csv_content.as_bytes())
         .collect::<Result<Vec<_>, _>>()?;
 
     if !has_headers {
@@ -88,50 +417,129 @@ pub fn parse_csv_internal(
     Ok((headers, rows, header_map))
 }
 
+/// Move a reader-reported position onto the line its record actually starts
+/// on. The csv reader positions a record where the previous one ended, i.e.
+/// before any blank lines it skipped.
+pub(crate) fn settle_position(content: &[u8], position: &mut csv::Position) {
+    let skipped = content
+        .get(position.byte() as usize..)
+        .unwrap_or_default()
+        .iter()
+        .take_while(|&&b| b == b'\n' || b == b'\r')
+        .filter(|&&b| b == b'\n')
+        .count();
+    if skipped > 0 {
+        position.set_line(position.line() + skipped as u64);
+    }
+}
+
+/// Records of `rdr` with positions settled by `settle_position`.
+fn records<'r, R: std::io::Read>(
+    rdr: &'r mut csv::Reader<R>,
+    content: &'r [u8],
+) -> impl Iterator<Item = csv::Result<StringRecord>> + 'r {
+    rdr.records().map(move |record| {
+        let mut record = record?;
+        if let Some(mut position) = record.position().cloned() {
+            settle_position(content, &mut position);
+            record.set_position(Some(position));
+        }
+        Ok(record)
+    })
+}
+
 /// Streaming CSV parser that emits progress and processes in chunks
 pub fn parse_csv_streaming<F>(
     csv_content: &str,
     has_headers: bool,
     chunk_size: usize,
+    on_progress: F,
+) -> Result<ParsedCsv, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    parse_csv_streaming_with_options(csv_content, &ParseOptions::new(has_headers), &StreamingConfig::new(chunk_size), on_progress)
+}
+
+/// Streaming CSV parser using the given tokenization options, chunked and
+/// reporting progress as `config` says
+pub fn parse_csv_streaming_with_options<F>(
+    csv_content: &str,
+    options: &ParseOptions,
+    config: &StreamingConfig,
+    on_progress: F,
+) -> Result<ParsedCsv, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let on_progress = progress_sink(options.message_format, on_progress);
+    let parsed = read_csv_streaming(csv_content, options, config, on_progress)?;
+    Ok(apply_column_options(parsed, options))
+}
+
+/// Parse a diff's source and then its target with
+/// `parse_csv_streaming_with_options`, reporting progress from 0 to 20%
+/// under "source" and "target" scopes, where the engines' comparison picks up.
+pub fn parse_pair_streaming<F>(
+    source_csv: &str,
+    target_csv: &str,
+    options: &ParseOptions,
+    config: &StreamingConfig,
+    on_progress: &mut F,
+) -> Result<(ParsedCsv, ParsedCsv), Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let source = parse_csv_streaming_with_options(source_csv, options, config, |percent, message| {
+        on_progress(percent * 0.1, &scoped("source", message)); // Scale to 0-10%
+    })?;
+    let target = parse_csv_streaming_with_options(target_csv, options, config, |percent, message| {
+        on_progress(10.0 + percent * 0.1, &scoped("target", message)); // Scale to 10-20%
+    })?;
+    Ok((source, target))
+}
+
+fn read_csv_streaming<F>(
+    csv_content: &str,
+    options: &ParseOptions,
+    config: &StreamingConfig,
     mut on_progress: F,
-) -> Result<(Vec<String>, Vec<StringRecord>, AHashMap<String, usize>), Box<dyn std::error::Error>>
+) -> Result<ParsedCsv, Box<dyn std::error::Error>>
 where
     F: FnMut(f64, &str),
 {
-    on_progress(0.0, "Initializing CSV reader...");
-    
-    let mut rdr = ReaderBuilder::new()
-        .has_headers(has_headers)
-        .trim(csv::Trim::All)
+    emit(&mut on_progress, 0.0, Message::new(codes::PARSE_INIT));
+
+    let has_headers = options.has_headers != HeaderMode::Absent;
+    let prepared = pretokenize(csv_content, options)?;
+    let csv_content: &str = &prepared;
+    let mut rdr = options.reader_builder(has_headers)
         .from_reader(csv_content.as_bytes());
     
     let headers: Vec<String>;
     let mut header_map: AHashMap<String, usize> = AHashMap::new();
 
+    // The first data rows are read up front for header detection and must be
+    // kept so they are not lost from the parsed rows. Only "auto" looks past
+    // the first one.
+    let sample_rows = if options.has_headers == HeaderMode::Auto { HEADER_SAMPLE_ROWS } else { 1 };
+    let leading_rows = records(&mut rdr, csv_content.as_bytes())
+        .take(sample_rows)
+        .collect::<Result<Vec<_>, _>>()?;
+
     // First, get headers
     if has_headers {
         let header_record = rdr.headers()?;
         headers = header_record.iter().map(|s| s.to_string()).collect();
         
         // Auto-detect if headers are actually data
-        let first_row_result = rdr.records().next();
-        if let Some(Ok(first_row)) = first_row_result {
-            let header_looks_like_data = headers.len() == first_row.len() && 
-                headers.iter().any(|h| {
-                    let trimmed = h.trim();
-                    trimmed.chars().all(|c| c.is_ascii_digit()) || 
-                    (trimmed.len() <= 6 && trimmed.chars().all(|c| c.is_ascii_digit()))
-                });
-            
-            if header_looks_like_data {
-                // Re-parse as CSV without headers
-                return parse_csv_streaming_no_headers(csv_content, chunk_size, on_progress);
-            }
+        if !leading_rows.is_empty() && !options.uses_header_row(&headers, &leading_rows) {
+            // Re-parse as CSV without headers
+            return parse_csv_streaming_no_headers(csv_content, options, config, on_progress);
         }
     } else {
         // Generate headers from first row
-        let first_row_result = rdr.records().next();
-        if let Some(Ok(first_row)) = first_row_result {
+        if let Some(first_row) = leading_rows.first() {
             let col_count = first_row.len();
             headers = (0..col_count)
                 .map(|i| format!("Column{}", i + 1))
@@ -145,77 +553,165 @@ where
         header_map.insert(h.clone(), i);
     }
 
-    on_progress(5.0, "Reading CSV data in chunks...");
+    emit(&mut on_progress, 5.0, Message::new(codes::PARSE_READING));
     
-    // Count total rows for progress calculation
-    let total_rows = csv_content.lines().count().saturating_sub(if has_headers { 1 } else { 0 });
-    let rows_processed = AtomicUsize::new(0);
+    // Progress follows the reader's byte position, which stays accurate when
+    // quoted fields contain newlines and needs no extra pass over the input
+    let total_bytes = csv_content.len();
+    let chunk_size = config.chunk_size_for(csv_content);
+    let mut rows_processed = 0;
     
     // Process rows in chunks to avoid memory spikes
-    let mut all_rows = Vec::with_capacity(total_rows.min(100000)); // Cap initial allocation
+    let mut all_rows = Vec::new();
     let mut chunk = Vec::with_capacity(chunk_size);
+    chunk.extend(leading_rows);
     
-    for record_result in rdr.records() {
+    for record_result in records(&mut rdr, csv_content.as_bytes()) {
         let record = record_result?;
-        chunk.push(record);
-        
+        // A chunk is flushed once the next record arrives, whose start is
+        // exactly where the chunk ended
         if chunk.len() >= chunk_size {
-            all_rows.extend(chunk.drain(..));
-            let processed = rows_processed.fetch_add(chunk_size, Ordering::Relaxed);
-            let progress = (processed as f64 / total_rows as f64) * 90.0 + 5.0; // 5-95%
-            on_progress(progress, &format!("Processed {} rows", processed + chunk_size));
+            let progress = byte_fraction(&record, total_bytes) * 90.0 + 5.0; // 5-95%
+            rows_processed += chunk.len();
+            all_rows.append(&mut chunk);
+            if config.reports_after(rows_processed / chunk_size) {
+                emit(&mut on_progress, progress, Message::new(codes::PARSE_ROWS).with("count", rows_processed));
+            }
         }
+        chunk.push(record);
     }
     
     // Process remaining records
-        if !chunk.is_empty() {
-            let chunk_len = chunk.len();
-            all_rows.extend(chunk);
-            let processed = rows_processed.fetch_add(chunk_len, Ordering::Relaxed);
-            let progress = (processed as f64 / total_rows as f64) * 90.0 + 5.0;
-            on_progress(progress, &format!("Processed {} rows", processed + chunk_len));
-        }    on_progress(100.0, "CSV parsing complete");
+    if !chunk.is_empty() {
+        rows_processed += chunk.len();
+        all_rows.append(&mut chunk);
+        if config.enable_progress_updates {
+            emit(&mut on_progress, 95.0, Message::new(codes::PARSE_ROWS).with("count", rows_processed));
+        }
+    }
+    emit(&mut on_progress, 100.0, Message::new(codes::PARSE_COMPLETE));
     
     Ok((headers, all_rows, header_map))
 }
 
+/// Share of the input consumed before `record` starts.
+fn byte_fraction(record: &StringRecord, total_bytes: usize) -> f64 {
+    let byte = record.position().map_or(0, |p| p.byte());
+    byte as f64 / total_bytes.max(1) as f64
+}
+
+/// Build a record from `fields`, keeping the file position of `original`
+/// so line numbers survive post-processing.
+pub(crate) fn rebuild_record<'a>(original: &StringRecord, fields: impl IntoIterator<Item = &'a str>) -> StringRecord {
+    let mut record: StringRecord = fields.into_iter().collect();
+    record.set_position(original.position().cloned());
+    record
+}
+
+/// Column-level post-processing applied to every parse result.
+pub(crate) fn apply_column_options(
+    parsed: ParsedCsv,
+    options: &ParseOptions,
+) -> ParsedCsv {
+    let parsed = if options.trims_uniformly() {
+        parsed
+    } else {
+        apply_column_trim(parsed, options)
+    };
+    if options.trim_trailing_empty_columns {
+        trim_trailing_empty_columns(parsed)
+    } else {
+        parsed
+    }
+}
+
+/// Trim each field according to its column's `TrimMode`. The reader leaves
+/// fields untouched in this case so whitespace can be preserved per column.
+fn apply_column_trim((headers, rows, header_map): ParsedCsv, options: &ParseOptions) -> ParsedCsv {
+    let modes: Vec<TrimMode> = headers
+        .iter()
+        .map(|h| options.column_trim.get(h).copied().unwrap_or(options.trim))
+        .collect();
+
+    let rows = rows
+        .iter()
+        .map(|r| {
+            rebuild_record(r, r.iter()
+                .enumerate()
+                .map(|(i, v)| modes.get(i).copied().unwrap_or(options.trim).apply(v)))
+        })
+        .collect();
+
+    (headers, rows, header_map)
+}
+
+/// Drop trailing columns whose header is blank (or auto-generated) and whose
+/// values are empty in every row.
+fn trim_trailing_empty_columns(
+    (mut headers, rows, mut header_map): ParsedCsv,
+) -> ParsedCsv {
+    let is_empty_column = |i: usize| {
+        let header = headers[i].trim();
+        (header.is_empty() || header == format!("Column{}", i + 1))
+            && rows.iter().all(|r| r.get(i).is_none_or(|v| v.is_empty()))
+    };
+
+    let mut keep = headers.len();
+    while keep > 0 && is_empty_column(keep - 1) {
+        keep -= 1;
+    }
+    if keep == headers.len() {
+        return (headers, rows, header_map);
+    }
+
+    headers.truncate(keep);
+    header_map.retain(|_, i| *i < keep);
+    let rows = rows
+        .iter()
+        .map(|r| rebuild_record(r, r.iter().take(keep)))
+        .collect();
+
+    (headers, rows, header_map)
+}
+
 /// Helper for parsing CSVs without headers in streaming fashion
 fn parse_csv_streaming_no_headers<F>(
     csv_content: &str,
-    chunk_size: usize,
+    options: &ParseOptions,
+    config: &StreamingConfig,
     mut on_progress: F,
-) -> Result<(Vec<String>, Vec<StringRecord>, AHashMap<String, usize>), Box<dyn std::error::Error>>
+) -> Result<ParsedCsv, Box<dyn std::error::Error>>
 where
     F: FnMut(f64, &str),
 {
-    on_progress(0.0, "Parsing as headerless CSV...");
+    emit(&mut on_progress, 0.0, Message::new(codes::PARSE_HEADERLESS));
     
-    let mut rdr = ReaderBuilder::new()
-        .has_headers(false)
-        .trim(csv::Trim::All)
+    let mut rdr = options.reader_builder(false)
         .from_reader(csv_content.as_bytes());
     
-    let total_rows = csv_content.lines().count();
-    let rows_processed = AtomicUsize::new(0);
+    let total_bytes = csv_content.len();
+    let chunk_size = config.chunk_size_for(csv_content);
+    let mut rows_processed = 0;
     
-    let mut all_rows = Vec::with_capacity(total_rows.min(100000));
+    let mut all_rows = Vec::new();
     let mut chunk = Vec::with_capacity(chunk_size);
     
     // Process first row to determine column count
     let mut col_count = 0;
-    for record_result in rdr.records() {
+    for record_result in records(&mut rdr, csv_content.as_bytes()) {
         let record = record_result?;
         if col_count == 0 {
             col_count = record.len();
         }
-        chunk.push(record);
-        
         if chunk.len() >= chunk_size {
-            all_rows.extend(chunk.drain(..));
-            let processed = rows_processed.fetch_add(chunk_size, Ordering::Relaxed);
-            let progress = (processed as f64 / total_rows as f64) * 95.0 + 5.0;
-            on_progress(progress, &format!("Processed {} rows", processed + chunk_size));
+            let progress = byte_fraction(&record, total_bytes) * 95.0 + 5.0;
+            rows_processed += chunk.len();
+            all_rows.append(&mut chunk);
+            if config.reports_after(rows_processed / chunk_size) {
+                emit(&mut on_progress, progress, Message::new(codes::PARSE_ROWS).with("count", rows_processed));
+            }
         }
+        chunk.push(record);
     }
     
     if !chunk.is_empty() {
@@ -232,9 +728,110 @@ where
             header_map.insert(h.clone(), i);
         }
         
-        on_progress(100.0, "Headerless CSV parsing complete");
+        emit(&mut on_progress, 100.0, Message::new(codes::PARSE_HEADERLESS_COMPLETE));
         Ok((auto_headers, all_rows, header_map))
     } else {
         Ok((vec![], vec![], AHashMap::new()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multi_character_delimiters() {
+        for delimiter in ["||", "~|~"] {
+            let csv = format!("id{d}name{d}note\n1{d}Alice{d}x\n2{d}Bob{d}y\n", d = delimiter);
+            let options = ParseOptions::new(true).with_delimiter(delimiter);
+            let (headers, rows, _) = parse_csv_with_options(&csv, &options).unwrap();
+
+            assert_eq!(headers, vec!["id", "name", "note"]);
+            assert_eq!(rows.len(), 2);
+            assert_eq!(&rows[1][1], "Bob");
+        }
+    }
+
+    #[test]
+    fn test_trim_trailing_empty_columns() {
+        let csv = "id,name,,\n1,Alice,,\n2,Bob,,\n";
+        let options = ParseOptions::new(true).with_trim_trailing_empty_columns(true);
+        let (headers, rows, header_map) = parse_csv_with_options(csv, &options).unwrap();
+
+        assert_eq!(headers, vec!["id", "name"]);
+        assert_eq!(rows[0].len(), 2);
+        assert!(!header_map.contains_key(""));
+
+        // A trailing column with values is kept even without a header
+        let csv = "id,name,\n1,Alice,x\n";
+        let (headers, _, _) = parse_csv_streaming_with_options(csv, &options, &StreamingConfig::new(10), |_, _| {}).unwrap();
+        assert_eq!(headers.len(), 3);
+    }
+
+    #[test]
+    fn test_per_column_trim() {
+        let csv = "code,name\n  A1 ,  Alice  \n";
+        let options = ParseOptions::new(true).with_column_trim("code", TrimMode::None);
+        let (_, rows, _) = parse_csv_with_options(csv, &options).unwrap();
+
+        assert_eq!(&rows[0][0], "  A1 ");
+        assert_eq!(&rows[0][1], "Alice");
+
+        let options = ParseOptions { trim: TrimMode::None, ..ParseOptions::new(true) }
+            .with_column_trim("name", TrimMode::End);
+        let (_, rows, _) = parse_csv_streaming_with_options(csv, &options, &StreamingConfig::new(10), |_, _| {}).unwrap();
+
+        assert_eq!(&rows[0][0], "  A1 ");
+        assert_eq!(&rows[0][1], "  Alice");
+    }
+
+    #[test]
+    fn test_multi_character_delimiter_inside_quotes() {
+        let csv = "id||note\n1||\"a||b\"\n";
+        let options = ParseOptions::new(true).with_delimiter("||");
+        let (_, rows, _) = parse_csv_streaming_with_options(csv, &options, &StreamingConfig::new(10), |_, _| {}).unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(&rows[0][1], "a||b");
+    }
+
+    #[test]
+    fn test_streaming_progress_with_quoted_newlines() {
+        let mut csv = String::from("id,note\n");
+        for i in 0..10 {
+            csv.push_str(&format!("{},\"line one\nline two\nline three\"\n", i));
+        }
+
+        let mut updates = Vec::new();
+        let (_, rows, _) = parse_csv_streaming(&csv, true, 5, |p, m| updates.push((p, m.to_string()))).unwrap();
+        assert_eq!(rows.len(), 10);
+
+        // Halfway through the rows is halfway through the bytes, not a third
+        // of the way as a line count would suggest
+        let (halfway, message) = &updates.iter().find(|(_, m)| m.starts_with("Processed")).unwrap();
+        assert_eq!(message, "Processed 5 rows");
+        assert!((45.0..=55.0).contains(halfway), "progress {}", halfway);
+        assert!(updates.windows(2).all(|w| w[0].0 <= w[1].0));
+    }
+
+    #[test]
+    fn test_detect_headers() {
+        let options = ParseOptions::default();
+        let detection = detect_headers("name,score,date\nAlice,3.5,2023-01-05\nBob,4.1,2023-02-01\n", &options).unwrap();
+        assert!(detection.has_headers && detection.confidence > 0.9);
+        assert_eq!(detection.evidence[0].signal, "typedColumns");
+        assert_eq!(detection.evidence[0].columns, vec![1, 2]);
+
+        // Decimal first rows slip past the `true` heuristic but not "auto"
+        let headerless = "Alice,3.5\nBob,4.1\n";
+        let detection = detect_headers(headerless, &options).unwrap();
+        assert!(!detection.has_headers);
+        assert_eq!(detection.evidence[0].signal, "numericHeader");
+        assert_eq!(parse_csv_with_options(headerless, &options).unwrap().0, vec!["Alice", "3.5"]);
+        let auto: ParseOptions = serde_json::from_str(r#"{"hasHeaders":"auto"}"#).unwrap();
+        assert_eq!(auto.has_headers, HeaderMode::Auto);
+        let (headers, rows, _) = parse_csv_with_options(headerless, &auto).unwrap();
+        assert_eq!((headers, rows.len()), (vec!["Column1".to_string(), "Column2".to_string()], 2));
+        assert!(serde_json::from_str::<ParseOptions>(r#"{"hasHeaders":"yes"}"#).is_err());
+    }
+}
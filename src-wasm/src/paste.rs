@@ -0,0 +1,169 @@
+//! Pasted tables.
+//!
+//! Converts GitHub-style Markdown tables and tab-separated clipboard content
+//! (as copied from spreadsheets) into CSV, so two pasted tables can be diffed
+//! without saving them to files first.
+
+use csv::{ReaderBuilder, WriterBuilder};
+use crate::options::DiffOptions;
+use crate::types::DiffResult;
+
+/// Layout of pasted table text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PastedFormat {
+    Markdown,
+    Tsv,
+}
+
+/// Markdown if the text starts with a header row followed by a `---`
+/// delimiter row, tab-separated otherwise.
+pub fn detect_pasted_format(text: &str) -> PastedFormat {
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+    match (lines.next(), lines.next()) {
+        (Some(_), Some(second)) if is_delimiter_row(second) => PastedFormat::Markdown,
+        _ => PastedFormat::Tsv,
+    }
+}
+
+/// Rows of a pasted table, header row first. Short rows are padded with
+/// empty cells so every row has the header's width.
+pub fn parse_pasted_table(text: &str) -> Result<Vec<Vec<String>>, Box<dyn std::error::Error>> {
+    let mut rows = match detect_pasted_format(text) {
+        PastedFormat::Markdown => parse_markdown(text),
+        PastedFormat::Tsv => parse_tsv(text)?,
+    };
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    if width == 0 {
+        return Err("Pasted table is empty.".into());
+    }
+    for row in &mut rows {
+        row.resize(width, String::new());
+    }
+    Ok(rows)
+}
+
+/// Pasted table text as comma-separated CSV.
+pub fn pasted_table_to_csv(text: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let mut writer = WriterBuilder::new().from_writer(Vec::new());
+    for row in parse_pasted_table(text)? {
+        writer.write_record(&row)?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// Diff two pasted tables with any engine. Both are converted to CSV, so the
+/// parse delimiter in `options` is ignored.
+pub fn diff_pasted_tables<F>(
+    source_text: &str,
+    target_text: &str,
+    options: &DiffOptions,
+    on_progress: F,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let source_csv = pasted_table_to_csv(source_text)?;
+    let target_csv = pasted_table_to_csv(target_text)?;
+    let mut options = options.clone();
+    options.parse.delimiter = ",".to_string();
+    crate::core::diff_with_options(&source_csv, &target_csv, &options, on_progress)
+}
+
+fn parse_markdown(text: &str) -> Vec<Vec<String>> {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .enumerate()
+        // The second line is the delimiter row
+        .filter(|&(i, _)| i != 1)
+        .map(|(_, line)| split_markdown_row(line))
+        .collect()
+}
+
+/// Cells of a Markdown table row; outer pipes are optional and `\|` is a
+/// literal pipe.
+fn split_markdown_row(line: &str) -> Vec<String> {
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = match line.strip_suffix('|') {
+        Some(rest) if !rest.ends_with('\\') => rest,
+        _ => line,
+    };
+
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                cell.push('|');
+                chars.next();
+            }
+            '|' => cells.push(std::mem::take(&mut cell).trim().to_string()),
+            c => cell.push(c),
+        }
+    }
+    cells.push(cell.trim().to_string());
+    cells
+}
+
+/// `| --- | :-: | --: |`: cells of dashes with optional alignment colons.
+fn is_delimiter_row(line: &str) -> bool {
+    let cells = split_markdown_row(line);
+    line.contains('|')
+        && cells.iter().all(|cell| {
+            let dashes = cell.strip_prefix(':').unwrap_or(cell);
+            let dashes = dashes.strip_suffix(':').unwrap_or(dashes);
+            !dashes.is_empty() && dashes.chars().all(|c| c == '-')
+        })
+}
+
+/// Spreadsheets quote cells containing tabs, newlines or quotes when copying,
+/// so the CSV reader with a tab delimiter handles clipboard content.
+fn parse_tsv(text: &str) -> Result<Vec<Vec<String>>, Box<dyn std::error::Error>> {
+    let mut reader = ReaderBuilder::new()
+        .delimiter(b'\t')
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        rows.push(record?.iter().map(str::to_string).collect());
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_markdown_table() {
+        let text = "\n| id | name  | note |\n|---:|:------|------|\n| 1  | Alice | a \\| b |\n| 2  | Bob |\n";
+        assert_eq!(detect_pasted_format(text), PastedFormat::Markdown);
+        let rows = parse_pasted_table(text).unwrap();
+        assert_eq!(rows, vec![
+            vec!["id", "name", "note"],
+            vec!["1", "Alice", "a | b"],
+            vec!["2", "Bob", ""],
+        ]);
+    }
+
+    #[test]
+    fn test_parse_clipboard_tsv() {
+        // Excel quotes cells with embedded newlines; trailing empty cells may be dropped
+        let text = "id\tname\tnote\r\n1\t\"Alice\nSmith\"\tx\r\n2\tBob\r\n";
+        assert_eq!(detect_pasted_format(text), PastedFormat::Tsv);
+        assert_eq!(pasted_table_to_csv(text).unwrap(), "id,name,note\n1,\"Alice\nSmith\",x\n2,Bob,\n");
+        assert!(parse_pasted_table("\n\n").is_err());
+    }
+
+    #[test]
+    fn test_diff_markdown_against_tsv() {
+        let source = "| id | name |\n| -- | -- |\n| 1 | Alice |\n| 2 | Bob |\n";
+        let target = "id\tname\n1\tAlice\n2\tRobert\n";
+        let options = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let result = diff_pasted_tables(source, target, &options, |_, _| {}).unwrap();
+        assert_eq!((result.unchanged.len(), result.modified.len()), (1, 1));
+        assert_eq!(result.modified[0].differences[0].new_value, "Robert");
+    }
+}
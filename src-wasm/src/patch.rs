@@ -0,0 +1,302 @@
+//! Changesets: a diff result as the edits that turn its source into its
+//! target, and the replay of those edits on a source file.
+//!
+//! A patch lists deletes, updates and inserts. Deletes and updates find
+//! their source row by the result's key columns or, for results without
+//! keys, by every value of the row; updates carry only the cells whose raw
+//! value changed. Applying a patch keeps the order of the source rows and
+//! appends inserted rows, so diffing the output against the target reports
+//! no changes in primary-key or content-match mode.
+//!
+//! Patches serialize as JSON or as a long-format CSV with one line per cell
+//! (`change,op,part,column,value`), which spreadsheets can review and
+//! `apply_patch` reads back as well.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::parse::{parse_csv_with_options, ParseOptions};
+use crate::types::DiffResult;
+
+/// Version of the patch layout, in both serializations.
+pub const PATCH_FORMAT_VERSION: u32 = 1;
+
+/// Edits turning a result's source file into its target file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Patch {
+    pub version: u32,
+    /// Headers of the patched file, in target order
+    pub headers: Vec<String>,
+    /// Columns whose values find a source row; empty matches whole rows
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub key_columns: Vec<String>,
+    pub changes: Vec<Change>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeOp {
+    Delete,
+    Update,
+    Insert,
+}
+
+impl ChangeOp {
+    const ALL: [ChangeOp; 3] = [ChangeOp::Delete, ChangeOp::Update, ChangeOp::Insert];
+
+    fn name(self) -> &'static str {
+        match self {
+            ChangeOp::Delete => "delete",
+            ChangeOp::Update => "update",
+            ChangeOp::Insert => "insert",
+        }
+    }
+}
+
+/// One edit: the source row it `match`es (deletes and updates) and the
+/// values it sets (updates and inserts).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Change {
+    pub op: ChangeOp,
+    #[serde(rename = "match", default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub matches: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub set: BTreeMap<String, String>,
+}
+
+/// Serialization of a patch.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PatchFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+/// The patch of a diff result: removed rows are deleted, modified rows
+/// updated and added rows inserted, in that order.
+pub fn build_patch(result: &DiffResult) -> Patch {
+    let key_columns = result.key_columns.clone();
+    let matches = |row: &HashMap<String, String>| -> BTreeMap<String, String> {
+        row.iter()
+            .filter(|(column, _)| key_columns.is_empty() || key_columns.contains(column))
+            .map(|(column, value)| (column.clone(), value.clone()))
+            .collect()
+    };
+
+    let deletes = result.removed.iter().map(|row| Change {
+        op: ChangeOp::Delete,
+        matches: matches(&row.source_row),
+        set: BTreeMap::new(),
+    });
+    let updates = result.modified.iter().map(|row| Change {
+        op: ChangeOp::Update,
+        matches: matches(&row.source_row),
+        set: row.target_row.iter()
+            .filter(|&(column, value)| row.source_row.get(column) != Some(value))
+            .map(|(column, value)| (column.clone(), value.clone()))
+            .collect(),
+    });
+    let inserts = result.added.iter().map(|row| Change {
+        op: ChangeOp::Insert,
+        matches: BTreeMap::new(),
+        set: row.target_row.iter().map(|(column, value)| (column.clone(), value.clone())).collect(),
+    });
+
+    Patch {
+        version: PATCH_FORMAT_VERSION,
+        headers: result.target.headers.clone(),
+        changes: deletes.chain(updates).chain(inserts).collect(),
+        key_columns,
+    }
+}
+
+/// Serialize a patch in `format`.
+pub fn write_patch(patch: &Patch, format: PatchFormat) -> Result<String, Box<dyn std::error::Error>> {
+    match format {
+        PatchFormat::Json => Ok(serde_json::to_string(patch)?),
+        PatchFormat::Csv => write_patch_csv(patch),
+    }
+}
+
+/// Read a patch written by `write_patch` in either format.
+pub fn read_patch(text: &str) -> Result<Patch, Box<dyn std::error::Error>> {
+    let patch: Patch = if text.trim_start().starts_with('{') {
+        serde_json::from_str(text)?
+    } else {
+        read_patch_csv(text)?
+    };
+    if patch.version > PATCH_FORMAT_VERSION {
+        return Err(format!(
+            "Patch uses format version {}; this release reads up to {}.",
+            patch.version, PATCH_FORMAT_VERSION
+        ).into());
+    }
+    Ok(patch)
+}
+
+/// Header line plus metadata lines (empty `change`) for the version,
+/// headers and key columns, then one line per cell of each change,
+/// numbered from 1.
+fn write_patch_csv(patch: &Patch) -> Result<String, Box<dyn std::error::Error>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["change", "op", "part", "column", "value"])?;
+    writer.write_record(["", "", "version", "", &patch.version.to_string()])?;
+    for header in &patch.headers {
+        writer.write_record(["", "", "header", header, ""])?;
+    }
+    for column in &patch.key_columns {
+        writer.write_record(["", "", "key", column, ""])?;
+    }
+    for (n, change) in patch.changes.iter().enumerate() {
+        let number = (n + 1).to_string();
+        let parts = [("match", &change.matches), ("set", &change.set)];
+        let mut cells = parts.iter().flat_map(|&(part, cells)| cells.iter().map(move |(column, value)| (part, column, value))).peekable();
+        // Keep changes without cells, e.g. an insert of an all-empty row
+        if cells.peek().is_none() {
+            writer.write_record([number.as_str(), change.op.name(), "", "", ""])?;
+        }
+        for (part, column, value) in cells {
+            writer.write_record([number.as_str(), change.op.name(), part, column, value])?;
+        }
+    }
+    let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+fn read_patch_csv(text: &str) -> Result<Patch, Box<dyn std::error::Error>> {
+    let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(text.as_bytes());
+    let mut patch = Patch { version: 0, headers: Vec::new(), key_columns: Vec::new(), changes: Vec::new() };
+    let mut last_number = String::new();
+    for record in reader.records() {
+        let record = record?;
+        let field = |i: usize| record.get(i).unwrap_or("");
+        let (number, op, part, column, value) = (field(0), field(1), field(2), field(3), field(4));
+        if number.is_empty() {
+            match part {
+                "version" => patch.version = value.parse()?,
+                "header" => patch.headers.push(column.to_string()),
+                "key" => patch.key_columns.push(column.to_string()),
+                _ => return Err(format!("Unknown patch line \"{}\".", part).into()),
+            }
+            continue;
+        }
+
+        if number != last_number {
+            let op = *ChangeOp::ALL.iter().find(|known| known.name() == op)
+                .ok_or_else(|| format!("Unknown patch operation \"{}\".", op))?;
+            patch.changes.push(Change { op, matches: BTreeMap::new(), set: BTreeMap::new() });
+            last_number = number.to_string();
+        }
+        let change = patch.changes.last_mut().unwrap();
+        match part {
+            "match" => change.matches.insert(column.to_string(), value.to_string()),
+            "set" => change.set.insert(column.to_string(), value.to_string()),
+            "" => None,
+            _ => return Err(format!("Unknown patch line \"{}\".", part).into()),
+        };
+    }
+    Ok(patch)
+}
+
+/// Apply `patch` to `source_csv`, parsed with `parse` as in the diff that
+/// produced it, and write the patched file as CSV with the same delimiter.
+/// Each delete or update takes the first source row it matches that no
+/// earlier change took; a change matching no row fails.
+pub fn apply_patch(source_csv: &str, patch: &Patch, parse: &ParseOptions) -> Result<String, Box<dyn std::error::Error>> {
+    let delimiter = match parse.delimiter.as_bytes() {
+        [byte] => *byte,
+        _ => return Err("Patched CSV supports single-byte delimiters only.".into()),
+    };
+    let (source_headers, source_rows, source_header_map) = parse_csv_with_options(source_csv, parse)?;
+
+    // Source rows as edited so far; `None` once deleted
+    let mut rows: Vec<Option<HashMap<&str, &str>>> = source_rows.iter()
+        .map(|row| Some(source_headers.iter().map(String::as_str).zip(row.iter()).collect()))
+        .collect();
+    let mut taken = vec![false; rows.len()];
+    let mut inserted = Vec::new();
+
+    for (n, change) in patch.changes.iter().enumerate() {
+        if change.op == ChangeOp::Insert {
+            inserted.push(change.set.iter().map(|(column, value)| (column.as_str(), value.as_str())).collect());
+            continue;
+        }
+
+        let found = source_rows.iter().enumerate().position(|(idx, row)| {
+            !taken[idx] && change.matches.iter().all(|(column, value)| {
+                let cell = source_header_map.get(column).and_then(|&i| row.get(i));
+                cell.unwrap_or("") == value
+            })
+        });
+        let Some(idx) = found else {
+            return Err(format!("Patch change {} ({}) matches no source row.", n + 1, change.op.name()).into());
+        };
+        taken[idx] = true;
+        match change.op {
+            ChangeOp::Delete => rows[idx] = None,
+            _ => {
+                let row = rows[idx].as_mut().unwrap();
+                for (column, value) in &change.set {
+                    row.insert(column, value);
+                }
+            }
+        }
+    }
+
+    let mut writer = csv::WriterBuilder::new().delimiter(delimiter).from_writer(Vec::new());
+    writer.write_record(&patch.headers)?;
+    for row in rows.iter().flatten().chain(&inserted) {
+        writer.write_record(patch.headers.iter().map(|h| row.get(h.as_str()).copied().unwrap_or("")))?;
+    }
+    let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::DiffOptions;
+
+    #[test]
+    fn test_patch_round_trip() {
+        let source = "id,name,note\n1,Alice,plain\n2,Bob,\"has, comma\"\n3,Carol,x\n";
+        let target = "id,name,note\n1,Alicia,plain\n3,Carol,x\n4,Dan,\"two\nlines\"\n5,,\n";
+        for options in [
+            DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() },
+            DiffOptions { mode: "content-match".to_string(), ..Default::default() },
+        ] {
+            let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            let patch = build_patch(&result);
+            let update = patch.changes.iter().find(|c| c.op == ChangeOp::Update).unwrap();
+            assert_eq!(update.set.keys().collect::<Vec<_>>(), ["name"]);
+
+            for format in [PatchFormat::Json, PatchFormat::Csv] {
+                let text = write_patch(&patch, format).unwrap();
+                assert_eq!(read_patch(&text).unwrap(), patch, "{:?}", format);
+            }
+
+            let patched = apply_patch(source, &patch, &options.parse).unwrap();
+            let recheck = crate::core::diff_with_options(&patched, target, &options, |_, _| {}).unwrap();
+            assert_eq!((recheck.added.len(), recheck.removed.len(), recheck.modified.len()), (0, 0, 0), "{}", patched);
+        }
+    }
+
+    #[test]
+    fn test_apply_patch_rejects_unmatched_changes() {
+        let patch = Patch {
+            version: PATCH_FORMAT_VERSION,
+            headers: vec!["id".to_string()],
+            key_columns: vec!["id".to_string()],
+            changes: vec![Change {
+                op: ChangeOp::Delete,
+                matches: BTreeMap::from([("id".to_string(), "9".to_string())]),
+                set: BTreeMap::new(),
+            }],
+        };
+        let error = apply_patch("id\n1\n", &patch, &ParseOptions::default()).unwrap_err();
+        assert_eq!(error.to_string(), "Patch change 1 (delete) matches no source row.");
+        assert!(read_patch(r#"{"version":9,"headers":[],"changes":[]}"#).is_err());
+    }
+}
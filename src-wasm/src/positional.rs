@@ -0,0 +1,365 @@
+//! Order-based diffs, for files whose row order is meaningful.
+//!
+//! Positional mode compares row N of the source against row N of the target:
+//! for machine-generated files that are guaranteed to be in the same order
+//! there is nothing to match, so it skips keys and fingerprints entirely.
+//! Rows past the end of the shorter file are added or removed.
+//!
+//! Sequence mode treats the row fingerprints of each file as a sequence and
+//! runs a Myers or patience diff over them, like `diff` on lines (logs,
+//! config exports). Inserted or deleted rows no longer shift every later row
+//! into a modification.
+
+use crate::types::*;
+use crate::summary::change_kind;
+use crate::utils::*;
+use crate::options::{DiffOptions, SequenceAlgorithm};
+use crate::normalize::Normalizer;
+use crate::messages::{codes, emit, progress_sink, Message};
+use crate::parse::{parse_pair_streaming, ParsedCsv};
+use crate::mapping::{map_target_columns, resolve_excluded_columns, schema_changes};
+use ahash::AHashSet;
+use csv::StringRecord;
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+/// Positional diff driven by a full `DiffOptions` set. Always sequential;
+/// `use_parallel` is ignored.
+pub fn diff_positional_with_options<F>(
+    source_csv: &str,
+    target_csv: &str,
+    options: &DiffOptions,
+    on_progress: F,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let mut on_progress = progress_sink(options.parse.message_format, on_progress);
+    let (source, target) = parse_pair_streaming(source_csv, target_csv, &options.parse, &options.streaming, &mut on_progress)?;
+    compare_positional(&source, &target, options, &mut on_progress)
+}
+
+/// Positional comparison of already parsed tables; progress picks up at 20%,
+/// where parsing ends. `on_progress` must already be a progress sink.
+pub(crate) fn compare_positional<F>(
+    (source_headers, source_rows, source_header_map): &ParsedCsv,
+    (target_headers, target_rows, target_header_map): &ParsedCsv,
+    options: &DiffOptions,
+    on_progress: &mut F,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let normalizer = Normalizer::new(options)?;
+    let (mut target_headers, mut target_header_map) = (target_headers.clone(), target_header_map.clone());
+
+    let renamed_columns = map_target_columns(
+        (source_headers, source_rows),
+        (&mut target_headers, &mut target_header_map, target_rows),
+        options,
+    )?;
+    let schema_changes = schema_changes(source_headers, &target_headers, options, &renamed_columns);
+    let (excluded_columns, warnings) = resolve_excluded_columns(source_headers, &target_headers, options)?;
+    let columns = compared_columns(source_headers, source_header_map, &target_header_map, &excluded_columns);
+
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let mut modified = Vec::new();
+    let mut unchanged = UnchangedRows::new(options.include_unchanged);
+
+    emit(on_progress, 20.0, Message::new(codes::COMPARE_ROWS));
+    let total = source_rows.len().max(target_rows.len());
+    let rows = RowPair { source_headers, target_headers: &target_headers, columns: &columns, normalizer: &normalizer };
+    for i in 0..total {
+        if i % 1000 == 0 && options.streaming.reports_after(i / 1000) {
+            let p = 20.0 + (i as f64 / total as f64) * 75.0;
+            emit(on_progress, p, Message::new(codes::COMPARE_ROWS_PROGRESS).with("processed", i).with("total", total));
+        }
+
+        let key = format!("Row {}", i + 1);
+        match (source_rows.get(i), target_rows.get(i)) {
+            (Some(source_row), Some(target_row)) => rows.compare(key, (i, source_row), (i, target_row), &mut modified, &mut unchanged),
+            (Some(source_row), None) => removed.push(RemovedRow {
+                key,
+                source_row: record_to_hashmap(source_row, source_headers),
+                source_line: record_line(source_row),
+                source_index: Some(i),
+            }),
+            (None, Some(target_row)) => added.push(AddedRow {
+                key,
+                target_row: record_to_hashmap(target_row, &target_headers),
+                target_line: record_line(target_row),
+                target_index: Some(i),
+            }),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    emit(on_progress, 100.0, Message::new(codes::COMPLETE));
+
+    Ok(DiffResult {
+        added,
+        removed,
+        modified,
+        unchanged: unchanged.rows,
+        source: DatasetMetadata {
+            headers: source_headers.clone(),
+            rows: dataset_rows(source_rows, source_headers, options.include_dataset_rows),
+        },
+        target: DatasetMetadata {
+            headers: target_headers.clone(),
+            rows: dataset_rows(target_rows, &target_headers, options.include_dataset_rows),
+        },
+        key_columns: Vec::new(),
+        excluded_columns,
+        mode: "positional".to_string(),
+        summary: DiffSummary::default(),
+        warnings,
+        seed: options.seed,
+        fallback: None,
+        renamed_columns,
+        schema_changes,
+        reversed: false,
+        header_policy: options.header_policy,
+        moved_rows: Vec::new(),
+        duplicate_counts: Vec::new(),
+        omitted_unchanged: unchanged.omitted,
+    }.with_summary().ordered(options.row_order))
+}
+
+/// Sequence diff driven by a full `DiffOptions` set: rows match when their
+/// compared columns are equal after normalization, and the longest run of
+/// matching rows in file order is kept, found with
+/// `options.sequence_algorithm`. Within a changed stretch, source and target
+/// rows pair up in order as modified rows and the leftovers are removed or
+/// added. Keys are "Row N" (source numbering; target numbering for added
+/// rows), or "Row N → M" when a pair sits at different positions.
+pub fn diff_sequence_with_options<F>(
+    source_csv: &str,
+    target_csv: &str,
+    options: &DiffOptions,
+    on_progress: F,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let mut on_progress = progress_sink(options.parse.message_format, on_progress);
+    let (source, target) = parse_pair_streaming(source_csv, target_csv, &options.parse, &options.streaming, &mut on_progress)?;
+    compare_sequence(&source, &target, options, &mut on_progress)
+}
+
+/// Sequence comparison of already parsed tables; progress picks up at 20%,
+/// where parsing ends. `on_progress` must already be a progress sink.
+pub(crate) fn compare_sequence<F>(
+    (source_headers, source_rows, source_header_map): &ParsedCsv,
+    (target_headers, target_rows, target_header_map): &ParsedCsv,
+    options: &DiffOptions,
+    on_progress: &mut F,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let normalizer = Normalizer::new(options)?;
+    let (mut target_headers, mut target_header_map) = (target_headers.clone(), target_header_map.clone());
+
+    let renamed_columns = map_target_columns(
+        (source_headers, source_rows),
+        (&mut target_headers, &mut target_header_map, target_rows),
+        options,
+    )?;
+    let schema_changes = schema_changes(source_headers, &target_headers, options, &renamed_columns);
+    let (excluded_columns, warnings) = resolve_excluded_columns(source_headers, &target_headers, options)?;
+    let columns = compared_columns(source_headers, source_header_map, &target_header_map, &excluded_columns);
+
+    emit(on_progress, 20.0, Message::new(codes::BUILD_FINGERPRINT_INDEX));
+    let compared: Vec<String> = columns.iter().map(|&(header, _, _)| header.clone()).collect();
+    let no_exclusions = AHashSet::new();
+    let fingerprints = |rows: &[StringRecord], header_map| -> Vec<u64> {
+        rows.iter()
+            .map(|row| get_row_fingerprint_hash(row, &compared, header_map, &normalizer, &no_exclusions))
+            .collect()
+    };
+    let source_fingerprints = fingerprints(source_rows, source_header_map);
+    let target_fingerprints = fingerprints(target_rows, &target_header_map);
+
+    emit(on_progress, 40.0, Message::new(codes::COMPARE_ROWS));
+    let algorithm = match options.sequence_algorithm {
+        SequenceAlgorithm::Myers => Algorithm::Myers,
+        SequenceAlgorithm::Patience => Algorithm::Patience,
+    };
+    let ops = capture_diff_slices(algorithm, &source_fingerprints, &target_fingerprints);
+
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let mut modified = Vec::new();
+    let mut unchanged = UnchangedRows::new(options.include_unchanged);
+    let rows = RowPair { source_headers, target_headers: &target_headers, columns: &columns, normalizer: &normalizer };
+    let pair_key = |s: usize, t: usize| if s == t { format!("Row {}", s + 1) } else { format!("Row {} → {}", s + 1, t + 1) };
+
+    for op in ops {
+        let (source_range, target_range) = (op.old_range(), op.new_range());
+        // Equal runs pair up entirely; a fingerprint collision still shows as a modification
+        let paired = match op {
+            DiffOp::Equal { .. } | DiffOp::Replace { .. } => source_range.len().min(target_range.len()),
+            DiffOp::Delete { .. } | DiffOp::Insert { .. } => 0,
+        };
+        for i in 0..paired {
+            let (s, t) = (source_range.start + i, target_range.start + i);
+            rows.compare(pair_key(s, t), (s, &source_rows[s]), (t, &target_rows[t]), &mut modified, &mut unchanged);
+        }
+        let first = source_range.start + paired;
+        for (s, row) in (first..).zip(&source_rows[first..source_range.end]) {
+            removed.push(RemovedRow {
+                key: format!("Row {}", s + 1),
+                source_row: record_to_hashmap(row, source_headers),
+                source_line: record_line(row),
+                source_index: Some(s),
+            });
+        }
+        let first = target_range.start + paired;
+        for (t, row) in (first..).zip(&target_rows[first..target_range.end]) {
+            added.push(AddedRow {
+                key: format!("Row {}", t + 1),
+                target_row: record_to_hashmap(row, &target_headers),
+                target_line: record_line(row),
+                target_index: Some(t),
+            });
+        }
+    }
+
+    emit(on_progress, 100.0, Message::new(codes::COMPLETE));
+
+    Ok(DiffResult {
+        added,
+        removed,
+        modified,
+        unchanged: unchanged.rows,
+        source: DatasetMetadata {
+            headers: source_headers.clone(),
+            rows: dataset_rows(source_rows, source_headers, options.include_dataset_rows),
+        },
+        target: DatasetMetadata {
+            headers: target_headers.clone(),
+            rows: dataset_rows(target_rows, &target_headers, options.include_dataset_rows),
+        },
+        key_columns: Vec::new(),
+        excluded_columns,
+        mode: "sequence".to_string(),
+        summary: DiffSummary::default(),
+        warnings,
+        seed: options.seed,
+        fallback: None,
+        renamed_columns,
+        schema_changes,
+        reversed: false,
+        header_policy: options.header_policy,
+        moved_rows: Vec::new(),
+        duplicate_counts: Vec::new(),
+        omitted_unchanged: unchanged.omitted,
+    }.with_summary().ordered(options.row_order))
+}
+
+/// Cell-by-cell comparison of two rows already matched by position.
+struct RowPair<'a> {
+    source_headers: &'a [String],
+    target_headers: &'a [String],
+    columns: &'a [ColumnPair<'a>],
+    normalizer: &'a Normalizer,
+}
+
+impl RowPair<'_> {
+    fn compare(
+        &self,
+        key: String,
+        (source_index, source_row): (usize, &StringRecord),
+        (target_index, target_row): (usize, &StringRecord),
+        modified: &mut Vec<ModifiedRow>,
+        unchanged: &mut UnchangedRows,
+    ) {
+        let mut differences = Vec::new();
+        for &(header, source_idx, target_idx) in self.columns {
+            let source_val = source_row.get(source_idx).unwrap_or("");
+            let target_val = target_row.get(target_idx).unwrap_or("");
+            if self.normalizer.normalize(header, source_val) != self.normalizer.normalize(header, target_val) {
+                differences.push(Difference {
+                    column: header.clone(),
+                    old_value: source_val.to_string(),
+                    new_value: target_val.to_string(),
+                    change_kind: change_kind(source_val, target_val),
+                    diff: self.normalizer.diff_values(header, source_val, target_val),
+                });
+            }
+        }
+
+        if differences.is_empty() {
+            unchanged.push_with(|| UnchangedRow {
+                key,
+                row: record_to_hashmap(source_row, self.source_headers),
+                source_index: Some(source_index),
+                target_index: Some(target_index),
+            });
+        } else {
+            modified.push(ModifiedRow {
+                key,
+                source_row: record_to_hashmap(source_row, self.source_headers),
+                source_line: record_line(source_row),
+                target_row: record_to_hashmap(target_row, self.target_headers),
+                target_line: record_line(target_row),
+                source_index: Some(source_index),
+                target_index: Some(target_index),
+                match_score: None,
+                target_key: None,
+                differences,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_positional_diff() {
+        let source = "id,name\n1,Alice\n2,Bob\n3,Carol\n";
+        let target = "id,name\n1,Alice\n2,Robert\n";
+        let options = DiffOptions { mode: "positional".to_string(), ..Default::default() };
+        let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+
+        assert_eq!(result.mode, "positional");
+        assert_eq!((result.unchanged.len(), result.modified.len(), result.removed.len(), result.added.len()), (1, 1, 1, 0));
+        assert_eq!(result.modified[0].key, "Row 2");
+        assert_eq!(result.modified[0].differences[0].new_value, "Robert");
+        assert_eq!(result.removed[0].key, "Row 3");
+
+        // Reordered rows are not matched up
+        let swapped = "id,name\n2,Bob\n1,Alice\n3,Carol\n";
+        let result = crate::core::diff_with_options(source, swapped, &options, |_, _| {}).unwrap();
+        assert_eq!((result.unchanged.len(), result.modified.len()), (1, 2));
+    }
+
+    #[test]
+    fn test_sequence_diff() {
+        let source = "ts,event\n1,start\n2,load\n3,run\n4,stop\n";
+        let target = "ts,event\n1,start\n1,init\n2,load\n3,RUN\n4,stop\n";
+        for algorithm in ["myers", "patience"] {
+            let options: DiffOptions = serde_json::from_value(serde_json::json!({
+                "mode": "sequence",
+                "sequenceAlgorithm": algorithm,
+            })).unwrap();
+            let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+
+            assert_eq!(result.mode, "sequence");
+            assert_eq!((result.unchanged.len(), result.modified.len(), result.removed.len(), result.added.len()), (3, 1, 0, 1), "{}", algorithm);
+            assert_eq!(result.added[0].key, "Row 2");
+            assert_eq!(result.modified[0].key, "Row 3 → 4");
+            assert_eq!(result.modified[0].differences[0].new_value, "RUN");
+            assert_eq!(result.unchanged.last().unwrap().key, "Row 4 → 5");
+        }
+
+        // Swapped rows are one removal and one addition, not two modifications
+        let swapped = "ts,event\n2,load\n1,start\n3,run\n4,stop\n";
+        let options = DiffOptions { mode: "sequence".to_string(), ..Default::default() };
+        let result = crate::core::diff_with_options(source, swapped, &options, |_, _| {}).unwrap();
+        assert_eq!((result.unchanged.len(), result.removed.len(), result.added.len()), (3, 1, 1));
+    }
+}
@@ -1,7 +1,16 @@
 use crate::types::*;
+use crate::summary::change_kind;
 use crate::utils::*;
-use super::parse::parse_csv_streaming;
+use crate::options::{DiffOptions, DuplicateKeys};
+use crate::normalize::Normalizer;
+use crate::similarity::{CellScorer, WeightedScore};
+use crate::messages::{codes, emit, progress_sink, render, Message};
+use super::parse::{parse_pair_streaming, ParsedCsv};
+use crate::mapping::{map_target_columns, resolve_excluded_columns, schema_changes};
 use ahash::{AHashMap};
+use std::collections::HashMap;
+use std::hash::Hash;
+use csv::StringRecord;
 
 pub fn diff_csv_primary_key_internal<F>(
     source_csv: &str,
@@ -12,66 +21,107 @@ pub fn diff_csv_primary_key_internal<F>(
     ignore_empty_vs_null: bool,
     excluded_columns: Vec<String>,
     has_headers: bool,
-    mut on_progress: F,
+    on_progress: F,
 ) -> Result<DiffResult, Box<dyn std::error::Error>>
 where
     F: FnMut(f64, &str),
 {
-    // Use streaming parser for better memory efficiency and progress reporting
-    let (source_headers, source_rows, source_header_map) = parse_csv_streaming(
-        source_csv, 
-        has_headers, 
-        5000,
-        |percent, message| {
-            on_progress(percent * 0.1, &format!("Source: {}", message)); // Scale to 0-10%
-        }
-    )?;
+    let options = DiffOptions::from_flags(
+        "primary-key",
+        key_columns,
+        case_sensitive,
+        ignore_whitespace,
+        ignore_empty_vs_null,
+        excluded_columns,
+        has_headers,
+    );
+    diff_primary_key_with_options(source_csv, target_csv, &options, on_progress)
+}
 
-    let (target_headers, target_rows, target_header_map) = parse_csv_streaming(
-        target_csv, 
-        has_headers, 
-        5000,
-        |percent, message| {
-            on_progress(10.0 + percent * 0.1, &format!("Target: {}", message)); // Scale to 10-20%
-        }
+/// Primary-key diff driven by a full `DiffOptions` set
+pub fn diff_primary_key_with_options<F>(
+    source_csv: &str,
+    target_csv: &str,
+    options: &DiffOptions,
+    on_progress: F,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let mut on_progress = progress_sink(options.parse.message_format, on_progress);
+
+    let (source, target) = parse_pair_streaming(source_csv, target_csv, &options.parse, &options.streaming, &mut on_progress)?;
+    compare_parsed(&source, &target, options, &mut on_progress)
+}
+
+/// Primary-key comparison of already parsed tables; progress picks up at
+/// 20%, where parsing ends. `on_progress` must already be a progress sink.
+/// The source is borrowed so one parse can serve several targets.
+pub(crate) fn compare_parsed<F>(
+    (source_headers, source_rows, source_header_map): &ParsedCsv,
+    (target_headers, target_rows, target_header_map): &ParsedCsv,
+    options: &DiffOptions,
+    on_progress: &mut F,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let key_columns = options.key_columns.clone();
+    let normalizer = Normalizer::new(options)?;
+    let (mut target_headers, mut target_header_map) = (target_headers.clone(), target_header_map.clone());
+
+    let renamed_columns = map_target_columns(
+        (source_headers, source_rows),
+        (&mut target_headers, &mut target_header_map, target_rows),
+        options,
     )?;
+    let schema_changes = schema_changes(source_headers, &target_headers, options, &renamed_columns);
+    let (excluded_columns, mut warnings) = resolve_excluded_columns(source_headers, &target_headers, options)?;
 
     // Validation of key columns
     for key in &key_columns {
         if !source_header_map.contains_key(key) {
-             return Err(format!("Primary key column \"{}\" not found in source dataset.", key).into());
+             return Err(Message::new(codes::KEY_COLUMN_MISSING).with("column", key).with("side", "source").into());
         }
         if !target_header_map.contains_key(key) {
-             return Err(format!("Primary key column \"{}\" not found in target dataset.", key).into());
+             return Err(Message::new(codes::KEY_COLUMN_MISSING).with("column", key).with("side", "target").into());
         }
     }
 
-    on_progress(20.0, "Building source map...");
-    let mut source_map: AHashMap<String, usize> = AHashMap::new();
-    for (i, row) in source_rows.iter().enumerate() {
-        let key = get_row_key(row, &source_header_map, &key_columns);
-        if source_map.contains_key(&key) {
-             return Err(format!("Duplicate Primary Key found in source: \"{}\". Primary Keys must be unique.", key).into());
-        }
-        source_map.insert(key, i);
-    }
+    let compared = compared_columns(source_headers, source_header_map, &target_header_map, &excluded_columns);
 
-    on_progress(40.0, "Building target map...");
-    let mut target_map: AHashMap<String, usize> = AHashMap::new();
-    for (i, row) in target_rows.iter().enumerate() {
-        let key = get_row_key(row, &target_header_map, &key_columns);
-        if target_map.contains_key(&key) {
-             return Err(format!("Duplicate Primary Key found in target: \"{}\". Primary Keys must be unique.", key).into());
-        }
-        target_map.insert(key, i);
-    }
+    // Checksum the compared cells of every row alongside its key
+    emit(on_progress, 20.0, Message::new(codes::BUILD_SOURCE_MAP));
+    let source_keys = source_rows.iter().map(|row| get_row_key(row, source_header_map, &key_columns, &options.key_normalization));
+    let (mut source_map, source_duplicates) = index_keys(source_keys, "source", options, &mut warnings)?;
+    let source_crcs: Vec<u32> = source_rows.iter()
+        .map(|row| row_crc(row, compared.iter().map(|&(h, s, _)| (h, s)), &normalizer))
+        .collect();
+
+    emit(on_progress, 40.0, Message::new(codes::BUILD_TARGET_MAP));
+    let target_keys = target_rows.iter().map(|row| get_row_key(row, &target_header_map, &key_columns, &options.key_normalization));
+    let (mut target_map, target_duplicates) = index_keys(target_keys, "target", options, &mut warnings)?;
+    let target_crcs: Vec<u32> = target_rows.iter()
+        .map(|row| row_crc(row, compared.iter().map(|&(h, _, t)| (h, t)), &normalizer))
+        .collect();
 
     let mut added = Vec::new();
     let mut removed = Vec::new();
     let mut modified = Vec::new();
-    let mut unchanged = Vec::new();
+    let mut unchanged = UnchangedRows::new(options.include_unchanged);
 
-    on_progress(60.0, "Comparing rows...");
+    // Repeated keys are compared group by group and leave the key maps
+    diff_key_groups(
+        (&mut source_map, source_duplicates, source_rows, source_headers, &source_crcs),
+        (&mut target_map, target_duplicates, target_rows, &target_headers, &target_crcs),
+        &compared,
+        &normalizer,
+        true,
+        (&mut added, &mut removed, &mut modified, &mut unchanged),
+    );
+    let moved_rows = if options.report_order_changes { moved_rows(&source_map, &target_map) } else { Vec::new() };
+
+    emit(on_progress, 60.0, Message::new(codes::COMPARE_ROWS));
 
     // Find removed
     for (key, &row_idx) in &source_map {
@@ -79,16 +129,25 @@ where
             removed.push(RemovedRow {
                 key: key.clone(),
                 source_row: record_to_hashmap(&source_rows[row_idx], &source_headers),
+                source_line: record_line(&source_rows[row_idx]),
+                source_index: Some(row_idx),
             });
         }
     }
 
+    // Skip columns that agree on every keyed pair before comparing cell by cell
+    let columns = changed_columns(
+        compared.clone(),
+        target_map.iter().filter_map(|(key, &t)| Some((&source_rows[*source_map.get(key)?], &target_rows[t]))),
+        &normalizer,
+    );
+
     // Find added and modified
     let total_target = target_map.len();
     for (i, (key, &target_row_idx)) in target_map.iter().enumerate() {
-        if i % 1000 == 0 {
+        if i % 1000 == 0 && options.streaming.reports_after(i / 1000) {
              let p = 60.0 + (i as f64 / total_target as f64) * 30.0;
-             on_progress(p, "Comparing rows...");
+             emit(on_progress, p, Message::new(codes::COMPARE_ROWS));
         }
 
         let target_row = &target_rows[target_row_idx];
@@ -98,46 +157,31 @@ where
                 added.push(AddedRow {
                     key: key.clone(),
                     target_row: record_to_hashmap(target_row, &target_headers),
+                    target_line: record_line(target_row),
+                    target_index: Some(target_row_idx),
                 });
             }
             Some(&source_row_idx) => {
                 let source_row = &source_rows[source_row_idx];
                 let mut differences = Vec::new();
                 
-                for header in &source_headers {
-                    if excluded_columns.contains(header) {
-                        continue;
-                    }
-                    
-                    let source_idx = source_header_map.get(header).unwrap();
-                    let target_idx = match target_header_map.get(header) {
-                        Some(idx) => idx,
-                        None => continue, 
-                    };
-
-                    let source_val_raw = source_row.get(*source_idx).unwrap_or("");
-                    let target_val_raw = target_row.get(*target_idx).unwrap_or("");
-
-                    let source_val = normalize_value_with_empty_vs_null(
-                        source_val_raw,
-                        case_sensitive,
-                        ignore_whitespace,
-                        ignore_empty_vs_null
-                    );
-                    let target_val = normalize_value_with_empty_vs_null(
-                        target_val_raw,
-                        case_sensitive,
-                        ignore_whitespace,
-                        ignore_empty_vs_null
-                    );
+                // Matching checksums: the row is unchanged, skip the cells
+                let columns = if source_crcs[source_row_idx] == target_crcs[target_row_idx] { &[][..] } else { &columns[..] };
+                for &(header, source_idx, target_idx) in columns {
+                    let source_val_raw = source_row.get(source_idx).unwrap_or("");
+                    let target_val_raw = target_row.get(target_idx).unwrap_or("");
+
+                    let source_val = normalizer.normalize(header, source_val_raw);
+                    let target_val = normalizer.normalize(header, target_val_raw);
 
                     if source_val != target_val {
-                        let diffs = crate::core::diff_text_internal(source_val_raw, target_val_raw, case_sensitive);
+                        let diffs = normalizer.diff_values(header, source_val_raw, target_val_raw);
 
                         differences.push(Difference {
                             column: header.clone(),
                             old_value: source_val_raw.to_string(),
                             new_value: target_val_raw.to_string(),
+                            change_kind: change_kind(source_val_raw, target_val_raw),
                             diff: diffs,
                         });
                     }
@@ -147,36 +191,588 @@ where
                     modified.push(ModifiedRow {
                         key: key.clone(),
                         source_row: record_to_hashmap(source_row, &source_headers),
+                        source_line: record_line(source_row),
                         target_row: record_to_hashmap(target_row, &target_headers),
+                        target_line: record_line(target_row),
+                        source_index: Some(source_row_idx),
+                        target_index: Some(target_row_idx),
+                        match_score: None,
+                        target_key: None,
                         differences,
                     });
                 } else {
-                    unchanged.push(UnchangedRow {
+                    unchanged.push_with(|| UnchangedRow {
                         key: key.clone(),
                         row: record_to_hashmap(source_row, &source_headers),
+                        source_index: Some(source_row_idx),
+                        target_index: Some(target_row_idx),
                     });
                 }
             }
         }
     }
 
-    on_progress(100.0, "Comparison complete");
+    modified.extend(pair_leftover_rows(&mut removed, &mut added, &compared, options, &normalizer, true));
+
+    emit(on_progress, 100.0, Message::new(codes::COMPLETE));
 
     Ok(DiffResult {
         added,
         removed,
         modified,
-        unchanged,
+        unchanged: unchanged.rows,
         source: DatasetMetadata {
             headers: source_headers.clone(),
-            rows: source_rows.iter().map(|r| record_to_hashmap(r, &source_headers)).collect(),
+            rows: dataset_rows(source_rows, source_headers, options.include_dataset_rows),
         },
         target: DatasetMetadata {
             headers: target_headers.clone(),
-            rows: target_rows.iter().map(|r| record_to_hashmap(r, &target_headers)).collect(),
+            rows: dataset_rows(target_rows, &target_headers, options.include_dataset_rows),
         },
         key_columns,
         excluded_columns,
         mode: "primary-key".to_string(),
-    })
-    }
\ No newline at end of file
+        summary: DiffSummary::default(),
+        warnings,
+        seed: options.seed,
+        fallback: None,
+        renamed_columns,
+        schema_changes,
+        reversed: false,
+        header_policy: options.header_policy,
+        moved_rows,
+        duplicate_counts: Vec::new(),
+        omitted_unchanged: unchanged.omitted,
+    }.with_summary().ordered(options.row_order))
+}
+
+/// Rows beyond the first of each repeated key, by key, set aside in
+/// `DuplicateKeys::Group` mode.
+pub(crate) type DuplicateRows<K> = AHashMap<K, Vec<usize>>;
+
+/// Row index by key, and the rows set aside for repeating a key.
+pub(crate) type KeyIndex<K> = (AHashMap<K, usize>, DuplicateRows<K>);
+
+/// Rows matched by key that are not part of the longest run of matched rows
+/// in the same relative order in both files, by source position.
+pub(crate) fn moved_rows<K: Hash + Eq + AsRef<[u8]>>(source_map: &AHashMap<K, usize>, target_map: &AHashMap<K, usize>) -> Vec<MovedRow> {
+    let mut pairs: Vec<(usize, usize, &K)> = source_map.iter()
+        .filter_map(|(key, &s)| Some((s, *target_map.get(key)?, key)))
+        .collect();
+    pairs.sort_unstable_by_key(|&(s, _, _)| s);
+
+    // Longest increasing run of target positions: `tails[n]` ends the best
+    // run of length n + 1, `previous` links each pair to its predecessor
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; pairs.len()];
+    for (i, &(_, t, _)) in pairs.iter().enumerate() {
+        let length = tails.partition_point(|&j| pairs[j].1 < t);
+        previous[i] = length.checked_sub(1).map(|n| tails[n]);
+        if length == tails.len() {
+            tails.push(i);
+        } else {
+            tails[length] = i;
+        }
+    }
+    let mut in_order = vec![false; pairs.len()];
+    let mut next = tails.last().copied();
+    while let Some(i) = next {
+        in_order[i] = true;
+        next = previous[i];
+    }
+
+    pairs.iter().zip(in_order)
+        .filter(|&(_, in_order)| !in_order)
+        .map(|(&(source_index, target_index, key), _)| MovedRow {
+            key: String::from_utf8_lossy(key.as_ref()).into_owned(),
+            source_index,
+            target_index,
+        })
+        .collect()
+}
+
+/// Index row keys (in row order) by row, handling keys that repeat per
+/// `options.duplicate_keys`: fail, keep the first row with a warning, or set
+/// the later rows aside in the returned `DuplicateRows`.
+pub(crate) fn index_keys<K: Hash + Eq + AsRef<[u8]>>(
+    keys: impl IntoIterator<Item = K>,
+    side: &str,
+    options: &DiffOptions,
+    warnings: &mut Vec<String>,
+) -> Result<KeyIndex<K>, Box<dyn std::error::Error>> {
+    let mut map = AHashMap::with_hasher(options.hash_state());
+    let mut duplicates = AHashMap::with_hasher(options.hash_state());
+    let mut skipped: Option<(K, usize)> = None;
+    for (i, key) in keys.into_iter().enumerate() {
+        if !map.contains_key(&key) {
+            map.insert(key, i);
+            continue;
+        }
+        match options.duplicate_keys {
+            DuplicateKeys::Error => {
+                let key = String::from_utf8_lossy(key.as_ref());
+                return Err(Message::new(codes::DUPLICATE_KEY).with("key", key).with("side", side).into());
+            }
+            DuplicateKeys::FirstWins => skipped.get_or_insert((key, 0)).1 += 1,
+            DuplicateKeys::Group => duplicates.entry(key).or_insert_with(Vec::new).push(i),
+        }
+    }
+    if let Some((key, count)) = skipped {
+        let warning = Message::new(codes::WARN_DUPLICATE_KEYS)
+            .with("count", count)
+            .with("side", side)
+            .with("key", String::from_utf8_lossy(key.as_ref()));
+        warnings.push(render(&warning.encode(), options.parse.message_format).into_owned());
+    }
+    Ok((map, duplicates))
+}
+
+/// One file's side of `diff_key_groups`: key map, set-aside rows, all rows,
+/// headers and row checksums.
+pub(crate) type KeyedSide<'a> = (
+    &'a mut AHashMap<String, usize>,
+    DuplicateRows<String>,
+    &'a [StringRecord],
+    &'a [String],
+    &'a [u32],
+);
+
+/// Compare the rows of every key repeated on either side as a group and
+/// take those keys out of both key maps. Rows with equal checksums pair up
+/// as unchanged first, the rest pair in file order and any left over count
+/// as removed or added.
+pub(crate) fn diff_key_groups(
+    (source_map, mut source_duplicates, source_rows, source_headers, source_crcs): KeyedSide,
+    (target_map, mut target_duplicates, target_rows, target_headers, target_crcs): KeyedSide,
+    columns: &[ColumnPair],
+    normalizer: &Normalizer,
+    char_diffs: bool,
+    (added, removed, modified, unchanged): (&mut Vec<AddedRow>, &mut Vec<RemovedRow>, &mut Vec<ModifiedRow>, &mut UnchangedRows),
+) {
+    let mut keys: Vec<String> = source_duplicates.keys().chain(target_duplicates.keys()).cloned().collect();
+    keys.sort_unstable();
+    keys.dedup();
+    for key in keys {
+        let mut sources: Vec<usize> = source_map.remove(&key).into_iter()
+            .chain(source_duplicates.remove(&key).unwrap_or_default())
+            .collect();
+        let mut targets: Vec<usize> = target_map.remove(&key).into_iter()
+            .chain(target_duplicates.remove(&key).unwrap_or_default())
+            .collect();
+
+        let mut pairs = Vec::new();
+        sources.retain(|&s| match targets.iter().position(|&t| target_crcs[t] == source_crcs[s]) {
+            Some(position) => {
+                pairs.push((s, targets.remove(position)));
+                false
+            }
+            None => true,
+        });
+        let paired = sources.len().min(targets.len());
+        pairs.extend(sources.iter().copied().zip(targets.iter().copied()));
+
+        for (s, t) in pairs {
+            let (source_row, target_row) = (&source_rows[s], &target_rows[t]);
+            let differences = if source_crcs[s] == target_crcs[t] {
+                Vec::new()
+            } else {
+                row_differences(source_row, target_row, columns, normalizer, char_diffs)
+            };
+            if differences.is_empty() {
+                unchanged.push_with(|| UnchangedRow {
+                    key: key.clone(),
+                    row: record_to_hashmap(source_row, source_headers),
+                    source_index: Some(s),
+                    target_index: Some(t),
+                });
+            } else {
+                modified.push(ModifiedRow {
+                    key: key.clone(),
+                    source_row: record_to_hashmap(source_row, source_headers),
+                    source_line: record_line(source_row),
+                    target_row: record_to_hashmap(target_row, target_headers),
+                    target_line: record_line(target_row),
+                    source_index: Some(s),
+                    target_index: Some(t),
+                    differences,
+                    match_score: None,
+                    target_key: None,
+                });
+            }
+        }
+        removed.extend(sources[paired..].iter().map(|&s| RemovedRow {
+            key: key.clone(),
+            source_row: record_to_hashmap(&source_rows[s], source_headers),
+            source_line: record_line(&source_rows[s]),
+            source_index: Some(s),
+        }));
+        added.extend(targets[paired..].iter().map(|&t| AddedRow {
+            key: key.clone(),
+            target_row: record_to_hashmap(&target_rows[t], target_headers),
+            target_line: record_line(&target_rows[t]),
+            target_index: Some(t),
+        }));
+    }
+}
+
+/// Differences between two rows over `columns`, with character-level diffs
+/// only when `char_diffs` is set.
+fn row_differences(
+    source_row: &StringRecord,
+    target_row: &StringRecord,
+    columns: &[ColumnPair],
+    normalizer: &Normalizer,
+    char_diffs: bool,
+) -> Vec<Difference> {
+    columns.iter()
+        .filter_map(|&(header, source_idx, target_idx)| {
+            let old_value = source_row.get(source_idx).unwrap_or("");
+            let new_value = target_row.get(target_idx).unwrap_or("");
+            (normalizer.normalize(header, old_value) != normalizer.normalize(header, new_value)).then(|| Difference {
+                column: header.clone(),
+                old_value: old_value.to_string(),
+                new_value: new_value.to_string(),
+                change_kind: change_kind(old_value, new_value),
+                diff: if char_diffs { normalizer.diff_values(header, old_value, new_value) } else { Vec::new() },
+            })
+        })
+        .collect()
+}
+
+/// Pair the removed and added rows left over by exact key matching into
+/// modified rows carrying the pairing's similarity: first rows whose keys
+/// are similar enough (`DiffOptions::fuzzy_key_threshold`), then rows whose
+/// other columns are (`DiffOptions::key_change_threshold`), so regenerated
+/// keys show as key changes. Best-scoring pairs go first. Every removed row
+/// is scored against every added row, so this is meant for the few rows
+/// left over. Character-level diffs of the changed cells are only computed
+/// with `char_diffs`.
+pub(crate) fn pair_leftover_rows(
+    removed: &mut Vec<RemovedRow>,
+    added: &mut Vec<AddedRow>,
+    columns: &[ColumnPair],
+    options: &DiffOptions,
+    normalizer: &Normalizer,
+    char_diffs: bool,
+) -> Vec<ModifiedRow> {
+    let scorer = CellScorer::new(&options.similarity, normalizer);
+    let mut modified = Vec::new();
+    if let Some(threshold) = options.fuzzy_key_threshold {
+        let key_score = |source: &RemovedRow, target: &AddedRow| {
+            let total: f64 = options.key_columns.iter()
+                .map(|column| scorer.score(column, cell(&source.source_row, column), cell(&target.target_row, column)).0)
+                .sum();
+            total / options.key_columns.len().max(1) as f64
+        };
+        modified.extend(pair_best(removed, added, threshold, key_score, columns, normalizer, char_diffs));
+    }
+    if let Some(threshold) = options.key_change_threshold {
+        let content_score = |source: &RemovedRow, target: &AddedRow| {
+            let mut score = WeightedScore::default();
+            for &(header, _, _) in columns.iter().filter(|(header, _, _)| !options.key_columns.contains(header)) {
+                let (cell_score, _) = scorer.score(header, cell(&source.source_row, header), cell(&target.target_row, header));
+                score.add(cell_score, scorer.weight(header));
+            }
+            score.average()
+        };
+        modified.extend(pair_best(removed, added, threshold, content_score, columns, normalizer, char_diffs));
+    }
+    modified
+}
+
+/// Greedily pair removed and added rows scoring at least `threshold`, best
+/// first, removing the paired rows from both lists.
+fn pair_best<S: Fn(&RemovedRow, &AddedRow) -> f64>(
+    removed: &mut Vec<RemovedRow>,
+    added: &mut Vec<AddedRow>,
+    threshold: f64,
+    score: S,
+    columns: &[ColumnPair],
+    normalizer: &Normalizer,
+    char_diffs: bool,
+) -> Vec<ModifiedRow> {
+    let mut candidates = Vec::new();
+    for (r, source) in removed.iter().enumerate() {
+        for (a, target) in added.iter().enumerate() {
+            let score = score(source, target);
+            if score >= threshold {
+                candidates.push((score, r, a));
+            }
+        }
+    }
+    // Ties go to the lexically first keys so the pairing doesn't depend on
+    // hash order
+    candidates.sort_by(|x, y| {
+        y.0.total_cmp(&x.0)
+            .then_with(|| removed[x.1].key.cmp(&removed[y.1].key))
+            .then_with(|| added[x.2].key.cmp(&added[y.2].key))
+    });
+
+    let (mut paired_removed, mut paired_added) = (vec![false; removed.len()], vec![false; added.len()]);
+    let mut modified = Vec::new();
+    for (score, r, a) in candidates {
+        if paired_removed[r] || paired_added[a] {
+            continue;
+        }
+        paired_removed[r] = true;
+        paired_added[a] = true;
+        let (source, target) = (&removed[r], &added[a]);
+        let differences = columns.iter()
+            .filter_map(|&(header, _, _)| {
+                let (old_value, new_value) = (cell(&source.source_row, header), cell(&target.target_row, header));
+                (normalizer.normalize(header, old_value) != normalizer.normalize(header, new_value)).then(|| Difference {
+                    column: header.clone(),
+                    old_value: old_value.to_string(),
+                    new_value: new_value.to_string(),
+                    change_kind: change_kind(old_value, new_value),
+                    diff: if char_diffs { normalizer.diff_values(header, old_value, new_value) } else { Vec::new() },
+                })
+            })
+            .collect();
+        modified.push(ModifiedRow {
+            key: source.key.clone(),
+            source_row: source.source_row.clone(),
+            source_line: source.source_line,
+            target_row: target.target_row.clone(),
+            target_line: target.target_line,
+            source_index: source.source_index,
+            target_index: target.target_index,
+            differences,
+            match_score: Some(score),
+            target_key: (target.key != source.key).then(|| target.key.clone()),
+        });
+    }
+
+    let mut paired = paired_removed.into_iter();
+    removed.retain(|_| !paired.next().unwrap_or(false));
+    let mut paired = paired_added.into_iter();
+    added.retain(|_| !paired.next().unwrap_or(false));
+    modified
+}
+
+fn cell<'a>(row: &'a HashMap<String, String>, column: &str) -> &'a str {
+    row.get(column).map_or("", String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::options::{DiffOptions, DuplicateKeys, ParallelPhases};
+
+    #[test]
+    fn test_fuzzy_key_pairing() {
+        let source = "id,name\nA-1001,Alice\nB-2002,Bob\n";
+        let target = "id,name\nA-1O01,Alice\nC-3003,Carol\n";
+        let mut options = DiffOptions {
+            key_columns: vec!["id".to_string()],
+            ..Default::default()
+        };
+        let exact = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+        assert_eq!((exact.added.len(), exact.removed.len(), exact.modified.len()), (2, 2, 0));
+
+        options.fuzzy_key_threshold = Some(0.8);
+        for parallel in [false, true] {
+            options.use_parallel = parallel;
+            options.parallel_phases = ParallelPhases::all();
+            let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            assert_eq!((result.added.len(), result.removed.len()), (1, 1));
+            let modified = &result.modified[0];
+            assert_eq!((modified.key.as_str(), modified.target_row["id"].as_str()), ("A-1001", "A-1O01"));
+            assert!(modified.match_score.unwrap() >= 0.8);
+            assert_eq!(modified.differences.len(), 1);
+            assert!(!modified.differences[0].diff.is_empty());
+            assert_eq!(result.removed[0].key, "B-2002");
+        }
+
+        let bytes = crate::byte_parse::diff_bytes_with_options(source.as_bytes(), target.as_bytes(), &options, |_, _| {}).unwrap();
+        assert_eq!((bytes.added.len(), bytes.removed.len(), bytes.modified.len()), (1, 1, 1));
+        assert_eq!(bytes.modified[0].target_row["id"], "A-1O01");
+    }
+
+    #[test]
+    fn test_key_change_pairing() {
+        let source = "id,name,city\n1,Alice,Oslo\n2,Bob,Rome\n3,Carol,Lima\n";
+        let target = "id,name,city\n1,Alice,Oslo\n92,Bob,Rome\n93,Zed,Kyiv\n";
+        let mut options = DiffOptions {
+            key_columns: vec!["id".to_string()],
+            key_change_threshold: Some(0.9),
+            ..Default::default()
+        };
+        for parallel in [false, true] {
+            options.use_parallel = parallel;
+            let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            assert_eq!((result.added.len(), result.removed.len(), result.modified.len()), (1, 1, 1));
+            let modified = &result.modified[0];
+            assert_eq!((modified.key.as_str(), modified.target_key.as_deref()), ("2", Some("92")));
+            assert_eq!(modified.match_score, Some(1.0));
+            assert_eq!(modified.differences[0].column, "id");
+            assert_eq!((result.removed[0].key.as_str(), result.added[0].key.as_str()), ("3", "93"));
+        }
+
+        let bytes = crate::byte_parse::diff_bytes_with_options(source.as_bytes(), target.as_bytes(), &options, |_, _| {}).unwrap();
+        assert_eq!((bytes.added.len(), bytes.removed.len(), bytes.modified.len()), (1, 1, 1));
+        assert_eq!(bytes.modified[0].target_key.as_deref(), Some("92"));
+    }
+
+    #[test]
+    fn test_reverse_diff() {
+        let source = "id,name\n1,Alice\n2,Bob\n";
+        let target = "id,name\n1,Alicia\n3,Carol\n";
+        let mut options = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let forward = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+        let backward = crate::core::diff_with_options(target, source, &options, |_, _| {}).unwrap();
+
+        options.reverse = true;
+        let reversed = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+        assert!(reversed.reversed);
+        assert_eq!(reversed.summary, backward.summary);
+        assert_eq!((reversed.added[0].key.as_str(), reversed.removed[0].key.as_str()), ("2", "3"));
+        let difference = &reversed.modified[0].differences[0];
+        assert_eq!((difference.old_value.as_str(), difference.new_value.as_str()), ("Alicia", "Alice"));
+        let changes = |difference: &crate::types::Difference| difference.diff.iter().map(|c| (c.added, c.removed, c.value.clone())).collect::<Vec<_>>();
+        assert_eq!(changes(difference), changes(&backward.modified[0].differences[0]));
+        assert_eq!(reversed.source.headers, backward.source.headers);
+
+        let bytes = crate::byte_parse::diff_bytes_with_options(source.as_bytes(), target.as_bytes(), &options, |_, _| {}).unwrap();
+        assert!(bytes.reversed);
+        assert_eq!(bytes.summary, backward.summary);
+        assert_eq!(bytes.modified[0].differences[0].old_value, "Alicia");
+
+        let twice = reversed.reversed();
+        assert!(!twice.reversed);
+        assert_eq!(twice.summary, forward.summary);
+        assert_eq!(twice.modified[0].differences[0].new_value, "Alicia");
+    }
+
+    #[test]
+    fn test_order_changes() {
+        let source = "id,v\n1,a\n2,b\n3,c\n4,d\n5,e\n";
+        let target = "id,v\n1,a\n3,c\n4,d\n2,b\n5,E\n";
+        let mut options = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let plain = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+        assert!(plain.moved_rows.is_empty());
+
+        options.report_order_changes = true;
+        for parallel in [false, true] {
+            options.use_parallel = parallel;
+            let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            assert_eq!(result.moved_rows, vec![crate::types::MovedRow { key: "2".to_string(), source_index: 1, target_index: 3 }]);
+        }
+        let bytes = crate::byte_parse::diff_bytes_with_options(source.as_bytes(), target.as_bytes(), &options, |_, _| {}).unwrap();
+        assert_eq!(bytes.moved_rows.len(), 1);
+    }
+
+    #[test]
+    fn test_row_indices() {
+        let source = "id,v\n1,a\n2,b\n3,c\n";
+        let target = "id,v\n4,d\n3,c\n1,A\n";
+        for mode in ["primary-key", "content-match"] {
+            let mut options = DiffOptions { mode: mode.to_string(), key_columns: vec!["id".to_string()], ..Default::default() };
+            let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            if mode == "primary-key" {
+                let modified = &result.modified[0];
+                assert_eq!((modified.source_index, modified.target_index), (Some(0), Some(2)));
+            }
+            assert_eq!((result.unchanged[0].source_index, result.unchanged[0].target_index), (Some(2), Some(1)), "mode {}", mode);
+            assert!(result.removed.iter().any(|r| r.source_index == Some(1)), "mode {}", mode);
+            assert!(result.added.iter().any(|r| r.target_index == Some(0)), "mode {}", mode);
+
+            options.reverse = true;
+            let reversed = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            assert_eq!((reversed.unchanged[0].source_index, reversed.unchanged[0].target_index), (Some(1), Some(2)), "mode {}", mode);
+        }
+    }
+
+    #[test]
+    fn test_omit_unchanged() {
+        let source = "id,v\n1,a\n2,b\n3,c\n";
+        let target = "id,v\n1,a\n2,B\n3,c\n4,d\n";
+        for mode in ["primary-key", "content-match", "positional"] {
+            for use_parallel in [false, true] {
+                let mut options = DiffOptions { mode: mode.to_string(), key_columns: vec!["id".to_string()], use_parallel, ..Default::default() };
+                let full = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+                options.include_unchanged = false;
+                let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+                assert!(result.unchanged.is_empty(), "mode {}", mode);
+                assert_eq!(result.omitted_unchanged, full.unchanged.len(), "mode {}", mode);
+                assert_eq!(result.summary, full.summary, "mode {}", mode);
+                assert_eq!(result.modified.len(), full.modified.len(), "mode {}", mode);
+            }
+        }
+    }
+
+    #[test]
+    fn test_surrogate_key() {
+        let source = "first,last,age\nAnn,Lee,30\nAnn,Kim,41\nBob,Lee,25\n";
+        let target = "first,last,age\nAnn,Lee,31\nAnn,Kim,41\nCy,Ng,50\n";
+        let options = DiffOptions {
+            surrogate_key_columns: vec!["first".to_string(), "last".to_string()],
+            ..Default::default()
+        };
+        let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+        assert_eq!(result.key_columns, vec!["first", "last"]);
+        assert_eq!((result.summary.modified, result.summary.unchanged, result.summary.added, result.summary.removed), (1, 1, 1, 1));
+        let key = &result.modified[0].key;
+        assert_eq!(key, &crate::utils::surrogate_key(b"Ann|Lee"));
+        assert!(key.len() == 16 && key.chars().all(|c| c.is_ascii_hexdigit()));
+
+        let bytes = crate::byte_parse::diff_bytes_with_options(source.as_bytes(), target.as_bytes(), &options, |_, _| {}).unwrap();
+        assert_eq!(&bytes.modified[0].key, key);
+    }
+
+    #[test]
+    fn test_duplicate_key_modes() {
+        let source = "id,v\n1,a\n1,b\n1,c\n2,x\n";
+        let target = "id,v\n1,c\n1,a\n2,x\n2,y\n";
+        let mut options = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let error = crate::core::diff_with_options(source, target, &options, |_, _| {}).err().unwrap();
+        assert!(error.to_string().contains("Duplicate Primary Key found in source"));
+
+        options.duplicate_keys = DuplicateKeys::FirstWins;
+        for use_parallel in [false, true] {
+            options.use_parallel = use_parallel;
+            let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            assert_eq!((result.summary.modified, result.summary.unchanged), (1, 1));
+            assert_eq!(result.warnings.len(), 2);
+            assert!(result.warnings[0].starts_with("2 rows in source repeat an earlier primary key (first: \"1\")"));
+        }
+
+        // Group 1 pairs a and c exactly and drops b; group 2 gains y
+        options.duplicate_keys = DuplicateKeys::Group;
+        for use_parallel in [false, true] {
+            options.use_parallel = use_parallel;
+            let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            assert_eq!((result.summary.unchanged, result.summary.modified), (3, 0));
+            assert_eq!((result.removed[0].key.as_str(), result.removed[0].source_row["v"].as_str()), ("1", "b"));
+            assert_eq!((result.added[0].key.as_str(), result.added[0].target_row["v"].as_str()), ("2", "y"));
+        }
+        let changed = crate::core::diff_with_options(source, "id,v\n1,a\n1,z\n", &options, |_, _| {}).unwrap();
+        assert_eq!((changed.summary.unchanged, changed.summary.modified, changed.summary.removed), (1, 1, 2));
+        assert!(crate::core::CsvDifferInternal::with_options(source, target, &options).is_err());
+    }
+
+    #[test]
+    fn test_content_match_fallback() {
+        let source = "id,name\n1,Alice\n1,Bob\n";
+        let target = "id,name\n1,Alice\n1,Bobby\n";
+        let mut options = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        assert!(crate::core::diff_with_options(source, target, &options, |_, _| {}).is_err());
+
+        options.content_match_fallback = true;
+        let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+        assert_eq!(result.mode, "content-match");
+        assert_eq!((result.summary.unchanged, result.summary.modified), (1, 1));
+        assert_eq!(result.fallback.as_deref(), Some("Duplicate Primary Key found in source: \"1\"; rows were matched by content instead."));
+
+        options.key_columns = vec!["sku".to_string()];
+        let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+        assert!(result.fallback.unwrap().starts_with("Primary key column \"sku\" not found in source"));
+        let bytes = crate::byte_parse::diff_bytes_with_options(source.as_bytes(), target.as_bytes(), &options, |_, _| {}).unwrap();
+        assert_eq!(bytes.mode, "content-match");
+        assert!(bytes.fallback.unwrap().starts_with("Primary key column \"sku\" not found in source"));
+
+        // Keyed runs that succeed report no fallback
+        options.key_columns = vec!["name".to_string()];
+        let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+        assert_eq!((result.mode.as_str(), result.fallback), ("primary-key", None));
+    }
+}
@@ -4,6 +4,8 @@
 /// performance bottlenecks in CSV parsing and diffing operations.
 
 use std::time::Instant;
+use crate::messages::Message;
+use crate::types::PhaseTiming;
 
 /// Performance profiler for tracking operation times
 pub struct Profiler {
@@ -97,3 +99,116 @@ impl Default for MemoryTracker {
         Self::new()
     }
 }
+
+/// Milliseconds since the Unix epoch. `Instant` panics on
+/// wasm32-unknown-unknown, so wasm builds ask JS for the time.
+pub fn now_ms() -> f64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        js_sys::Date::now()
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0.0, |d| d.as_secs_f64() * 1000.0)
+    }
+}
+
+/// Current size of the wasm linear memory in bytes.
+pub fn memory_size_bytes() -> Option<usize> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        Some(core::arch::wasm32::memory_size(0) * 65536)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        None
+    }
+}
+
+/// Splits a diff into phases by watching its progress messages.
+///
+/// A phase starts at the first message with a new code and lasts until the
+/// next phase starts. Scoped parse messages count towards "parse.source" or
+/// "parse.target", and "...Progress" updates towards their parent phase.
+pub struct PhaseTimer {
+    start: f64,
+    phases: Vec<PhaseTiming>,
+    current: Option<(String, f64)>,
+}
+
+impl PhaseTimer {
+    pub fn new() -> Self {
+        Self {
+            start: now_ms(),
+            phases: Vec::new(),
+            current: None,
+        }
+    }
+
+    /// Feed one raw progress message; non-catalog strings are ignored.
+    pub fn observe(&mut self, raw: &str) {
+        let Some(message) = Message::decode(raw) else { return };
+        let phase = match message.scope {
+            Some(scope) => format!("parse.{}", scope),
+            None => message.code.strip_suffix("Progress").unwrap_or(&message.code).to_string(),
+        };
+        if self.current.as_ref().is_some_and(|(current, _)| *current == phase) {
+            return;
+        }
+        let now = now_ms();
+        self.close(now);
+        self.current = Some((phase, now));
+    }
+
+    /// Total duration and per-phase durations, in order of first appearance.
+    pub fn finish(mut self) -> (f64, Vec<PhaseTiming>) {
+        let now = now_ms();
+        self.close(now);
+        (now - self.start, self.phases)
+    }
+
+    fn close(&mut self, now: f64) {
+        let Some((phase, started)) = self.current.take() else { return };
+        match self.phases.iter_mut().find(|p| p.phase == phase) {
+            Some(timing) => timing.duration_ms += now - started,
+            None => self.phases.push(PhaseTiming { phase, duration_ms: now - started }),
+        }
+    }
+}
+
+impl Default for PhaseTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::options::DiffOptions;
+
+    #[test]
+    fn test_diff_metrics_hook() {
+        let options = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let mut messages = Vec::new();
+        let mut reported = None;
+        crate::core::diff_with_metrics(
+            "id,name\n1,a\n2,b\n3,c\n",
+            "id,name\n1,a\n2,x\n",
+            &options,
+            |_, message| messages.push(message.to_string()),
+            |metrics| reported = Some(metrics.clone()),
+        ).unwrap();
+
+        // The caller still gets rendered text
+        assert!(messages.iter().all(|m| !m.starts_with(crate::messages::MESSAGE_PREFIX)));
+
+        let metrics = reported.unwrap();
+        assert_eq!((metrics.source_rows, metrics.target_rows, metrics.columns), (3, 2, 2));
+        assert_eq!(metrics.strategy, "primary-key");
+        let phases: Vec<&str> = metrics.phases.iter().map(|p| p.phase.as_str()).collect();
+        assert_eq!(&phases[..3], &["parse.source", "parse.target", "diff.buildSourceMap"]);
+        assert!(metrics.total_ms >= metrics.phases.iter().map(|p| p.duration_ms).sum::<f64>() - 1e-6);
+    }
+}
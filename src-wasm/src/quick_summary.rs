@@ -0,0 +1,164 @@
+//! Summary-only diffs.
+//!
+//! `quick_summary` answers "has anything changed, and where?" without
+//! building result rows: primary-key diffs count added, removed, modified
+//! and unchanged rows and the changed cells per column in one pass over the
+//! keyed rows. Other modes, keys repeated within a file and pairing of
+//! unmatched rows (fuzzy keys, key changes) run the full engine with
+//! everything optional (unchanged rows, dataset rows, char diffs) turned
+//! off and keep only its counts.
+
+use crate::mapping::{map_target_columns, resolve_excluded_columns};
+use crate::messages::{codes, Message};
+use crate::normalize::Normalizer;
+use crate::options::{DiffOptions, RowOrder};
+use crate::parse::parse_csv_with_options;
+use crate::primary_key::index_keys;
+use crate::summary::{classify_change, column_stats};
+use crate::types::{ChangeTaxonomy, ColumnStats, DiffSummary, QuickSummary};
+use crate::utils::{compared_columns, get_row_key};
+
+/// Counts of the diff `options` select between the two files.
+pub fn quick_summary(
+    source_csv: &str,
+    target_csv: &str,
+    options: &DiffOptions,
+) -> Result<QuickSummary, Box<dyn std::error::Error>> {
+    let options = &*options.resolve_keys();
+    if options.is_primary_key() {
+        match keyed_summary(source_csv, target_csv, options) {
+            Ok(Some(summary)) => return Ok(summary),
+            Ok(None) => {}
+            // The engine falls back to content match where the keys fail
+            Err(_) if options.content_match_fallback => {}
+            Err(error) => return Err(error),
+        }
+    }
+
+    let lean = DiffOptions {
+        include_unchanged: false,
+        include_dataset_rows: false,
+        char_diffs: false,
+        row_order: RowOrder::Unsorted,
+        ..options.clone()
+    };
+    let result = crate::core::diff_with_options(source_csv, target_csv, &lean, |_, _| {})?;
+    Ok(QuickSummary {
+        mode: result.mode.clone(),
+        summary: result.summary.clone(),
+        columns: column_stats(&result),
+    })
+}
+
+/// Primary-key counts from comparing keyed rows cell by cell; None when a
+/// key repeats within a file and rows need grouping, or when unmatched rows
+/// are paired afterwards.
+fn keyed_summary(
+    source_csv: &str,
+    target_csv: &str,
+    options: &DiffOptions,
+) -> Result<Option<QuickSummary>, Box<dyn std::error::Error>> {
+    if options.pairs_leftover_rows() {
+        return Ok(None);
+    }
+    let normalizer = Normalizer::new(options)?;
+    let (source_headers, source_rows, source_header_map) = parse_csv_with_options(source_csv, &options.parse)?;
+    let (mut target_headers, target_rows, mut target_header_map) = parse_csv_with_options(target_csv, &options.parse)?;
+    map_target_columns(
+        (&source_headers, &source_rows),
+        (&mut target_headers, &mut target_header_map, &target_rows),
+        options,
+    )?;
+    let (excluded_columns, mut warnings) = resolve_excluded_columns(&source_headers, &target_headers, options)?;
+
+    for key in &options.key_columns {
+        if !source_header_map.contains_key(key) {
+            return Err(Message::new(codes::KEY_COLUMN_MISSING).with("column", key).with("side", "source").into());
+        }
+        if !target_header_map.contains_key(key) {
+            return Err(Message::new(codes::KEY_COLUMN_MISSING).with("column", key).with("side", "target").into());
+        }
+    }
+
+    let source_keys = source_rows.iter().map(|row| get_row_key(row, &source_header_map, &options.key_columns, &options.key_normalization));
+    let (source_map, source_duplicates) = index_keys(source_keys, "source", options, &mut warnings)?;
+    let target_keys = target_rows.iter().map(|row| get_row_key(row, &target_header_map, &options.key_columns, &options.key_normalization));
+    let (target_map, target_duplicates) = index_keys(target_keys, "target", options, &mut warnings)?;
+    if !source_duplicates.is_empty() || !target_duplicates.is_empty() {
+        return Ok(None);
+    }
+
+    // Same column set as `column_stats`: every compared source column
+    let mut columns: Vec<ColumnStats> = source_headers.iter()
+        .filter(|h| !excluded_columns.contains(h))
+        .map(|h| ColumnStats {
+            column: h.clone(),
+            modified_cells: 0,
+            change_rate: 0.0,
+            change_taxonomy: ChangeTaxonomy::default(),
+        })
+        .collect();
+    let compared: Vec<_> = compared_columns(&source_headers, &source_header_map, &target_header_map, &excluded_columns)
+        .into_iter()
+        .filter_map(|(header, s, t)| Some((columns.iter().position(|c| &c.column == header)?, header, s, t)))
+        .collect();
+
+    let mut summary = DiffSummary::default();
+    for (key, &t) in &target_map {
+        let Some(&s) = source_map.get(key) else {
+            summary.added += 1;
+            continue;
+        };
+        let (source_row, target_row) = (&source_rows[s], &target_rows[t]);
+        let mut changed = false;
+        for &(slot, header, s, t) in &compared {
+            let (old, new) = (source_row.get(s).unwrap_or(""), target_row.get(t).unwrap_or(""));
+            if normalizer.normalize(header, old) != normalizer.normalize(header, new) {
+                let category = classify_change(old, new);
+                columns[slot].modified_cells += 1;
+                columns[slot].change_taxonomy.count(category);
+                summary.change_taxonomy.count(category);
+                changed = true;
+            }
+        }
+        if changed {
+            summary.modified += 1;
+        } else {
+            summary.unchanged += 1;
+        }
+    }
+    summary.removed = source_map.keys().filter(|key| !target_map.contains_key(*key)).count();
+    if options.reverse {
+        std::mem::swap(&mut summary.added, &mut summary.removed);
+    }
+
+    let paired = summary.modified + summary.unchanged;
+    for column in &mut columns {
+        column.change_rate = if paired > 0 { column.modified_cells as f64 / paired as f64 } else { 0.0 };
+    }
+    Ok(Some(QuickSummary { mode: "primary-key".to_string(), summary, columns }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quick_summary_matches_full_diff() {
+        let source = "id,name,city\n1,Anna,Oslo\n2,Bob,Bergen\n3,Carl,Oslo\n4,Dana,Molde\n";
+        let target = "id,name,city\n1,ANNA,Oslo\n3,Carl,Trondheim\n4,Dana,Molde\n5,Eve,Oslo\n";
+        for options in [
+            DiffOptions { key_columns: vec!["id".to_string()], case_sensitive: true, ..Default::default() },
+            DiffOptions { key_columns: vec!["id".to_string()], reverse: true, ..Default::default() },
+            DiffOptions { key_columns: vec!["id".to_string()], fuzzy_key_threshold: Some(0.0), ..Default::default() },
+            DiffOptions { key_columns: vec!["id".to_string()], key_change_threshold: Some(0.0), ..Default::default() },
+            DiffOptions { mode: "content-match".to_string(), ..Default::default() },
+        ] {
+            let full = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            let quick = quick_summary(source, target, &options).unwrap();
+            assert_eq!(quick.mode, full.mode);
+            assert_eq!(quick.summary, full.summary, "mode {}", options.mode);
+            assert_eq!(quick.columns, column_stats(&full), "mode {}", options.mode);
+        }
+    }
+}
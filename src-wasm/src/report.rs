@@ -0,0 +1,120 @@
+//! Persisted diff results.
+//!
+//! Results the UI keeps around (IndexedDB, exported files) are wrapped in a
+//! versioned envelope so later releases can still load them after
+//! `DiffResult` changes. Layout history:
+//!
+//! - version 1: a bare `DiffResult` object, as earlier releases stored it.
+//!   It may lack `summary`, and parallel engines spelled the mode with an
+//!   underscore (`primary_key`, `content_match`).
+//! - version 2: `{ schemaVersion, engineVersion, result }`.
+//!
+//! `load_result` upgrades older layouts one version at a time, so a breaking
+//! change only needs a bump of `RESULT_SCHEMA_VERSION` and one more step in
+//! `MIGRATIONS`. The binary encoding is a transfer format and is never
+//! persisted.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::types::DiffResult;
+
+pub const RESULT_SCHEMA_VERSION: u32 = 2;
+
+type Migration = fn(Value) -> Result<Value, Box<dyn std::error::Error>>;
+
+/// Upgrade step from version `i + 1` to `i + 2`.
+const MIGRATIONS: [Migration; 1] = [migrate_v1];
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Envelope<'a> {
+    schema_version: u32,
+    engine_version: &'static str,
+    result: &'a DiffResult,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredEnvelope {
+    result: DiffResult,
+}
+
+/// Serialize a result in the current persisted layout.
+pub fn persist_result(result: &DiffResult) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(serde_json::to_string(&Envelope {
+        schema_version: RESULT_SCHEMA_VERSION,
+        engine_version: env!("CARGO_PKG_VERSION"),
+        result,
+    })?)
+}
+
+/// Load a result persisted by this or any earlier release.
+pub fn load_result(json: &str) -> Result<DiffResult, Box<dyn std::error::Error>> {
+    let mut value: Value = serde_json::from_str(json)?;
+    let stored_version = schema_version(&value)?;
+    if stored_version > RESULT_SCHEMA_VERSION {
+        return Err(format!(
+            "Saved result uses schema version {}; this release reads up to {}.",
+            stored_version, RESULT_SCHEMA_VERSION
+        ).into());
+    }
+    for migrate in &MIGRATIONS[stored_version as usize - 1..] {
+        value = migrate(value)?;
+    }
+
+    let result = serde_json::from_value::<StoredEnvelope>(value)?.result;
+    // Summaries were not always stored; they derive from the rows anyway
+    Ok(if stored_version == 1 { result.with_summary() } else { result })
+}
+
+fn schema_version(value: &Value) -> Result<u32, Box<dyn std::error::Error>> {
+    match value.get("schemaVersion") {
+        Some(version) => match version.as_u64() {
+            Some(version @ 1..=0xFFFF_FFFF) => Ok(version as u32),
+            _ => Err(format!("Invalid saved result schema version {}.", version).into()),
+        },
+        None if value.get("added").is_some() && value.get("mode").is_some() => Ok(1),
+        None => Err("Not a saved diff result.".into()),
+    }
+}
+
+fn migrate_v1(mut result: Value) -> Result<Value, Box<dyn std::error::Error>> {
+    if let Some(mode) = result.get_mut("mode") {
+        if let Some(spelled) = mode.as_str() {
+            *mode = Value::from(spelled.replace('_', "-"));
+        }
+    }
+    Ok(json!({ "schemaVersion": 2, "engineVersion": "", "result": result }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::DiffOptions;
+
+    #[test]
+    fn test_persisted_results_load_across_versions() {
+        let source = "id,name\n1,Alice\n2,Bob\n";
+        let target = "id,name\n1,Alice\n2,Robert\n3,Carol\n";
+        let options = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+
+        let saved = persist_result(&result).unwrap();
+        let envelope: Value = serde_json::from_str(&saved).unwrap();
+        assert_eq!(envelope["schemaVersion"], RESULT_SCHEMA_VERSION);
+        let loaded = load_result(&saved).unwrap();
+        assert_eq!((loaded.modified.len(), loaded.added.len()), (1, 1));
+
+        // Version 1: a bare result without a summary, parallel mode spelling
+        let mut legacy = serde_json::to_value(&result).unwrap();
+        legacy.as_object_mut().unwrap().remove("summary");
+        legacy["mode"] = Value::from("primary_key");
+        let loaded = load_result(&legacy.to_string()).unwrap();
+        assert_eq!(loaded.mode, "primary-key");
+        assert_eq!((loaded.summary.modified, loaded.summary.added), (1, 1));
+
+        assert!(load_result(r#"{"schemaVersion":99,"result":{}}"#).is_err());
+        assert!(load_result(r#"{"schemaVersion":0}"#).is_err());
+        assert!(load_result("[]").is_err());
+    }
+}
@@ -0,0 +1,163 @@
+//! Full-text search over a finished diff result.
+//!
+//! Search scans row keys and cell values and reports, per matching row,
+//! where the query occurs in each field so the UI can highlight it.
+//! Offsets count UTF-16 code units, like JS string indices.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::filter::RowStatus;
+use crate::types::DiffResult;
+use crate::writer::ModifiedSide;
+
+/// Options for `search_result`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SearchOptions {
+    pub case_sensitive: bool,
+    /// Search only the changed cells of modified rows, both values
+    pub changed_only: bool,
+    /// Most rows reported
+    pub limit: Option<usize>,
+}
+
+/// A row with at least one occurrence of the query.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub status: RowStatus,
+    /// Position of the row among the result's rows of its status
+    pub index: usize,
+    pub key: String,
+    pub fields: Vec<FieldHit>,
+}
+
+/// Occurrences of the query in one field of a row.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldHit {
+    /// Column of the cell; absent for the row key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<String>,
+    /// Which value of a modified row's cell matched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub side: Option<ModifiedSide>,
+    /// Start and end of each occurrence
+    pub ranges: Vec<(usize, usize)>,
+}
+
+/// Rows of `result` containing `query`, in result order: added, removed,
+/// modified, then unchanged rows. An empty query matches nothing.
+pub fn search_result(result: &DiffResult, query: &str, options: &SearchOptions) -> Vec<SearchHit> {
+    let mut hits = Vec::new();
+    if query.is_empty() {
+        return hits;
+    }
+    let query: Vec<char> = query.chars().collect();
+    let limit = options.limit.unwrap_or(usize::MAX);
+    let find = |text: &str| find_ranges(text, &query, options.case_sensitive);
+
+    let cells = |row: &HashMap<String, String>, side: Option<ModifiedSide>| -> Vec<FieldHit> {
+        let mut columns: Vec<(&String, &String)> = row.iter().collect();
+        columns.sort();
+        columns.into_iter()
+            .filter_map(|(column, value)| {
+                let ranges = find(value);
+                (!ranges.is_empty()).then(|| FieldHit { column: Some(column.clone()), side, ranges })
+            })
+            .collect()
+    };
+    let key_hit = |key: &str| -> Option<FieldHit> {
+        let ranges = find(key);
+        (!ranges.is_empty()).then_some(FieldHit { column: None, side: None, ranges })
+    };
+    let mut push = |status: RowStatus, index: usize, key: &str, fields: Vec<FieldHit>| {
+        if !fields.is_empty() && hits.len() < limit {
+            hits.push(SearchHit { status, index, key: key.to_string(), fields });
+        }
+    };
+
+    if !options.changed_only {
+        for (index, row) in result.added.iter().enumerate() {
+            push(RowStatus::Added, index, &row.key, key_hit(&row.key).into_iter().chain(cells(&row.target_row, None)).collect());
+        }
+        for (index, row) in result.removed.iter().enumerate() {
+            push(RowStatus::Removed, index, &row.key, key_hit(&row.key).into_iter().chain(cells(&row.source_row, None)).collect());
+        }
+    }
+    for (index, row) in result.modified.iter().enumerate() {
+        let fields = if options.changed_only {
+            row.differences.iter()
+                .flat_map(|d| [(&d.column, &d.old_value, ModifiedSide::Source), (&d.column, &d.new_value, ModifiedSide::Target)])
+                .filter_map(|(column, value, side)| {
+                    let ranges = find(value);
+                    (!ranges.is_empty()).then(|| FieldHit { column: Some(column.clone()), side: Some(side), ranges })
+                })
+                .collect()
+        } else {
+            key_hit(&row.key).into_iter()
+                .chain(cells(&row.source_row, Some(ModifiedSide::Source)))
+                .chain(cells(&row.target_row, Some(ModifiedSide::Target)))
+                .collect()
+        };
+        push(RowStatus::Modified, index, &row.key, fields);
+    }
+    if !options.changed_only {
+        for (index, row) in result.unchanged.iter().enumerate() {
+            push(RowStatus::Unchanged, index, &row.key, key_hit(&row.key).into_iter().chain(cells(&row.row, None)).collect());
+        }
+    }
+    hits
+}
+
+/// Non-overlapping occurrences of `query` in `text`, as UTF-16 ranges.
+fn find_ranges(text: &str, query: &[char], case_sensitive: bool) -> Vec<(usize, usize)> {
+    let same = |a: char, b: char| a == b || (!case_sensitive && a.to_lowercase().eq(b.to_lowercase()));
+    let chars: Vec<char> = text.chars().collect();
+    let mut ranges = Vec::new();
+    let (mut i, mut offset) = (0, 0);
+    while i + query.len() <= chars.len() {
+        if chars[i..i + query.len()].iter().zip(query).all(|(&a, &b)| same(a, b)) {
+            let length: usize = chars[i..i + query.len()].iter().map(|c| c.len_utf16()).sum();
+            ranges.push((offset, offset + length));
+            offset += length;
+            i += query.len();
+        } else {
+            offset += chars[i].len_utf16();
+            i += 1;
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::DiffOptions;
+
+    #[test]
+    fn test_find_ranges() {
+        assert_eq!(find_ranges("abcABC", &['b', 'c'], false), [(1, 3), (4, 6)]);
+        assert_eq!(find_ranges("abcABC", &['b', 'c'], true), [(1, 3)]);
+        // "😀" takes two UTF-16 code units
+        assert_eq!(find_ranges("😀aa", &['a'], true), [(2, 3), (3, 4)]);
+    }
+
+    #[test]
+    fn test_search_result() {
+        let source = "id,name,city\n1,Anna,Oslo\n2,Bob,Bergen\n3,Carl,Oslo\n";
+        let target = "id,name,city\n1,Anna,Bergen\n3,Carl,Oslo\n4,Dana,Bergen\n";
+        let options = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+
+        let hits = search_result(&result, "bergen", &SearchOptions::default());
+        let rows: Vec<(RowStatus, &str)> = hits.iter().map(|h| (h.status, h.key.as_str())).collect();
+        assert_eq!(rows, [(RowStatus::Added, "4"), (RowStatus::Removed, "2"), (RowStatus::Modified, "1")]);
+        assert_eq!(hits[2].fields, [FieldHit { column: Some("city".to_string()), side: Some(ModifiedSide::Target), ranges: vec![(0, 6)] }]);
+
+        let changed = search_result(&result, "oslo", &SearchOptions { changed_only: true, ..Default::default() });
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].fields[0].side, Some(ModifiedSide::Source));
+        assert_eq!(search_result(&result, "o", &SearchOptions { limit: Some(2), ..Default::default() }).len(), 2);
+    }
+}
@@ -0,0 +1,282 @@
+//! Retained diff sessions addressed by numeric handles.
+//!
+//! A session keeps the parsed inputs, the options and the finished result in
+//! Rust memory so follow-up queries (match explanations, filtering, ...) can
+//! run without re-sending the CSVs from JS.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use ahash::AHashMap;
+use crate::columnar::ColumnStore;
+use crate::options::DiffOptions;
+use crate::messages::progress_sink;
+use crate::parse::parse_pair_streaming;
+use crate::mapping::apply_column_mapping;
+use crate::types::DiffResult;
+use crate::utils::record_to_hashmap;
+use crate::writer::ModifiedSide;
+
+static SESSIONS: Mutex<BTreeMap<u32, DiffSession>> = Mutex::new(BTreeMap::new());
+static NEXT_HANDLE: AtomicU32 = AtomicU32::new(1);
+
+/// Parsed inputs, options and result of one diff run.
+pub struct DiffSession {
+    pub options: DiffOptions,
+    pub source_headers: Vec<String>,
+    pub source_rows: ColumnStore,
+    pub source_header_map: AHashMap<String, usize>,
+    pub target_headers: Vec<String>,
+    pub target_rows: ColumnStore,
+    pub target_header_map: AHashMap<String, usize>,
+    pub result: DiffResult,
+}
+
+impl DiffSession {
+    /// Parse both inputs and run the diff selected by `options` on the
+    /// parsed tables, which the session then keeps.
+    pub fn run<F>(
+        source_csv: &str,
+        target_csv: &str,
+        options: DiffOptions,
+        on_progress: F,
+    ) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        F: FnMut(f64, &str),
+    {
+        // Follow-up queries look rows up by the key the diff used
+        let mut options = options.resolve_keys().into_owned();
+        let mut on_progress = progress_sink(options.parse.message_format, on_progress);
+        let (source, target) = parse_pair_streaming(source_csv, target_csv, &options.parse, &options.streaming, &mut on_progress)?;
+        let result = crate::core::diff_parsed(&source, &target, &options, &mut on_progress)?;
+        // Follow-up queries use the excluded columns as resolved against the
+        // headers, and the detected renames as explicit mappings
+        options.excluded_columns = result.excluded_columns.clone();
+        options.column_mapping.extend(result.renamed_columns.iter().map(|r| (r.source.clone(), r.target.clone())));
+        let (source_headers, source_rows, source_header_map) = source;
+        let (mut target_headers, target_rows, mut target_header_map) = target;
+        apply_column_mapping(&mut target_headers, &mut target_header_map, &options.column_mapping)?;
+        let source_rows = ColumnStore::from_records(&source_rows, source_headers.len())?;
+        let target_rows = ColumnStore::from_records(&target_rows, target_headers.len())?;
+
+        Ok(Self {
+            options,
+            source_headers,
+            source_rows,
+            source_header_map,
+            target_headers,
+            target_rows,
+            target_header_map,
+            result,
+        })
+    }
+
+    /// Up to `count` rows of one input from index `start`, as header →
+    /// value maps like `DatasetMetadata::rows`. Sides follow the result, so
+    /// they swap when it is reversed.
+    pub fn rows(&self, side: ModifiedSide, start: usize, count: usize) -> Vec<HashMap<String, String>> {
+        let source = (side == ModifiedSide::Source) != self.result.reversed;
+        let (rows, headers) = if source {
+            (&self.source_rows, &self.source_headers)
+        } else {
+            (&self.target_rows, &self.target_headers)
+        };
+        (start.min(rows.len())..start.saturating_add(count).min(rows.len()))
+            .map(|index| record_to_hashmap(&rows.row(index), headers))
+            .collect()
+    }
+}
+
+/// Store a session and return its handle.
+pub fn insert(session: DiffSession) -> u32 {
+    let handle = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
+    SESSIONS.lock().unwrap_or_else(|e| e.into_inner()).insert(handle, session);
+    handle
+}
+
+/// Run `f` against the session behind `handle`.
+pub fn with_session<T>(
+    handle: u32,
+    f: impl FnOnce(&DiffSession) -> Result<T, Box<dyn std::error::Error>>,
+) -> Result<T, Box<dyn std::error::Error>> {
+    let sessions = SESSIONS.lock().unwrap_or_else(|e| e.into_inner());
+    let session = sessions
+        .get(&handle)
+        .ok_or_else(|| format!("Unknown diff session handle {}", handle))?;
+    f(session)
+}
+
+/// Run `f` against the session behind `handle`, allowing it to reorder or
+/// otherwise update the retained result.
+pub fn with_session_mut<T>(
+    handle: u32,
+    f: impl FnOnce(&mut DiffSession) -> Result<T, Box<dyn std::error::Error>>,
+) -> Result<T, Box<dyn std::error::Error>> {
+    let mut sessions = SESSIONS.lock().unwrap_or_else(|e| e.into_inner());
+    let session = sessions
+        .get_mut(&handle)
+        .ok_or_else(|| format!("Unknown diff session handle {}", handle))?;
+    f(session)
+}
+
+/// Drop the session behind `handle`. Returns false if it did not exist.
+pub fn remove(handle: u32) -> bool {
+    SESSIONS.lock().unwrap_or_else(|e| e.into_inner()).remove(&handle).is_some()
+}
+
+/// Drop every session. Returns how many there were.
+pub fn clear() -> usize {
+    std::mem::take(&mut *SESSIONS.lock().unwrap_or_else(|e| e.into_inner())).len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Held by tests that touch the global registries, since
+    /// `test_free_all_reclaims_retained_state` clears them.
+    static REGISTRY_LOCK: Mutex<()> = Mutex::new(());
+
+    fn registry_lock() -> std::sync::MutexGuard<'static, ()> {
+        REGISTRY_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[test]
+    fn test_session_lifecycle() {
+        let _guard = registry_lock();
+        let options = DiffOptions {
+            key_columns: vec!["id".to_string()],
+            ..Default::default()
+        };
+        let session = DiffSession::run("id,name\n1,a\n", "id,name\n1,b\n", options, |_, _| {}).unwrap();
+        let handle = insert(session);
+
+        let modified = with_session(handle, |s| Ok(s.result.modified.len())).unwrap();
+        assert_eq!(modified, 1);
+
+        assert!(remove(handle));
+        assert!(with_session(handle, |_| Ok(())).is_err());
+        assert!(!remove(handle));
+    }
+
+    #[test]
+    fn test_rows_on_demand() {
+        let mut options = DiffOptions {
+            key_columns: vec!["id".to_string()],
+            include_dataset_rows: false,
+            ..Default::default()
+        };
+        let session = DiffSession::run("id,name\n1,a\n2,b\n3,c\n", "id,name\n1,x\n", options.clone(), |_, _| {}).unwrap();
+        assert!(session.result.source.rows.is_empty() && session.result.target.rows.is_empty());
+
+        let names = |rows: Vec<HashMap<String, String>>| rows.into_iter().map(|r| r["name"].clone()).collect::<Vec<_>>();
+        assert_eq!(names(session.rows(ModifiedSide::Source, 1, 5)), ["b", "c"]);
+        assert_eq!(names(session.rows(ModifiedSide::Target, 0, 5)), ["x"]);
+        assert!(session.rows(ModifiedSide::Source, 7, 2).is_empty());
+
+        options.reverse = true;
+        let reversed = DiffSession::run("id,name\n1,a\n2,b\n3,c\n", "id,name\n1,x\n", options, |_, _| {}).unwrap();
+        assert_eq!(names(reversed.rows(ModifiedSide::Source, 0, 5)), ["x"]);
+    }
+
+    #[test]
+    fn test_session_matches_one_shot_diff() {
+        let source = "id,name\n1,a\n2,b\n2,c\n";
+        let target = "id,label\n1,a\n2,x\n3,d\n";
+        for (mode, use_parallel) in [("primary-key", false), ("primary-key", true), ("content-match", false), ("content-match", true), ("positional", false), ("sequence", false)] {
+            let options = DiffOptions {
+                mode: mode.to_string(),
+                key_columns: vec!["id".to_string()],
+                use_parallel,
+                content_match_fallback: true,
+                column_mapping: [("name".to_string(), "label".to_string())].into_iter().collect(),
+                reverse: true,
+                ..Default::default()
+            };
+            let expected = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            let session = DiffSession::run(source, target, options, |_, _| {}).unwrap();
+            assert_eq!(session.result.summary, expected.summary, "{} {}", mode, use_parallel);
+            assert_eq!(session.result.fallback, expected.fallback, "{} {}", mode, use_parallel);
+            assert_eq!(session.target_headers, ["id", "name"]);
+            assert_eq!(session.target_rows.len(), 3);
+        }
+    }
+
+    #[test]
+    fn test_interleaved_diffs_keep_separate_state() {
+        let _guard = registry_lock();
+        let options = DiffOptions {
+            key_columns: vec!["id".to_string()],
+            ..Default::default()
+        };
+        let full = ("id,name\n1,a\n2,b\n3,c\n", "id,name\n1,a\n2,x\n4,d\n");
+        let preview = ("id,name\n1,a\n", "id,name\n1,z\n");
+
+        // A preview diff started from inside the full diff's progress callback
+        let mut preview_handle = None;
+        let session = DiffSession::run(full.0, full.1, options.clone(), |_, _| {
+            if preview_handle.is_none() {
+                let preview = DiffSession::run(preview.0, preview.1, options.clone(), |_, _| {}).unwrap();
+                preview_handle = Some(insert(preview));
+            }
+        }).unwrap();
+        let full_handle = insert(session);
+        let preview_handle = preview_handle.unwrap();
+
+        // ... and diffs running on other threads at the same time
+        let threads: Vec<_> = (0..4).map(|i| {
+            let options = options.clone();
+            std::thread::spawn(move || {
+                let target = format!("id,name\n1,t{}\n", i);
+                let handle = insert(DiffSession::run("id,name\n1,a\n", &target, options, |_, _| {}).unwrap());
+                let value = with_session(handle, |s| Ok(s.result.modified[0].differences[0].new_value.clone())).unwrap();
+                assert!(remove(handle));
+                value
+            })
+        }).collect();
+        let values: Vec<String> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+        assert_eq!(values, vec!["t0", "t1", "t2", "t3"]);
+
+        let counts = |handle| with_session(handle, |s| {
+            Ok((s.result.added.len(), s.result.removed.len(), s.result.modified.len()))
+        }).unwrap();
+        assert_eq!(counts(full_handle), (1, 1, 1));
+        assert_eq!(counts(preview_handle), (0, 0, 1));
+
+        let encode = |handle| with_session(handle, |s| {
+            let mut encoder = crate::binary_encoder::BinaryEncoder::new();
+            encoder.encode_diff_result(&s.result);
+            Ok(crate::memory::BinaryResult::new(encoder.into_vec()))
+        }).unwrap();
+        let (full_bytes, preview_bytes) = (encode(full_handle), encode(preview_handle));
+        assert_ne!(full_bytes.to_bytes(), preview_bytes.to_bytes());
+        assert_eq!(full_bytes.len(), full_bytes.to_bytes().len());
+
+        assert!(remove(full_handle) && remove(preview_handle));
+    }
+
+    #[test]
+    fn test_free_all_reclaims_retained_state() {
+        let _guard = registry_lock();
+        let options = DiffOptions {
+            key_columns: vec!["id".to_string()],
+            ..Default::default()
+        };
+        let handle = insert(DiffSession::run("id\n1\n", "id\n2\n", options, |_, _| {}).unwrap());
+        let result = crate::memory::BinaryResult::new(vec![1, 2, 3]);
+        let leaked = crate::memory::leak_binary_result(vec![4, 5, 6]);
+        let allocated = crate::memory::alloc(16);
+
+        let freed = crate::memory::free_all_buffers();
+        assert_eq!((clear(), freed.binary_results, freed.buffers), (1, 1, 2));
+
+        assert!(with_session(handle, |_| Ok(())).is_err());
+        assert!(result.is_empty() && result.ptr().is_null());
+        assert_eq!(crate::memory::get_last_binary_result_length(), 0);
+        // Late frees of reclaimed memory are ignored rather than double-freed
+        crate::memory::dealloc(leaked, 3);
+        crate::memory::dealloc(allocated, 16);
+        drop(result);
+        assert_eq!(crate::memory::free_all_buffers(), crate::memory::FreedCounts::default());
+    }
+}
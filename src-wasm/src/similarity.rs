@@ -0,0 +1,272 @@
+//! String similarity for fuzzy row matching.
+//!
+//! Content-match mode pairs rows without an exact match by the average
+//! similarity of their cells. `SimilarityOptions` picks the metric, either
+//! for every column or per column (the default keeps the length-based
+//! choice between Jaro-Winkler and Levenshtein), and how much each column
+//! counts towards the average. Numbers in numeric columns and parsed dates
+//! score by how far apart they are instead.
+
+use std::collections::HashMap;
+use ahash::{AHashMap, AHashSet};
+use serde::{Deserialize, Serialize};
+use strsim::{jaro_winkler, normalized_levenshtein, sorensen_dice};
+use crate::normalize::Normalizer;
+
+/// Values up to this many bytes count as short for `SimilarityMetric::Auto`.
+const SHORT_VALUE_LEN: usize = 20;
+
+/// Dates this many days apart score 0.5.
+const DATE_HALF_SCORE_DAYS: f64 = 30.0;
+
+/// A string similarity metric, scoring from 0.0 (nothing shared) to 1.0.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SimilarityMetric {
+    /// Jaro-Winkler for short values (names, IDs), normalized Levenshtein
+    /// for longer text
+    #[default]
+    Auto,
+    JaroWinkler,
+    Levenshtein,
+    /// Sørensen–Dice coefficient of character bigrams
+    SorensenDice,
+    /// Cosine similarity of character bigram counts
+    NgramCosine,
+    /// Share of distinct words the values have in common (Jaccard index),
+    /// ignoring word order and repeats
+    TokenSet,
+    /// Normalized Levenshtein after sorting each value's words, so
+    /// reordered text still scores typos character by character
+    TokenSort,
+}
+
+impl SimilarityMetric {
+    /// Similarity of `a` and `b`, and the name of the metric that scored it.
+    pub fn score(self, a: &str, b: &str) -> (f64, &'static str) {
+        match self {
+            Self::Auto if a.len() <= SHORT_VALUE_LEN && b.len() <= SHORT_VALUE_LEN => Self::JaroWinkler.score(a, b),
+            Self::Auto => Self::Levenshtein.score(a, b),
+            Self::JaroWinkler => (jaro_winkler(a, b), "jaro-winkler"),
+            Self::Levenshtein => (normalized_levenshtein(a, b), "levenshtein"),
+            Self::SorensenDice => (sorensen_dice(a, b), "sorensen-dice"),
+            Self::NgramCosine => (ngram_cosine(a, b), "ngram-cosine"),
+            Self::TokenSet => (token_set(a, b), "token-set"),
+            Self::TokenSort => (normalized_levenshtein(&sorted_words(a), &sorted_words(b)), "token-sort"),
+        }
+    }
+}
+
+/// Metric used globally and overrides for individual columns.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SimilarityOptions {
+    pub metric: SimilarityMetric,
+    /// Column → metric, e.g. Jaro-Winkler for names and Sørensen–Dice for
+    /// free-text notes
+    pub column_metrics: HashMap<String, SimilarityMetric>,
+    /// Column → weight in the row average (default 1), so identifying
+    /// columns can dominate noisy ones. Zero or negative weights leave a
+    /// column out.
+    pub column_weights: HashMap<String, f64>,
+}
+
+impl SimilarityOptions {
+    /// Similarity of two values of `column`, and the name of the metric.
+    pub fn cell_similarity(&self, column: &str, a: &str, b: &str) -> (f64, &'static str) {
+        self.column_metrics.get(column).copied().unwrap_or(self.metric).score(a, b)
+    }
+
+    /// Weight of `column` in the row average.
+    pub fn weight(&self, column: &str) -> f64 {
+        self.column_weights.get(column).map_or(1.0, |&weight| weight.max(0.0))
+    }
+}
+
+/// Scores cell pairs for fuzzy matching: numbers in numeric columns and
+/// dates in `date_formats` by distance, so 100 is closer to 101 than to
+/// 900, and everything else with the column's string metric.
+pub struct CellScorer<'a> {
+    options: &'a SimilarityOptions,
+    normalizer: &'a Normalizer,
+}
+
+impl<'a> CellScorer<'a> {
+    pub fn new(options: &'a SimilarityOptions, normalizer: &'a Normalizer) -> Self {
+        Self { options, normalizer }
+    }
+
+    /// Similarity of two values of `column`, and the name of the metric.
+    pub fn score(&self, column: &str, a: &str, b: &str) -> (f64, &'static str) {
+        let numbers = (self.normalizer.numeric_value(column, a), self.normalizer.numeric_value(column, b));
+        if let (Some(x), Some(y)) = numbers {
+            return (numeric_similarity(x, y), "numeric-distance");
+        }
+        if let (Some(x), Some(y)) = (self.normalizer.date_value(a), self.normalizer.date_value(b)) {
+            let days = (x - y).num_seconds().unsigned_abs() as f64 / 86_400.0;
+            return (1.0 / (1.0 + days / DATE_HALF_SCORE_DAYS), "date-distance");
+        }
+        self.options.cell_similarity(column, a, b)
+    }
+
+    pub fn weight(&self, column: &str) -> f64 {
+        self.options.weight(column)
+    }
+}
+
+/// One minus the difference relative to the larger magnitude: 100 vs 101
+/// scores 0.99, 100 vs 900 scores 0.11, opposite signs score 0.
+fn numeric_similarity(x: f64, y: f64) -> f64 {
+    if x == y {
+        return 1.0;
+    }
+    (1.0 - (x - y).abs() / x.abs().max(y.abs())).max(0.0)
+}
+
+/// Running weighted average of cell similarities for one row pair.
+#[derive(Default)]
+pub struct WeightedScore {
+    total: f64,
+    weights: f64,
+}
+
+impl WeightedScore {
+    pub fn add(&mut self, score: f64, weight: f64) {
+        self.total += score * weight;
+        self.weights += weight;
+    }
+
+    /// The weighted average, or 0.0 if nothing carried weight.
+    pub fn average(&self) -> f64 {
+        if self.weights > 0.0 { self.total / self.weights } else { 0.0 }
+    }
+}
+
+/// Cosine similarity of the character bigram count vectors of `a` and `b`.
+/// Values too short to have a bigram only match themselves.
+fn ngram_cosine(a: &str, b: &str) -> f64 {
+    fn bigrams(value: &str) -> AHashMap<(char, char), f64> {
+        let chars: Vec<char> = value.chars().collect();
+        let mut counts = AHashMap::new();
+        for pair in chars.windows(2) {
+            *counts.entry((pair[0], pair[1])).or_default() += 1.0;
+        }
+        counts
+    }
+
+    if a == b {
+        return 1.0;
+    }
+    let (a, b) = (bigrams(a), bigrams(b));
+    let dot: f64 = a.iter().map(|(gram, count)| count * b.get(gram).unwrap_or(&0.0)).sum();
+    let norm = |counts: &AHashMap<(char, char), f64>| counts.values().map(|c| c * c).sum::<f64>().sqrt();
+    let norms = norm(&a) * norm(&b);
+    if norms == 0.0 { 0.0 } else { dot / norms }
+}
+
+/// Jaccard index of the sets of whitespace-separated words in `a` and `b`.
+fn token_set(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+    let (a, b): (AHashSet<&str>, AHashSet<&str>) = (a.split_whitespace().collect(), b.split_whitespace().collect());
+    let union = a.union(&b).count();
+    if union == 0 { 1.0 } else { a.intersection(&b).count() as f64 / union as f64 }
+}
+
+/// The words of `value` in sorted order, joined by single spaces.
+fn sorted_words(value: &str) -> String {
+    let mut words: Vec<&str> = value.split_whitespace().collect();
+    words.sort_unstable();
+    words.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::DiffOptions;
+
+    #[test]
+    fn test_similarity_metrics() {
+        let long = "The quick brown fox jumps over the lazy dog";
+        assert_eq!(SimilarityMetric::Auto.score("Alice", "Alicia").1, "jaro-winkler");
+        assert_eq!(SimilarityMetric::Auto.score(long, long).1, "levenshtein");
+        for metric in [SimilarityMetric::JaroWinkler, SimilarityMetric::Levenshtein, SimilarityMetric::SorensenDice, SimilarityMetric::NgramCosine] {
+            assert_eq!(metric.score("night", "night").0, 1.0, "{:?}", metric);
+            let (similar, different) = (metric.score("night", "nights").0, metric.score("night", "xyz").0);
+            assert!(similar > different && (0.0..1.0).contains(&similar), "{:?}", metric);
+        }
+        // Word order barely matters to bigram metrics
+        assert!(SimilarityMetric::NgramCosine.score("john smith", "smith john").0 > 0.7);
+        assert!(SimilarityMetric::Levenshtein.score("john smith", "smith john").0 < 0.5);
+
+        // Token metrics ignore word order, token-sort still credits typos
+        let (reordered, typo) = ("red large box", "box large red");
+        assert_eq!(SimilarityMetric::TokenSet.score("large red box", reordered), (1.0, "token-set"));
+        assert_eq!(SimilarityMetric::TokenSort.score("large red box", reordered).0, 1.0);
+        assert_eq!(SimilarityMetric::TokenSet.score("large red box", "large red fox").0, 0.5);
+        assert!(SimilarityMetric::TokenSort.score(typo, "fox large red").0 > 0.9);
+        assert_eq!(SimilarityMetric::TokenSet.score("a  b", "b a").0, 1.0);
+
+        let options: SimilarityOptions = serde_json::from_str(r#"{"metric":"levenshtein","columnMetrics":{"name":"sorensen-dice"}}"#).unwrap();
+        assert_eq!(options.cell_similarity("name", "ab", "ab").1, "sorensen-dice");
+        assert_eq!(options.cell_similarity("city", "ab", "ab").1, "levenshtein");
+
+        // Reordered names pair up under a bigram metric but not by edit distance
+        let source = "name,city,team\njohn smith,Berlin,red\n";
+        let target = "name,city,team\nsmith john,Paris,red\n";
+        let diff = |metric| {
+            let similarity = SimilarityOptions { metric, ..Default::default() };
+            let options = DiffOptions { mode: "content-match".to_string(), similarity, ..Default::default() };
+            crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap().modified.len()
+        };
+        assert_eq!((diff(SimilarityMetric::NgramCosine), diff(SimilarityMetric::Levenshtein)), (1, 0));
+    }
+
+    #[test]
+    fn test_type_aware_scores() {
+        let options = DiffOptions {
+            numeric_columns: vec!["amount".to_string()],
+            date_formats: vec!["iso".to_string()],
+            ..Default::default()
+        };
+        let normalizer = Normalizer::new(&options).unwrap();
+        let scorer = CellScorer::new(&options.similarity, &normalizer);
+        let score = |column, a, b| scorer.score(column, a, b).0;
+        assert!(score("amount", "100", "101") > 0.98 && score("amount", "100", "900") < 0.2);
+        assert_eq!(scorer.score("amount", "100", "100.0"), (1.0, "numeric-distance"));
+        assert_eq!(score("amount", "5", "-5"), 0.0);
+        assert!(score("due", "2024-01-01", "2024-01-02") > score("due", "2024-01-01", "2024-12-01"));
+        assert_eq!(scorer.score("note", "100", "101").1, "jaro-winkler");
+
+        // As strings 1000 looks closer to 100 than 101 does
+        let source = "name,amount\nwidget,100\n";
+        let target = "name,amount\nwidget,1000\nwidget,101\n";
+        let paired_amount = |options: &DiffOptions| {
+            let options = DiffOptions { mode: "content-match".to_string(), ..options.clone() };
+            let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            result.modified[0].target_row["amount"].clone()
+        };
+        assert_eq!(paired_amount(&DiffOptions::default()), "1000");
+        assert_eq!(paired_amount(&options), "101");
+    }
+
+    #[test]
+    fn test_column_weights() {
+        // The right person changed every noisy column; someone else shares them
+        let source = "name,email,notes,status,region\nAlice,alice@x.com,n1,1,EU\n";
+        let target = "name,email,notes,status,region\nAlice,alice@x.com,xx,2,APAC\nBob,bob@y.org,n1,1,EU\n";
+        let paired_name = |column_weights: &[(&str, f64)]| {
+            let similarity = SimilarityOptions {
+                column_weights: column_weights.iter().map(|&(column, weight)| (column.to_string(), weight)).collect(),
+                ..Default::default()
+            };
+            let options = DiffOptions { mode: "content-match".to_string(), similarity, ..Default::default() };
+            let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+            result.modified[0].target_row["name"].clone()
+        };
+        assert_eq!(paired_name(&[]), "Bob");
+        assert_eq!(paired_name(&[("name", 5.0), ("email", 5.0)]), "Alice");
+        assert_eq!(paired_name(&[("notes", 0.0), ("status", 0.0), ("region", -1.0)]), "Alice");
+    }
+}
@@ -0,0 +1,81 @@
+//! Machine-readable diff snapshots for monitoring dashboards.
+//!
+//! A snapshot condenses a finished diff into counts, per-column statistics
+//! and severity tallies, stamped with caller-supplied run metadata, so data
+//! drift between scheduled exports can be tracked over time. The layout is
+//! versioned through `SNAPSHOT_SCHEMA_VERSION`; bump it on breaking changes.
+
+use crate::summary::{classify_change, severity};
+use crate::types::*;
+
+/// Version of the `DiffSnapshot` JSON layout.
+pub const SNAPSHOT_SCHEMA_VERSION: u32 = 1;
+
+/// Build a snapshot of `result`.
+pub fn build_snapshot(result: &DiffResult, metadata: SnapshotMetadata) -> DiffSnapshot {
+    let counts = crate::summary::summarize(result);
+    let mut tally = SeverityTally {
+        rows_added: counts.added,
+        rows_removed: counts.removed,
+        ..Default::default()
+    };
+
+    for diff in result.modified.iter().flat_map(|row| &row.differences) {
+        match severity(classify_change(&diff.old_value, &diff.new_value)) {
+            Severity::Cosmetic => tally.cosmetic += 1,
+            Severity::Minor => tally.minor += 1,
+            Severity::Major => tally.major += 1,
+        }
+    }
+    let paired = counts.modified + counts.unchanged;
+
+    DiffSnapshot {
+        schema_version: SNAPSHOT_SCHEMA_VERSION,
+        engine_version: env!("CARGO_PKG_VERSION").to_string(),
+        metadata,
+        mode: result.mode.clone(),
+        key_columns: result.key_columns.clone(),
+        excluded_columns: result.excluded_columns.clone(),
+        // Every source row ends up removed, modified or unchanged, and every
+        // target row added, modified or unchanged
+        source_rows: counts.removed + paired,
+        target_rows: counts.added + paired,
+        counts,
+        severity: tally,
+        columns: crate::summary::column_stats(result),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::DiffOptions;
+
+    #[test]
+    fn test_snapshot_counts_and_columns() {
+        let source = "id,name,score,note\n1,Alice,10,a\n2,Bob,20,b\n3,Carol,30,c\n4,Dan,40,d\n";
+        let target = "id,name,score,note\n1,ALICE,10,a\n2,Bob,25,b\n3,Carol,30,c\n5,Eve,50,e\n";
+        let options = DiffOptions {
+            key_columns: vec!["id".to_string()],
+            excluded_columns: vec!["note".to_string()],
+            ..Default::default()
+        };
+        let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+        let metadata = SnapshotMetadata { label: Some("nightly".to_string()), ..Default::default() };
+        let snapshot = build_snapshot(&result, metadata);
+
+        assert_eq!((snapshot.source_rows, snapshot.target_rows), (4, 4));
+        assert_eq!(snapshot.severity, SeverityTally { cosmetic: 1, minor: 0, major: 1, rows_added: 1, rows_removed: 1 });
+
+        let names: Vec<&str> = snapshot.columns.iter().map(|c| c.column.as_str()).collect();
+        assert_eq!(names, vec!["id", "name", "score"]);
+        let score = &snapshot.columns[2];
+        assert_eq!((score.modified_cells, score.change_taxonomy.numeric_delta), (1, 1));
+        assert!((score.change_rate - 1.0 / 3.0).abs() < 1e-9);
+
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(json["schemaVersion"], SNAPSHOT_SCHEMA_VERSION);
+        assert_eq!(json["label"], "nightly");
+        assert_eq!(json["counts"]["modified"], 2);
+    }
+}
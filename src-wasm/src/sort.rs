@@ -0,0 +1,195 @@
+//! Ordering of result rows.
+//!
+//! Sorting reorders the rows of each kind (added, removed, modified,
+//! unchanged) in place, so a session's retained result can be ordered
+//! without a round trip through JS. Sorts are stable: rows that compare
+//! equal keep their previous order. Engines put their results in
+//! `DiffOptions::row_order` with `order_result`.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::options::RowOrder;
+use crate::types::DiffResult;
+
+/// What rows are ordered by.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    /// Row key, with digit runs compared as numbers ("row2" before "row10")
+    Key,
+    /// Number of changed cells; rows other than modified ones have none
+    Differences,
+    /// Value of a column, numerically when both values are numbers;
+    /// modified rows use their target value
+    Column(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Sort the rows of `result` by `by`.
+pub fn sort_result(result: &mut DiffResult, by: &SortBy, direction: SortDirection) {
+    let directed = |ordering: Ordering| match direction {
+        SortDirection::Asc => ordering,
+        SortDirection::Desc => ordering.reverse(),
+    };
+
+    match by {
+        SortBy::Key => {
+            result.added.sort_by(|a, b| directed(natural_cmp(&a.key, &b.key)));
+            result.removed.sort_by(|a, b| directed(natural_cmp(&a.key, &b.key)));
+            result.modified.sort_by(|a, b| directed(natural_cmp(&a.key, &b.key)));
+            result.unchanged.sort_by(|a, b| directed(natural_cmp(&a.key, &b.key)));
+        }
+        SortBy::Differences => {
+            result.modified.sort_by(|a, b| directed(a.differences.len().cmp(&b.differences.len())));
+        }
+        SortBy::Column(column) => {
+            result.added.sort_by(|a, b| directed(value_cmp(cell(&a.target_row, column), cell(&b.target_row, column))));
+            result.removed.sort_by(|a, b| directed(value_cmp(cell(&a.source_row, column), cell(&b.source_row, column))));
+            result.modified.sort_by(|a, b| directed(value_cmp(cell(&a.target_row, column), cell(&b.target_row, column))));
+            result.unchanged.sort_by(|a, b| directed(value_cmp(cell(&a.row, column), cell(&b.row, column))));
+        }
+    }
+}
+
+/// Put the rows of `result` in `order`. Rows without a recorded position
+/// go last.
+pub fn order_result(result: &mut DiffResult, order: RowOrder) {
+    if order == RowOrder::Unsorted {
+        return;
+    }
+    let position = |index: Option<usize>| index.unwrap_or(usize::MAX);
+    result.added.sort_by_key(|row| position(row.target_index));
+    result.removed.sort_by_key(|row| position(row.source_index));
+    result.modified.sort_by_key(|row| (position(row.source_index), position(row.target_index)));
+    result.unchanged.sort_by_key(|row| (position(row.source_index), position(row.target_index)));
+    if order == RowOrder::Key {
+        sort_result(result, &SortBy::Key, SortDirection::Asc);
+    }
+}
+
+fn cell<'a>(row: &'a HashMap<String, String>, column: &str) -> &'a str {
+    row.get(column).map_or("", String::as_str)
+}
+
+/// Numbers in numeric order, before any other values, which compare
+/// naturally.
+fn value_cmp(a: &str, b: &str) -> Ordering {
+    match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        (Ok(x), Ok(y)) => x.total_cmp(&y),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => natural_cmp(a, b),
+    }
+}
+
+/// Compare strings with runs of ASCII digits compared by numeric value.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (x_run, x_rest) = a.split_at(a.iter().take_while(|c| c.is_ascii_digit()).count());
+                let (y_run, y_rest) = b.split_at(b.iter().take_while(|c| c.is_ascii_digit()).count());
+                let x_digits = trim_zeros(x_run);
+                let y_digits = trim_zeros(y_run);
+                let ordering = x_digits.len().cmp(&y_digits.len())
+                    .then_with(|| x_digits.cmp(y_digits))
+                    // "01" after "1", so the order stays total
+                    .then_with(|| x_run.len().cmp(&y_run.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a = x_rest;
+                b = y_rest;
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                a = &a[1..];
+                b = &b[1..];
+            }
+        }
+    }
+}
+
+fn trim_zeros(digits: &[u8]) -> &[u8] {
+    let zeros = digits.iter().take_while(|&&c| c == b'0').count();
+    &digits[zeros..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::DiffOptions;
+
+    #[test]
+    fn test_natural_cmp() {
+        let mut keys = vec!["row10", "row2", "row02", "Row1", "row1a", "row1"];
+        keys.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(keys, ["Row1", "row1", "row1a", "row2", "row02", "row10"]);
+    }
+
+    #[test]
+    fn test_row_order() {
+        let source = "id,v\nb,1\na10,2\na2,3\nc,4\n";
+        let target = "id,v\nc,4\na2,30\nb,10\nz,5\na10,20\ny,6\n";
+        let keys = |options: &DiffOptions| {
+            let result = crate::core::diff_with_options(source, target, options, |_, _| {}).unwrap();
+            let added: Vec<String> = result.added.iter().map(|r| r.key.clone()).collect();
+            let modified: Vec<String> = result.modified.iter().map(|r| r.key.clone()).collect();
+            (added, modified)
+        };
+
+        for use_parallel in [false, true] {
+            let mut options = DiffOptions { key_columns: vec!["id".to_string()], use_parallel, ..Default::default() };
+            let (added, modified) = keys(&options);
+            assert_eq!(added, ["z", "y"]);
+            assert_eq!(modified, ["b", "a10", "a2"]);
+            for seed in [1, 2, 3] {
+                options.seed = seed;
+                assert_eq!(keys(&options), keys(&DiffOptions { seed: 0, ..options.clone() }));
+            }
+
+            options.row_order = RowOrder::Key;
+            let (added, modified) = keys(&options);
+            assert_eq!(added, ["y", "z"]);
+            assert_eq!(modified, ["a2", "a10", "b"]);
+        }
+    }
+
+    #[test]
+    fn test_sort_result() {
+        let source = "id,price,name\nr1,5,a\nr2,10,b\nr10,9.5,c\n";
+        let target = "id,price,name\nr1,50,x\nr2,1.5,b\nr10,100,y\nr3,7,d\nr20,n/a,e\n";
+        let options = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let mut result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+        let modified_keys = |result: &DiffResult| result.modified.iter().map(|r| r.key.clone()).collect::<Vec<_>>();
+
+        sort_result(&mut result, &SortBy::Key, SortDirection::Desc);
+        assert_eq!(modified_keys(&result), ["r10", "r2", "r1"]);
+
+        sort_result(&mut result, &SortBy::Differences, SortDirection::Desc);
+        // r1 and r10 change two cells, in their previous (stable) order
+        assert_eq!(modified_keys(&result), ["r10", "r1", "r2"]);
+
+        sort_result(&mut result, &SortBy::Column("price".to_string()), SortDirection::Asc);
+        assert_eq!(modified_keys(&result), ["r2", "r1", "r10"]);
+        let added: Vec<&str> = result.added.iter().map(|r| r.key.as_str()).collect();
+        assert_eq!(added, ["r3", "r20"]);
+
+        let by: SortBy = serde_json::from_str(r#"{"column":"price"}"#).unwrap();
+        assert_eq!(by, SortBy::Column("price".to_string()));
+    }
+}
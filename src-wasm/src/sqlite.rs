@@ -0,0 +1,544 @@
+//! SQLite database input.
+//!
+//! A small read-only reader for the SQLite file format, in pure Rust so it
+//! runs in wasm without a C toolchain. It walks the table b-tree of one
+//! rowid table and renders every value as text, so two databases can be
+//! diffed with the primary-key engine like two CSV files.
+//!
+//! Only what a diff needs is supported: rowid tables in UTF-8 or UTF-16
+//! databases. WITHOUT ROWID tables, views and the write-ahead log are not
+//! read; checkpoint the database before exporting it.
+
+use ahash::AHashMap;
+use csv::StringRecord;
+use crate::messages::{codes, progress_sink, scoped, Message};
+use crate::options::DiffOptions;
+use crate::parse::ParsedCsv;
+use crate::types::DiffResult;
+
+const HEADER_MAGIC: &[u8] = b"SQLite format 3\0";
+const SCHEMA_ROOT_PAGE: u32 = 1;
+
+/// Primary-key diff of the table `table` in two SQLite database files.
+/// Without `key_columns` the table's declared primary key is used. Reversal
+/// and content-match fallback apply as in `diff_with_options`.
+pub fn diff_sqlite_tables<F>(
+    source: &[u8],
+    target: &[u8],
+    table: &str,
+    options: &DiffOptions,
+    on_progress: F,
+) -> Result<DiffResult, Box<dyn std::error::Error>>
+where
+    F: FnMut(f64, &str),
+{
+    let mut on_progress = progress_sink(options.parse.message_format, on_progress);
+    let reading = Message::new(codes::PARSE_SQLITE_TABLE).with("table", table).encode();
+
+    on_progress(0.0, &scoped("source", &reading));
+    let source = SqliteTable::read(source, table)?;
+    on_progress(10.0, &scoped("target", &reading));
+    let target = SqliteTable::read(target, table)?;
+
+    let mut options = DiffOptions { mode: "primary-key".to_string(), ..options.clone() };
+    if options.key_columns.is_empty() {
+        if source.primary_key.is_empty() {
+            return Err(format!("Table \"{}\" has no declared primary key; choose key columns.", table).into());
+        }
+        options.key_columns = source.primary_key.clone();
+    }
+    crate::core::diff_parsed(&source.into_parsed(), &target.into_parsed(), &options, &mut on_progress)
+}
+
+/// Rows of one table, rendered as text.
+#[derive(Debug)]
+pub struct SqliteTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<StringRecord>,
+    /// Declared primary key columns, empty if the table has none
+    pub primary_key: Vec<String>,
+}
+
+impl SqliteTable {
+    /// Read `table` from the bytes of a SQLite database file.
+    pub fn read(bytes: &[u8], table: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let database = Database::open(bytes)?;
+
+        let mut schema = None;
+        database.walk_table(SCHEMA_ROOT_PAGE, &mut |_, values| {
+            // sqlite_schema columns: type, name, tbl_name, rootpage, sql
+            let is_table = matches!(values.first(), Some(Value::Text(kind)) if kind == "table");
+            let name_matches = matches!(values.get(1), Some(Value::Text(name)) if name.eq_ignore_ascii_case(table));
+            if is_table && name_matches && schema.is_none() {
+                if let (Some(Value::Integer(root)), Some(Value::Text(sql))) = (values.get(3), values.get(4)) {
+                    schema = Some((*root, sql.clone()));
+                }
+            }
+            Ok(())
+        })?;
+        let (root, sql) = schema.ok_or_else(|| format!("Table \"{}\" not found in SQLite database.", table))?;
+        let root = u32::try_from(root).map_err(|_| format!("Invalid root page {} for table \"{}\".", root, table))?;
+
+        let definition = TableDefinition::parse(&sql)?;
+        if definition.without_rowid {
+            return Err(format!("Table \"{}\" is a WITHOUT ROWID table, which is not supported.", table).into());
+        }
+
+        let mut rows = Vec::new();
+        database.walk_table(root, &mut |rowid, values| {
+            let mut record = StringRecord::with_capacity(0, definition.columns.len());
+            for (index, column) in definition.columns.iter().enumerate() {
+                if column.rowid_alias {
+                    // The record stores NULL for an INTEGER PRIMARY KEY column
+                    record.push_field(&rowid.to_string());
+                } else {
+                    // Columns added by ALTER TABLE may be missing from older records
+                    let text = match values.get(index) {
+                        // REAL columns store integral values as integers on disk
+                        Some(Value::Integer(value)) if column.real_affinity => Value::Real(*value as f64).to_text(),
+                        Some(value) => value.to_text(),
+                        None => String::new(),
+                    };
+                    record.push_field(&text);
+                }
+            }
+            rows.push(record);
+            Ok(())
+        })?;
+
+        Ok(Self {
+            headers: definition.columns.into_iter().map(|c| c.name).collect(),
+            rows,
+            primary_key: definition.primary_key,
+        })
+    }
+
+    pub fn into_parsed(self) -> ParsedCsv {
+        let header_map: AHashMap<String, usize> = self.headers.iter()
+            .enumerate()
+            .map(|(i, h)| (h.clone(), i))
+            .collect();
+        (self.headers, self.rows, header_map)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum TextEncoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl Value {
+    /// Text form, matching what the sqlite3 shell prints in CSV mode.
+    fn to_text(&self) -> String {
+        match self {
+            Value::Null => String::new(),
+            Value::Integer(value) => value.to_string(),
+            Value::Real(value) if value.fract() == 0.0 && value.abs() < 1e15 => format!("{:.1}", value),
+            Value::Real(value) => value.to_string(),
+            Value::Text(value) => value.clone(),
+            Value::Blob(bytes) => bytes.iter().map(|b| format!("{:02X}", b)).collect(),
+        }
+    }
+}
+
+struct Database<'a> {
+    bytes: &'a [u8],
+    page_size: usize,
+    /// Page size minus the reserved bytes at the end of every page
+    usable_size: usize,
+    encoding: TextEncoding,
+}
+
+impl<'a> Database<'a> {
+    fn open(bytes: &'a [u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        if bytes.len() < 100 || !bytes.starts_with(HEADER_MAGIC) {
+            return Err("Not a SQLite database file.".into());
+        }
+        let page_size = match u16::from_be_bytes([bytes[16], bytes[17]]) {
+            1 => 65536,
+            size => size as usize,
+        };
+        if page_size < 512 || !page_size.is_power_of_two() {
+            return Err(format!("Invalid SQLite page size {}.", page_size).into());
+        }
+        let encoding = match u32::from_be_bytes([bytes[56], bytes[57], bytes[58], bytes[59]]) {
+            // 0 is written by databases that have no schema yet
+            0 | 1 => TextEncoding::Utf8,
+            2 => TextEncoding::Utf16Le,
+            3 => TextEncoding::Utf16Be,
+            other => return Err(format!("Unknown SQLite text encoding {}.", other).into()),
+        };
+
+        Ok(Self {
+            bytes,
+            page_size,
+            usable_size: page_size - bytes[20] as usize,
+            encoding,
+        })
+    }
+
+    fn page(&self, number: u32) -> Result<&'a [u8], Box<dyn std::error::Error>> {
+        // Corrupt child pointers can overflow a 32-bit usize
+        let start = (number as usize).checked_sub(1).and_then(|n| n.checked_mul(self.page_size));
+        start
+            .and_then(|start| self.bytes.get(start..start.checked_add(self.page_size)?))
+            .ok_or_else(|| format!("SQLite page {} is out of range.", number).into())
+    }
+
+    fn page_count(&self) -> usize {
+        self.bytes.len() / self.page_size
+    }
+
+    /// Visit every row of the table b-tree rooted at `root`, in rowid order.
+    fn walk_table(
+        &self,
+        root: u32,
+        visit: &mut dyn FnMut(i64, Vec<Value>) -> Result<(), Box<dyn std::error::Error>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut stack = vec![root];
+        let mut visited = 0;
+        while let Some(number) = stack.pop() {
+            // A well-formed tree never visits a page twice
+            visited += 1;
+            if visited > self.page_count() {
+                return Err("Corrupt SQLite database: b-tree loop.".into());
+            }
+
+            let page = self.page(number)?;
+            let header = if number == 1 { 100 } else { 0 };
+            let kind = page[header];
+            let cell_count = read_u16(page, header + 3)? as usize;
+            match kind {
+                // Interior table page: children left to right, right-most last
+                0x05 => {
+                    let mut children = Vec::with_capacity(cell_count + 1);
+                    for cell in 0..cell_count {
+                        let offset = read_u16(page, header + 12 + cell * 2)? as usize;
+                        children.push(read_u32(page, offset)?);
+                    }
+                    children.push(read_u32(page, header + 8)?);
+                    stack.extend(children.into_iter().rev());
+                }
+                // Leaf table page
+                0x0D => {
+                    for cell in 0..cell_count {
+                        let offset = read_u16(page, header + 8 + cell * 2)? as usize;
+                        let (payload_size, n) = read_varint(page, offset)?;
+                        let (rowid, m) = read_varint(page, offset + n)?;
+                        let payload = self.payload(page, offset + n + m, payload_size as usize)?;
+                        visit(rowid as i64, self.decode_record(&payload)?)?;
+                    }
+                }
+                other => return Err(format!("Unexpected SQLite page type {:#04x} on page {}.", other, number).into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// The full payload of a leaf cell, following overflow pages if needed.
+    fn payload(&self, page: &[u8], offset: usize, size: usize) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let usable = self.usable_size;
+        let max_local = usable - 35;
+        let local = if size <= max_local {
+            size
+        } else {
+            let min_local = (usable - 12) * 32 / 255 - 23;
+            let local = min_local + (size - min_local) % (usable - 4);
+            if local <= max_local { local } else { min_local }
+        };
+
+        let mut payload = slice(page, offset, local)?.to_vec();
+        if local < size {
+            let mut next = read_u32(page, offset + local)?;
+            let mut pages = 0;
+            while payload.len() < size {
+                pages += 1;
+                if next == 0 || pages > self.page_count() {
+                    return Err("Corrupt SQLite database: overflow chain too short.".into());
+                }
+                let overflow = self.page(next)?;
+                let take = (size - payload.len()).min(usable - 4);
+                payload.extend_from_slice(slice(overflow, 4, take)?);
+                next = read_u32(overflow, 0)?;
+            }
+        }
+        Ok(payload)
+    }
+
+    fn decode_record(&self, payload: &[u8]) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+        let (header_size, mut header_offset) = read_varint(payload, 0)?;
+        let mut body_offset = header_size as usize;
+        let mut values = Vec::new();
+        while header_offset < header_size as usize {
+            let (serial_type, n) = read_varint(payload, header_offset)?;
+            header_offset += n;
+
+            let (value, size) = match serial_type {
+                0 => (Value::Null, 0),
+                1..=6 => {
+                    let size = [1, 2, 3, 4, 6, 8][serial_type as usize - 1];
+                    (Value::Integer(read_int(slice(payload, body_offset, size)?)), size)
+                }
+                7 => {
+                    let bytes = slice(payload, body_offset, 8)?;
+                    (Value::Real(f64::from_be_bytes(bytes.try_into()?)), 8)
+                }
+                8 => (Value::Integer(0), 0),
+                9 => (Value::Integer(1), 0),
+                10 | 11 => return Err(format!("Reserved SQLite serial type {}.", serial_type).into()),
+                even if even % 2 == 0 => {
+                    let size = (even as usize - 12) / 2;
+                    (Value::Blob(slice(payload, body_offset, size)?.to_vec()), size)
+                }
+                odd => {
+                    let size = (odd as usize - 13) / 2;
+                    (Value::Text(self.decode_text(slice(payload, body_offset, size)?)), size)
+                }
+            };
+            body_offset += size;
+            values.push(value);
+        }
+        Ok(values)
+    }
+
+    fn decode_text(&self, bytes: &[u8]) -> String {
+        let units = |to_u16: fn([u8; 2]) -> u16| -> Vec<u16> {
+            (0..bytes.len() / 2).map(|i| to_u16([bytes[2 * i], bytes[2 * i + 1]])).collect()
+        };
+        match self.encoding {
+            TextEncoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            TextEncoding::Utf16Le => String::from_utf16_lossy(&units(u16::from_le_bytes)),
+            TextEncoding::Utf16Be => String::from_utf16_lossy(&units(u16::from_be_bytes)),
+        }
+    }
+}
+
+fn slice(bytes: &[u8], offset: usize, len: usize) -> Result<&[u8], Box<dyn std::error::Error>> {
+    bytes.get(offset..offset + len).ok_or_else(|| "Corrupt SQLite database: read past end of page.".into())
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, Box<dyn std::error::Error>> {
+    Ok(u16::from_be_bytes(slice(bytes, offset, 2)?.try_into()?))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Box<dyn std::error::Error>> {
+    Ok(u32::from_be_bytes(slice(bytes, offset, 4)?.try_into()?))
+}
+
+/// Big-endian two's complement integer of 1 to 8 bytes.
+fn read_int(bytes: &[u8]) -> i64 {
+    let sign = if bytes[0] & 0x80 != 0 { -1i64 } else { 0 };
+    bytes.iter().fold(sign, |value, &b| (value << 8) | b as i64)
+}
+
+/// SQLite varint: up to 9 bytes, 7 bits each except a full 9th byte.
+/// Returns the value and the number of bytes read.
+fn read_varint(bytes: &[u8], offset: usize) -> Result<(u64, usize), Box<dyn std::error::Error>> {
+    let mut value = 0u64;
+    for i in 0..9 {
+        let byte = *bytes.get(offset + i).ok_or("Corrupt SQLite database: truncated varint.")?;
+        if i == 8 {
+            return Ok(((value << 8) | byte as u64, 9));
+        }
+        value = (value << 7) | (byte & 0x7F) as u64;
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    unreachable!()
+}
+
+struct ColumnDefinition {
+    name: String,
+    /// `INTEGER PRIMARY KEY` columns hold the rowid instead of a stored value
+    rowid_alias: bool,
+    real_affinity: bool,
+}
+
+/// The parts of a CREATE TABLE statement the reader needs.
+struct TableDefinition {
+    columns: Vec<ColumnDefinition>,
+    primary_key: Vec<String>,
+    without_rowid: bool,
+}
+
+impl TableDefinition {
+    fn parse(sql: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let open = sql.find('(').ok_or("Unsupported CREATE TABLE statement.")?;
+        let close = sql.rfind(')').filter(|&c| c > open).ok_or("Unsupported CREATE TABLE statement.")?;
+        let options = sql[close + 1..].to_ascii_uppercase();
+
+        let mut columns = Vec::new();
+        let mut primary_key = Vec::new();
+        for definition in split_top_level(&sql[open + 1..close]) {
+            let (name, rest) = split_identifier(definition);
+            let keyword = name.to_ascii_uppercase();
+            if ["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"].contains(&keyword.as_str()) {
+                let upper = definition.to_ascii_uppercase();
+                if let (Some(start), Some(end)) = (upper.find("PRIMARY KEY"), definition.rfind(')')) {
+                    let list = definition[start..end].split_once('(').map_or("", |(_, list)| list);
+                    primary_key = split_top_level(list).map(|c| split_identifier(c).0).collect();
+                }
+                continue;
+            }
+
+            let constraints = rest.to_ascii_uppercase();
+            let words: Vec<&str> = constraints.split_whitespace().collect();
+            let is_primary_key = words.windows(2).any(|w| w == ["PRIMARY", "KEY"]);
+            if is_primary_key {
+                primary_key = vec![name.clone()];
+            }
+            // Type affinity rules: INT wins over REAL, FLOA and DOUB
+            let declared_type = words.first().copied().unwrap_or_default();
+            let real_affinity = !declared_type.contains("INT")
+                && ["REAL", "FLOA", "DOUB"].iter().any(|t| declared_type.contains(t));
+            columns.push(ColumnDefinition {
+                name,
+                rowid_alias: is_primary_key && declared_type == "INTEGER" && !constraints.contains(" DESC"),
+                real_affinity,
+            });
+        }
+
+        if columns.is_empty() {
+            return Err("Unsupported CREATE TABLE statement.".into());
+        }
+        // Only single-column INTEGER keys alias the rowid
+        if primary_key.len() != 1 {
+            columns.iter_mut().for_each(|c| c.rowid_alias = false);
+        }
+        Ok(Self {
+            columns,
+            primary_key,
+            without_rowid: options.split_whitespace().collect::<Vec<_>>().windows(2).any(|w| w == ["WITHOUT", "ROWID"]),
+        })
+    }
+}
+
+/// Split on commas that are outside parentheses and quotes.
+fn split_top_level(list: &str) -> impl Iterator<Item = &str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut quote, mut start) = (0, None, 0);
+    for (i, c) in list.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'' | '`') => quote = Some(c),
+            (None, '[') => quote = Some(']'),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                parts.push(&list[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&list[start..]);
+    parts.into_iter().map(str::trim).filter(|p| !p.is_empty())
+}
+
+/// Leading identifier of `definition`, unquoted, and the text after it.
+fn split_identifier(definition: &str) -> (String, &str) {
+    let definition = definition.trim_start();
+    let close = match definition.chars().next() {
+        Some('"') => '"',
+        Some('`') => '`',
+        Some('\'') => '\'',
+        Some('[') => ']',
+        _ => {
+            let end = definition.find(|c: char| c.is_whitespace() || c == '(').unwrap_or(definition.len());
+            return (definition[..end].to_string(), &definition[end..]);
+        }
+    };
+
+    // Doubling the quote character escapes it
+    let mut name = String::new();
+    let mut chars = definition.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == close {
+            if close != ']' && chars.peek().map(|&(_, next)| next) == Some(close) {
+                chars.next();
+            } else {
+                return (name, &definition[i + 1..]);
+            }
+        }
+        name.push(c);
+    }
+    (name, "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE_DB: &[u8] = include_bytes!("../tests/fixtures/people_source.db");
+    const TARGET_DB: &[u8] = include_bytes!("../tests/fixtures/people_target.db");
+
+    #[test]
+    fn test_read_sqlite_table() {
+        // 150 rows on 512-byte pages: an interior root, several leaves and
+        // an overflow chain for the long note in row 7
+        let table = SqliteTable::read(SOURCE_DB, "people").unwrap();
+        assert_eq!(table.headers, vec!["id", "full name", "score", "data", "note"]);
+        assert_eq!(table.primary_key, vec!["id"]);
+        assert_eq!(table.rows.len(), 150);
+        assert_eq!(table.rows[2].iter().collect::<Vec<_>>(), vec!["3", "Person 3", "4.5", "01AB", ""]);
+        assert_eq!(&table.rows[3][2], "6.0");
+        assert_eq!(table.rows[6][4].len(), 1500);
+
+        assert!(SqliteTable::read(SOURCE_DB, "tags").unwrap_err().to_string().contains("WITHOUT ROWID"));
+        assert!(SqliteTable::read(SOURCE_DB, "missing").is_err());
+        assert!(SqliteTable::read(b"id,name\n1,a\n", "people").is_err());
+
+        let database = Database::open(SOURCE_DB).unwrap();
+        for number in [0, u32::MAX] {
+            assert!(database.page(number).unwrap_err().to_string().contains("out of range"));
+        }
+    }
+
+    #[test]
+    fn test_diff_sqlite_tables() {
+        let result = diff_sqlite_tables(SOURCE_DB, TARGET_DB, "people", &DiffOptions::default(), |_, _| {}).unwrap();
+        assert_eq!(result.key_columns, vec!["id"]);
+        assert_eq!((result.added.len(), result.removed.len(), result.modified.len()), (1, 1, 3));
+        assert_eq!(result.added[0].target_row["note"], "é");
+
+        let mut modified: Vec<(&str, &str)> = result.modified.iter()
+            .map(|row| (row.key.as_str(), row.differences[0].column.as_str()))
+            .collect();
+        modified.sort();
+        assert_eq!(modified, vec![("10", "full name"), ("20", "score"), ("7", "note")]);
+
+        // Reversal and key fallback apply as they do to CSV input
+        let options = DiffOptions { reverse: true, ..Default::default() };
+        let reversed = diff_sqlite_tables(SOURCE_DB, TARGET_DB, "people", &options, |_, _| {}).unwrap();
+        assert!(reversed.reversed);
+        assert_eq!(reversed.removed[0].source_row["note"], "é");
+
+        let options = DiffOptions { key_columns: vec!["missing".to_string()], content_match_fallback: true, ..Default::default() };
+        let fallback = diff_sqlite_tables(SOURCE_DB, TARGET_DB, "people", &options, |_, _| {}).unwrap();
+        assert_eq!(fallback.mode, "content-match");
+        assert!(fallback.fallback.is_some());
+    }
+
+    #[test]
+    fn test_parse_table_definition() {
+        let definition = TableDefinition::parse(
+            "CREATE TABLE t ([order] text, \"a \"\"b\"\"\" INT, `c`, d DECIMAL(10, 2), PRIMARY KEY (\"order\", d))",
+        ).unwrap();
+        let names: Vec<&str> = definition.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["order", "a \"b\"", "c", "d"]);
+        assert_eq!(definition.primary_key, vec!["order", "d"]);
+        assert!(definition.columns.iter().all(|c| !c.rowid_alias));
+    }
+}
@@ -3,7 +3,9 @@
 use csv::{ReaderBuilder, StringRecord};
 use ahash::AHashMap;
 use crate::types::{DiffResult, AddedRow, RemovedRow, ModifiedRow, UnchangedRow};
+use crate::normalize::Normalizer;
 use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
 
 /// Streaming CSV reader that yields chunks of records
 pub struct StreamingCsvReader {
@@ -121,7 +123,7 @@ impl StreamingDiffResult {
     
     /// Convert to final DiffResult
     pub fn to_diff_result(self, source_headers: Vec<String>, target_headers: Vec<String>, key_columns: Vec<String>, excluded_columns: Vec<String>, mode: String) -> DiffResult {
-        use crate::types::DatasetMetadata;
+        use crate::types::{DatasetMetadata, DiffSummary};
 
         
         DiffResult {
@@ -140,7 +142,18 @@ impl StreamingDiffResult {
             key_columns,
             excluded_columns,
             mode,
-        }
+            summary: DiffSummary::default(),
+            warnings: Vec::new(),
+            seed: crate::options::DEFAULT_SEED,
+            fallback: None,
+            renamed_columns: Vec::new(),
+            schema_changes: Default::default(),
+            reversed: false,
+            header_policy: Default::default(),
+            moved_rows: Vec::new(),
+            duplicate_counts: Vec::new(),
+            omitted_unchanged: 0,
+        }.with_summary()
     }
     
     /// Get progress percentage
@@ -153,20 +166,36 @@ impl StreamingDiffResult {
     }
 }
 
+/// Bounds for auto-tuned chunk sizes, in rows
+const MIN_AUTO_CHUNK_ROWS: usize = 500;
+const MAX_AUTO_CHUNK_ROWS: usize = 50_000;
+/// Rough in-memory cost of a parsed row relative to its CSV text
+/// (record buffers plus the per-row hashmaps built for results)
+const ROW_MEMORY_FACTOR: usize = 4;
+/// Bytes sampled from the start of the input to estimate row width
+const ROW_WIDTH_SAMPLE_BYTES: usize = 64 * 1024;
+const WASM_PAGE_BYTES: usize = 64 * 1024;
+
 /// Configuration for streaming diff operations
-#[derive(Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
 pub struct StreamingConfig {
-    pub chunk_size: usize,
+    /// Rows per chunk; `None` auto-tunes from row width and available memory
+    pub chunk_size: Option<usize>,
+    /// Periodic row-count updates; phase messages are always sent
     pub enable_progress_updates: bool,
     pub progress_update_interval: usize, // Update every N chunks
+    /// Upper bound on the memory one auto-tuned chunk may take, in bytes
+    pub chunk_memory_budget: usize,
 }
 
 impl Default for StreamingConfig {
     fn default() -> Self {
         Self {
-            chunk_size: 5000,
+            chunk_size: None,
             enable_progress_updates: true,
-            progress_update_interval: 10,
+            progress_update_interval: 1,
+            chunk_memory_budget: 8 * 1024 * 1024,
         }
     }
 }
@@ -175,7 +204,7 @@ impl StreamingConfig {
     /// Create a new streaming config with custom chunk size
     pub fn new(chunk_size: usize) -> Self {
         Self {
-            chunk_size,
+            chunk_size: Some(chunk_size),
             ..Default::default()
         }
     }
@@ -185,6 +214,47 @@ impl StreamingConfig {
         self.progress_update_interval = interval;
         self
     }
+
+    /// Whether a periodic progress update is due after `chunks_done` chunks.
+    pub fn reports_after(&self, chunks_done: usize) -> bool {
+        self.enable_progress_updates && chunks_done.is_multiple_of(self.progress_update_interval.max(1))
+    }
+
+    /// Chunk size to use for `csv_content`: the configured one if set,
+    /// otherwise auto-tuned.
+    pub fn chunk_size_for(&self, csv_content: &str) -> usize {
+        self.chunk_size
+            .unwrap_or_else(|| auto_chunk_size(csv_content, self.chunk_memory_budget))
+            .max(1)
+    }
+}
+
+/// Pick a chunk size so one chunk of parsed rows stays within `memory_budget`
+/// (and a small share of the memory still available to the module).
+///
+/// Row width is estimated from the first `ROW_WIDTH_SAMPLE_BYTES` of input,
+/// so wide exports get small chunks and narrow ones large chunks.
+pub fn auto_chunk_size(csv_content: &str, memory_budget: usize) -> usize {
+    let sample = &csv_content.as_bytes()[..csv_content.len().min(ROW_WIDTH_SAMPLE_BYTES)];
+    let lines = sample.iter().filter(|&&b| b == b'\n').count().max(1);
+    let row_width = (sample.len() / lines).max(1);
+
+    let budget = memory_budget.min(available_memory() / 16);
+    (budget / (row_width * ROW_MEMORY_FACTOR)).clamp(MIN_AUTO_CHUNK_ROWS, MAX_AUTO_CHUNK_ROWS)
+}
+
+/// Memory the module can still grow into. wasm32 linear memory tops out at
+/// 65536 pages; native builds are not the constrained target.
+fn available_memory() -> usize {
+    #[cfg(target_arch = "wasm32")]
+    {
+        const MAX_PAGES: usize = 65536;
+        (MAX_PAGES - core::arch::wasm32::memory_size(0)).saturating_mul(WASM_PAGE_BYTES)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        usize::MAX / WASM_PAGE_BYTES
+    }
 }
 
 /// Chunked diff for primary key mode
@@ -199,26 +269,26 @@ pub fn diff_chunk_primary_key<F>(
     has_headers: bool,
     chunk_start: usize,
     chunk_size: usize,
-    _config: &StreamingConfig,
+    config: &StreamingConfig,
     mut on_progress: F,
 ) -> Result<StreamingDiffResult, Box<dyn std::error::Error>>
 where
     F: FnMut(f64, &str),
 {
     // Parse only the required chunks
-    let (source_headers, source_rows, _) = crate::parse::parse_csv_streaming(
+    let (source_headers, source_rows, _) = crate::parse::parse_csv_streaming_with_options(
         source_csv, 
-        has_headers, 
-        chunk_size,
+        &crate::parse::ParseOptions::new(has_headers), 
+        config,
         |percent, message| {
             on_progress(percent * 0.3, &format!("Parsing source chunk: {}", message));
         }
     )?;
     
-    let (target_headers, target_rows, _) = crate::parse::parse_csv_streaming(
+    let (target_headers, target_rows, _) = crate::parse::parse_csv_streaming_with_options(
         target_csv, 
-        has_headers, 
-        chunk_size,
+        &crate::parse::ParseOptions::new(has_headers), 
+        config,
         |percent, message| {
             on_progress(30.0 + percent * 0.3, &format!("Parsing target chunk: {}", message));
         }
@@ -238,15 +308,15 @@ where
     }
     
     // Build hash maps for this chunk only
-    let mut source_map: ahash::AHashMap<String, usize> = ahash::AHashMap::new();
+    let mut source_map: ahash::AHashMap<String, usize> = ahash::AHashMap::with_hasher(crate::utils::seeded_state(crate::options::DEFAULT_SEED));
     for (i, row) in source_rows.iter().enumerate() {
-        let key = crate::utils::get_row_key(row, &source_header_map, &key_columns);
+        let key = crate::utils::get_row_key(row, &source_header_map, key_columns, &Default::default());
         source_map.insert(key, i);
     }
     
     let mut target_map: ahash::AHashMap<String, usize> = ahash::AHashMap::new();
     for (i, row) in target_rows.iter().enumerate() {
-        let key = crate::utils::get_row_key(row, &target_header_map, &key_columns);
+        let key = crate::utils::get_row_key(row, &target_header_map, key_columns, &Default::default());
         target_map.insert(key, i);
     }
     
@@ -280,12 +350,20 @@ where
                 result.unchanged.push(UnchangedRow {
                     key: format!("row_{}", chunk_start + source_idx),
                     row: crate::utils::record_to_hashmap(source_row, &source_headers),
+                    source_index: Some(source_idx),
+                    target_index: Some(target_idx),
                 });
             } else {
                 result.modified.push(ModifiedRow {
                     key: format!("row_{}", chunk_start + source_idx),
                     source_row: crate::utils::record_to_hashmap(source_row, &source_headers),
+                    source_line: crate::utils::record_line(source_row),
                     target_row: crate::utils::record_to_hashmap(target_row, &target_headers),
+                    target_line: crate::utils::record_line(target_row),
+                    source_index: Some(source_idx),
+                    target_index: Some(target_idx),
+                    match_score: None,
+                    target_key: None,
                     differences: vec![],
                 });
             }
@@ -293,6 +371,8 @@ where
             result.removed.push(RemovedRow {
                 key: format!("row_{}", chunk_start + source_idx),
                 source_row: crate::utils::record_to_hashmap(&source_rows[source_idx], &source_headers),
+                source_line: crate::utils::record_line(&source_rows[source_idx]),
+                source_index: Some(source_idx),
             });
         }
     }
@@ -302,6 +382,8 @@ where
             result.added.push(AddedRow {
                 key: format!("row_{}", chunk_start + target_idx),
                 target_row: crate::utils::record_to_hashmap(&target_rows[target_idx], &target_headers),
+                target_line: crate::utils::record_line(&target_rows[target_idx]),
+                target_index: Some(target_idx),
             });
         }
     }
@@ -323,31 +405,32 @@ pub fn diff_chunk_content_match<F>(
     has_headers: bool,
     chunk_start: usize,
     chunk_size: usize,
-    _config: &StreamingConfig,
+    config: &StreamingConfig,
     mut on_progress: F,
 ) -> Result<StreamingDiffResult, Box<dyn std::error::Error>>
 where
     F: FnMut(f64, &str),
 {
     // Parse only the required chunks
-    let (source_headers, source_rows, _) = crate::parse::parse_csv_streaming(
+    let (source_headers, source_rows, _) = crate::parse::parse_csv_streaming_with_options(
         source_csv, 
-        has_headers, 
-        chunk_size,
+        &crate::parse::ParseOptions::new(has_headers), 
+        config,
         |percent, message| {
             on_progress(percent * 0.3, &format!("Parsing source chunk: {}", message));
         }
     )?;
     
-    let (target_headers, target_rows, _) = crate::parse::parse_csv_streaming(
+    let (target_headers, target_rows, _) = crate::parse::parse_csv_streaming_with_options(
         target_csv, 
-        has_headers, 
-        chunk_size,
+        &crate::parse::ParseOptions::new(has_headers), 
+        config,
         |percent, message| {
             on_progress(30.0 + percent * 0.3, &format!("Parsing target chunk: {}", message));
         }
     )?;
     
+    let normalizer = Normalizer::from_flags(case_sensitive, ignore_whitespace, ignore_empty_vs_null);
     on_progress(60.0, "Building fingerprint indexes for chunk...");
     
     // Use hash-based fingerprinting for faster comparison
@@ -366,9 +449,7 @@ where
             row,
             &target_headers,
             &target_header_map,
-            case_sensitive,
-            ignore_whitespace,
-            ignore_empty_vs_null,
+            &normalizer,
             &excluded_set,
         );
         target_fingerprint_lookup.entry(fp).or_default().push(idx);
@@ -391,9 +472,7 @@ where
             source_row,
             &source_headers,
             &source_header_map,
-            case_sensitive,
-            ignore_whitespace,
-            ignore_empty_vs_null,
+            &normalizer,
             &excluded_set,
         );
         
@@ -422,12 +501,20 @@ where
                         result.unchanged.push(UnchangedRow {
                             key: format!("row_{}", chunk_start + source_idx),
                             row: crate::utils::record_to_hashmap(source_row, &source_headers),
+                            source_index: Some(source_idx),
+                            target_index: Some(target_idx),
                         });
                     } else {
                         result.modified.push(ModifiedRow {
                             key: format!("row_{}", chunk_start + source_idx),
                             source_row: crate::utils::record_to_hashmap(source_row, &source_headers),
+                            source_line: crate::utils::record_line(source_row),
                             target_row: crate::utils::record_to_hashmap(&target_rows[target_idx], &target_headers),
+                            target_line: crate::utils::record_line(&target_rows[target_idx]),
+                            source_index: Some(source_idx),
+                            target_index: Some(target_idx),
+                            match_score: None,
+                            target_key: None,
                             differences: vec![],
                         });
                     }
@@ -445,6 +532,8 @@ where
             result.removed.push(RemovedRow {
                 key: row_key,
                 source_row: crate::utils::record_to_hashmap(source_row, &source_headers),
+                source_line: crate::utils::record_line(source_row),
+                source_index: Some(source_idx),
             });
         }
     }
@@ -454,6 +543,8 @@ where
             result.added.push(AddedRow {
                 key: format!("row_{}", chunk_start + target_idx),
                 target_row: crate::utils::record_to_hashmap(target_row, &target_headers),
+                target_line: crate::utils::record_line(target_row),
+                target_index: Some(target_idx),
             });
         }
     }
@@ -471,12 +562,49 @@ mod tests {
     #[test]
     fn test_streaming_config() {
         let config = StreamingConfig::new(1000);
-        assert_eq!(config.chunk_size, 1000);
+        assert_eq!(config.chunk_size, Some(1000));
+        assert_eq!(config.chunk_size_for("a,b\n1,2\n"), 1000);
         assert!(config.enable_progress_updates);
         
         let config2 = StreamingConfig::default().with_progress_interval(5);
         assert_eq!(config2.progress_update_interval, 5);
     }
+
+    #[test]
+    fn test_diff_options_streaming_config_is_honored() {
+        let csv: String = std::iter::once("id,v\n".to_string())
+            .chain((0..100).map(|i| format!("{},x\n", i)))
+            .collect();
+        let row_updates = |streaming: StreamingConfig| {
+            let options = crate::options::DiffOptions {
+                key_columns: vec!["id".to_string()],
+                streaming,
+                ..Default::default()
+            };
+            let mut count = 0;
+            crate::core::diff_with_options(&csv, &csv, &options, |_, m| {
+                count += m.contains("Processed") as usize;
+            }).unwrap();
+            count
+        };
+
+        // Two sides, 100 rows each: 10 chunks of 10 rows per side, and with
+        // an interval of 5 only chunks 5 and 10 (the last) report
+        assert_eq!(row_updates(StreamingConfig::new(10)), 20);
+        assert_eq!(row_updates(StreamingConfig::new(10).with_progress_interval(5)), 4);
+        assert_eq!(row_updates(StreamingConfig { enable_progress_updates: false, ..StreamingConfig::new(10) }), 0);
+    }
+
+    #[test]
+    fn test_auto_chunk_size_follows_row_width() {
+        let narrow = "a,b\n".repeat(1000);
+        let wide = format!("{}\n", "x".repeat(4000)).repeat(10);
+        let config = StreamingConfig::default();
+
+        assert_eq!(config.chunk_size_for(&narrow), MAX_AUTO_CHUNK_ROWS);
+        assert!(config.chunk_size_for(&wide) < config.chunk_size_for(&narrow));
+        assert!(config.chunk_size_for(&wide) >= MIN_AUTO_CHUNK_ROWS);
+    }
     
     #[test]
     fn test_streaming_diff_result() {
@@ -0,0 +1,425 @@
+//! Summary statistics computed from a finished diff.
+//!
+//! Modified cells are classified with the same normalizers the comparison
+//! engine uses, so a "case-only" change here is exactly a change that would
+//! disappear with `case_sensitive = false`. `narrate` turns the counts into a
+//! sentence for exports and notifications.
+
+use std::collections::BTreeMap;
+use crate::messages::{codes, Message};
+use crate::types::*;
+use crate::utils::normalize_value_cow;
+
+/// Separators recognised when checking whether a cell is a reordered list.
+const LIST_SEPARATORS: [char; 3] = [';', ',', '|'];
+
+/// Share of modified cells a narrative's named columns must account for.
+const HOT_COLUMN_SHARE: f64 = 0.8;
+/// Most columns a narrative names.
+const MAX_HOT_COLUMNS: usize = 3;
+/// Values per column `column_distributions` reports unless told otherwise.
+pub const DEFAULT_TOP_VALUES: usize = 10;
+
+/// Classify a single cell modification into a change category.
+pub fn classify_change(old: &str, new: &str) -> ChangeCategory {
+    if collapse_whitespace(old) == collapse_whitespace(new) {
+        return ChangeCategory::WhitespaceOnly;
+    }
+
+    if normalize_value_cow(&collapse_whitespace(old), false, true, false)
+        == normalize_value_cow(&collapse_whitespace(new), false, true, false)
+    {
+        return ChangeCategory::CaseOnly;
+    }
+
+    // Numbers as `change_kind` reads them, so its format-only changes are numeric here too
+    if plain_number(old.trim()).is_some() && plain_number(new.trim()).is_some() {
+        return ChangeCategory::NumericDelta;
+    }
+
+    if is_list_reorder(old, new) {
+        return ChangeCategory::ListReorder;
+    }
+
+    ChangeCategory::TextEdit
+}
+
+/// Kind of a change between two unequal cell values.
+pub fn change_kind(old: &str, new: &str) -> ChangeKind {
+    let (old, new) = (collapse_whitespace(old), collapse_whitespace(new));
+    if old == new {
+        ChangeKind::WhitespaceOnly
+    } else if old.to_lowercase() == new.to_lowercase() {
+        ChangeKind::CaseOnly
+    } else if matches!((plain_number(&old), plain_number(&new)), (Some(a), Some(b)) if a == b) {
+        ChangeKind::NumericFormatOnly
+    } else {
+        ChangeKind::Substantive
+    }
+}
+
+/// Value of a number written with an optional sign, comma digit grouping
+/// ("1,234,567") and decimals or exponent.
+fn plain_number(value: &str) -> Option<f64> {
+    let unsigned = value.strip_prefix('+').unwrap_or(value);
+    let digits = unsigned.strip_prefix('-').unwrap_or(unsigned);
+    let integer = digits.split(['.', 'e', 'E']).next()?;
+    if integer.contains(',') {
+        let mut groups = integer.split(',');
+        let first = groups.next()?;
+        if !(1..=3).contains(&first.len()) || groups.any(|group| group.len() != 3) {
+            return None;
+        }
+    }
+    unsigned.replace(',', "").parse().ok().filter(|n: &f64| n.is_finite())
+}
+
+/// Severity of a change category.
+pub fn severity(category: ChangeCategory) -> Severity {
+    match category {
+        ChangeCategory::CaseOnly | ChangeCategory::WhitespaceOnly => Severity::Cosmetic,
+        ChangeCategory::ListReorder => Severity::Minor,
+        ChangeCategory::NumericDelta | ChangeCategory::TextEdit => Severity::Major,
+    }
+}
+
+/// Build the summary (row counts and change taxonomy) for a diff result.
+pub fn summarize(result: &DiffResult) -> DiffSummary {
+    let mut taxonomy = ChangeTaxonomy::default();
+
+    for row in &result.modified {
+        for diff in &row.differences {
+            taxonomy.count(classify_change(&diff.old_value, &diff.new_value));
+        }
+    }
+
+    DiffSummary {
+        added: result.added.len(),
+        removed: result.removed.len(),
+        modified: result.modified.len(),
+        unchanged: result.unchanged.len() + result.omitted_unchanged,
+        change_taxonomy: taxonomy,
+    }
+}
+
+/// Describe a diff result in a sentence, e.g. "1204 rows added, 0 removed,
+/// 37 modified — 90% of modifications are in columns price, updated_at".
+/// The columns clause only appears when at most three columns hold most of
+/// the modified cells.
+pub fn narrate(result: &DiffResult) -> DiffNarrative {
+    let counts = summarize(result);
+    let mut messages = Vec::new();
+    if counts.added + counts.removed + counts.modified == 0 {
+        messages.push(Message::new(codes::SUMMARY_IDENTICAL).with("unchanged", counts.unchanged));
+    } else {
+        messages.push(Message::new(codes::SUMMARY_ROW_COUNTS)
+            .with("added", counts.added)
+            .with("removed", counts.removed)
+            .with("modified", counts.modified));
+    }
+
+    let mut per_column: BTreeMap<&str, usize> = BTreeMap::new();
+    for difference in result.modified.iter().flat_map(|row| &row.differences) {
+        *per_column.entry(difference.column.as_str()).or_default() += 1;
+    }
+    let total: usize = per_column.values().sum();
+    let mut ranked: Vec<(&str, usize)> = per_column.into_iter().collect();
+    ranked.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+
+    let mut covered = 0;
+    for (taken, &(_, count)) in ranked.iter().enumerate().take(MAX_HOT_COLUMNS) {
+        covered += count;
+        if covered as f64 >= total as f64 * HOT_COLUMN_SHARE {
+            let percent = (covered as f64 / total as f64 * 100.0).round();
+            let columns: Vec<&str> = ranked[..=taken].iter().map(|&(column, _)| column).collect();
+            messages.push(match columns.as_slice() {
+                [column] => Message::new(codes::SUMMARY_HOT_COLUMN).with("percent", percent).with("column", column),
+                _ => Message::new(codes::SUMMARY_HOT_COLUMNS).with("percent", percent).with("columns", columns.join(", ")),
+            });
+            break;
+        }
+    }
+
+    let text = messages.iter().map(Message::to_string).collect::<Vec<_>>().join(" — ");
+    DiffNarrative { text, messages }
+}
+
+/// Per-column change statistics. Every compared source column gets an
+/// entry, changed or not, so dashboards see a stable column set from run to
+/// run; columns only the target has follow once they change.
+pub fn column_stats(result: &DiffResult) -> Vec<ColumnStats> {
+    let empty = |column: &str| ColumnStats {
+        column: column.to_string(),
+        modified_cells: 0,
+        change_rate: 0.0,
+        change_taxonomy: ChangeTaxonomy::default(),
+    };
+    let mut columns: Vec<ColumnStats> = result.source.headers.iter()
+        .filter(|h| !result.excluded_columns.contains(h))
+        .map(|h| empty(h))
+        .collect();
+
+    for diff in result.modified.iter().flat_map(|row| &row.differences) {
+        let index = match columns.iter().position(|c| c.column == diff.column) {
+            Some(index) => index,
+            None => {
+                columns.push(empty(&diff.column));
+                columns.len() - 1
+            }
+        };
+        columns[index].modified_cells += 1;
+        columns[index].change_taxonomy.count(classify_change(&diff.old_value, &diff.new_value));
+    }
+
+    let paired = result.modified.len() + result.unchanged.len() + result.omitted_unchanged;
+    for column in &mut columns {
+        column.change_rate = if paired > 0 { column.modified_cells as f64 / paired as f64 } else { 0.0 };
+    }
+    columns
+}
+
+/// Dashboard statistics of a diff: per-column changes, the columns
+/// changing most and how many cells modified rows change.
+pub fn compute_stats(result: &DiffResult) -> DiffStats {
+    let columns = column_stats(result);
+
+    let mut ranked: Vec<&ColumnStats> = columns.iter().filter(|c| c.modified_cells > 0).collect();
+    // Stable sort keeps file order among equally changed columns
+    ranked.sort_by_key(|c| std::cmp::Reverse(c.modified_cells));
+    let most_changed_columns = ranked.iter().map(|c| c.column.clone()).collect();
+
+    let mut rows_by_change_count = Vec::new();
+    for row in &result.modified {
+        let changed = row.differences.len();
+        if changed == 0 {
+            continue;
+        }
+        if rows_by_change_count.len() < changed {
+            rows_by_change_count.resize(changed, 0);
+        }
+        rows_by_change_count[changed - 1] += 1;
+    }
+
+    let paired = result.modified.len() + result.unchanged.len() + result.omitted_unchanged;
+    DiffStats {
+        modified_rate: if paired > 0 { result.modified.len() as f64 / paired as f64 } else { 0.0 },
+        columns,
+        most_changed_columns,
+        rows_by_change_count,
+    }
+}
+
+/// How each column's values shifted: the most frequent old → new
+/// transitions among modified cells, and the values whose number of rows
+/// changed most between the files, at most `top_k` of each. Each file's
+/// rows are its side of the removed or added, modified and unchanged rows.
+/// Columns without changes are left out.
+pub fn column_distributions(result: &DiffResult, top_k: usize) -> Vec<ColumnDistribution> {
+    let mut columns: Vec<&String> = result.source.headers.iter().collect();
+    columns.extend(result.target.headers.iter().filter(|h| !result.source.headers.contains(h)));
+    columns.retain(|h| !result.excluded_columns.contains(h));
+
+    let source_rows = result.removed.iter().map(|r| &r.source_row)
+        .chain(result.modified.iter().map(|r| &r.source_row))
+        .chain(result.unchanged.iter().map(|r| &r.row));
+    let target_rows = result.added.iter().map(|r| &r.target_row)
+        .chain(result.modified.iter().map(|r| &r.target_row))
+        .chain(result.unchanged.iter().map(|r| &r.row));
+    // Counts per column and value, as (source, target)
+    let mut counts: BTreeMap<&str, BTreeMap<&str, (usize, usize)>> = BTreeMap::new();
+    for row in source_rows {
+        for (column, value) in row {
+            counts.entry(column).or_default().entry(value).or_default().0 += 1;
+        }
+    }
+    for row in target_rows {
+        for (column, value) in row {
+            counts.entry(column).or_default().entry(value).or_default().1 += 1;
+        }
+    }
+
+    let mut transitions: BTreeMap<&str, BTreeMap<(&str, &str), usize>> = BTreeMap::new();
+    for diff in result.modified.iter().flat_map(|row| &row.differences) {
+        *transitions.entry(&diff.column).or_default()
+            .entry((&diff.old_value, &diff.new_value)).or_default() += 1;
+    }
+
+    columns.into_iter().filter_map(|column| {
+        let mut column_transitions: Vec<ValueTransition> = transitions.remove(column.as_str()).unwrap_or_default()
+            .into_iter()
+            .map(|((from, to), count)| ValueTransition { from: from.to_string(), to: to.to_string(), count })
+            .collect();
+        column_transitions.sort_by_key(|t| std::cmp::Reverse(t.count));
+        column_transitions.truncate(top_k);
+
+        let mut value_deltas: Vec<ValueCountDelta> = counts.get(column.as_str()).into_iter().flatten()
+            .filter(|(_, (source, target))| source != target)
+            .map(|(value, &(source_count, target_count))| ValueCountDelta { value: value.to_string(), source_count, target_count })
+            .collect();
+        value_deltas.sort_by_key(|d| std::cmp::Reverse(d.source_count.abs_diff(d.target_count)));
+        value_deltas.truncate(top_k);
+
+        if column_transitions.is_empty() && value_deltas.is_empty() {
+            return None;
+        }
+        Some(ColumnDistribution { column: column.clone(), transitions: column_transitions, value_deltas })
+    }).collect()
+}
+
+/// Trim and collapse internal whitespace runs to a single space.
+fn collapse_whitespace(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// True when both values are lists with the same items in a different order.
+fn is_list_reorder(old: &str, new: &str) -> bool {
+    LIST_SEPARATORS.iter().any(|&sep| {
+        if !old.contains(sep) || !new.contains(sep) {
+            return false;
+        }
+        let mut old_items: Vec<&str> = old.split(sep).map(str::trim).collect();
+        let mut new_items: Vec<&str> = new.split(sep).map(str::trim).collect();
+        old_items.sort_unstable();
+        new_items.sort_unstable();
+        old_items == new_items
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_change() {
+        assert_eq!(classify_change("Alice", " Alice "), ChangeCategory::WhitespaceOnly);
+        assert_eq!(classify_change("New  York", "New York"), ChangeCategory::WhitespaceOnly);
+        assert_eq!(classify_change("Alice", "ALICE"), ChangeCategory::CaseOnly);
+        assert_eq!(classify_change("10.5", "11"), ChangeCategory::NumericDelta);
+        assert_eq!(classify_change("a;b;c", "c; a; b"), ChangeCategory::ListReorder);
+        assert_eq!(classify_change("a;b;c", "a;b;d"), ChangeCategory::TextEdit);
+        assert_eq!(classify_change("Bob", "Bobby"), ChangeCategory::TextEdit);
+    }
+
+    #[test]
+    fn test_change_kind() {
+        assert_eq!(change_kind(" New  York", "New York"), ChangeKind::WhitespaceOnly);
+        assert_eq!(change_kind("Alice ", "ALICE"), ChangeKind::CaseOnly);
+        assert_eq!(change_kind("1,000.50", "1000.5"), ChangeKind::NumericFormatOnly);
+        assert_eq!(change_kind("+1e3", "1000"), ChangeKind::NumericFormatOnly);
+        assert_eq!(change_kind("1,5", "15"), ChangeKind::Substantive);
+        assert_eq!(change_kind("10", "12"), ChangeKind::Substantive);
+
+        let result = crate::core::diff_with_options("id,v\n1,a\n2,7.0\n", "id,v\n1,A\n2,7\n", &crate::options::DiffOptions {
+            key_columns: vec!["id".to_string()],
+            ..Default::default()
+        }, |_, _| {}).unwrap();
+        let mut kinds: Vec<(String, ChangeKind)> = result.modified.iter()
+            .map(|row| (row.key.clone(), row.differences[0].change_kind))
+            .collect();
+        kinds.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(kinds, vec![("1".to_string(), ChangeKind::CaseOnly), ("2".to_string(), ChangeKind::NumericFormatOnly)]);
+    }
+
+    #[test]
+    fn test_change_kind_agrees_with_category() {
+        let cases = [
+            (" New  York", "New York", ChangeCategory::WhitespaceOnly, ChangeKind::WhitespaceOnly),
+            ("Alice ", "ALICE", ChangeCategory::CaseOnly, ChangeKind::CaseOnly),
+            ("1,000.50", "1000.5", ChangeCategory::NumericDelta, ChangeKind::NumericFormatOnly),
+            ("+1e3", "1000", ChangeCategory::NumericDelta, ChangeKind::NumericFormatOnly),
+            ("10", "12", ChangeCategory::NumericDelta, ChangeKind::Substantive),
+            ("1,5", "15", ChangeCategory::TextEdit, ChangeKind::Substantive),
+            ("Bob", "Bobby", ChangeCategory::TextEdit, ChangeKind::Substantive),
+        ];
+        for (old, new, category, kind) in cases {
+            assert_eq!((classify_change(old, new), change_kind(old, new)), (category, kind), "{:?} -> {:?}", old, new);
+        }
+    }
+
+    #[test]
+    fn test_summary_counts_taxonomy() {
+        let difference = |column: &str, old: &str, new: &str| Difference {
+            column: column.to_string(),
+            old_value: old.to_string(),
+            new_value: new.to_string(),
+            change_kind: change_kind(old, new),
+            diff: vec![],
+        };
+        let result = DiffResult {
+            added: vec![],
+            removed: vec![],
+            modified: vec![ModifiedRow {
+                key: "1".to_string(),
+                source_row: Default::default(),
+                target_row: Default::default(),
+                differences: vec![
+                    difference("name", "Alice", "ALICE"),
+                    difference("score", "10", "12"),
+                    difference("tags", "a;b", "b;a"),
+                ],
+                source_line: None,
+                target_line: None,
+                source_index: None,
+                target_index: None,
+                match_score: None,
+                target_key: None,
+            }],
+            mode: "primary-key".to_string(),
+            ..Default::default()
+        };
+
+        let summary = summarize(&result);
+        assert_eq!(summary.modified, 1);
+        assert_eq!(summary.change_taxonomy.case_only, 1);
+        assert_eq!(summary.change_taxonomy.numeric_delta, 1);
+        assert_eq!(summary.change_taxonomy.list_reorder, 1);
+        assert_eq!(summary.change_taxonomy.text_edit, 0);
+    }
+
+    #[test]
+    fn test_narrate() {
+        let source = "id,price,updated_at,name\n1,10,2024-01-01,a\n2,20,2024-01-01,b\n3,30,2024-01-01,c\n";
+        let target = "id,price,updated_at,name\n1,11,2024-02-01,a\n2,21,2024-01-01,b\n3,31,2024-02-01,C\n4,40,2024-02-01,d\n";
+        let options = crate::options::DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+
+        let narrative = narrate(&result);
+        assert_eq!(narrative.text, "1 rows added, 0 removed, 3 modified — 83% of modifications are in columns price, updated_at");
+        assert_eq!(narrative.messages[1].code, codes::SUMMARY_HOT_COLUMNS);
+
+        let identical = crate::core::diff_with_options(source, source, &options, |_, _| {}).unwrap();
+        assert_eq!(narrate(&identical).text, "No differences; 3 rows unchanged");
+    }
+
+    #[test]
+    fn test_compute_stats() {
+        let source = "id,a,b,c\n1,x,x,x\n2,x,x,x\n3,x,x,x\n4,x,x,x\n";
+        let target = "id,a,b,c\n1,y,y,x\n2,x,y,x\n3,x,x,x\n4,x,x,x\n";
+        let options = crate::options::DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+
+        let stats = compute_stats(&result);
+        assert_eq!(stats.most_changed_columns, ["b", "a"]);
+        assert_eq!(stats.rows_by_change_count, [1, 1]);
+        assert_eq!(stats.modified_rate, 0.5);
+        let b = stats.columns.iter().find(|c| c.column == "b").unwrap();
+        assert_eq!((b.modified_cells, b.change_rate), (2, 0.5));
+    }
+
+    #[test]
+    fn test_column_distributions() {
+        let source = "id,status\n1,open\n2,open\n3,open\n4,closed\n5,open\n";
+        let target = "id,status\n1,closed\n2,closed\n3,open\n4,closed\n6,new\n";
+        let options = crate::options::DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let result = crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+
+        let distributions = column_distributions(&result, 2);
+        let status = distributions.iter().find(|d| d.column == "status").unwrap();
+        assert_eq!(status.transitions, [ValueTransition { from: "open".to_string(), to: "closed".to_string(), count: 2 }]);
+        // open 4 → 1 and closed 1 → 3; "new" 0 → 1 falls beyond the top 2
+        let deltas: Vec<(&str, usize, usize)> = status.value_deltas.iter()
+            .map(|d| (d.value.as_str(), d.source_count, d.target_count))
+            .collect();
+        assert_eq!(deltas, [("open", 4, 1), ("closed", 1, 3)]);
+    }
+}
@@ -0,0 +1,183 @@
+//! Three-way diff for merge workflows.
+//!
+//! `diff_csv_three_way` diffs two descendants of a common base file against
+//! that base by primary key and classifies every changed key: changed on one
+//! side only, changed on both sides compatibly (e.g. different cells, or the
+//! same new value), or conflicting.
+
+use std::collections::{BTreeSet, HashMap};
+use ahash::AHashMap;
+use crate::normalize::Normalizer;
+use crate::options::DiffOptions;
+use crate::types::*;
+
+type Row = HashMap<String, String>;
+
+/// How one descendant changed a key relative to the base.
+enum SideChange<'a> {
+    Added(&'a Row),
+    Removed,
+    Modified(&'a Row, &'a [Difference]),
+}
+
+impl SideChange<'_> {
+    /// The descendant's row, given the base row it changed.
+    fn row(&self) -> Option<&Row> {
+        match self {
+            Self::Added(row) | Self::Modified(row, _) => Some(row),
+            Self::Removed => None,
+        }
+    }
+}
+
+/// Changes of one descendant by key, plus the base rows they touch.
+fn side_changes<'a>(result: &'a DiffResult, base_rows: &mut AHashMap<&'a str, &'a Row>) -> AHashMap<&'a str, SideChange<'a>> {
+    let mut changes = AHashMap::new();
+    for row in &result.added {
+        changes.insert(row.key.as_str(), SideChange::Added(&row.target_row));
+    }
+    for row in &result.removed {
+        base_rows.insert(row.key.as_str(), &row.source_row);
+        changes.insert(row.key.as_str(), SideChange::Removed);
+    }
+    for row in &result.modified {
+        base_rows.insert(row.key.as_str(), &row.source_row);
+        changes.insert(row.key.as_str(), SideChange::Modified(&row.target_row, &row.differences));
+    }
+    changes
+}
+
+/// Diff `ours_csv` and `theirs_csv` against `base_csv` in primary-key mode
+/// (whatever `options.mode` says) and classify each changed key. Values
+/// compare under the usual normalization options; unchanged keys are left
+/// out. Needs key columns.
+pub fn diff_csv_three_way(
+    base_csv: &str,
+    ours_csv: &str,
+    theirs_csv: &str,
+    options: &DiffOptions,
+) -> Result<ThreeWayResult, Box<dyn std::error::Error>> {
+    if options.key_columns.is_empty() {
+        return Err("Three-way diff needs key columns.".into());
+    }
+    let options = DiffOptions { mode: "primary-key".to_string(), content_match_fallback: false, ..options.clone() };
+    let normalizer = Normalizer::new(&options)?;
+    let ours = crate::core::diff_with_options(base_csv, ours_csv, &options, |_, _| {})?;
+    let theirs = crate::core::diff_with_options(base_csv, theirs_csv, &options, |_, _| {})?;
+
+    let mut base_rows = AHashMap::new();
+    let ours_changes = side_changes(&ours, &mut base_rows);
+    let theirs_changes = side_changes(&theirs, &mut base_rows);
+    let keys: BTreeSet<&str> = ours_changes.keys().chain(theirs_changes.keys()).copied().collect();
+
+    let differ = |column: &str, a: &str, b: &str| normalizer.normalize(column, a) != normalizer.normalize(column, b);
+    let mut summary = ThreeWaySummary::default();
+    let mut changes = Vec::with_capacity(keys.len());
+    for key in keys {
+        let base_row = base_rows.get(key).copied();
+        let (ours_change, theirs_change) = (ours_changes.get(key), theirs_changes.get(key));
+        let ours_row = ours_change.map_or(base_row, SideChange::row);
+        let theirs_row = theirs_change.map_or(base_row, SideChange::row);
+
+        let (status, merged_row, conflicting_columns) = match (ours_change, theirs_change) {
+            (Some(_), None) => (ThreeWayStatus::ChangedInOurs, ours_row.cloned(), Vec::new()),
+            (None, Some(_)) => (ThreeWayStatus::ChangedInTheirs, theirs_row.cloned(), Vec::new()),
+            (Some(SideChange::Removed), Some(SideChange::Removed)) => (ThreeWayStatus::ChangedInBoth, None, Vec::new()),
+            (Some(SideChange::Added(ours_added)), Some(SideChange::Added(theirs_added))) => {
+                let columns: BTreeSet<&String> = ours_added.keys().chain(theirs_added.keys()).collect();
+                let conflicting: Vec<String> = columns.into_iter()
+                    .filter(|&column| differ(column, cell(ours_added, column), cell(theirs_added, column)))
+                    .cloned()
+                    .collect();
+                if conflicting.is_empty() {
+                    (ThreeWayStatus::ChangedInBoth, Some((*ours_added).clone()), conflicting)
+                } else {
+                    (ThreeWayStatus::Conflict, None, conflicting)
+                }
+            }
+            (Some(SideChange::Modified(_, ours_diffs)), Some(SideChange::Modified(_, theirs_diffs))) => {
+                let conflicting: Vec<String> = ours_diffs.iter()
+                    .filter(|ours_diff| theirs_diffs.iter().any(|theirs_diff| {
+                        theirs_diff.column == ours_diff.column && differ(&ours_diff.column, &ours_diff.new_value, &theirs_diff.new_value)
+                    }))
+                    .map(|d| d.column.clone())
+                    .collect();
+                if conflicting.is_empty() {
+                    let mut merged = base_row.cloned().unwrap_or_default();
+                    for difference in ours_diffs.iter().chain(theirs_diffs.iter()) {
+                        merged.insert(difference.column.clone(), difference.new_value.clone());
+                    }
+                    (ThreeWayStatus::ChangedInBoth, Some(merged), conflicting)
+                } else {
+                    (ThreeWayStatus::Conflict, None, conflicting)
+                }
+            }
+            // A deletion against an edit
+            (Some(SideChange::Modified(_, diffs)), Some(_)) | (Some(_), Some(SideChange::Modified(_, diffs))) => {
+                (ThreeWayStatus::Conflict, None, diffs.iter().map(|d| d.column.clone()).collect())
+            }
+            _ => (ThreeWayStatus::Conflict, None, Vec::new()),
+        };
+
+        match status {
+            ThreeWayStatus::ChangedInOurs => summary.changed_in_ours += 1,
+            ThreeWayStatus::ChangedInTheirs => summary.changed_in_theirs += 1,
+            ThreeWayStatus::ChangedInBoth => summary.changed_in_both += 1,
+            ThreeWayStatus::Conflict => summary.conflicts += 1,
+        }
+        changes.push(ThreeWayChange {
+            key: key.to_string(),
+            status,
+            base_row: base_row.cloned(),
+            ours_row: ours_row.cloned(),
+            theirs_row: theirs_row.cloned(),
+            merged_row,
+            conflicting_columns,
+        });
+    }
+
+    let mut warnings = ours.warnings.clone();
+    warnings.extend(theirs.warnings.iter().filter(|w| !ours.warnings.contains(w)).cloned());
+    Ok(ThreeWayResult {
+        changes,
+        summary,
+        key_columns: options.key_columns.clone(),
+        warnings,
+    })
+}
+
+fn cell<'a>(row: &'a Row, column: &str) -> &'a str {
+    row.get(column).map_or("", String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_three_way_diff() {
+        let base = "id,name,city\n1,Ann,Oslo\n2,Bob,Rome\n3,Cy,Lima\n4,Di,Bern\n5,Ed,Nice\n";
+        let ours = "id,name,city\n1,Anne,Oslo\n2,Bob,Milan\n3,Cy,Lima\n5,Ed,Nice\n6,Flo,Pisa\n";
+        let theirs = "id,name,city\n1,Ann,Bergen\n2,Bob,Turin\n3,Cy,Lima\n4,Di,Basel\n5,Ed,Nice\n6,Flo,Pisa\n";
+        let options = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        let result = diff_csv_three_way(base, ours, theirs, &options).unwrap();
+
+        let statuses: Vec<(&str, ThreeWayStatus)> = result.changes.iter().map(|c| (c.key.as_str(), c.status)).collect();
+        assert_eq!(statuses, vec![
+            ("1", ThreeWayStatus::ChangedInBoth),
+            ("2", ThreeWayStatus::Conflict),
+            ("4", ThreeWayStatus::Conflict),
+            ("6", ThreeWayStatus::ChangedInBoth),
+        ]);
+        let merged = result.changes[0].merged_row.as_ref().unwrap();
+        assert_eq!((merged["name"].as_str(), merged["city"].as_str()), ("Anne", "Bergen"));
+        assert_eq!(result.changes[1].conflicting_columns, vec!["city"]);
+        assert_eq!((result.changes[2].ours_row.as_ref(), result.changes[2].conflicting_columns.clone()), (None, vec!["city".to_string()]));
+        assert_eq!(result.summary, ThreeWaySummary { changed_in_ours: 0, changed_in_theirs: 0, changed_in_both: 2, conflicts: 2 });
+
+        let one_sided = diff_csv_three_way(base, ours, base, &options).unwrap();
+        assert!(one_sided.changes.iter().all(|c| c.status == ThreeWayStatus::ChangedInOurs));
+        assert_eq!(one_sided.summary.changed_in_ours, 4);
+        assert!(one_sided.changes.iter().find(|c| c.key == "4").unwrap().merged_row.is_none());
+    }
+}
@@ -8,7 +8,7 @@ pub struct ParseResult {
     pub rows: Vec<HashMap<String, String>>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct DiffResult {
     pub added: Vec<AddedRow>,
@@ -20,12 +20,140 @@ pub struct DiffResult {
     pub key_columns: Vec<String>,
     pub excluded_columns: Vec<String>,
     pub mode: String,
+    #[serde(default)]
+    pub summary: DiffSummary,
+    /// Non-fatal problems with the options, e.g. unknown excluded columns
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// Seed the run used (`DiffOptions::seed`); rerunning with it reproduces
+    /// the result exactly
+    #[serde(default)]
+    pub seed: u64,
+    /// Why a primary-key run matched rows by content instead
+    /// (`DiffOptions::content_match_fallback`); `mode` then says
+    /// "content-match"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<String>,
+    /// Columns mapped by `DiffOptions::detect_renames` or, by position, by
+    /// `HeaderPolicy::Positional`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub renamed_columns: Vec<crate::mapping::MappingProposal>,
+    /// How columns were paired up (`DiffOptions::header_policy`)
+    #[serde(default)]
+    pub header_policy: crate::mapping::HeaderPolicy,
+    /// Added, removed, renamed and reordered columns
+    #[serde(default)]
+    pub schema_changes: SchemaChanges,
+    /// Whether the result describes target → source (`DiffOptions::reverse`
+    /// or `DiffResult::reversed`): "added" rows are then in the source file
+    /// only and "removed" rows in the target only
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub reversed: bool,
+    /// Keyed rows out of order relative to the other matched rows
+    /// (`DiffOptions::report_order_changes`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub moved_rows: Vec<MovedRow>,
+    /// In content-match mode, rows occurring in both files but a different
+    /// number of times; the surplus copies are among the added or removed
+    /// rows
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub duplicate_counts: Vec<DuplicateCount>,
+    /// Unchanged rows left out of `unchanged`
+    /// (`DiffOptions::include_unchanged`); the summary counts them
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub omitted_unchanged: usize,
 }
 
-#[derive(Serialize, Deserialize)]
+fn is_zero(count: &usize) -> bool {
+    *count == 0
+}
+
+impl DiffResult {
+    /// Fill in the summary from the rows currently held in the result.
+    pub fn with_summary(mut self) -> Self {
+        self.summary = crate::summary::summarize(&self);
+        self
+    }
+
+    /// The result with its rows in `order` (see `sort::order_result`).
+    pub fn ordered(mut self, order: crate::options::RowOrder) -> Self {
+        crate::sort::order_result(&mut self, order);
+        self
+    }
+
+    /// The same diff in the other direction, without re-running it: added
+    /// and removed rows, source and target sides, and old and new values
+    /// swap. Unchanged rows keep the row they were reported with; their
+    /// indices swap. Reversing twice gives the original result back.
+    pub fn reversed(self) -> Self {
+        let added = self.removed.into_iter()
+            .map(|row| AddedRow { key: row.key, target_row: row.source_row, target_line: row.source_line, target_index: row.source_index })
+            .collect();
+        let removed = self.added.into_iter()
+            .map(|row| RemovedRow { key: row.key, source_row: row.target_row, source_line: row.target_line, source_index: row.target_index })
+            .collect();
+        let modified = self.modified.into_iter()
+            .map(|row| {
+                let (key, target_key) = match row.target_key {
+                    Some(target_key) => (target_key, Some(row.key)),
+                    None => (row.key, None),
+                };
+                ModifiedRow {
+                    key,
+                    source_row: row.target_row,
+                    target_row: row.source_row,
+                    differences: row.differences.into_iter().map(Difference::reversed).collect(),
+                    source_line: row.target_line,
+                    target_line: row.source_line,
+                    source_index: row.target_index,
+                    target_index: row.source_index,
+                    match_score: row.match_score,
+                    target_key,
+                }
+            })
+            .collect();
+        let schema = self.schema_changes;
+        let swap = |proposals: Vec<crate::mapping::MappingProposal>| -> Vec<_> {
+            proposals.into_iter()
+                .map(|p| crate::mapping::MappingProposal { source: p.target, target: p.source, ..p })
+                .collect()
+        };
+
+        Self {
+            added,
+            removed,
+            modified,
+            source: self.target,
+            target: self.source,
+            renamed_columns: swap(self.renamed_columns),
+            schema_changes: SchemaChanges {
+                added: schema.removed,
+                removed: schema.added,
+                renamed: swap(schema.renamed),
+                reordered: schema.reordered,
+            },
+            reversed: !self.reversed,
+            unchanged: self.unchanged.into_iter()
+                .map(|row| UnchangedRow { source_index: row.target_index, target_index: row.source_index, ..row })
+                .collect(),
+            moved_rows: self.moved_rows.into_iter()
+                .map(|row| MovedRow { source_index: row.target_index, target_index: row.source_index, ..row })
+                .collect(),
+            duplicate_counts: self.duplicate_counts.into_iter()
+                .map(|row| DuplicateCount { source_count: row.target_count, target_count: row.source_count, ..row })
+                .collect(),
+            ..self
+        }.with_summary()
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
 pub struct DatasetMetadata {
     pub headers: Vec<String>,
-    pub rows: Vec<HashMap<String, String>>, 
+    /// Every row of the file with `DiffOptions::include_dataset_rows`; the
+    /// byte, chunked and streaming engines always leave it empty
+    #[serde(default)]
+    pub rows: Vec<HashMap<String, String>>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -33,6 +161,12 @@ pub struct DatasetMetadata {
 pub struct AddedRow {
     pub key: String,
     pub target_row: HashMap<String, String>,
+    /// 1-based line of the record in the target file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_line: Option<u64>,
+    /// 0-based position among the data rows of the target file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_index: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -40,12 +174,78 @@ pub struct AddedRow {
 pub struct RemovedRow {
     pub key: String,
     pub source_row: HashMap<String, String>,
+    /// 1-based line of the record in the source file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_line: Option<u64>,
+    /// 0-based position among the data rows of the source file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_index: Option<usize>,
+}
+
+/// A row present in both files whose position moved relative to the other
+/// rows matched by key. Rows may also be modified.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MovedRow {
+    pub key: String,
+    /// 0-based positions among the data rows of each file
+    pub source_index: usize,
+    pub target_index: usize,
+}
+
+/// A row repeated a different number of times in each file, as compared
+/// (`DiffOptions::identity_columns` only, if set).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateCount {
+    /// First copy of the row in the source file
+    pub row: HashMap<String, String>,
+    pub source_count: usize,
+    pub target_count: usize,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct UnchangedRow {
     pub key: String,
     pub row: HashMap<String, String>,
+    /// 0-based positions of the paired rows among the data rows of each file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_index: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_index: Option<usize>,
+}
+
+/// Unchanged rows as an engine collects them: the rows, or only their
+/// number when `DiffOptions::include_unchanged` is off.
+pub struct UnchangedRows {
+    keep: bool,
+    pub rows: Vec<UnchangedRow>,
+    pub omitted: usize,
+}
+
+impl UnchangedRows {
+    pub fn new(keep: bool) -> Self {
+        Self { keep, rows: Vec::new(), omitted: 0 }
+    }
+
+    pub fn keeps(&self) -> bool {
+        self.keep
+    }
+
+    /// Record an unchanged row, building it only if rows are kept.
+    pub fn push_with(&mut self, row: impl FnOnce() -> UnchangedRow) {
+        let row = self.keep.then(row);
+        self.record(row);
+    }
+
+    /// Record an unchanged row built elsewhere, or `None` for one left out.
+    pub fn record(&mut self, row: Option<UnchangedRow>) {
+        match row {
+            Some(row) => self.rows.push(row),
+            None => self.omitted += 1,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -55,6 +255,24 @@ pub struct ModifiedRow {
     pub source_row: HashMap<String, String>,
     pub target_row: HashMap<String, String>,
     pub differences: Vec<Difference>,
+    /// 1-based lines of the paired records in the original files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_line: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_line: Option<u64>,
+    /// 0-based positions of the paired rows among the data rows of each file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_index: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_index: Option<usize>,
+    /// Similarity (0-1) of the pairing, for rows paired by similar keys or
+    /// content rather than equal keys; 1 for content-match rows paired on
+    /// equal identity columns. Low scores flag pairings worth reviewing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_score: Option<f64>,
+    /// Key of the target row, when rows were paired despite different keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_key: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -63,7 +281,28 @@ pub struct Difference {
     pub column: String,
     pub old_value: String,
     pub new_value: String,
-    pub diff: Vec<DiffChange>, // Word-level diff for highlighting
+    /// Whether the values differ only cosmetically, for filtering
+    #[serde(default)]
+    pub change_kind: ChangeKind,
+    pub diff: Vec<DiffChange>, // Word-level diff for highlighting, or changed paths of JSON cells
+}
+
+impl Difference {
+    /// The change from `new_value` back to `old_value`.
+    pub fn reversed(self) -> Self {
+        let mut diff: Vec<DiffChange> = self.diff.into_iter().map(DiffChange::reversed).collect();
+        // Keep removals ahead of additions within each changed stretch
+        for stretch in diff.split_mut(|change| !change.added && !change.removed) {
+            stretch.sort_by_key(|change| !change.removed);
+        }
+        Self {
+            column: self.column,
+            old_value: self.new_value,
+            new_value: self.old_value,
+            change_kind: self.change_kind,
+            diff,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -72,4 +311,486 @@ pub struct DiffChange {
     pub added: bool,
     pub removed: bool,
     pub value: String,
+    /// JSON Pointer of the changed value in structurally compared JSON cells
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Characters of a replaced word, unchanged or changed, when the word
+    /// it replaces (the neighbouring change) shares some of them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub intraline: Vec<DiffChange>,
+}
+
+impl DiffChange {
+    /// The same change seen from the other file.
+    pub fn reversed(self) -> Self {
+        Self {
+            added: self.removed,
+            removed: self.added,
+            intraline: self.intraline.into_iter().map(DiffChange::reversed).collect(),
+            ..self
+        }
+    }
+}
+
+/// Aggregate counts for a diff, including a breakdown of what kind of
+/// modifications were found so users can tell cosmetic from substantive changes.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffSummary {
+    pub added: usize,
+    pub removed: usize,
+    pub modified: usize,
+    pub unchanged: usize,
+    pub change_taxonomy: ChangeTaxonomy,
+}
+
+/// Short natural-language description of a diff: one catalog message per
+/// clause, for localization, and their English rendering.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffNarrative {
+    pub text: String,
+    pub messages: Vec<crate::messages::Message>,
+}
+
+/// Number of changed cells per change category.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeTaxonomy {
+    pub case_only: usize,
+    pub whitespace_only: usize,
+    pub numeric_delta: usize,
+    pub list_reorder: usize,
+    pub text_edit: usize,
+}
+
+impl ChangeTaxonomy {
+    pub fn count(&mut self, category: ChangeCategory) {
+        match category {
+            ChangeCategory::CaseOnly => self.case_only += 1,
+            ChangeCategory::WhitespaceOnly => self.whitespace_only += 1,
+            ChangeCategory::NumericDelta => self.numeric_delta += 1,
+            ChangeCategory::ListReorder => self.list_reorder += 1,
+            ChangeCategory::TextEdit => self.text_edit += 1,
+        }
+    }
+}
+
+/// Category of a single cell modification.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangeCategory {
+    CaseOnly,
+    WhitespaceOnly,
+    NumericDelta,
+    ListReorder,
+    TextEdit,
+}
+
+/// How far apart two unequal cell values are: the first of whitespace
+/// (trimming, runs of spaces), letter case and number formatting ("1,000.50"
+/// and "1000.5") that accounts for every difference, else substantive.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangeKind {
+    WhitespaceOnly,
+    CaseOnly,
+    NumericFormatOnly,
+    #[default]
+    Substantive,
+}
+
+/// Why content match would (or would not) pair a specific source and target row.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchExplanation {
+    pub source_index: usize,
+    pub target_index: usize,
+    /// Normalized row fingerprints are identical (exact-match path).
+    pub exact_match: bool,
+    /// Average per-column similarity used by fuzzy matching.
+    pub similarity: f64,
+    pub threshold: f64,
+    /// True when the pair clears the threshold (or matches exactly).
+    pub above_threshold: bool,
+    pub columns: Vec<ColumnExplanation>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnExplanation {
+    pub column: String,
+    pub source_value: String,
+    pub target_value: String,
+    pub normalized_source: String,
+    pub normalized_target: String,
+    /// Normalization steps that changed at least one of the two values.
+    pub normalization_steps: Vec<String>,
+    /// `None` for excluded columns or columns missing from the target.
+    pub similarity: Option<f64>,
+    pub algorithm: Option<String>,
+    pub excluded: bool,
+}
+
+/// A value before and after the comparator's normalization.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NormalizationPreview {
+    pub original: String,
+    pub normalized: String,
+    pub steps: Vec<String>,
+}
+
+/// Normalization previews for the first rows of a CSV.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NormalizationSample {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<NormalizationPreview>>,
+}
+
+/// How much a change category matters to downstream consumers.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Formatting only: case or whitespace
+    Cosmetic,
+    /// Same content, different arrangement
+    Minor,
+    /// Different values
+    Major,
+}
+
+/// Changed cells per severity, plus row-level additions and removals.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SeverityTally {
+    pub cosmetic: usize,
+    pub minor: usize,
+    pub major: usize,
+    pub rows_added: usize,
+    pub rows_removed: usize,
+}
+
+/// Change statistics for one compared column.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnStats {
+    pub column: String,
+    pub modified_cells: usize,
+    /// Share of paired rows (modified + unchanged) whose value changed.
+    pub change_rate: f64,
+    pub change_taxonomy: ChangeTaxonomy,
+}
+
+/// Dashboard statistics of a diff (`compute_stats`).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffStats {
+    pub columns: Vec<ColumnStats>,
+    /// Columns with modified cells, most first
+    pub most_changed_columns: Vec<String>,
+    /// Modified rows by number of changed cells: entry `i` counts the rows
+    /// changing `i + 1` cells
+    pub rows_by_change_count: Vec<usize>,
+    /// Share of paired rows (modified + unchanged) that were modified
+    pub modified_rate: f64,
+}
+
+/// Row counts and per-column change totals of a diff, without its rows
+/// (`quick_summary`).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct QuickSummary {
+    pub mode: String,
+    pub summary: DiffSummary,
+    /// As `column_stats` reports them for the full result
+    pub columns: Vec<ColumnStats>,
+}
+
+/// How one column's values shifted between the files
+/// (`column_distributions`).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnDistribution {
+    pub column: String,
+    /// Most frequent value changes among modified cells
+    pub transitions: Vec<ValueTransition>,
+    /// Values whose row counts differ most between the files
+    pub value_deltas: Vec<ValueCountDelta>,
+}
+
+/// Modified cells of a column changing `from` one value `to` another.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueTransition {
+    pub from: String,
+    pub to: String,
+    pub count: usize,
+}
+
+/// Rows holding a value in each file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueCountDelta {
+    pub value: String,
+    pub source_count: usize,
+    pub target_count: usize,
+}
+
+/// Rows of a result meeting filter criteria (`filter_result`).
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FilteredRows {
+    pub added: Vec<AddedRow>,
+    pub removed: Vec<RemovedRow>,
+    pub modified: Vec<ModifiedRow>,
+    pub unchanged: Vec<UnchangedRow>,
+}
+
+/// Caller-supplied details identifying a scheduled run.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SnapshotMetadata {
+    pub label: Option<String>,
+    /// Timestamp of the run, passed through as given (e.g. ISO 8601).
+    pub generated_at: Option<String>,
+    pub source_name: Option<String>,
+    pub target_name: Option<String>,
+}
+
+/// Compact, versioned summary of a diff for drift-tracking dashboards.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffSnapshot {
+    pub schema_version: u32,
+    pub engine_version: String,
+    #[serde(flatten)]
+    pub metadata: SnapshotMetadata,
+    pub mode: String,
+    pub key_columns: Vec<String>,
+    pub excluded_columns: Vec<String>,
+    pub source_rows: usize,
+    pub target_rows: usize,
+    pub counts: DiffSummary,
+    pub severity: SeverityTally,
+    pub columns: Vec<ColumnStats>,
+}
+
+/// Wall-clock time spent in one phase of a diff.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PhaseTiming {
+    /// Progress message code that opened the phase, e.g. "diff.compareRows",
+    /// or "parse.source"/"parse.target" for parsing
+    pub phase: String,
+    pub duration_ms: f64,
+}
+
+/// Anonymous performance figures of one diff run: sizes and timings only,
+/// never headers or values.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffMetrics {
+    pub engine_version: String,
+    pub mode: String,
+    /// Engine that ran, e.g. "primary-key" or "content-match-parallel"
+    pub strategy: String,
+    pub source_rows: usize,
+    pub target_rows: usize,
+    pub columns: usize,
+    pub total_ms: f64,
+    pub phases: Vec<PhaseTiming>,
+    /// Size of the wasm linear memory after the run, which never shrinks;
+    /// absent outside wasm
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_bytes: Option<usize>,
+}
+
+/// Column-level changes between the two files of a diff.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaChanges {
+    /// Target columns with no source counterpart
+    pub added: Vec<String>,
+    /// Source columns with no target counterpart
+    pub removed: Vec<String>,
+    /// Columns compared under different names: explicit mappings (reason
+    /// "mapping"), then detected renames
+    pub renamed: Vec<crate::mapping::MappingProposal>,
+    /// Source names of shared columns that moved relative to the others
+    pub reordered: Vec<String>,
+}
+
+/// Column-level differences between two files, computed from headers alone.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HeaderDiff {
+    pub source_headers: Vec<String>,
+    pub target_headers: Vec<String>,
+    /// Target columns with no source counterpart
+    pub added: Vec<String>,
+    /// Source columns with no target counterpart
+    pub removed: Vec<String>,
+    /// Source names of shared columns that moved relative to the others
+    pub reordered: Vec<String>,
+    /// Columns from the explicit column mapping (reason "mapping") followed
+    /// by proposed renames of the remaining unmatched columns
+    pub renamed: Vec<crate::mapping::MappingProposal>,
+    /// Same columns in the same order
+    pub identical: bool,
+}
+
+/// Outcome of guessing whether a file's first record is a header row.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HeaderDetection {
+    pub has_headers: bool,
+    /// Confidence in `has_headers`, from 0.5 (a coin toss) to 1.0
+    pub confidence: f64,
+    pub evidence: Vec<HeaderEvidence>,
+}
+
+/// One signal that influenced header detection.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HeaderEvidence {
+    /// "numericHeader", "typedColumns", "repeatedValue", "duplicateNames"
+    /// or "blankNames"
+    pub signal: String,
+    /// 0-based indices of the columns showing the signal
+    pub columns: Vec<usize>,
+    /// Contribution to the score; positive favours a header row
+    pub weight: f64,
+}
+
+/// Share of rows affected by one kind of change, extrapolated from a sample.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RateEstimate {
+    pub percent: f64,
+    /// Bounds of the 95% confidence interval; equal to `percent` when every
+    /// row was sampled
+    pub lower_percent: f64,
+    pub upper_percent: f64,
+    pub estimated_rows: usize,
+}
+
+/// Sampled estimate of how much two keyed files differ. Added rows are a
+/// share of the target, removed and modified rows a share of the source.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeRateEstimate {
+    pub source_rows: usize,
+    pub target_rows: usize,
+    pub sampled_source: usize,
+    pub sampled_target: usize,
+    pub added: RateEstimate,
+    pub removed: RateEstimate,
+    pub modified: RateEstimate,
+    /// Seed that placed the samples (`DiffOptions::seed`)
+    pub seed: u64,
+}
+
+/// How a key changed across a three-way diff.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThreeWayStatus {
+    ChangedInOurs,
+    ChangedInTheirs,
+    /// Changed on both sides without touching the same cells differently
+    ChangedInBoth,
+    /// Changed on both sides in incompatible ways
+    Conflict,
+}
+
+/// One key changed on at least one side of a three-way diff. Rows are
+/// `None` where the key does not exist (never added, or deleted).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreeWayChange {
+    pub key: String,
+    pub status: ThreeWayStatus,
+    pub base_row: Option<HashMap<String, String>>,
+    pub ours_row: Option<HashMap<String, String>>,
+    pub theirs_row: Option<HashMap<String, String>>,
+    /// Row after applying both sides' changes; `None` for conflicts and
+    /// for keys deleted by the merge
+    pub merged_row: Option<HashMap<String, String>>,
+    /// Columns changed differently on both sides (empty when a deletion
+    /// conflicts with an edit of the whole row)
+    pub conflicting_columns: Vec<String>,
+}
+
+/// Changed keys per `ThreeWayStatus`.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreeWaySummary {
+    pub changed_in_ours: usize,
+    pub changed_in_theirs: usize,
+    pub changed_in_both: usize,
+    pub conflicts: usize,
+}
+
+/// Changes of two descendants ("ours", "theirs") of a common base file,
+/// matched by key.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreeWayResult {
+    /// Changed keys, sorted by key
+    pub changes: Vec<ThreeWayChange>,
+    pub summary: ThreeWaySummary,
+    pub key_columns: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Totals across the targets of an N-way diff.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NWaySummary {
+    pub targets: usize,
+    /// Row counts summed over all targets
+    pub added: usize,
+    pub removed: usize,
+    pub modified: usize,
+    /// Keys added, removed or modified in every target, sorted
+    pub changed_in_all: Vec<String>,
+    /// Keys added, removed or modified in at least one target
+    pub changed_in_any: usize,
+    /// Source rows unchanged in every target
+    pub unchanged_in_all: usize,
+}
+
+/// One source diffed against several targets: a result per target, in
+/// input order, and a cross-target summary.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NWayResult {
+    pub results: Vec<DiffResult>,
+    pub summary: NWaySummary,
+}
+
+/// How well one column, or a combination of columns, identifies rows.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyCandidate {
+    pub columns: Vec<String>,
+    /// Distinct key values per row; 1.0 when no value repeats
+    pub uniqueness: f64,
+    /// Share of rows with a null value (`DiffOptions::null_values`, else
+    /// empty or "null") in one of the columns
+    pub null_rate: f64,
+    /// `uniqueness × (1 - null_rate)`; suggestions are ranked by it
+    pub score: f64,
+}
+
+/// Per-column key statistics of a file and ranked primary-key suggestions.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyAnalysis {
+    pub rows: usize,
+    /// One entry per column, in header order
+    pub columns: Vec<KeyCandidate>,
+    /// Best single columns and column pairs, best first
+    pub suggestions: Vec<KeyCandidate>,
 }
@@ -1,10 +1,28 @@
 use std::collections::HashMap;
 use std::borrow::Cow;
 use std::hash::{Hash, Hasher};
-use csv::StringRecord;
+use crate::columnar::Fields;
+use crate::normalize::{KeyNormalization, Normalizer};
 use ahash::{AHashMap, AHashSet, AHasher};
 use strsim::{jaro_winkler, normalized_levenshtein};
+use crate::similarity::{CellScorer, WeightedScore};
 
+/// Minimum row similarity for content match to pair two rows as modified.
+pub const MATCH_SIMILARITY_THRESHOLD: f64 = 0.5;
+
+/// Similarity at which fuzzy matching takes a candidate without scoring
+/// the rest.
+pub const EARLY_EXIT_SIMILARITY: f64 = 0.99;
+
+/// Candidate target rows of a source row, from the number of values each
+/// shares with it: most shared values first, ties in file order, at most
+/// `max`.
+pub fn ranked_candidates(shared: AHashMap<usize, usize>, max: Option<usize>) -> Vec<usize> {
+    let mut ranked: Vec<(usize, usize)> = shared.into_iter().collect();
+    ranked.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    ranked.truncate(max.unwrap_or(usize::MAX));
+    ranked.into_iter().map(|(target_idx, _)| target_idx).collect()
+}
 
 pub fn is_empty_or_null(value: &str) -> bool {
     let v = value.trim();
@@ -40,189 +58,231 @@ pub fn normalize_value_cow<'a>(
     }
 }
 
-pub fn normalize_value_with_empty_vs_null(
-    value: &str, 
-    case_sensitive: bool, 
-    ignore_whitespace: bool,
-    ignore_empty_vs_null: bool
-) -> String {
-    normalize_value_cow(value, case_sensitive, ignore_whitespace, ignore_empty_vs_null).into_owned()
-}
-
-/// Build a fingerprint with pre-computed excluded columns set for O(1) lookup
-/// Optimized version with reduced allocations and better performance
+/// Fingerprint of a row's normalized cells, hashed cell by cell so wide rows
+/// never build a concatenated string. Rows with equal fingerprints are equal
+/// after normalization, up to a 64-bit collision.
 #[inline]
-pub fn get_row_fingerprint_fast(
-    row: &StringRecord,
+pub fn get_row_fingerprint_hash<R: Fields + ?Sized>(
+    row: &R,
     headers: &[String],
     header_map: &AHashMap<String, usize>,
-    case_sensitive: bool,
-    ignore_whitespace: bool,
-    ignore_empty_vs_null: bool,
+    normalizer: &Normalizer,
     excluded_set: &AHashSet<String>,
-) -> String {
-    // Pre-calculate capacity to avoid reallocations
-    let non_excluded_count = headers.iter().filter(|h| !excluded_set.contains(h.as_str())).count();
-    let mut result = String::with_capacity(non_excluded_count * 32); // Increased estimate
-    
-    let mut first = true;
+) -> u64 {
+    let mut hasher = AHasher::default();
     
     for h in headers {
         if excluded_set.contains(h) {
             continue;
         }
         
-        if !first {
-            result.push_str("||");
-        }
-        first = false;
-        
         let val = if let Some(&idx) = header_map.get(h) {
-            row.get(idx).unwrap_or("")
+            row.field(idx).unwrap_or("")
         } else {
             ""
         };
         
-        // Apply normalization inline to reduce function call overhead
-        let trimmed = if ignore_whitespace { val.trim() } else { val };
-        
-        if ignore_empty_vs_null && (trimmed.is_empty() || trimmed.eq_ignore_ascii_case("null")) {
-            result.push_str("EMPTY_OR_NULL");
-        } else if case_sensitive {
-            if ignore_whitespace && trimmed.len() != val.len() {
-                result.push_str(trimmed);
-            } else {
-                result.push_str(val);
-            }
-        } else {
-            // Lowercase allocation is unavoidable for case-insensitive comparison
-            result.push_str(&trimmed.to_lowercase());
-        }
+        hash_normalized_value(h, val, normalizer, &mut hasher);
     }
     
-    result
+    hasher.finish()
 }
 
-/// Ultra-fast fingerprint using 64-bit hash instead of string concatenation
-/// Much faster for large datasets with minimal collision risk
+/// Feed the normalized form of `val` into `hasher`, allocating only for
+/// canonicalized values. Values hash equal exactly when they are equal after
+/// `Normalizer::normalize` (folding case per char, as `str::to_lowercase`
+/// does outside of final-sigma context).
 #[inline]
-pub fn get_row_fingerprint_hash(
-    row: &StringRecord,
-    headers: &[String],
-    header_map: &AHashMap<String, usize>,
-    case_sensitive: bool,
-    ignore_whitespace: bool,
-    ignore_empty_vs_null: bool,
-    excluded_set: &AHashSet<String>,
-) -> u64 {
-    let mut hasher = AHasher::default();
+fn hash_normalized_value<H: Hasher>(column: &str, val: &str, normalizer: &Normalizer, hasher: &mut H) {
+    let is_null = normalizer.null_values.is_null(column, val);
+    let canonical = normalizer.canonicalize(column, val);
+    let val = canonical.as_ref();
+    let Normalizer { case_sensitive, ignore_whitespace, .. } = *normalizer;
+
+    // Apply normalization inline and hash directly
+    let trimmed = if ignore_whitespace { val.trim() } else { val };
     
-    for h in headers {
-        if excluded_set.contains(h) {
-            continue;
-        }
-        
-        let val = if let Some(&idx) = header_map.get(h) {
-            row.get(idx).unwrap_or("")
+    if is_null {
+        crate::normalize::NULL_VALUE.hash(hasher);
+    } else if case_sensitive {
+        if ignore_whitespace && trimmed.len() != val.len() {
+            trimmed.hash(hasher);
         } else {
-            ""
-        };
-        
-        // Apply normalization inline and hash directly
-        let trimmed = if ignore_whitespace { val.trim() } else { val };
-        
-        if ignore_empty_vs_null && (trimmed.is_empty() || trimmed.eq_ignore_ascii_case("null")) {
-            "EMPTY_OR_NULL".hash(&mut hasher);
-        } else if case_sensitive {
-            if ignore_whitespace && trimmed.len() != val.len() {
-                trimmed.hash(&mut hasher);
-            } else {
-                val.hash(&mut hasher);
+            val.hash(hasher);
+        }
+    } else {
+        // Hash the lowercase chars without allocating a string
+        if trimmed.is_ascii() {
+            for byte in trimmed.bytes() {
+                hasher.write_u32(byte.to_ascii_lowercase() as u32);
             }
         } else {
-            // Hash lowercase version without allocating string
-            for byte in trimmed.bytes() {
-                if byte >= b'A' && byte <= b'Z' {
-                    (byte + 32).hash(&mut hasher); // Convert to lowercase
-                } else {
-                    byte.hash(&mut hasher);
-                }
+            for c in trimmed.chars().flat_map(char::to_lowercase) {
+                hasher.write_u32(c as u32);
             }
         }
-        
-        // Add separator to avoid collisions between different column combinations
-        hasher.write_u8(0xFF);
     }
     
-    hasher.finish()
+    // Add separator to avoid collisions between different column combinations
+    hasher.write_u8(0xFF);
 }
 
-pub fn get_row_fingerprint(
-    row: &StringRecord,
-    headers: &[String],
-    header_map: &AHashMap<String, usize>,
-    case_sensitive: bool,
-    ignore_whitespace: bool,
-    ignore_empty_vs_null: bool,
+/// CRC32 of a row's normalized `cells` (header name and field index).
+/// Rows with equal checksums are equal after normalization, up to a 32-bit
+/// collision, so primary-key diffs only inspect pairs whose checksums differ.
+pub fn row_crc<'a, R: Fields + ?Sized>(
+    row: &R,
+    cells: impl IntoIterator<Item = (&'a String, usize)>,
+    normalizer: &Normalizer,
+) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    for (column, idx) in cells {
+        hash_normalized_value(column, row.field(idx).unwrap_or(""), normalizer, &mut hasher);
+    }
+    hasher.finalize()
+}
+
+/// Fixed keys completing a run seed into an ahash state.
+const SEED_KEYS: [u64; 3] = [0x243F_6A88_85A3_08D3, 0x1319_8A2E_0370_7344, 0xA409_3822_299F_31D0];
+
+/// Hash state for maps and sets whose iteration order reaches the output
+/// (row order, fuzzy-match tie-breaks). Equal seeds give equal orders.
+pub fn seeded_state(seed: u64) -> ahash::RandomState {
+    ahash::RandomState::with_seeds(seed, SEED_KEYS[0], SEED_KEYS[1], SEED_KEYS[2])
+}
+
+/// A compared column: header name and its index in source and target rows.
+pub type ColumnPair<'a> = (&'a String, usize, usize);
+
+/// Columns a diff compares, in source header order: not excluded and
+/// present on both sides.
+pub fn compared_columns<'a>(
+    source_headers: &'a [String],
+    source_header_map: &AHashMap<String, usize>,
+    target_header_map: &AHashMap<String, usize>,
     excluded_columns: &[String],
-) -> String {
-    headers.iter()
+) -> Vec<ColumnPair<'a>> {
+    source_headers.iter()
         .filter(|h| !excluded_columns.contains(h))
-        .map(|h| {
-            let val = if let Some(&idx) = header_map.get(h) {
-                row.get(idx).unwrap_or("")
-            } else {
-                ""
-            };
-            normalize_value_with_empty_vs_null(val, case_sensitive, ignore_whitespace, ignore_empty_vs_null)
-        })
-        .collect::<Vec<_>>()
-        .join("||")
+        .filter_map(|h| Some((h, *source_header_map.get(h)?, *target_header_map.get(h)?)))
+        .collect()
 }
 
-pub fn get_row_key(
-    row: &StringRecord,
+/// Drop the columns whose normalized values agree for every keyed row pair,
+/// so the per-row comparison only visits columns that changed somewhere.
+///
+/// Each column's values are hashed in pair order on both sides; matching
+/// aggregate hashes mean the column is unchanged (up to a 64-bit collision).
+pub fn changed_columns<'a, S: Fields, T: Fields>(
+    columns: Vec<ColumnPair<'a>>,
+    pairs: impl IntoIterator<Item = (S, T)>,
+    normalizer: &Normalizer,
+) -> Vec<ColumnPair<'a>> {
+    let mut hashers: Vec<(AHasher, AHasher)> = columns.iter().map(|_| Default::default()).collect();
+    for (source_row, target_row) in pairs {
+        for (&(column, source_idx, target_idx), (source_hash, target_hash)) in columns.iter().zip(&mut hashers) {
+            let source_val = source_row.field(source_idx).unwrap_or("");
+            let target_val = target_row.field(target_idx).unwrap_or("");
+            hash_normalized_value(column, source_val, normalizer, source_hash);
+            hash_normalized_value(column, target_val, normalizer, target_hash);
+        }
+    }
+
+    columns.into_iter()
+        .zip(hashers)
+        .filter(|(_, (source_hash, target_hash))| source_hash.finish() != target_hash.finish())
+        .map(|(column, _)| column)
+        .collect()
+}
+
+/// Key of `row`: its `key_columns` values, cleaned up by `normalization`.
+/// A single column's value is the key as is; composite keys join the values
+/// with `|` after escaping `\` and `|` inside them, so ("a|b", "c") and
+/// ("a", "b|c") get different keys. Surrogate keys are hashed afterwards.
+pub fn get_row_key<R: Fields + ?Sized>(
+    row: &R,
     header_map: &AHashMap<String, usize>,
     key_columns: &[String],
+    normalization: &KeyNormalization,
 ) -> String {
-    key_columns.iter()
-        .map(|k| {
-            if let Some(&idx) = header_map.get(k) {
-                row.get(idx).unwrap_or("")
-            } else {
-                ""
+    let value = |column: &String| match header_map.get(column) {
+        Some(&idx) => normalization.apply(column, row.field(idx).unwrap_or("")),
+        None => Cow::Borrowed(""),
+    };
+    let key = if let [column] = key_columns {
+        value(column).into_owned()
+    } else {
+        let mut key = String::new();
+        for (i, column) in key_columns.iter().enumerate() {
+            if i > 0 {
+                key.push('|');
             }
-        })
-        .collect::<Vec<_>>()
-        .join("|")
+            push_escaped_key_part(&mut key, &value(column));
+        }
+        key
+    };
+    if normalization.hash { surrogate_key(key.as_bytes()) } else { key }
 }
 
-pub fn record_to_hashmap(
-    row: &StringRecord,
+/// Surrogate key of a (composite) key value: 16 hex digits of its 64-bit
+/// FNV-1a hash, which unlike the hash maps' hasher is the same on every
+/// platform and version.
+pub fn surrogate_key(key: &[u8]) -> String {
+    let hash = key.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3));
+    format!("{:016x}", hash)
+}
+
+/// Append `part` to a composite key with `\` and `|` backslash-escaped.
+pub fn push_escaped_key_part(key: &mut String, part: &str) {
+    for c in part.chars() {
+        if c == '\\' || c == '|' {
+            key.push('\\');
+        }
+        key.push(c);
+    }
+}
+
+pub fn record_to_hashmap<R: Fields + ?Sized>(
+    row: &R,
     headers: &[String],
 ) -> HashMap<String, String> {
     headers.iter().enumerate()
-        .map(|(i, h)| (h.clone(), row.get(i).unwrap_or("").to_string()))
+        .map(|(i, h)| (h.clone(), row.field(i).unwrap_or("").to_string()))
         .collect()
 }
 
-/// Calculate row similarity score using strsim algorithms.
-/// Combines Jaro-Winkler for short fields and Levenshtein for longer text.
-/// Returns a value between 0.0 and 1.0 where higher means more similar.
+/// `rows` as header → value maps for `DatasetMetadata::rows`, or none when
+/// `include` is off (`DiffOptions::include_dataset_rows`).
+pub fn dataset_rows<'a, R: Fields + 'a>(
+    rows: impl IntoIterator<Item = &'a R>,
+    headers: &[String],
+    include: bool,
+) -> Vec<HashMap<String, String>> {
+    if !include {
+        return Vec::new();
+    }
+    rows.into_iter().map(|row| record_to_hashmap(row, headers)).collect()
+}
+
+/// 1-based line where `row` starts in its original file, if the parser
+/// recorded one.
+pub fn record_line<R: Fields + ?Sized>(row: &R) -> Option<u64> {
+    row.line()
+}
 
-/// Calculate row similarity score using strsim algorithms.
-/// Combines Jaro-Winkler for short fields and Levenshtein for longer text.
+/// Calculate row similarity score using strsim algorithms: the average of
+/// the cell similarities, scored and weighted by `scorer`.
 /// Returns a value between 0.0 and 1.0 where higher means more similar.
-pub fn calculate_row_similarity(
-    row1: &StringRecord,
-    row2: &StringRecord,
+pub fn calculate_row_similarity<A: Fields + ?Sized, B: Fields + ?Sized>(
+    row1: &A,
+    row2: &B,
     headers: &[String],
     header_map1: &AHashMap<String, usize>,
     header_map2: &AHashMap<String, usize>,
     excluded_columns: &[String],
+    scorer: &CellScorer,
 ) -> f64 {
-    let mut total_similarity = 0.0;
-    let mut compared_fields = 0;
+    let mut score = WeightedScore::default();
 
     for header in headers {
         if excluded_columns.contains(header) {
@@ -233,27 +293,15 @@ pub fn calculate_row_similarity(
         let idx2 = header_map2.get(header);
 
         if let (Some(&i1), Some(&i2)) = (idx1, idx2) {
-            let val1 = row1.get(i1).unwrap_or("");
-            let val2 = row2.get(i2).unwrap_or("");
-
-            // Use Jaro-Winkler for short strings (better for names, IDs)
-            // Use Levenshtein for longer strings (better for descriptions)
-            let similarity = if val1.len() <= 20 && val2.len() <= 20 {
-                jaro_winkler(val1, val2)
-            } else {
-                normalized_levenshtein(val1, val2)
-            };
-
-            total_similarity += similarity;
-            compared_fields += 1;
+            let val1 = row1.field(i1).unwrap_or("");
+            let val2 = row2.field(i2).unwrap_or("");
+
+            let (cell_score, _) = scorer.score(header, val1, val2);
+            score.add(cell_score, scorer.weight(header));
         }
     }
 
-    if compared_fields > 0 {
-        total_similarity / compared_fields as f64
-    } else {
-        0.0
-    }
+    score.average()
 }
 
 pub fn similarity_jaro_winkler(a: &str, b: &str) -> f64 {
@@ -263,3 +311,115 @@ pub fn similarity_jaro_winkler(a: &str, b: &str) -> f64 {
 pub fn similarity_levenshtein(a: &str, b: &str) -> f64 {
     normalized_levenshtein(a, b)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_columns_skips_unchanged() {
+        let headers: Vec<String> = ["id", "name", "city", "score"].iter().map(|h| h.to_string()).collect();
+        let header_map: AHashMap<String, usize> = headers.iter().enumerate().map(|(i, h)| (h.clone(), i)).collect();
+        let source = [csv::StringRecord::from(vec!["1", "Alice", "Oslo", "10"]), csv::StringRecord::from(vec!["2", "Bob", "Rome", "20"])];
+        let target = [csv::StringRecord::from(vec!["1", "ALICE", "Oslo", "10"]), csv::StringRecord::from(vec!["2", "Bob", "Rome", "25"])];
+        let pairs = || source.iter().zip(target.iter());
+        let names = |columns: Vec<ColumnPair>| columns.into_iter().map(|(h, _, _)| h.clone()).collect::<Vec<_>>();
+
+        let columns = compared_columns(&headers, &header_map, &header_map, &["city".to_string()]);
+        assert_eq!(names(columns.clone()), vec!["id", "name", "score"]);
+        assert_eq!(names(changed_columns(columns.clone(), pairs(), &Normalizer::from_flags(true, false, false))), vec!["name", "score"]);
+        assert_eq!(names(changed_columns(columns, pairs(), &Normalizer::from_flags(false, false, false))), vec!["score"]);
+    }
+
+    #[test]
+    fn test_composite_keys_do_not_collide() {
+        let key_columns = vec!["a".to_string(), "b".to_string()];
+        let header_map: AHashMap<String, usize> = key_columns.iter().enumerate().map(|(i, h)| (h.clone(), i)).collect();
+        let key = |values: Vec<&str>| get_row_key(&csv::StringRecord::from(values), &header_map, &key_columns, &KeyNormalization::default());
+        assert_ne!(key(vec!["a|b", "c"]), key(vec!["a", "b|c"]));
+        assert_ne!(key(vec!["a\\", "|c"]), key(vec!["a\\|", "c"]));
+        assert_eq!(key(vec!["a|b", "c\\d"]), r"a\|b|c\\d");
+        // Single-column keys are reported unescaped
+        assert_eq!(get_row_key(&csv::StringRecord::from(vec!["a|b"]), &header_map, &key_columns[..1], &KeyNormalization::default()), "a|b");
+
+        let source = "a,b,v\na|b,c,1\na,b|c,2\n";
+        let target = "a,b,v\na|b,c,1\na,b|c,3\n";
+        let options = crate::options::DiffOptions { key_columns, ..Default::default() };
+        let strings = crate::primary_key::diff_primary_key_with_options(source, target, &options, |_, _| {}).unwrap();
+        let bytes = crate::byte_parse::diff_bytes_with_options(source.as_bytes(), target.as_bytes(), &options, |_, _| {}).unwrap();
+        for result in [strings, bytes] {
+            assert_eq!((result.unchanged.len(), result.modified.len()), (1, 1));
+            assert_eq!(result.modified[0].key, r"a|b\|c");
+        }
+    }
+
+    #[test]
+    fn test_row_crc_skips_only_equal_rows() {
+        let headers: Vec<String> = ["id", "name"].iter().map(|h| h.to_string()).collect();
+        let cells = || headers.iter().zip(0..);
+        let crc = |values: Vec<&str>, normalizer: &Normalizer| row_crc(&csv::StringRecord::from(values), cells(), normalizer);
+        let insensitive = Normalizer::from_flags(false, true, false);
+        assert_eq!(crc(vec!["1", " Alice"], &insensitive), crc(vec!["1", "ALICE "], &insensitive));
+        assert_ne!(crc(vec!["1", "Alice"], &insensitive), crc(vec!["1", "Alicia"], &insensitive));
+        assert_ne!(crc(vec!["1", "Alice"], &Normalizer::from_flags(true, false, false)), crc(vec!["1", "ALICE"], &Normalizer::from_flags(true, false, false)));
+
+        // Case folding covers non-ASCII letters too
+        let source = "id,name\n1,Émile\n2,Bob\n";
+        let target = "id,name\n1,émile\n2,Bobby\n";
+        let options = crate::options::DiffOptions { key_columns: vec!["id".to_string()], case_sensitive: false, ..Default::default() };
+        let result = crate::primary_key::diff_primary_key_with_options(source, target, &options, |_, _| {}).unwrap();
+        assert_eq!((result.unchanged.len(), result.modified.len()), (1, 1));
+    }
+
+    #[test]
+    fn test_seeded_runs_are_reproducible() {
+        let rows = |range: std::ops::Range<usize>, value: &str| -> String {
+            std::iter::once("id,value\n".to_string()).chain(range.map(|i| format!("{},{}{}\n", i, value, i % 7))).collect()
+        };
+        let (source, target) = (rows(0..200, "v"), rows(100..300, "w"));
+        let keys = |result: &crate::types::DiffResult| -> Vec<String> {
+            let added = result.added.iter().map(|r| r.key.clone());
+            let removed = result.removed.iter().map(|r| r.key.clone());
+            added.chain(removed).chain(result.modified.iter().map(|r| r.key.clone())).collect()
+        };
+        for use_parallel in [false, true] {
+            let options = crate::options::DiffOptions { key_columns: vec!["id".to_string()], use_parallel, seed: 42, ..Default::default() };
+            let first = crate::core::diff_with_options(&source, &target, &options, |_, _| {}).unwrap();
+            let second = crate::core::diff_with_options(&source, &target, &options, |_, _| {}).unwrap();
+            assert_eq!(first.seed, 42);
+            assert_eq!(keys(&first), keys(&second));
+        }
+    }
+
+    #[test]
+    fn test_wide_rows_content_match() {
+        let columns = 1000;
+        let header: Vec<String> = (0..columns).map(|c| format!("c{}", c)).collect();
+        let row = |r: usize| (0..columns).map(|c| format!("r{}v{}", r, c)).collect::<Vec<_>>().join(",");
+        let source: String = std::iter::once(header.join(",")).chain((0..50).map(row)).collect::<Vec<_>>().join("\n");
+        // Same rows in reverse order, one cell changed in row 7
+        let target: String = std::iter::once(header.join(","))
+            .chain((0..50).rev().map(|r| if r == 7 { row(r).replacen("r7v500", "changed", 1) } else { row(r) }))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        for use_parallel in [false, true] {
+            let options = crate::options::DiffOptions { mode: "content-match".to_string(), use_parallel, ..Default::default() };
+            let result = crate::core::diff_with_options(&source, &target, &options, |_, _| {}).unwrap();
+            assert_eq!((result.unchanged.len(), result.modified.len()), (49, 1), "parallel={}", use_parallel);
+            assert_eq!(result.modified[0].differences[0].column, "c500");
+        }
+
+        let headers = header;
+        let header_map: AHashMap<String, usize> = headers.iter().enumerate().map(|(i, h)| (h.clone(), i)).collect();
+        let fingerprint = |line: &str| get_row_fingerprint_hash(
+            &csv::StringRecord::from(line.split(',').collect::<Vec<_>>()),
+            &headers,
+            &header_map,
+            &Normalizer::from_flags(false, false, false),
+            &AHashSet::new(),
+        );
+        assert_eq!(fingerprint(&row(3)), fingerprint(&row(3).to_uppercase()));
+        assert_ne!(fingerprint(&row(3)), fingerprint(&row(4)));
+    }
+}
@@ -4,9 +4,15 @@ use js_sys::Function;
 use csv::ReaderBuilder;
 use ahash::AHashMap;
 use crate::types::ParseResult;
+use crate::options::{DiffOptions, TextGranularity};
+use crate::parse::ParseOptions;
+use crate::session::DiffSession;
+use crate::explain::RowRef;
+use crate::writer::{ModifiedSide, WriteOptions};
+use crate::messages::{error_text, CATALOG};
 use crate::utils::record_to_hashmap;
 use crate::binary_encoder::BinaryEncoder;
-use crate::memory::{set_last_binary_result_length, set_last_binary_result_capacity};
+use crate::memory::{leak_binary_result, BinaryResult};
 
 use rayon::prelude::*;
 use std::time::Instant;
@@ -25,6 +31,23 @@ pub fn parse_csv(csv_content: &str, has_headers: bool) -> Result<JsValue, JsValu
     Ok(result.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
 }
 
+/// Parse with explicit parse options (`hasHeaders`, `delimiter`).
+#[wasm_bindgen]
+pub fn parse_csv_with_options(csv_content: &str, options_val: JsValue) -> Result<JsValue, JsValue> {
+    let options: ParseOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let (headers, rows, _) = crate::parse::parse_csv_with_options(csv_content, &options)
+        .map_err(|e| JsValue::from_str(&error_text(e.as_ref(), options.message_format)))?;
+
+    let rows_hashmap: Vec<_> = rows.iter()
+        .map(|r| record_to_hashmap(r, &headers))
+        .collect();
+
+    let result = ParseResult { headers, rows: rows_hashmap };
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(result.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
 #[wasm_bindgen]
 pub fn parse_csv_headers_only(csv_content: &str, has_headers: bool) -> Result<JsValue, JsValue> {
     let (headers, _, _) = crate::core::parse_csv_internal(csv_content, has_headers)
@@ -62,13 +85,30 @@ pub fn parse_csv_headers_only(csv_content: &str, has_headers: bool) -> Result<Js
     Ok(result.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
 }
 
+/// Guess whether the first row of a CSV is a header row. Returns
+/// `{ hasHeaders, confidence, evidence: [{ signal, columns, weight }] }`.
+/// `options_val` is an optional `ParseOptions` object for the delimiter;
+/// pass `hasHeaders: "auto"` to the diff entry points to apply the same check.
+#[wasm_bindgen]
+pub fn detect_headers(csv_content: &str, options_val: JsValue) -> Result<JsValue, JsValue> {
+    let options: ParseOptions = if options_val.is_undefined() || options_val.is_null() {
+        ParseOptions::default()
+    } else {
+        serde_wasm_bindgen::from_value(options_val).map_err(|e| JsValue::from_str(&e.to_string()))?
+    };
+    let detection = crate::parse::detect_headers(csv_content, &options)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(detection.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
 #[wasm_bindgen]
 pub fn parse_csv_with_progress(csv_content: &str, has_headers: bool, on_progress: &Function) -> Result<JsValue, JsValue> {
     // Use the new streaming parser for better memory efficiency and progress reporting
-    let (headers, rows, _) = crate::parse::parse_csv_streaming(
+    let (headers, rows, _) = crate::parse::parse_csv_streaming_with_options(
         csv_content, 
-        has_headers, 
-        5000, // Process in chunks of 5000 rows
+        &ParseOptions::new(has_headers), 
+        &crate::streaming::StreamingConfig::default(),
         |percent, message| {
             on_progress.call2(&JsValue::NULL, &JsValue::from_f64(percent), &JsValue::from_str(message));
         }
@@ -173,9 +213,816 @@ pub fn diff_csv(
     Ok(result.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
 }
 
+/// Options-object entry point: `options_val` is a camelCase `DiffOptions`
+/// (mode, keyColumns, delimiter, hasHeaders, useParallel, ...).
+/// The optional `on_metrics` receives a `DiffMetrics` object when the diff
+/// completes (row/column counts, phase durations, engine, memory).
+#[wasm_bindgen]
+pub fn diff_csv_with_options(
+    source_csv: &str,
+    target_csv: &str,
+    options_val: JsValue,
+    on_progress: &Function,
+    on_metrics: Option<Function>,
+) -> Result<JsValue, JsValue> {
+    let options: DiffOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let callback = |progress: f64, message: &str| {
+        let this = JsValue::NULL;
+        let _ = on_progress.call2(&this, &JsValue::from_f64(progress), &JsValue::from_str(message));
+    };
+
+    let result = match on_metrics {
+        Some(on_metrics) => crate::core::diff_with_metrics(source_csv, target_csv, &options, callback, |metrics| {
+            let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+            if let Ok(metrics) = metrics.serialize(&serializer) {
+                let _ = on_metrics.call1(&JsValue::NULL, &metrics);
+            }
+        }),
+        None => crate::core::diff_with_options(source_csv, target_csv, &options, callback),
+    };
+    let result = result
+        .map_err(|e| JsValue::from_str(&error_text(e.as_ref(), options.parse.message_format)))?;
+
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(result.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// `diff_csv_with_options`, calling `on_batch` with `{ index, added, removed,
+/// modified, unchanged }` every `batch_size` rows (5000 by default) as they
+/// are classified. Returns the result without rows, for its summary and
+/// metadata. If `on_batch` throws, the diff stops with that error.
+#[wasm_bindgen]
+pub fn diff_csv_batched(
+    source_csv: &str,
+    target_csv: &str,
+    options_val: JsValue,
+    batch_size: Option<usize>,
+    on_batch: &Function,
+    on_progress: &Function,
+) -> Result<JsValue, JsValue> {
+    let options: DiffOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let callback = |progress: f64, message: &str| {
+        let this = JsValue::NULL;
+        let _ = on_progress.call2(&this, &JsValue::from_f64(progress), &JsValue::from_str(message));
+    };
+    let send = |batch: crate::batches::RowBatch| -> Result<(), Box<dyn std::error::Error>> {
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        let batch = batch.serialize(&serializer).map_err(|e| e.to_string())?;
+        on_batch.call1(&JsValue::NULL, &batch)
+            .map_err(|e| e.as_string().unwrap_or_else(|| "Batch callback failed".to_string()))?;
+        Ok(())
+    };
+
+    let batch_size = batch_size.unwrap_or(crate::batches::DEFAULT_BATCH_SIZE);
+    let result = crate::batches::diff_in_batches(source_csv, target_csv, &options, batch_size, send, callback)
+        .map_err(|e| JsValue::from_str(&error_text(e.as_ref(), options.parse.message_format)))?;
+
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(result.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// Options-object diff over raw bytes (e.g. `Uint8Array`s from `File.arrayBuffer()`).
+/// Skips the JS string round-trip and per-field allocations; result rows are
+/// decoded lossily, and `source.rows`/`target.rows` are filled only with
+/// `includeDatasetRows`.
+#[wasm_bindgen]
+pub fn diff_csv_bytes(
+    source: &[u8],
+    target: &[u8],
+    options_val: JsValue,
+    on_progress: &Function,
+) -> Result<JsValue, JsValue> {
+    let options: DiffOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let callback = |progress: f64, message: &str| {
+        let this = JsValue::NULL;
+        let _ = on_progress.call2(&this, &JsValue::from_f64(progress), &JsValue::from_str(message));
+    };
+
+    let result = crate::byte_parse::diff_bytes_with_options(source, target, &options, callback)
+        .map_err(|e| JsValue::from_str(&error_text(e.as_ref(), options.parse.message_format)))?;
+
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(result.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// Diff two files framed in one buffer (see `encode_frame_header`), e.g. to
+/// pass a `Uint8Array` assembled on the JS side in a single call.
+#[wasm_bindgen]
+pub fn diff_framed(buffer: &[u8], options_val: JsValue, on_progress: &Function) -> Result<JsValue, JsValue> {
+    let options: DiffOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    diff_framed_slice(buffer, &options, on_progress)
+}
+
+/// `diff_framed` over a buffer from `alloc` that JS streamed the framed
+/// input into, avoiding the copy into wasm memory. The buffer stays
+/// allocated; release it with `dealloc`.
+#[wasm_bindgen]
+pub fn diff_framed_ptr(ptr: *const u8, len: usize, options_val: JsValue, on_progress: &Function) -> Result<JsValue, JsValue> {
+    let options: DiffOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    crate::memory::with_allocated(ptr, len, |buffer| diff_framed_slice(buffer, &options, on_progress))
+        .ok_or_else(|| JsValue::from_str("Pointer is not a live alloc buffer of the given length."))?
+}
+
+fn diff_framed_slice(buffer: &[u8], options: &DiffOptions, on_progress: &Function) -> Result<JsValue, JsValue> {
+    let callback = |progress: f64, message: &str| {
+        let this = JsValue::NULL;
+        let _ = on_progress.call2(&this, &JsValue::from_f64(progress), &JsValue::from_str(message));
+    };
+
+    let result = crate::framing::diff_framed(buffer, options, callback)
+        .map_err(|e| JsValue::from_str(&error_text(e.as_ref(), options.parse.message_format)))?;
+
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(result.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// Frame header for a source and a target of the given byte lengths.
+/// Dialects are optional `{ delimiter, hasHeaders }` objects overriding the
+/// diff options per file.
+#[wasm_bindgen]
+pub fn encode_frame_header(
+    source_len: usize,
+    source_dialect: JsValue,
+    target_len: usize,
+    target_dialect: JsValue,
+) -> Result<Vec<u8>, JsValue> {
+    let dialect = |value: JsValue| -> Result<crate::framing::Dialect, JsValue> {
+        if value.is_undefined() || value.is_null() {
+            return Ok(Default::default());
+        }
+        serde_wasm_bindgen::from_value(value).map_err(|e| JsValue::from_str(&e.to_string()))
+    };
+    crate::framing::encode_frame_header(source_len, &dialect(source_dialect)?, target_len, &dialect(target_dialect)?)
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Diff two pasted tables (Markdown or tab-separated clipboard content).
+#[wasm_bindgen]
+pub fn diff_pasted_tables(
+    source_text: &str,
+    target_text: &str,
+    options_val: JsValue,
+    on_progress: &Function,
+) -> Result<JsValue, JsValue> {
+    let options: DiffOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let callback = |progress: f64, message: &str| {
+        let this = JsValue::NULL;
+        let _ = on_progress.call2(&this, &JsValue::from_f64(progress), &JsValue::from_str(message));
+    };
+
+    let result = crate::paste::diff_pasted_tables(source_text, target_text, &options, callback)
+        .map_err(|e| JsValue::from_str(&error_text(e.as_ref(), options.parse.message_format)))?;
+
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(result.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// Convert a pasted table to CSV text, e.g. to preview or save it.
+#[wasm_bindgen]
+pub fn pasted_table_to_csv(text: &str) -> Result<String, JsValue> {
+    crate::paste::pasted_table_to_csv(text).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Primary-key diff of one table in two SQLite database files (raw bytes).
+/// `keyColumns` defaults to the table's declared primary key.
+#[cfg(feature = "sqlite")]
+#[wasm_bindgen]
+pub fn diff_sqlite_tables(
+    source: &[u8],
+    target: &[u8],
+    table: &str,
+    options_val: JsValue,
+    on_progress: &Function,
+) -> Result<JsValue, JsValue> {
+    let options: DiffOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let callback = |progress: f64, message: &str| {
+        let this = JsValue::NULL;
+        let _ = on_progress.call2(&this, &JsValue::from_f64(progress), &JsValue::from_str(message));
+    };
+
+    let result = crate::sqlite::diff_sqlite_tables(source, target, table, &options, callback)
+        .map_err(|e| JsValue::from_str(&error_text(e.as_ref(), options.parse.message_format)))?;
+
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(result.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// Run a diff and retain its inputs and result in a session.
+/// Returns the session handle; release it with `free_diff_session`.
+#[wasm_bindgen]
+pub fn create_diff_session(
+    source_csv: &str,
+    target_csv: &str,
+    options_val: JsValue,
+    on_progress: &Function,
+) -> Result<u32, JsValue> {
+    let options: DiffOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let callback = |progress: f64, message: &str| {
+        let this = JsValue::NULL;
+        let _ = on_progress.call2(&this, &JsValue::from_f64(progress), &JsValue::from_str(message));
+    };
+
+    let message_format = options.parse.message_format;
+    let session = DiffSession::run(source_csv, target_csv, options, callback)
+        .map_err(|e| JsValue::from_str(&error_text(e.as_ref(), message_format)))?;
+    Ok(crate::session::insert(session))
+}
+
+/// Get the diff result retained by a session
+#[wasm_bindgen]
+pub fn get_session_result(handle: u32) -> Result<JsValue, JsValue> {
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    crate::session::with_session(handle, |session| {
+        session.result.serialize(&serializer).map_err(|e| e.to_string().into())
+    }).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Up to `count` rows of a session's `"source"` or `"target"` input from
+/// index `start`, for results run without `includeDatasetRows`.
+#[wasm_bindgen]
+pub fn get_session_rows(handle: u32, side: JsValue, start: usize, count: usize) -> Result<JsValue, JsValue> {
+    let side: ModifiedSide = serde_wasm_bindgen::from_value(side)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    crate::session::with_session(handle, |session| {
+        session.rows(side, start, count).serialize(&serializer).map_err(|e| e.to_string().into())
+    }).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Release a session. Returns false if the handle was unknown.
+#[wasm_bindgen]
+pub fn free_diff_session(handle: u32) -> bool {
+    crate::session::remove(handle)
+}
+
+/// Rows of a session's result meeting `criteria_val`, a camelCase
+/// `FilterCriteria` object: `statuses`, `changedColumns`, `keyPrefix`,
+/// `valuePattern` (a regular expression) and `minDifferences`. Returns
+/// `{ added, removed, modified, unchanged }`.
+#[wasm_bindgen]
+pub fn filter_results(handle: u32, criteria_val: JsValue) -> Result<JsValue, JsValue> {
+    let criteria: crate::filter::FilterCriteria = if criteria_val.is_undefined() || criteria_val.is_null() {
+        Default::default()
+    } else {
+        serde_wasm_bindgen::from_value(criteria_val).map_err(|e| JsValue::from_str(&e.to_string()))?
+    };
+    let rows = crate::session::with_session(handle, |session| crate::filter::filter_result(&session.result, &criteria))
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    rows.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Rows of a session's result containing `query` in their key or a cell,
+/// with the UTF-16 ranges of every occurrence per field for highlighting.
+/// `options_val` is a camelCase `SearchOptions` object (`caseSensitive`,
+/// `changedOnly`, `limit`).
+#[wasm_bindgen]
+pub fn search_results(handle: u32, query: &str, options_val: JsValue) -> Result<JsValue, JsValue> {
+    let options: crate::search::SearchOptions = if options_val.is_undefined() || options_val.is_null() {
+        Default::default()
+    } else {
+        serde_wasm_bindgen::from_value(options_val).map_err(|e| JsValue::from_str(&e.to_string()))?
+    };
+    let hits = crate::session::with_session(handle, |session| Ok(crate::search::search_result(&session.result, query, &options)))
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    hits.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Reorder the rows of a session's result, each kind (added, removed,
+/// modified, unchanged) on its own. `by` is "key" (digit runs compared as
+/// numbers), "differences" or `{ column: name }`; `direction` is "asc"
+/// (the default when undefined) or "desc". Later session queries see the
+/// new order.
+#[wasm_bindgen]
+pub fn sort_results(handle: u32, by: JsValue, direction: JsValue) -> Result<(), JsValue> {
+    let by: crate::sort::SortBy = serde_wasm_bindgen::from_value(by)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let direction: Option<crate::sort::SortDirection> = serde_wasm_bindgen::from_value(direction)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    crate::session::with_session_mut(handle, |session| {
+        crate::sort::sort_result(&mut session.result, &by, direction.unwrap_or_default());
+        Ok(())
+    }).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Write rows of a diff result (as returned by the diff functions) back to
+/// CSV text. `options_val` is a camelCase `WriteOptions` object selecting
+/// added/removed/modified rows; all three are written by default.
+#[wasm_bindgen]
+pub fn write_result_csv(result_val: JsValue, options_val: JsValue) -> Result<String, JsValue> {
+    let result: crate::types::DiffResult = serde_wasm_bindgen::from_value(result_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let options = write_options(options_val)?;
+    crate::writer::write_result_csv(&result, &options).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// `write_result_csv` for the result retained by a session, without
+/// sending it back from JS.
+#[wasm_bindgen]
+pub fn write_session_csv(handle: u32, options_val: JsValue) -> Result<String, JsValue> {
+    let options = write_options(options_val)?;
+    crate::session::with_session(handle, |session| {
+        crate::writer::write_result_csv(&session.result, &options)
+    }).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+fn write_options(options_val: JsValue) -> Result<WriteOptions, JsValue> {
+    if options_val.is_undefined() || options_val.is_null() {
+        return Ok(WriteOptions::default());
+    }
+    serde_wasm_bindgen::from_value(options_val).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Dashboard statistics of a diff result: per-column modified cells and
+/// change rates, the most changed columns, modified rows by number of
+/// changed cells and the share of paired rows modified.
+#[wasm_bindgen]
+pub fn compute_stats(result_val: JsValue) -> Result<JsValue, JsValue> {
+    let result: crate::types::DiffResult = serde_wasm_bindgen::from_value(result_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    crate::summary::compute_stats(&result).serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// `compute_stats` for the result retained by a session.
+#[wasm_bindgen]
+pub fn compute_session_stats(handle: u32) -> Result<JsValue, JsValue> {
+    let stats = crate::session::with_session(handle, |session| Ok(crate::summary::compute_stats(&session.result)))
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    stats.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Per-column value shifts of a diff result: the most frequent old → new
+/// transitions and the values whose row counts changed most, at most
+/// `top_k` of each (10 when undefined).
+#[wasm_bindgen]
+pub fn column_distributions(result_val: JsValue, top_k: Option<usize>) -> Result<JsValue, JsValue> {
+    let result: crate::types::DiffResult = serde_wasm_bindgen::from_value(result_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    crate::summary::column_distributions(&result, top_k.unwrap_or(crate::summary::DEFAULT_TOP_VALUES))
+        .serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// `column_distributions` for the result retained by a session.
+#[wasm_bindgen]
+pub fn column_distributions_session(handle: u32, top_k: Option<usize>) -> Result<JsValue, JsValue> {
+    let distributions = crate::session::with_session(handle, |session| {
+        Ok(crate::summary::column_distributions(&session.result, top_k.unwrap_or(crate::summary::DEFAULT_TOP_VALUES)))
+    }).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    distributions.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// One-sentence description of a diff result for exports and
+/// notifications: `{ text, messages }`, where `messages` are the catalog
+/// messages (code and params) behind `text` so JS can localize them.
+#[wasm_bindgen]
+pub fn describe_diff(result_val: JsValue) -> Result<JsValue, JsValue> {
+    let result: crate::types::DiffResult = serde_wasm_bindgen::from_value(result_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    crate::summary::narrate(&result).serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// `describe_diff` for the result retained by a session.
+#[wasm_bindgen]
+pub fn describe_session(handle: u32) -> Result<JsValue, JsValue> {
+    let narrative = crate::session::with_session(handle, |session| Ok(crate::summary::narrate(&session.result)))
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    narrative.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Condense a diff result into a versioned JSON snapshot (counts, per-column
+/// stats, severity tallies) for dashboards. `metadata_val` is an optional
+/// `{ label, generatedAt, sourceName, targetName }` object stored as-is.
+#[wasm_bindgen]
+pub fn export_diff_snapshot(result_val: JsValue, metadata_val: JsValue) -> Result<String, JsValue> {
+    let result: crate::types::DiffResult = serde_wasm_bindgen::from_value(result_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let snapshot = crate::snapshot::build_snapshot(&result, snapshot_metadata(metadata_val)?);
+    serde_json::to_string(&snapshot).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// `export_diff_snapshot` for the result retained by a session.
+#[wasm_bindgen]
+pub fn export_session_snapshot(handle: u32, metadata_val: JsValue) -> Result<String, JsValue> {
+    let metadata = snapshot_metadata(metadata_val)?;
+    crate::session::with_session(handle, |session| {
+        let snapshot = crate::snapshot::build_snapshot(&session.result, metadata);
+        Ok(serde_json::to_string(&snapshot)?)
+    }).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Serialize a diff result in the versioned layout used for persisting
+/// (IndexedDB, saved files). Reload it with `load_diff_result`.
+#[wasm_bindgen]
+pub fn persist_diff_result(result_val: JsValue) -> Result<String, JsValue> {
+    let result: crate::types::DiffResult = serde_wasm_bindgen::from_value(result_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    crate::report::persist_result(&result).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// `persist_diff_result` for the result retained by a session.
+#[wasm_bindgen]
+pub fn persist_session_result(handle: u32) -> Result<String, JsValue> {
+    crate::session::with_session(handle, |session| crate::report::persist_result(&session.result))
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Load a result saved by `persist_diff_result` in this or an earlier
+/// release, or a bare result object stored before results were versioned.
+#[wasm_bindgen]
+pub fn load_diff_result(json: &str) -> Result<JsValue, JsValue> {
+    let result = crate::report::load_result(json).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    result.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Render a diff result as a standalone, styled HTML page: summary,
+/// per-row tables and highlighted cell changes. `options_val` is a
+/// camelCase `HtmlOptions` object (`title`, `includeUnchanged`, `maxRows`).
+#[wasm_bindgen]
+pub fn export_diff_html(result_val: JsValue, options_val: JsValue) -> Result<String, JsValue> {
+    let result: crate::types::DiffResult = serde_wasm_bindgen::from_value(result_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let options = html_options(options_val)?;
+    Ok(crate::html::write_result_html(&result, &options))
+}
+
+/// `export_diff_html` for the result retained by a session.
+#[wasm_bindgen]
+pub fn export_session_html(handle: u32, options_val: JsValue) -> Result<String, JsValue> {
+    let options = html_options(options_val)?;
+    crate::session::with_session(handle, |session| Ok(crate::html::write_result_html(&session.result, &options)))
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+fn html_options(options_val: JsValue) -> Result<crate::html::HtmlOptions, JsValue> {
+    if options_val.is_undefined() || options_val.is_null() {
+        return Ok(Default::default());
+    }
+    serde_wasm_bindgen::from_value(options_val).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Export a diff result as an Excel workbook (xlsx bytes) with Added,
+/// Removed and Modified sheets; changed cells of modified rows are filled
+/// and annotated with their old value.
+#[cfg(feature = "xlsx")]
+#[wasm_bindgen]
+pub fn export_diff_xlsx(result_val: JsValue) -> Result<Vec<u8>, JsValue> {
+    let result: crate::types::DiffResult = serde_wasm_bindgen::from_value(result_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    crate::xlsx::write_result_xlsx(&result).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// `export_diff_xlsx` for the result retained by a session.
+#[cfg(feature = "xlsx")]
+#[wasm_bindgen]
+pub fn export_session_xlsx(handle: u32) -> Result<Vec<u8>, JsValue> {
+    crate::session::with_session(handle, |session| crate::xlsx::write_result_xlsx(&session.result))
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Export a primary-key diff result as SQL statements (DELETE, UPDATE,
+/// INSERT, one per line) for the table named in `options_val`, a camelCase
+/// `SqlOptions` object (`table`, `emptyAsNull`).
+#[wasm_bindgen]
+pub fn export_diff_sql(result_val: JsValue, options_val: JsValue) -> Result<String, JsValue> {
+    let result: crate::types::DiffResult = serde_wasm_bindgen::from_value(result_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let options: crate::sql::SqlOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    crate::sql::write_result_sql(&result, &options).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// `export_diff_sql` for the result retained by a session.
+#[wasm_bindgen]
+pub fn export_session_sql(handle: u32, options_val: JsValue) -> Result<String, JsValue> {
+    let options: crate::sql::SqlOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    crate::session::with_session(handle, |session| crate::sql::write_result_sql(&session.result, &options))
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Export a diff result as the changeset turning its source file into its
+/// target: `format` is "json" (the default when undefined) or "csv", one
+/// line per cell. Replay it with `apply_patch`.
+#[wasm_bindgen]
+pub fn export_diff_patch(result_val: JsValue, format: JsValue) -> Result<String, JsValue> {
+    let result: crate::types::DiffResult = serde_wasm_bindgen::from_value(result_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let format = patch_format(format)?;
+    crate::patch::write_patch(&crate::patch::build_patch(&result), format).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// `export_diff_patch` for the result retained by a session.
+#[wasm_bindgen]
+pub fn export_session_patch(handle: u32, format: JsValue) -> Result<String, JsValue> {
+    let format = patch_format(format)?;
+    crate::session::with_session(handle, |session| {
+        crate::patch::write_patch(&crate::patch::build_patch(&session.result), format)
+    }).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Apply a patch from `export_diff_patch`, in either format, to
+/// `source_csv` and return the patched CSV. `options_val` holds the parse
+/// options (`hasHeaders`, `delimiter`, ...) the diff used.
+#[wasm_bindgen]
+pub fn apply_patch(source_csv: &str, patch: &str, options_val: JsValue) -> Result<String, JsValue> {
+    let options: ParseOptions = if options_val.is_undefined() || options_val.is_null() {
+        ParseOptions::default()
+    } else {
+        serde_wasm_bindgen::from_value(options_val).map_err(|e| JsValue::from_str(&e.to_string()))?
+    };
+    let patch = crate::patch::read_patch(patch).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    crate::patch::apply_patch(source_csv, &patch, &options)
+        .map_err(|e| JsValue::from_str(&error_text(e.as_ref(), options.message_format)))
+}
+
+/// Merge a diff result into `source_csv` and return the merged CSV, for
+/// "accept changes and download". `merge_val` holds `{ strategy, decisions }`:
+/// `strategy` ("target", the default, or "source") picks the side of every
+/// changed row, and `decisions` overrides it per row key. `options_val`
+/// holds the parse options the diff used.
+#[wasm_bindgen]
+pub fn merge_result_csv(source_csv: &str, result_val: JsValue, merge_val: JsValue, options_val: JsValue) -> Result<String, JsValue> {
+    let result: crate::types::DiffResult = serde_wasm_bindgen::from_value(result_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let merge: crate::merge::MergeOptions = if merge_val.is_undefined() || merge_val.is_null() {
+        Default::default()
+    } else {
+        serde_wasm_bindgen::from_value(merge_val).map_err(|e| JsValue::from_str(&e.to_string()))?
+    };
+    let options: ParseOptions = if options_val.is_undefined() || options_val.is_null() {
+        ParseOptions::default()
+    } else {
+        serde_wasm_bindgen::from_value(options_val).map_err(|e| JsValue::from_str(&e.to_string()))?
+    };
+    crate::merge::merge_csv(source_csv, &result, &merge, &options)
+        .map_err(|e| JsValue::from_str(&error_text(e.as_ref(), options.message_format)))
+}
+
+/// `merge_result_csv` for the result retained by a session, which already
+/// holds the parse options.
+#[wasm_bindgen]
+pub fn merge_session_csv(handle: u32, source_csv: &str, merge_val: JsValue) -> Result<String, JsValue> {
+    let merge: crate::merge::MergeOptions = if merge_val.is_undefined() || merge_val.is_null() {
+        Default::default()
+    } else {
+        serde_wasm_bindgen::from_value(merge_val).map_err(|e| JsValue::from_str(&e.to_string()))?
+    };
+    crate::session::with_session(handle, |session| {
+        crate::merge::merge_csv(source_csv, &session.result, &merge, &session.options.parse)
+    }).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+fn patch_format(format: JsValue) -> Result<crate::patch::PatchFormat, JsValue> {
+    let format: Option<crate::patch::PatchFormat> = serde_wasm_bindgen::from_value(format)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    Ok(format.unwrap_or_default())
+}
+
+fn snapshot_metadata(metadata_val: JsValue) -> Result<crate::types::SnapshotMetadata, JsValue> {
+    if metadata_val.is_undefined() || metadata_val.is_null() {
+        return Ok(Default::default());
+    }
+    serde_wasm_bindgen::from_value(metadata_val).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Drop all retained state: diff sessions, `BinaryResult` payloads and
+/// buffers still handed out by pointer. Lets a long-lived worker reclaim
+/// memory between user sessions; every outstanding handle and pointer
+/// becomes invalid. Returns `{ sessions, binaryResults, buffers }` counts.
+#[wasm_bindgen]
+pub fn free_all() -> Result<JsValue, JsValue> {
+    let freed = crate::memory::FreedCounts {
+        sessions: crate::session::clear(),
+        ..crate::memory::free_all_buffers()
+    };
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(freed.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// `free_all` without the report, for teardown and abort paths.
+#[wasm_bindgen]
+pub fn reset() {
+    crate::session::clear();
+    crate::memory::free_all_buffers();
+}
+
+/// Suggest a `columnMapping` for two header lists by fuzzy-matching the
+/// names of source columns missing from the target. Returns
+/// `[{ source, target, score, reason }]` for the UI to confirm.
+#[wasm_bindgen]
+pub fn propose_column_mapping(source_headers: JsValue, target_headers: JsValue) -> Result<JsValue, JsValue> {
+    let source_headers: Vec<String> = serde_wasm_bindgen::from_value(source_headers)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let target_headers: Vec<String> = serde_wasm_bindgen::from_value(target_headers)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let proposals = crate::mapping::propose_column_mapping(&source_headers, &target_headers);
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(proposals.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// Compare the columns of two files from their header rows only, e.g. to
+/// show schema compatibility before the full diff is configured. Returns
+/// `{ sourceHeaders, targetHeaders, added, removed, reordered, renamed, identical }`.
+#[wasm_bindgen]
+pub fn diff_headers(source_csv: &str, target_csv: &str, options_val: JsValue) -> Result<JsValue, JsValue> {
+    let options: DiffOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let diff = crate::mapping::diff_headers(source_csv, target_csv, &options)
+        .map_err(|e| JsValue::from_str(&error_text(e.as_ref(), options.parse.message_format)))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(diff.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// Rank the columns, and pairs of columns, of a CSV as primary keys by
+/// uniqueness and null rate, for the key picker. Returns
+/// `{ rows, columns, suggestions }` of `{ columns, uniqueness, nullRate, score }`.
+#[wasm_bindgen]
+pub fn analyze_key_candidates(csv_content: &str, options_val: JsValue) -> Result<JsValue, JsValue> {
+    let options: DiffOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let analysis = crate::key_candidates::analyze_key_candidates(csv_content, &options)
+        .map_err(|e| JsValue::from_str(&error_text(e.as_ref(), options.parse.message_format)))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(analysis.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// Estimate the added/removed/modified row percentages of a primary-key diff
+/// from `sample_keys` rows per file, with 95% confidence bounds, to judge
+/// whether a full diff is worth running.
+#[wasm_bindgen]
+pub fn estimate_change_rate(
+    source_csv: &str,
+    target_csv: &str,
+    options_val: JsValue,
+    sample_keys: usize,
+) -> Result<JsValue, JsValue> {
+    let options: DiffOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let estimate = crate::estimate::estimate_change_rate(source_csv, target_csv, &options, sample_keys)
+        .map_err(|e| JsValue::from_str(&error_text(e.as_ref(), options.parse.message_format)))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(estimate.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// Row counts and per-column change totals of the diff `options_val`
+/// selects, without building result rows: a quick "has anything changed?"
+/// check, or the sizes to lay out the UI before the full diff arrives.
+#[wasm_bindgen]
+pub fn diff_summary_only(source_csv: &str, target_csv: &str, options_val: JsValue) -> Result<JsValue, JsValue> {
+    let options: DiffOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let summary = crate::quick_summary::quick_summary(source_csv, target_csv, &options)
+        .map_err(|e| JsValue::from_str(&error_text(e.as_ref(), options.parse.message_format)))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(summary.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// Flip a `DiffResult` to describe target → source without re-running the
+/// diff (see `DiffOptions::reverse`).
+#[wasm_bindgen]
+pub fn reverse_diff_result(result_val: JsValue) -> Result<JsValue, JsValue> {
+    let result: crate::types::DiffResult = serde_wasm_bindgen::from_value(result_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(result.reversed().serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// Diff two descendants of a common base file by key and classify each
+/// changed key as changed in ours, in theirs, in both, or conflicting.
+/// `options_val` is a camelCase `DiffOptions` with `keyColumns`; returns
+/// `{ changes, summary, keyColumns, warnings }`.
+#[wasm_bindgen]
+pub fn diff_csv_three_way(base_csv: &str, ours_csv: &str, theirs_csv: &str, options_val: JsValue) -> Result<JsValue, JsValue> {
+    let options: DiffOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let result = crate::three_way::diff_csv_three_way(base_csv, ours_csv, theirs_csv, &options)
+        .map_err(|e| JsValue::from_str(&error_text(e.as_ref(), options.parse.message_format)))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(result.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// Diff one source against several targets (`target_csvs_val`: array of
+/// CSV strings), parsing the source once. Returns
+/// `{ results, summary }` with a `DiffResult` per target, in order.
 #[wasm_bindgen]
-pub fn diff_text(old: &str, new: &str, case_sensitive: bool) -> Result<JsValue, JsValue> {
-    let diffs = crate::core::diff_text_internal(old, new, case_sensitive);
+pub fn diff_csv_n_way(
+    source_csv: &str,
+    target_csvs_val: JsValue,
+    options_val: JsValue,
+    on_progress: &Function,
+) -> Result<JsValue, JsValue> {
+    let target_csvs: Vec<String> = serde_wasm_bindgen::from_value(target_csvs_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let options: DiffOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let callback = |progress: f64, message: &str| {
+        let this = JsValue::NULL;
+        let _ = on_progress.call2(&this, &JsValue::from_f64(progress), &JsValue::from_str(message));
+    };
+
+    let targets: Vec<&str> = target_csvs.iter().map(String::as_str).collect();
+    let result = crate::n_way::diff_csv_n_way(source_csv, &targets, &options, callback)
+        .map_err(|e| JsValue::from_str(&error_text(e.as_ref(), options.parse.message_format)))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(result.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// Explain the per-column similarity scores and normalization steps content
+/// match uses for a source/target row pair. Rows are referenced by 0-based
+/// index or by result key.
+#[wasm_bindgen]
+pub fn explain_match(handle: u32, source_ref: JsValue, target_ref: JsValue) -> Result<JsValue, JsValue> {
+    let source_ref: RowRef = serde_wasm_bindgen::from_value(source_ref)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let target_ref: RowRef = serde_wasm_bindgen::from_value(target_ref)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let explanation = crate::session::with_session(handle, |session| {
+        crate::explain::explain_match(session, &source_ref, &target_ref)
+    }).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(explanation.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// Changes in `column` of the modified row `key` of a session's result,
+/// for diffs run with `charDiffs: false`. `granularity` is "character",
+/// "word" or "line"; undefined uses the session's `textGranularity`.
+#[wasm_bindgen]
+pub fn get_cell_diff(handle: u32, key: &str, column: &str, granularity: JsValue) -> Result<JsValue, JsValue> {
+    let granularity: Option<TextGranularity> = serde_wasm_bindgen::from_value(granularity)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let changes = crate::session::with_session(handle, |session| {
+        crate::cell_diff::cell_diff(session, key, column, granularity)
+    }).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(changes.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// Show the normalized form the comparator uses for `value` under the given
+/// diff options (caseSensitive, ignoreWhitespace, ignoreEmptyVsNull, ...).
+/// Pass `column` to apply that column's rules (transforms, null values,
+/// numeric, boolean and JSON columns) as well.
+#[wasm_bindgen]
+pub fn preview_normalization(value: &str, column: Option<String>, options_val: JsValue) -> Result<JsValue, JsValue> {
+    let options: DiffOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let preview = crate::explain::preview_normalization(value, column.as_deref(), &options)
+        .map_err(|e| JsValue::from_str(&error_text(e.as_ref(), options.parse.message_format)))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(preview.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// Batch variant of `preview_normalization` over the first `max_rows` rows of a CSV.
+#[wasm_bindgen]
+pub fn preview_normalization_sample(csv_content: &str, options_val: JsValue, max_rows: usize) -> Result<JsValue, JsValue> {
+    let options: DiffOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let sample = crate::explain::preview_normalization_sample(csv_content, &options, max_rows)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(sample.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// Message codes and their English templates, for localizing progress
+/// messages and errors emitted with `messageFormat: "code"`.
+#[wasm_bindgen]
+pub fn get_message_catalog() -> Result<JsValue, JsValue> {
+    let catalog: std::collections::BTreeMap<&str, &str> = CATALOG.iter().copied().collect();
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(catalog.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
+}
+
+/// Changes from `old` to `new` by `granularity`: "character", "word" (the
+/// default when undefined) or "line".
+#[wasm_bindgen]
+pub fn diff_text(old: &str, new: &str, case_sensitive: bool, granularity: JsValue) -> Result<JsValue, JsValue> {
+    let granularity: Option<TextGranularity> = serde_wasm_bindgen::from_value(granularity)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let diffs = crate::core::diff_text_with_granularity(old, new, case_sensitive, granularity.unwrap_or_default());
     let serializer = serde_wasm_bindgen::Serializer::json_compatible();
     Ok(diffs.serialize(&serializer).map_err(|e| JsValue::from_str(&e.to_string()))?)
 }
@@ -219,19 +1066,10 @@ pub fn diff_csv_primary_key_binary(
     // Encode to binary format
     let mut encoder = BinaryEncoder::new();
     encoder.encode_diff_result(&result);
-    let mut binary_data = encoder.into_vec();
-
-    // Return pointer to the binary data
-    let ptr = binary_data.as_mut_ptr();
-    let len = binary_data.len();
-    let capacity = binary_data.capacity();
-
-    // Store metadata for retrieval/deallocation on the JS side via memory module
-    set_last_binary_result_length(len);
-    set_last_binary_result_capacity(capacity);
 
-    std::mem::forget(binary_data); // Don't drop, JS will read it
-    Ok(ptr)
+    // Return pointer to the binary data; metadata is kept for retrieval and
+    // deallocation on the JS side
+    Ok(leak_binary_result(encoder.into_vec()))
 }
 
 #[wasm_bindgen]
@@ -267,19 +1105,39 @@ pub fn diff_csv_binary(
     // Encode to binary format
     let mut encoder = BinaryEncoder::new();
     encoder.encode_diff_result(&result);
-    let mut binary_data = encoder.into_vec();
 
-    // Return pointer to the binary data
-    let ptr = binary_data.as_mut_ptr();
-    let len = binary_data.len();
-    let capacity = binary_data.capacity();
+    // Return pointer to the binary data; metadata is kept for retrieval and
+    // deallocation on the JS side
+    Ok(leak_binary_result(encoder.into_vec()))
+}
+
+/// Options-object diff returning a binary-encoded result owned by the
+/// returned handle. Unlike the pointer-returning variants, overlapping calls
+/// (e.g. a preview diff started from a progress callback) never overwrite
+/// each other's result metadata. The layout follows `binaryFormat` ("v1",
+/// "v2" or "columnar"; "v1" unless set); char diffs are included with
+/// `binaryCharDiffs`.
+#[wasm_bindgen]
+pub fn diff_csv_with_options_binary(
+    source_csv: &str,
+    target_csv: &str,
+    options_val: JsValue,
+    on_progress: &Function,
+) -> Result<BinaryResult, JsValue> {
+    let options: DiffOptions = serde_wasm_bindgen::from_value(options_val)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let callback = |progress: f64, message: &str| {
+        let this = JsValue::NULL;
+        let _ = on_progress.call2(&this, &JsValue::from_f64(progress), &JsValue::from_str(message));
+    };
 
-    // Store metadata for retrieval/deallocation on the JS side via memory module
-    set_last_binary_result_length(len);
-    set_last_binary_result_capacity(capacity);
+    let result = crate::core::diff_with_options(source_csv, target_csv, &options, callback)
+        .map_err(|e| JsValue::from_str(&error_text(e.as_ref(), options.parse.message_format)))?;
 
-    std::mem::forget(binary_data); // Don't drop, JS will read it
-    Ok(ptr)
+    let mut encoder = BinaryEncoder::with_format(options.binary_format).with_char_diffs(options.binary_char_diffs);
+    encoder.encode_diff_result(&result);
+    Ok(BinaryResult::new(encoder.into_vec()))
 }
 
 /// Initialize panic hook for better error messages
@@ -342,6 +1200,7 @@ pub fn get_streaming_config() -> Result<JsValue, JsValue> {
     js_sys::Reflect::set(&obj, &"chunkSize".into(), &config.chunk_size.into())?;
     js_sys::Reflect::set(&obj, &"enableProgressUpdates".into(), &config.enable_progress_updates.into())?;
     js_sys::Reflect::set(&obj, &"progressUpdateInterval".into(), &config.progress_update_interval.into())?;
+    js_sys::Reflect::set(&obj, &"chunkMemoryBudget".into(), &config.chunk_memory_budget.into())?;
     Ok(obj.into())
 }
 
@@ -412,19 +1271,10 @@ pub fn diff_csv_parallel_binary(
     // Encode to binary format
     let mut encoder = BinaryEncoder::new();
     encoder.encode_diff_result(&result);
-    let mut binary_data = encoder.into_vec();
-
-    // Return pointer to the binary data
-    let ptr = binary_data.as_mut_ptr();
-    let len = binary_data.len();
-    let capacity = binary_data.capacity();
-
-    // Store metadata for retrieval/deallocation on the JS side via memory module
-    set_last_binary_result_length(len);
-    set_last_binary_result_capacity(capacity);
 
-    std::mem::forget(binary_data); // Don't drop, JS will read it
-    Ok(ptr)
+    // Return pointer to the binary data; metadata is kept for retrieval and
+    // deallocation on the JS side
+    Ok(leak_binary_result(encoder.into_vec()))
 }
 
 /// Initialize the Rayon thread pool for parallel processing
@@ -448,10 +1298,10 @@ pub fn parse_csv_binary(
         .map_err(|e| JsValue::from_str(&format!("Invalid UTF-8: {}", e)))?;
 
     // Use streaming parser
-    let (headers, rows, _) = crate::parse::parse_csv_streaming(
+    let (headers, rows, _) = crate::parse::parse_csv_streaming_with_options(
         csv_content, 
-        has_headers, 
-        5000,
+        &ParseOptions::new(has_headers), 
+        &crate::streaming::StreamingConfig::default(),
         |percent, message| {
             on_progress.call2(&JsValue::NULL, &JsValue::from_f64(percent), &JsValue::from_str(message));
         }
@@ -470,17 +1320,7 @@ pub fn parse_csv_binary(
     let json_str = serde_json::to_string(&result)
         .map_err(|e| JsValue::from_str(&e.to_string()))?;
     
-    let binary_data = json_str.into_bytes();
-    let ptr = binary_data.as_ptr();
-    let len = binary_data.len();
-    let capacity = binary_data.capacity();
-
-    // Store metadata for retrieval
-    set_last_binary_result_length(len);
-    set_last_binary_result_capacity(capacity);
-
-    std::mem::forget(binary_data);
-    Ok(ptr)
+    Ok(leak_binary_result(json_str.into_bytes()))
 }
 
 /// Get metadata about the last binary result (length and capacity)
@@ -540,15 +1380,14 @@ pub fn init_differ(
     
     // Build fingerprint indexes for fast matching
     let excluded_set = ahash::AHashSet::<String>::new(); // Empty for now
+    let normalizer = crate::normalize::Normalizer::from_flags(true, false, false);
     
     let source_fingerprints: Vec<u64> = source_rows.iter()
         .map(|row| crate::utils::get_row_fingerprint_hash(
             row,
             &source_headers,
             &source_header_map,
-            true, // case_sensitive
-            false, // ignore_whitespace
-            false, // ignore_empty_vs_null
+            &normalizer,
             &excluded_set,
         ))
         .collect();
@@ -558,9 +1397,7 @@ pub fn init_differ(
             row,
             &target_headers,
             &target_header_map,
-            true, // case_sensitive
-            false, // ignore_whitespace
-            false, // ignore_empty_vs_null
+            &normalizer,
             &excluded_set,
         ))
         .collect();
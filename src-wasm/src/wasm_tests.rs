@@ -258,6 +258,28 @@ mod tests {
         assert!(result.is_ok(), "Mixed line endings test failed: {:?}", result);
     }
 
+    // ===== LINE NUMBERS =====
+
+    #[test]
+    fn test_result_rows_carry_file_lines() {
+        let source = "id,note\n1,\"two\nlines\"\n2,keep\n3,gone\n";
+        let target = "id,note\n\n2,changed\n1,\"two\nlines\"\n4,new\n";
+
+        for use_parallel in [false, true] {
+            let options = options::DiffOptions {
+                key_columns: vec!["id".to_string()],
+                use_parallel,
+                ..Default::default()
+            };
+            let result = core::diff_with_options(source, target, &options, |_, _| {}).unwrap();
+
+            assert_eq!(result.removed[0].source_line, Some(5), "parallel: {}", use_parallel);
+            assert_eq!(result.added[0].target_line, Some(6), "parallel: {}", use_parallel);
+            let modified = &result.modified[0];
+            assert_eq!((modified.source_line, modified.target_line), (Some(4), Some(3)), "parallel: {}", use_parallel);
+        }
+    }
+
     // ===== PROGRESS CALLBACK TEST =====
 
     #[test]
@@ -397,6 +419,29 @@ mod tests {
         assert!(has_changes);
     }
 
+    #[test]
+    fn test_diff_text_granularity() {
+        let changed = |old, new, granularity| -> Vec<String> {
+            core::diff_text_with_granularity(old, new, true, granularity).into_iter()
+                .filter(|d| d.added || d.removed)
+                .map(|d| d.value)
+                .collect()
+        };
+
+        // Words change whole; the 3-argument form diffs by word
+        assert_eq!(changed("the red car", "the blue car", options::TextGranularity::Word), ["red", "blue"]);
+        assert_eq!(
+            core::diff_text_internal("the red car", "the blue car", true).len(),
+            core::diff_text_with_granularity("the red car", "the blue car", true, options::TextGranularity::Word).len()
+        );
+
+        // Lines change whole, newline included
+        assert_eq!(
+            changed("one\ntwo\nthree", "one\n2\nthree", options::TextGranularity::Line),
+            ["two\n", "2\n"]
+        );
+    }
+
     // ===== CHUNKED PROCESSING TESTS =====
 
     #[test]
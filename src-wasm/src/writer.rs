@@ -0,0 +1,154 @@
+//! Serialize diff result rows back into CSV.
+//!
+//! Lets the UI offer downloads such as "added rows as CSV". Any subset of
+//! added, removed and modified rows can be written; the header row is the
+//! union of the headers of the sides that contribute rows, in file order.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::types::DiffResult;
+
+/// Which side of a modified row to write.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ModifiedSide {
+    Source,
+    #[default]
+    Target,
+}
+
+/// Options for `write_result_csv`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WriteOptions {
+    pub added: bool,
+    pub removed: bool,
+    pub modified: bool,
+    /// Values written for modified rows
+    pub modified_side: ModifiedSide,
+    /// Prepend a `status` column ("added", "removed", "modified")
+    pub include_status: bool,
+    /// Single-byte field delimiter
+    pub delimiter: String,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            added: true,
+            removed: true,
+            modified: true,
+            modified_side: ModifiedSide::Target,
+            include_status: false,
+            delimiter: ",".to_string(),
+        }
+    }
+}
+
+/// Write the selected rows of `result` as CSV. Fields are quoted only when
+/// they contain the delimiter, quotes or line breaks.
+pub fn write_result_csv(result: &DiffResult, options: &WriteOptions) -> Result<String, Box<dyn std::error::Error>> {
+    let delimiter = match options.delimiter.as_bytes() {
+        [byte] => *byte,
+        _ => return Err("CSV writer supports single-byte delimiters only.".into()),
+    };
+
+    let uses_source = options.removed || (options.modified && options.modified_side == ModifiedSide::Source);
+    let uses_target = options.added || (options.modified && options.modified_side == ModifiedSide::Target);
+    let mut headers: Vec<&String> = Vec::new();
+    let sides = [(uses_source, &result.source.headers), (uses_target, &result.target.headers)];
+    for header in sides.iter().filter(|(used, _)| *used).flat_map(|(_, headers)| headers.iter()) {
+        if !headers.contains(&header) {
+            headers.push(header);
+        }
+    }
+
+    let mut writer = csv::WriterBuilder::new().delimiter(delimiter).from_writer(Vec::new());
+    let status_header = options.include_status.then_some("status");
+    writer.write_record(status_header.into_iter().chain(headers.iter().map(|h| h.as_str())))?;
+
+    let mut write_row = |status: &str, row: &HashMap<String, String>| {
+        let values = headers.iter().map(|h| row.get(*h).map(String::as_str).unwrap_or(""));
+        if options.include_status {
+            writer.write_record(std::iter::once(status).chain(values))
+        } else {
+            writer.write_record(values)
+        }
+    };
+
+    if options.added {
+        for row in &result.added {
+            write_row("added", &row.target_row)?;
+        }
+    }
+    if options.removed {
+        for row in &result.removed {
+            write_row("removed", &row.source_row)?;
+        }
+    }
+    if options.modified {
+        for row in &result.modified {
+            let values = match options.modified_side {
+                ModifiedSide::Source => &row.source_row,
+                ModifiedSide::Target => &row.target_row,
+            };
+            write_row("modified", values)?;
+        }
+    }
+
+    let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::DiffOptions;
+
+    fn only(kind: &str) -> WriteOptions {
+        WriteOptions {
+            added: kind == "added",
+            removed: kind == "removed",
+            modified: kind == "modified",
+            ..Default::default()
+        }
+    }
+
+    fn sample_result() -> DiffResult {
+        let source = "id,name,note\n1,Alice,plain\n2,Bob,\"has, comma\"\n3,Carol,x\n";
+        let target = "id,name,note\n1,Alice,\"say \"\"hi\"\"\"\n3,Carol,x\n4,Dan,\"two\nlines\"\n";
+        let options = DiffOptions { key_columns: vec!["id".to_string()], ..Default::default() };
+        crate::core::diff_with_options(source, target, &options, |_, _| {}).unwrap()
+    }
+
+    #[test]
+    fn test_written_rows_round_trip() {
+        let result = sample_result();
+        let csv = write_result_csv(&result, &only("added")).unwrap();
+        assert_eq!(csv, "id,name,note\n4,Dan,\"two\nlines\"\n");
+
+        let csv = write_result_csv(&result, &WriteOptions { include_status: true, ..Default::default() }).unwrap();
+        let (headers, rows, _) = crate::parse::parse_csv_internal(&csv, true).unwrap();
+        assert_eq!(headers, vec!["status", "id", "name", "note"]);
+        let rows: Vec<Vec<&str>> = rows.iter().map(|r| r.iter().collect()).collect();
+        assert_eq!(rows, vec![
+            vec!["added", "4", "Dan", "two\nlines"],
+            vec!["removed", "2", "Bob", "has, comma"],
+            vec!["modified", "1", "Alice", "say \"hi\""],
+        ]);
+    }
+
+    #[test]
+    fn test_modified_side_and_delimiter() {
+        let result = sample_result();
+        let options = WriteOptions {
+            modified_side: ModifiedSide::Source,
+            delimiter: ";".to_string(),
+            ..only("modified")
+        };
+        assert_eq!(write_result_csv(&result, &options).unwrap(), "id;name;note\n1;Alice;plain\n");
+
+        let options = WriteOptions { delimiter: "||".to_string(), ..Default::default() };
+        assert!(write_result_csv(&result, &options).is_err());
+    }
+}
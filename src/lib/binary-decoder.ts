@@ -6,7 +6,9 @@ import { SharedStringDecoder } from "./shared-string-decoder";
  * This module provides zero-copy binary decoding for diff results,
  * eliminating JSON serialization overhead.
  *
- * Binary format matches src-wasm/src/binary.rs:
+ * Binary formats match src-wasm/src/binary_encoder.rs. Buffers starting
+ * with the bytes "CDV2" use v2 (see `decodeV2`), those starting with "CDCO"
+ * the columnar layout (see `decodeColumnar`); any other buffer is v1:
  *
  * Header (20 bytes):
  * - total_rows: u32 (4 bytes)
@@ -20,6 +22,37 @@ import { SharedStringDecoder } from "./shared-string-decoder";
  * - key_len: u32
  * - key: UTF-8 bytes
  * - Row data (varies by type)
+ *
+ * Trailer (absent in buffers from older builds):
+ * - omitted_unchanged: u32, unchanged rows left out of the result
+ * - row indices: u32 each (0xFFFFFFFF when unknown), in row order: target
+ *   index of added rows, source index of removed rows, source and target
+ *   index of modified and unchanged rows
+ * - char diffs (only when encoded with them): per difference of each
+ *   modified row, fragment count: u32, then per fragment a tag: u8
+ *   (0 = unchanged, 1 = added, 2 = removed) and its value as a string
+ *
+ * v2 writes every integer as an unsigned LEB128 varint and every string as
+ * a position in a table of distinct strings:
+ * - magic "CDV2"
+ * - added, removed, modified, unchanged and omitted unchanged counts
+ * - string count, then byte length and UTF-8 bytes of each string
+ * - rows of each kind in turn, without type bytes: key, indices (0 when
+ *   unknown, otherwise index + 1), row data and, for modified rows, the
+ *   differences
+ * - char diffs, as in v1 but with varint counts and table strings
+ *
+ * Columnar writes u32 words only, 4-byte aligned, so its arrays decode as
+ * `Uint32Array` views:
+ * - magic "CDCO"
+ * - added, removed, modified, unchanged and omitted unchanged counts,
+ *   string count and string byte length
+ * - string offsets (count + 1), then the string bytes padded to 4
+ * - per kind of row: its columns, then keys, indices and values arrays
+ *   (row by row, one per column), with 0xFFFFFFFF for a missing cell or
+ *   an unknown index; modified rows add difference counts and the
+ *   columns, old and new values of all differences
+ * - char diffs (optional): fragment counts, tags and values
  */
 
 export interface DiffResult {
@@ -32,21 +65,28 @@ export interface DiffResult {
   keyColumns?: Array<string>;
   excludedColumns?: Array<string>;
   mode?: string;
+  /** Unchanged rows left out of `unchanged` (`includeUnchanged: false`) */
+  omittedUnchanged?: number;
 }
 
 export interface DatasetMetadata {
   headers: Array<string>;
+  /** Empty when the diff ran with `includeDatasetRows: false` */
   rows: Array<Record<string, string>>;
 }
 
 export interface AddedRow {
   key: string;
   targetRow: Record<string, string>;
+  /** 0-based position among the target file's data rows */
+  targetIndex?: number;
 }
 
 export interface RemovedRow {
   key: string;
   sourceRow: Record<string, string>;
+  /** 0-based position among the source file's data rows */
+  sourceIndex?: number;
 }
 
 export interface ModifiedRow {
@@ -54,11 +94,15 @@ export interface ModifiedRow {
   sourceRow: Record<string, string>;
   targetRow: Record<string, string>;
   differences: Array<Difference>;
+  sourceIndex?: number;
+  targetIndex?: number;
 }
 
 export interface UnchangedRow {
   key: string;
   row: Record<string, string>;
+  sourceIndex?: number;
+  targetIndex?: number;
 }
 
 export interface Difference {
@@ -74,6 +118,15 @@ export interface DiffChange {
   value: string;
 }
 
+/** First bytes of a v2 buffer ("CDV2") */
+const V2_MAGIC = [0x43, 0x44, 0x56, 0x32];
+/** First bytes of a columnar buffer ("CDCO") */
+const COLUMNAR_MAGIC = [0x43, 0x44, 0x43, 0x4f];
+/** Missing cell or unknown index in a columnar buffer */
+const COLUMNAR_NONE = 0xffffffff;
+/** Typed arrays use platform byte order; buffers are little-endian */
+const LITTLE_ENDIAN = new Uint8Array(new Uint32Array([1]).buffer)[0] === 1;
+
 export class BinaryDecoder {
   private buffer: Uint8Array;
   private view: DataView;
@@ -94,6 +147,13 @@ export class BinaryDecoder {
    * Decode the entire binary diff result.
    */
   decode(): DiffResult {
+    if (this.startsWith(V2_MAGIC)) {
+      return this.decodeV2();
+    }
+    if (this.startsWith(COLUMNAR_MAGIC)) {
+      return this.decodeColumnar();
+    }
+
     // Read header
     const _totalRows = this.readU32();
     const addedCount = this.readU32();
@@ -151,9 +211,375 @@ export class BinaryDecoder {
       result.unchanged.push({ key, row });
     }
 
+    if (this.position + 4 <= this.buffer.length) {
+      result.omittedUnchanged = this.readU32();
+    }
+
+    if (this.position < this.buffer.length) {
+      for (const row of result.added) row.targetIndex = this.readIndex();
+      for (const row of result.removed) row.sourceIndex = this.readIndex();
+      for (const row of [...result.modified, ...result.unchanged]) {
+        row.sourceIndex = this.readIndex();
+        row.targetIndex = this.readIndex();
+      }
+    }
+
+    if (this.position < this.buffer.length) {
+      this.readCharDiffs(
+        result,
+        () => this.readU32(),
+        () => this.readString(),
+      );
+    }
+
+    return result;
+  }
+
+  private startsWith(magic: Array<number>): boolean {
+    return (
+      this.buffer.length >= magic.length &&
+      magic.every((byte, i) => this.buffer[i] === byte)
+    );
+  }
+
+  /**
+   * Decode a v2 buffer: counts, the string table, then rows whose strings
+   * are table positions.
+   */
+  private decodeV2(): DiffResult {
+    this.position = V2_MAGIC.length;
+    const addedCount = this.readVarint();
+    const removedCount = this.readVarint();
+    const modifiedCount = this.readVarint();
+    const unchangedCount = this.readVarint();
+    const omittedUnchanged = this.readVarint();
+
+    const strings: Array<string> = [];
+    const stringCount = this.readVarint();
+    for (let i = 0; i < stringCount; i++) {
+      strings.push(this.readBytes(this.readVarint()));
+    }
+    const str = (): string => {
+      const id = this.readVarint();
+      if (id >= strings.length) {
+        throw new Error(
+          `Invalid string id ${id} at position ${this.position}, table size ${strings.length}`,
+        );
+      }
+      return strings[id];
+    };
+    const row = (): Record<string, string> => {
+      const fieldCount = this.readVarint();
+      const fields: Record<string, string> = {};
+      for (let i = 0; i < fieldCount; i++) {
+        const column = str();
+        fields[column] = str();
+      }
+      return fields;
+    };
+    const index = (): number | undefined => {
+      const value = this.readVarint();
+      return value === 0 ? undefined : value - 1;
+    };
+
+    const result: DiffResult = {
+      added: [],
+      removed: [],
+      modified: [],
+      unchanged: [],
+      omittedUnchanged,
+    };
+
+    for (let i = 0; i < addedCount; i++) {
+      const key = str();
+      const targetIndex = index();
+      result.added.push({ key, targetIndex, targetRow: row() });
+    }
+
+    for (let i = 0; i < removedCount; i++) {
+      const key = str();
+      const sourceIndex = index();
+      result.removed.push({ key, sourceIndex, sourceRow: row() });
+    }
+
+    for (let i = 0; i < modifiedCount; i++) {
+      const key = str();
+      const sourceIndex = index();
+      const targetIndex = index();
+      const sourceRow = row();
+      const targetRow = row();
+      const diffCount = this.readVarint();
+      const differences: Array<Difference> = [];
+      for (let j = 0; j < diffCount; j++) {
+        const column = str();
+        const oldValue = str();
+        const newValue = str();
+        differences.push({ column, oldValue, newValue });
+      }
+      result.modified.push({
+        key,
+        sourceRow,
+        targetRow,
+        differences,
+        sourceIndex,
+        targetIndex,
+      });
+    }
+
+    for (let i = 0; i < unchangedCount; i++) {
+      const key = str();
+      const sourceIndex = index();
+      const targetIndex = index();
+      result.unchanged.push({ key, row: row(), sourceIndex, targetIndex });
+    }
+
+    if (this.position < this.buffer.length) {
+      this.readCharDiffs(result, () => this.readVarint(), str);
+    }
+
+    return result;
+  }
+
+  /**
+   * Decode a columnar buffer: counts, the string table, then per kind of
+   * row its columns and arrays of string ids.
+   */
+  private decodeColumnar(): DiffResult {
+    this.position = COLUMNAR_MAGIC.length;
+    const [
+      addedCount,
+      removedCount,
+      modifiedCount,
+      unchangedCount,
+      omittedUnchanged,
+      stringCount,
+      stringBytes,
+    ] = this.readU32Array(7);
+
+    const offsets = this.readU32Array(stringCount + 1);
+    const strings: Array<string> = [];
+    const start = this.position;
+    for (let i = 0; i < stringCount; i++) {
+      this.position = start + offsets[i];
+      strings.push(this.readBytes(offsets[i + 1] - offsets[i]));
+    }
+    this.position = start + Math.ceil(stringBytes / 4) * 4;
+
+    const str = (id: number): string => {
+      if (id >= strings.length) {
+        throw new Error(
+          `Invalid string id ${id} near position ${this.position}, table size ${strings.length}`,
+        );
+      }
+      return strings[id];
+    };
+    const columns = (): Array<string> =>
+      Array.from(this.readU32Array(this.readU32()), str);
+    const indices = (count: number): Array<number | undefined> =>
+      Array.from(this.readU32Array(count), (index) =>
+        index === COLUMNAR_NONE ? undefined : index,
+      );
+    const rows = (
+      count: number,
+      names: Array<string>,
+    ): Array<Record<string, string>> => {
+      const values = this.readU32Array(count * names.length);
+      const result: Array<Record<string, string>> = [];
+      for (let i = 0; i < count; i++) {
+        const row: Record<string, string> = {};
+        for (let j = 0; j < names.length; j++) {
+          const id = values[i * names.length + j];
+          if (id !== COLUMNAR_NONE) {
+            row[names[j]] = str(id);
+          }
+        }
+        result.push(row);
+      }
+      return result;
+    };
+
+    const result: DiffResult = {
+      added: [],
+      removed: [],
+      modified: [],
+      unchanged: [],
+      omittedUnchanged,
+    };
+
+    {
+      const names = columns();
+      const keys = this.readU32Array(addedCount);
+      const targetIndices = indices(addedCount);
+      const targetRows = rows(addedCount, names);
+      for (let i = 0; i < addedCount; i++) {
+        result.added.push({
+          key: str(keys[i]),
+          targetRow: targetRows[i],
+          targetIndex: targetIndices[i],
+        });
+      }
+    }
+
+    {
+      const names = columns();
+      const keys = this.readU32Array(removedCount);
+      const sourceIndices = indices(removedCount);
+      const sourceRows = rows(removedCount, names);
+      for (let i = 0; i < removedCount; i++) {
+        result.removed.push({
+          key: str(keys[i]),
+          sourceRow: sourceRows[i],
+          sourceIndex: sourceIndices[i],
+        });
+      }
+    }
+
+    {
+      const sourceNames = columns();
+      const targetNames = columns();
+      const keys = this.readU32Array(modifiedCount);
+      const sourceIndices = indices(modifiedCount);
+      const targetIndices = indices(modifiedCount);
+      const sourceRows = rows(modifiedCount, sourceNames);
+      const targetRows = rows(modifiedCount, targetNames);
+      const diffCounts = this.readU32Array(modifiedCount);
+      const diffTotal = diffCounts.reduce((sum, count) => sum + count, 0);
+      const diffColumns = this.readU32Array(diffTotal);
+      const oldValues = this.readU32Array(diffTotal);
+      const newValues = this.readU32Array(diffTotal);
+      let d = 0;
+      for (let i = 0; i < modifiedCount; i++) {
+        const differences: Array<Difference> = [];
+        for (let j = 0; j < diffCounts[i]; j++, d++) {
+          differences.push({
+            column: str(diffColumns[d]),
+            oldValue: str(oldValues[d]),
+            newValue: str(newValues[d]),
+          });
+        }
+        result.modified.push({
+          key: str(keys[i]),
+          sourceRow: sourceRows[i],
+          targetRow: targetRows[i],
+          differences,
+          sourceIndex: sourceIndices[i],
+          targetIndex: targetIndices[i],
+        });
+      }
+    }
+
+    {
+      const names = columns();
+      const keys = this.readU32Array(unchangedCount);
+      const sourceIndices = indices(unchangedCount);
+      const targetIndices = indices(unchangedCount);
+      const unchangedRows = rows(unchangedCount, names);
+      for (let i = 0; i < unchangedCount; i++) {
+        result.unchanged.push({
+          key: str(keys[i]),
+          row: unchangedRows[i],
+          sourceIndex: sourceIndices[i],
+          targetIndex: targetIndices[i],
+        });
+      }
+    }
+
+    if (this.position < this.buffer.length) {
+      const differences = result.modified.flatMap((row) => row.differences);
+      const fragmentCounts = this.readU32Array(differences.length);
+      const total = fragmentCounts.reduce((sum, count) => sum + count, 0);
+      const tags = this.readU32Array(total);
+      const values = this.readU32Array(total);
+      let f = 0;
+      differences.forEach((difference, i) => {
+        const diff: Array<DiffChange> = [];
+        for (let j = 0; j < fragmentCounts[i]; j++, f++) {
+          diff.push({
+            added: tags[f] === 1,
+            removed: tags[f] === 2,
+            value: str(values[f]),
+          });
+        }
+        difference.diff = diff;
+      });
+    }
+
     return result;
   }
 
+  /**
+   * Read `count` little-endian u32s: a view into the buffer when it is
+   * 4-byte aligned, a copy otherwise.
+   */
+  private readU32Array(count: number): Uint32Array {
+    const length = count * 4;
+    if (this.position + length > this.buffer.length) {
+      throw new Error(
+        `Buffer overflow: attempted to read ${count} u32s at position ${this.position}, buffer length ${this.buffer.length}`,
+      );
+    }
+    const offset = this.buffer.byteOffset + this.position;
+    const array =
+      offset % 4 === 0 && LITTLE_ENDIAN
+        ? new Uint32Array(this.buffer.buffer, offset, count)
+        : Uint32Array.from({ length: count }, (_, i) =>
+            this.view.getUint32(this.position + i * 4, true),
+          );
+    this.position += length;
+    return array;
+  }
+
+  /**
+   * Read the char diff fragments of every difference of the modified rows,
+   * with the count and string readers of the buffer's format.
+   */
+  private readCharDiffs(
+    result: DiffResult,
+    readCount: () => number,
+    readValue: () => string,
+  ): void {
+    for (const row of result.modified) {
+      for (const difference of row.differences) {
+        const fragmentCount = readCount();
+        const diff: Array<DiffChange> = [];
+        for (let i = 0; i < fragmentCount; i++) {
+          const tag = this.readU8();
+          diff.push({
+            added: tag === 1,
+            removed: tag === 2,
+            value: readValue(),
+          });
+        }
+        difference.diff = diff;
+      }
+    }
+  }
+
+  /**
+   * Read an unsigned LEB128 varint. Arithmetic rather than bit operations
+   * keeps values above 2^31 exact.
+   */
+  private readVarint(): number {
+    let value = 0;
+    let scale = 1;
+    for (;;) {
+      const byte = this.readU8();
+      value += (byte & 0x7f) * scale;
+      if (byte < 0x80) {
+        return value;
+      }
+      scale *= 128;
+    }
+  }
+
+  /**
+   * Read a row index written as u32, 0xFFFFFFFF meaning unknown.
+   */
+  private readIndex(): number | undefined {
+    const index = this.readU32();
+    return index === 0xffffffff ? undefined : index;
+  }
+
   /**
    * Read a single byte (u8).
    */
@@ -186,7 +612,13 @@ export class BinaryDecoder {
    * Read string with length prefix.
    */
   private readString(): string {
-    const length = this.readU32();
+    return this.readBytes(this.readU32());
+  }
+
+  /**
+   * Read `length` bytes as a UTF-8 string.
+   */
+  private readBytes(length: number): string {
     if (this.position + length > this.buffer.length) {
       throw new Error(
         `Buffer overflow: attempted to read ${length} bytes at position ${this.position}, buffer length ${this.buffer.length}`,